@@ -10,7 +10,7 @@ use reth_network_p2p::{
 };
 use reth_network_peers::PeerId;
 use reth_primitives::{
-    BlockBody, BlockHash, BlockHashOrNumber, BlockNumber, Header, SealedHeader, B256,
+    Block, BlockBody, BlockHash, BlockHashOrNumber, BlockNumber, Header, SealedHeader, B256,
 };
 use std::{collections::HashMap, io, path::Path};
 use thiserror::Error;
@@ -88,6 +88,22 @@ impl FileClient {
         Ok(Self::from_reader(&reader[..], file_len).await?.0)
     }
 
+    /// Create a new file client from blocks that were decoded from another source.
+    pub fn from_blocks(blocks: impl IntoIterator<Item = Block>) -> Self {
+        let mut headers = HashMap::new();
+        let mut hash_to_number = HashMap::new();
+        let mut bodies = HashMap::new();
+
+        for block in blocks {
+            let block_hash = block.header.hash_slow();
+            headers.insert(block.header.number, block.header.clone());
+            hash_to_number.insert(block_hash, block.header.number);
+            bodies.insert(block_hash, block.into());
+        }
+
+        Self { headers, hash_to_number, bodies }
+    }
+
     /// Get the tip hash of the chain.
     pub fn tip(&self) -> Option<B256> {
         self.headers.get(&self.max_block()?).map(|h| h.hash_slow())
@@ -586,6 +602,35 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_download_bodies_from_blocks() {
+        // Generate some random blocks
+        let factory = create_test_provider_factory();
+        let (headers, mut bodies) = generate_bodies(0..=19);
+
+        let client = Arc::new(FileClient::from_blocks(headers.iter().map(|header| {
+            let body = bodies[&header.hash()].clone();
+            body.create_block(header.clone().unseal())
+        })));
+        assert_eq!(client.headers_len(), 20);
+        assert_eq!(client.tip(), Some(headers.last().unwrap().hash()));
+
+        // insert headers in db for the bodies downloader
+        insert_headers(factory.db_ref().db(), &headers);
+
+        let mut downloader = BodiesDownloaderBuilder::default().build(
+            client.clone(),
+            Arc::new(TestConsensus::default()),
+            factory,
+        );
+        downloader.set_download_range(0..=19).expect("failed to set download range");
+
+        assert_matches!(
+            downloader.next().await,
+            Some(Ok(res)) => assert_eq!(res, zip_blocks(headers.iter(), &mut bodies))
+        );
+    }
+
     #[tokio::test]
     async fn test_chunk_download_headers_from_file() {
         reth_tracing::init_test_tracing();
@@ -84,6 +84,25 @@ impl Zstd {
         }
     }
 
+    /// Trains a dictionary of at most `max_dict_size` bytes on `samples`.
+    pub fn train_dictionary(
+        samples: impl IntoIterator<Item = Vec<u8>>,
+        max_dict_size: usize,
+    ) -> Result<RawDictionary, NippyJarError> {
+        // ZSTD requires all training data to be continuous in memory, alongside the size of
+        // each entry
+        let mut sizes = vec![];
+        let data: Vec<_> = samples
+            .into_iter()
+            .flat_map(|data| {
+                sizes.push(data.len());
+                data
+            })
+            .collect();
+
+        Ok(zstd::dict::from_continuous(&data, &sizes, max_dict_size)?)
+    }
+
     /// Compresses a value using a dictionary. Reserves additional capacity for `buffer` if
     /// necessary.
     pub fn compress_with_dictionary(
@@ -211,18 +230,7 @@ impl Compression for Zstd {
 
         let mut dictionaries = vec![];
         for column in columns {
-            // ZSTD requires all training data to be continuous in memory, alongside the size of
-            // each entry
-            let mut sizes = vec![];
-            let data: Vec<_> = column
-                .into_iter()
-                .flat_map(|data| {
-                    sizes.push(data.len());
-                    data
-                })
-                .collect();
-
-            dictionaries.push(zstd::dict::from_continuous(&data, &sizes, self.max_dict_size)?);
+            dictionaries.push(Self::train_dictionary(column, self.max_dict_size)?);
         }
 
         debug_assert_eq!(dictionaries.len(), self.columns);
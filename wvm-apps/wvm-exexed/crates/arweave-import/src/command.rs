@@ -0,0 +1,230 @@
+use crate::source::{ArchivedBlocks, DEFAULT_FETCH_CONCURRENCY};
+use clap::Parser;
+use irys::args::StorageArgs;
+use outbox::{sidecar::ReadSidecarOutbox, upload::UploadOutbox};
+use precompiles::{
+    args::PrecompileArgs,
+    inner::{registry::PrecompileForks, sidecar::ReadSidecars},
+    node::wvm_executor,
+};
+use reth::{
+    args::LogArgs,
+    primitives::SealedBlock,
+    providers::{
+        BlockNumReader, ChainSpecProvider, HeaderProvider, ProviderError, StageCheckpointReader,
+    },
+};
+use reth_beacon_consensus::EthBeaconConsensus;
+use reth_cli_commands::{
+    common::{AccessRights, Environment, EnvironmentArgs},
+    import::build_import_pipeline,
+};
+use reth_downloaders::file_client::FileClient;
+use reth_prune::PruneModes;
+use reth_stages::StageId;
+use reth_static_file::StaticFileProducer;
+use reth_tracing::{
+    tracing::{info, warn},
+    FileWorkerGuard,
+};
+use std::sync::Arc;
+
+/// Name the command is invoked with.
+pub const IMPORT_ARWEAVE_COMMAND: &str = "import-arweave";
+
+/// Rebuilds the chain from blocks archived on Arweave.
+///
+/// Blocks are looked up by their `Block-Number` and `Network` tags, starting after the local
+/// head. Only blocks uploaded by the archivers of the chain spec are imported. Each block has to
+/// match its hash and link to its parent before it is fed through the sync pipeline, which executes
+/// and validates it like a block received from a peer.
+///
+/// Blocks from the read sidecar fork on replay the reads of the precompiles from their archived
+/// sidecar. Blocks before it have no sidecar, their reads go to the live storage and gateways
+/// again, so their import depends on the network and fails if a read returns something else by
+/// now. They are only imported with `--allow-live-reads`.
+#[derive(Debug, Parser)]
+#[command(name = IMPORT_ARWEAVE_COMMAND)]
+pub struct ImportArweaveCommand {
+    #[command(flatten)]
+    env: EnvironmentArgs,
+
+    #[command(flatten)]
+    storage: StorageArgs,
+
+    #[command(flatten)]
+    precompiles: PrecompileArgs,
+
+    #[command(flatten)]
+    logs: LogArgs,
+
+    /// Network tag of the archived blocks, defaults to the network of this node.
+    #[arg(long, value_name = "TAG")]
+    network: Option<String>,
+
+    /// Last block to import, by default all archived blocks are imported.
+    #[arg(long, value_name = "BLOCK")]
+    to: Option<u64>,
+
+    /// Number of blocks fetched and imported per pipeline run.
+    #[arg(long, value_name = "BLOCKS", default_value_t = 1_000)]
+    chunk_blocks: u64,
+
+    /// Number of blocks fetched concurrently.
+    #[arg(long, value_name = "REQUESTS", default_value_t = DEFAULT_FETCH_CONCURRENCY)]
+    concurrency: usize,
+
+    /// Disables stages that require state.
+    #[arg(long)]
+    no_state: bool,
+
+    /// Imports blocks before the read sidecar fork, whose reads are executed against the live
+    /// storage and gateways again instead of being replayed.
+    #[arg(long)]
+    allow_live_reads: bool,
+}
+
+impl ImportArweaveCommand {
+    pub fn init_tracing(&self) -> eyre::Result<Option<FileWorkerGuard>> {
+        self.logs.init_tracing()
+    }
+
+    /// Execute `import-arweave` command
+    pub async fn execute(self, default_network: &str) -> eyre::Result<()> {
+        let network = self.network.as_deref().unwrap_or(default_network);
+        info!(target: "reth::cli", network, "Importing blocks from Arweave");
+
+        let Environment { provider_factory, config, data_dir } = self.env.init(AccessRights::RW)?;
+
+        let storage = self.storage.backend(data_dir.data_dir())?;
+        let forks = PrecompileForks::from_chain_spec(&provider_factory.chain_spec())?;
+        let filter = forks.archive.with_network(network);
+        let source =
+            ArchivedBlocks::new(storage.clone(), filter).with_concurrency(self.concurrency);
+
+        // the payloads of the upload precompile were archived when the blocks were first built,
+        // so whatever the import stages is thrown away
+        let upload_dir = tempfile::tempdir()?;
+        let upload_outbox = Arc::new(UploadOutbox::new(upload_dir.path())?);
+        // blocks that commit to a read sidecar are replayed with the archived sidecar
+        let io = self.precompiles.io()?;
+        let sidecars = ReadSidecars::new(
+            Arc::new(ReadSidecarOutbox::at_datadir(data_dir.data_dir())?),
+            storage.clone(),
+            io.clone(),
+            forks.read_sidecars,
+        )
+        .with_peers(self.precompiles.sidecar_peers.clone());
+        let read_cache = self.precompiles.read_cache(data_dir.data_dir())?;
+        let (_, executor) = wvm_executor(
+            provider_factory.chain_spec(),
+            upload_outbox,
+            storage,
+            read_cache,
+            self.precompiles.blob_source(data_dir.data_dir())?,
+            io,
+            sidecars.clone(),
+        )?;
+
+        let consensus = Arc::new(EthBeaconConsensus::new(self.env.chain.clone()));
+
+        let mut total_imported_blocks = 0;
+        loop {
+            let last_block_number = provider_factory.last_block_number()?;
+            let local_head = provider_factory
+                .sealed_header(last_block_number)?
+                .ok_or(ProviderError::HeaderNotFound(last_block_number.into()))?;
+
+            let max_blocks = match self.to {
+                Some(to) => to.saturating_sub(local_head.number).min(self.chunk_blocks),
+                None => self.chunk_blocks,
+            };
+            if max_blocks == 0 {
+                break
+            }
+
+            let blocks = source.fetch_chain(&local_head, max_blocks).await?;
+            let Some(last) = blocks.last() else { break };
+            // blocks are in order, so either the first one has a sidecar or the chunk starts before
+            // the fork
+            if !sidecars.is_active(blocks[0].number, blocks[0].timestamp) {
+                if !self.allow_live_reads {
+                    eyre::bail!(
+                        "block {} predates read sidecars, its reads would be executed against the \
+                         live storage, import it with `--allow-live-reads`",
+                        blocks[0].number
+                    )
+                }
+                warn!(
+                    target: "reth::cli",
+                    from = blocks[0].number,
+                    "Importing blocks without read sidecars, their reads are executed again"
+                );
+            }
+            info!(
+                target: "reth::cli",
+                from = local_head.number + 1,
+                to = last.number,
+                "Fetched archived blocks"
+            );
+
+            let imported_blocks = blocks.len();
+            let file_client = FileClient::from_blocks(blocks.into_iter().map(SealedBlock::unseal));
+            let tip = file_client.tip().ok_or(eyre::eyre!("file client has no tip"))?;
+
+            let (mut pipeline, events) = build_import_pipeline(
+                &config,
+                provider_factory.clone(),
+                &consensus,
+                Arc::new(file_client),
+                StaticFileProducer::new(provider_factory.clone(), PruneModes::default()),
+                self.no_state,
+                executor.clone(),
+            )?;
+            pipeline.set_tip(tip);
+
+            let latest_block_number = provider_factory
+                .provider()?
+                .get_stage_checkpoint(StageId::Finish)?
+                .map(|checkpoint| checkpoint.block_number);
+            tokio::spawn(reth_node_events::node::handle_events(None, latest_block_number, events));
+
+            tokio::select! {
+                res = pipeline.run() => res?,
+                _ = tokio::signal::ctrl_c() => return Ok(()),
+            }
+
+            total_imported_blocks += imported_blocks;
+        }
+
+        info!(target: "reth::cli", total_imported_blocks, "Arweave import finished");
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod command_tests {
+    use crate::command::ImportArweaveCommand;
+    use clap::Parser;
+
+    #[test]
+    pub fn test_parse_import_arweave_command() {
+        let command = ImportArweaveCommand::parse_from([
+            "import-arweave",
+            "--network",
+            "Devnet v0.2.0",
+            "--to",
+            "100",
+            "--wvm.storage",
+            "local",
+        ]);
+        assert_eq!(command.network.as_deref(), Some("Devnet v0.2.0"));
+        assert_eq!(command.to, Some(100));
+        assert_eq!(command.chunk_blocks, 1_000);
+        assert!(!command.allow_live_reads);
+
+        let command = ImportArweaveCommand::parse_from(["import-arweave", "--allow-live-reads"]);
+        assert!(command.allow_live_reads);
+    }
+}
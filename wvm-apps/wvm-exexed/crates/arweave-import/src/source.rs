@@ -0,0 +1,239 @@
+use futures::{stream, StreamExt, TryStreamExt};
+use irys::backend::PermanentStorageBackend;
+use precompiles::inner::wevm_block_precompile::{find_archived_block, ArchiveFilter};
+use reth::primitives::{SealedBlock, SealedHeader};
+use std::sync::Arc;
+
+/// Default number of blocks fetched concurrently.
+pub const DEFAULT_FETCH_CONCURRENCY: usize = 8;
+
+/// Blocks that were archived to permanent storage by the archivers of a network.
+///
+/// Anyone can upload an item tagged like a block, so candidates that don't match their hash or
+/// don't link to their parent are skipped in favor of older ones.
+pub struct ArchivedBlocks {
+    storage: Arc<dyn PermanentStorageBackend>,
+    filter: ArchiveFilter,
+    concurrency: usize,
+}
+
+impl ArchivedBlocks {
+    pub fn new(storage: Arc<dyn PermanentStorageBackend>, filter: ArchiveFilter) -> Self {
+        ArchivedBlocks { storage, filter, concurrency: DEFAULT_FETCH_CONCURRENCY }
+    }
+
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Fetches the canonical block archived at `block_number`, `None` if there is none.
+    pub async fn fetch(&self, block_number: u64) -> eyre::Result<Option<SealedBlock>> {
+        self.fetch_matching(block_number, |_| true).await
+    }
+
+    /// Fetches the newest valid block archived at `block_number` that is accepted by `accept`.
+    async fn fetch_matching(
+        &self,
+        block_number: u64,
+        accept: impl Fn(&SealedBlock) -> bool,
+    ) -> eyre::Result<Option<SealedBlock>> {
+        let block =
+            find_archived_block(self.storage.as_ref(), block_number, &self.filter, |block| {
+                verify_block(&block.block, block_number).is_ok() && accept(&block.block)
+            })
+            .await?;
+
+        Ok(block.map(|block| block.0.block))
+    }
+
+    /// Fetches up to `max_blocks` consecutive blocks on top of `parent`.
+    ///
+    /// Stops at the first block that was not archived. Fails if no block archived at a height
+    /// links to the one before it.
+    pub async fn fetch_chain(
+        &self,
+        parent: &SealedHeader,
+        max_blocks: u64,
+    ) -> eyre::Result<Vec<SealedBlock>> {
+        let numbers = parent.number + 1..=parent.number.saturating_add(max_blocks);
+        let fetched: Vec<Option<SealedBlock>> = stream::iter(numbers)
+            .map(|block_number| self.fetch(block_number))
+            .buffered(self.concurrency)
+            .try_collect()
+            .await?;
+
+        let mut blocks: Vec<SealedBlock> = Vec::with_capacity(fetched.len());
+        for block in fetched.into_iter().map_while(|block| block) {
+            let parent = blocks.last().map(|block| &block.header).unwrap_or(parent);
+            let block = match verify_parent(&block, parent) {
+                Ok(()) => block,
+                // the newest candidate is of another chain, an older one may link
+                Err(err) => self
+                    .fetch_matching(block.number, |block| block.parent_hash == parent.hash())
+                    .await?
+                    .ok_or(err)?,
+            };
+            blocks.push(block);
+        }
+
+        Ok(blocks)
+    }
+}
+
+/// Checks that an archived block is the block at `block_number` and matches its sealed hash.
+pub fn verify_block(block: &SealedBlock, block_number: u64) -> eyre::Result<()> {
+    if block.number != block_number {
+        return Err(eyre::eyre!("archived block {} has block number {}", block_number, block.number))
+    }
+
+    let hash = block.header.header().hash_slow();
+    if hash != block.hash() {
+        return Err(eyre::eyre!(
+            "archived block {} has hash {} but is sealed with {}",
+            block_number,
+            hash,
+            block.hash()
+        ))
+    }
+
+    Ok(())
+}
+
+/// Checks that `block` is a child of `parent`.
+pub fn verify_parent(block: &SealedBlock, parent: &SealedHeader) -> eyre::Result<()> {
+    if block.parent_hash != parent.hash() {
+        return Err(eyre::eyre!(
+            "archived block {} does not link to block {}: parent hash {}, expected {}",
+            block.number,
+            parent.number,
+            block.parent_hash,
+            parent.hash()
+        ))
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod source_tests {
+    use crate::source::{verify_block, ArchivedBlocks};
+    use irys::{
+        backend::{PermanentStorageBackend, Tag},
+        local::LocalStorage,
+    };
+    use precompiles::inner::wevm_block_precompile::ArchiveFilter;
+    use rbrotli::to_brotli;
+    use reth::primitives::{
+        BlockHash, Header, SealedBlock, SealedBlockWithSenders, SealedHeader, B256,
+    };
+    use std::sync::Arc;
+    use wevm_borsh::{
+        block::BorshSealedBlockWithSenders,
+        format::{encode_payload, FORMAT_VERSION_TAG},
+    };
+
+    fn block(number: u64, parent_hash: BlockHash) -> SealedBlock {
+        let header = Header { number, parent_hash, ..Default::default() };
+        SealedBlock { header: header.seal_slow(), ..Default::default() }
+    }
+
+    fn filter(network: &str) -> ArchiveFilter {
+        ArchiveFilter::default().with_network(network)
+    }
+
+    async fn upload_block(storage: &LocalStorage, block: &SealedBlock, network: &str) {
+        let block = SealedBlockWithSenders { block: block.clone(), senders: vec![] };
+        let data = to_brotli(encode_payload(&BorshSealedBlockWithSenders(block.clone())).unwrap());
+        let tags = vec![
+            Tag::new("WeaveVM:Encoding", "Borsh-Brotli"),
+            Tag::new(FORMAT_VERSION_TAG, "1"),
+            Tag::new("Block-Number", block.number.to_string().as_str()),
+            Tag::new("Block-Hash", block.hash().to_string().as_str()),
+            Tag::new("Network", network),
+        ];
+
+        storage.upload(data, tags).await.unwrap();
+    }
+
+    #[tokio::test]
+    pub async fn test_fetch_chain() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = Arc::new(LocalStorage::new(dir.path()).unwrap());
+
+        let genesis = block(0, B256::ZERO);
+        let first = block(1, genesis.hash());
+        let second = block(2, first.hash());
+        upload_block(&storage, &first, "Test").await;
+        upload_block(&storage, &second, "Test").await;
+        // blocks of other networks are ignored
+        upload_block(&storage, &block(3, second.hash()), "Other").await;
+
+        let source = ArchivedBlocks::new(storage, filter("Test"));
+        let blocks = source.fetch_chain(&genesis.header, 10).await.unwrap();
+        assert_eq!(blocks, vec![first, second]);
+    }
+
+    #[tokio::test]
+    pub async fn test_fetch_chain_broken_link() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = Arc::new(LocalStorage::new(dir.path()).unwrap());
+
+        let genesis = block(0, B256::ZERO);
+        upload_block(&storage, &block(1, B256::random()), "Test").await;
+
+        let source = ArchivedBlocks::new(storage, filter("Test"));
+        assert!(source.fetch_chain(&genesis.header, 10).await.is_err());
+    }
+
+    #[tokio::test]
+    pub async fn test_fetch_chain_skips_bad_candidates() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = Arc::new(LocalStorage::new(dir.path()).unwrap());
+
+        let genesis = block(0, B256::ZERO);
+        let first = block(1, genesis.hash());
+        upload_block(&storage, &first, "Test").await;
+        // newer uploads of another chain and with a forged hash
+        upload_block(&storage, &block(1, B256::random()), "Test").await;
+        let forged = SealedBlock {
+            header: SealedHeader::new(Header { number: 1, ..Default::default() }, B256::random()),
+            ..Default::default()
+        };
+        upload_block(&storage, &forged, "Test").await;
+
+        let source = ArchivedBlocks::new(storage, filter("Test"));
+        let blocks = source.fetch_chain(&genesis.header, 10).await.unwrap();
+        assert_eq!(blocks, vec![first]);
+    }
+
+    #[tokio::test]
+    pub async fn test_fetch_blocks_of_archivers() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = Arc::new(LocalStorage::new(dir.path()).unwrap());
+        upload_block(&storage, &block(1, B256::ZERO), "Test").await;
+
+        // items of the local storage have no owner
+        let source = ArchivedBlocks::new(
+            storage,
+            ArchiveFilter::new(vec!["archiver".to_string()]).with_network("Test"),
+        );
+        assert_eq!(source.fetch(1).await.unwrap(), None);
+    }
+
+    #[test]
+    pub fn test_verify_block_hash() {
+        let block = block(1, B256::ZERO);
+        assert!(verify_block(&block, 1).is_ok());
+        assert!(verify_block(&block, 2).is_err());
+
+        let tampered = SealedBlock {
+            header: SealedHeader::new(
+                Header { number: 1, gas_limit: 1, ..Default::default() },
+                block.hash(),
+            ),
+            ..Default::default()
+        };
+        assert!(verify_block(&tampered, 1).is_err());
+    }
+}
@@ -4,7 +4,8 @@ use std::collections::HashMap;
 use gcp_bigquery_client::{
     error::BQError,
     model::{
-        table::Table, table_data_insert_all_request::TableDataInsertAllRequest,
+        query_request::QueryRequest, table::Table,
+        table_data_insert_all_request::TableDataInsertAllRequest,
         table_field_schema::TableFieldSchema, table_schema::TableSchema,
     },
     Client,
@@ -12,6 +13,7 @@ use gcp_bigquery_client::{
 
 use phf::phf_ordered_map;
 use polars::prelude::*;
+use reth::primitives::BlockHash;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
@@ -32,7 +34,13 @@ pub static COMMON_COLUMNS: phf::OrderedMap<&'static str, &'static str> = phf_ord
     "block_number" => "int",
     "sealed_block_with_senders" => "string",
     "arweave_id" => "string",
-    "timestamp" => "int"
+    // JSON object of the ids of the block by archive destination
+    "archive_ids" => "string",
+    "timestamp" => "int",
+    "block_hash" => "string",
+    // NULL for rows written before reorgs were tracked, read as canonical. A reverted block has a
+    // tombstone row with FALSE, its other rows are not updated
+    "canonical" => "bool"
 };
 
 pub fn prepare_blockstate_table_config() -> HashMap<String, IndexMap<String, String>> {
@@ -167,10 +175,45 @@ impl BigQueryClient {
                 Ok(..) => {}
                 Err(err) => return Err(err),
             }
+
+            self.add_missing_columns(table_name, column_map).await?;
         }
         Ok(())
     }
 
+    ///
+    /// Adds configured columns that are missing in an existing table
+    ///
+    /// # Arguments
+    ///
+    /// * `table_name` - name of table
+    /// * `column_map` - map of column names to types
+    pub async fn add_missing_columns(
+        &self,
+        table_name: &str,
+        column_map: &IndexMap<String, String>,
+    ) -> Result<(), BQError> {
+        let columns: Vec<String> = column_map
+            .iter()
+            .map(|(name, db_type)| {
+                format!(
+                    "ADD COLUMN IF NOT EXISTS {} {}",
+                    name,
+                    BigQueryClient::db_type_to_sql_type(db_type)
+                )
+            })
+            .collect();
+
+        let query = format!("ALTER TABLE {} {}", self.table_ref(table_name), columns.join(", "));
+        self.client.job().query(self.project_id.as_str(), QueryRequest::new(query)).await?;
+
+        Ok(())
+    }
+
+    fn table_ref(&self, table_name: &str) -> String {
+        format!("`{}.{}.{}`", self.project_id, self.dataset_id, table_name)
+    }
+
     ///
     /// Create a single table in GCP bigquery, from configured datatypes
     ///
@@ -323,6 +366,22 @@ impl BigQueryClient {
         match db_type {
             "int" => TableFieldSchema::integer(name),
             "string" => TableFieldSchema::string(name),
+            "bool" => TableFieldSchema::bool(name),
+            _ => panic!("Unsupported db type: {}", db_type),
+        }
+    }
+
+    ///
+    ///  Maps configured column types to GoogleSQL types
+    ///
+    /// # Arguments
+    ///
+    /// * `db_type` - stored datatype configured for this column
+    pub fn db_type_to_sql_type(db_type: &str) -> &'static str {
+        match db_type {
+            "int" => "INT64",
+            "string" => "STRING",
+            "bool" => "BOOL",
             _ => panic!("Unsupported db type: {}", db_type),
         }
     }
@@ -355,8 +414,11 @@ impl BigQueryClient {
         #[derive(Serialize)]
         struct StateRow {
             block_number: u64,
+            block_hash: String,
             arweave_id: String,
+            archive_ids: String,
             sealed_block_with_senders: String,
+            canonical: bool,
         }
 
         let mut insert_request = TableDataInsertAllRequest::new();
@@ -365,8 +427,11 @@ impl BigQueryClient {
             None,
             StateRow {
                 arweave_id: state.arweave_id,
+                archive_ids: serde_json::to_string(&state.archive_ids)?,
                 block_number: state.block_number,
+                block_hash: state.block_hash.to_string(),
                 sealed_block_with_senders: state.sealed_block_with_senders_serialized,
+                canonical: true,
             },
         )?;
 
@@ -394,4 +459,50 @@ impl BigQueryClient {
             }
         }
     }
+
+    ///
+    /// Marks the rows of a block as no longer part of the canonical chain
+    ///
+    /// Rows that are still in the streaming buffer can't be updated, so a tombstone row with
+    /// `canonical = FALSE` is inserted instead. A block is canonical as long as it has no
+    /// tombstone.
+    ///
+    /// # Arguments
+    ///
+    /// * `table_name` - name of table being operated upon
+    /// * `block_number` - number of the reverted block
+    /// * `block_hash` - hash of the reverted block
+    pub async fn bq_mark_non_canonical(
+        &self,
+        table_name: &str,
+        block_number: u64,
+        block_hash: BlockHash,
+    ) -> eyre::Result<()> {
+        #[derive(Serialize)]
+        struct TombstoneRow {
+            block_number: u64,
+            block_hash: String,
+            canonical: bool,
+        }
+
+        let mut insert_request = TableDataInsertAllRequest::new();
+        insert_request.add_row(
+            // retried inserts of the same tombstone are deduplicated
+            Some(format!("tombstone-{}", block_hash)),
+            TombstoneRow { block_number, block_hash: block_hash.to_string(), canonical: false },
+        )?;
+
+        self.client
+            .tabledata()
+            .insert_all(
+                self.project_id.as_str(),
+                self.dataset_id.as_str(),
+                table_name,
+                insert_request,
+            )
+            .await
+            .wrap_err("Failed to mark rows as non-canonical in BigQuery")?;
+
+        Ok(())
+    }
 }
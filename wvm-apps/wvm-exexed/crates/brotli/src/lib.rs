@@ -1,28 +1,67 @@
-use brotlic::{CompressorWriter, DecompressorReader};
-use std::io::{Read, Write};
+use brotlic::{BrotliEncoderOptions, CompressorWriter, DecompressorReader, Quality, WindowSize};
+use std::io::{Error, ErrorKind, Read, Write};
+
+/// Quality used by [`to_brotli`], the highest brotli supports.
+pub const DEFAULT_QUALITY: u8 = 11;
+/// Base two logarithm of the window used by [`to_brotli`].
+pub const DEFAULT_WINDOW: u8 = 22;
+/// Maximum size [`try_from_brotli`] decompresses to.
+pub const MAX_DECOMPRESSED_SIZE: usize = 128 * 1024 * 1024;
 
 pub fn to_brotli(data: Vec<u8>) -> Vec<u8> {
-    let buff: Vec<u8> = vec![];
-    let mut compressor = CompressorWriter::new(buff);
-    compressor.write_all(data.as_slice()).unwrap();
-    compressor.into_inner().unwrap()
+    compress(data.as_slice(), DEFAULT_QUALITY, DEFAULT_WINDOW).unwrap()
+}
+
+/// Compresses `data` with the given `quality` (0 to 11) and base two logarithm of the `window`
+/// (10 to 24).
+pub fn compress(data: &[u8], quality: u8, window: u8) -> std::io::Result<Vec<u8>> {
+    let invalid = |parameter: &str, value: u8| {
+        Error::new(ErrorKind::InvalidInput, format!("invalid brotli {parameter} {value}"))
+    };
+
+    let encoder = BrotliEncoderOptions::new()
+        .quality(Quality::new(quality).map_err(|_| invalid("quality", quality))?)
+        .window_size(WindowSize::new(window).map_err(|_| invalid("window", window))?)
+        .build()
+        .map_err(|_| Error::new(ErrorKind::InvalidInput, "invalid brotli parameters"))?;
+
+    let mut compressor = CompressorWriter::with_encoder(encoder, Vec::new());
+    compressor.write_all(data)?;
+    compressor.into_inner().map_err(|_| Error::new(ErrorKind::Other, "brotli stream not finished"))
 }
 
 pub fn from_brotli(data: Vec<u8>) -> Vec<u8> {
+    try_from_brotli(data.as_slice()).unwrap()
+}
+
+/// Decompresses `data`, failing instead of panicking on invalid input or if it decompresses to
+/// more than [`MAX_DECOMPRESSED_SIZE`] bytes.
+pub fn try_from_brotli(data: &[u8]) -> std::io::Result<Vec<u8>> {
+    try_from_brotli_with_limit(data, MAX_DECOMPRESSED_SIZE)
+}
+
+/// Decompresses `data`, failing if it decompresses to more than `limit` bytes.
+pub fn try_from_brotli_with_limit(data: &[u8], limit: usize) -> std::io::Result<Vec<u8>> {
     // create a wrapper around BufRead that supports on the fly brotli decompression.
-    let mut decompressed_reader = DecompressorReader::new(data.as_slice());
+    let decompressed_reader = DecompressorReader::new(data);
     let mut decoded_input: Vec<u8> = Vec::new();
 
-    decompressed_reader.read_to_end(&mut decoded_input).unwrap();
+    decompressed_reader.take(limit as u64 + 1).read_to_end(&mut decoded_input)?;
+    if decoded_input.len() > limit {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("brotli payload decompresses to more than {limit} bytes"),
+        ))
+    }
 
-    decoded_input
+    Ok(decoded_input)
 }
 
 #[cfg(test)]
 mod brotlic_tests {
+    use crate::{compress, from_brotli, to_brotli, try_from_brotli, try_from_brotli_with_limit};
     use reth::primitives::SealedBlockWithSenders;
     use wevm_borsh::block::BorshSealedBlockWithSenders;
-    use crate::{from_brotli, to_brotli};
 
     #[test]
     pub fn test_brotlic_block() {
@@ -34,4 +73,22 @@ mod brotlic_tests {
         let unbrotli = from_brotli(brotli);
         assert_eq!(borsh_vec, unbrotli);
     }
+
+    #[test]
+    pub fn test_brotli_parameters() {
+        let data = vec![7u8; 4096];
+        let fast = compress(data.as_slice(), 1, 16).unwrap();
+        assert_eq!(try_from_brotli(fast.as_slice()).unwrap(), data);
+
+        assert!(compress(data.as_slice(), 12, 22).is_err());
+        assert!(compress(data.as_slice(), 11, 8).is_err());
+    }
+
+    #[test]
+    pub fn test_brotli_limit() {
+        let data = vec![7u8; 4096];
+        let compressed = to_brotli(data.clone());
+        assert_eq!(try_from_brotli_with_limit(compressed.as_slice(), 4096).unwrap(), data);
+        assert!(try_from_brotli_with_limit(compressed.as_slice(), 4095).is_err());
+    }
 }
@@ -0,0 +1,1344 @@
+use futures::future::join_all;
+use irys::{
+    backend::{DroppedUpload, PermanentStorageBackend, TagQuery},
+    irys::IrysRequest,
+};
+use outbox::{
+    archive::{ArchiveEntry, ArchiveOutbox, RevertEntry},
+    sidecar::ReadSidecarOutbox,
+};
+use repository::state_repository::StateRepository;
+use reth::{
+    primitives::{BlockHash, SealedBlockWithSenders},
+    providers::{BlockReader, Chain, TransactionVariant},
+};
+use reth_tracing::tracing::{error, info, warn};
+use std::{
+    collections::BTreeMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use types::types::ExecutionTipState;
+use wevm_borsh::{
+    batch::BlockBatch,
+    block::BorshSealedBlockWithSenders,
+    codec::{Codec, ZstdDictionary, ZSTD_DICTIONARY_TAG, ZSTD_DICTIONARY_TYPE},
+    format::{encode_payload, CURRENT_FORMAT_VERSION, ENCODING_TAG, FORMAT_VERSION_TAG},
+    outcome::BlockOutcome,
+    sidecar::{sidecar_commitment, ReadSidecar, READ_SIDECAR_TYPE, SIDECAR_HASH_TAG},
+    state_diff::StateDiff,
+};
+
+/// Index archived blocks are recorded in once they are stored permanently.
+#[async_trait::async_trait]
+pub trait ArchiveIndex: Send + Sync {
+    async fn record(&self, state: ExecutionTipState) -> eyre::Result<()>;
+
+    /// Flags the records of a reverted block as no longer canonical.
+    async fn mark_non_canonical(
+        &self,
+        block_number: u64,
+        block_hash: BlockHash,
+    ) -> eyre::Result<()>;
+}
+
+#[async_trait::async_trait]
+impl ArchiveIndex for StateRepository {
+    async fn record(&self, state: ExecutionTipState) -> eyre::Result<()> {
+        self.save(state).await
+    }
+
+    async fn mark_non_canonical(
+        &self,
+        block_number: u64,
+        block_hash: BlockHash,
+    ) -> eyre::Result<()> {
+        StateRepository::mark_non_canonical(self, block_number, block_hash).await
+    }
+}
+
+/// Upper bound for [`BatchConfig::max_blocks`].
+///
+/// Every block of a batch adds a tag to the item, this keeps the tags well below the size limit of
+/// a data item.
+pub const MAX_BATCH_BLOCKS: usize = 64;
+
+/// Limits of a batch of blocks archived as a single item.
+#[derive(Debug, Clone, Copy)]
+pub struct BatchConfig {
+    /// Maximum number of blocks per item, `1` archives every block as its own item.
+    pub max_blocks: usize,
+    /// Size budget of the Borsh encoded blocks of one item, the batch is closed once it is
+    /// reached.
+    pub max_bytes: usize,
+    /// Maximum time a block waits for its batch to fill up.
+    pub max_delay: Duration,
+}
+
+impl Default for BatchConfig {
+    fn default() -> Self {
+        BatchConfig {
+            max_blocks: 1,
+            max_bytes: 4 * 1024 * 1024,
+            max_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Execution results archived next to every block.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OutcomeConfig {
+    pub receipts: bool,
+    /// Blocks staged by [`Archiver::resume`] are archived without state changes, they are not
+    /// known anymore once the blocks are persisted.
+    pub state_diffs: bool,
+}
+
+impl OutcomeConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.receipts || self.state_diffs
+    }
+}
+
+/// Name of the destination of the storage an [`Archiver`] is created with.
+pub const PRIMARY_DESTINATION: &str = "primary";
+
+/// A storage backend items are archived to, next to the primary one.
+#[derive(Debug, Clone)]
+pub struct ArchiveDestination {
+    /// Name the ids of blocks at the destination are recorded under.
+    pub name: String,
+    pub storage: Arc<dyn PermanentStorageBackend>,
+}
+
+impl ArchiveDestination {
+    pub fn new(name: &str, storage: Arc<dyn PermanentStorageBackend>) -> Self {
+        ArchiveDestination { name: name.to_string(), storage }
+    }
+}
+
+/// Exponential backoff between archival attempts.
+#[derive(Debug, Clone, Copy)]
+pub struct Backoff {
+    min: Duration,
+    max: Duration,
+    failures: u32,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(1), Duration::from_secs(5 * 60))
+    }
+}
+
+impl Backoff {
+    pub fn new(min: Duration, max: Duration) -> Self {
+        Backoff { min, max, failures: 0 }
+    }
+
+    /// Records a failed attempt and returns how long to wait before the next one.
+    pub fn fail(&mut self) -> Duration {
+        self.failures = self.failures.saturating_add(1);
+        self.min.saturating_mul(1u32 << (self.failures - 1).min(16)).min(self.max)
+    }
+
+    pub fn reset(&mut self) {
+        self.failures = 0;
+    }
+
+    pub fn failures(&self) -> u32 {
+        self.failures
+    }
+}
+
+/// Archives committed blocks to permanent storage.
+///
+/// Blocks are staged in an [`ArchiveOutbox`] first and archived strictly in order: a block is
+/// uploaded, recorded in the [`ArchiveIndex`] and only then removed from the outbox. Failures
+/// leave the block in the outbox and are retried with an exponential backoff.
+///
+/// Every item is written in the current archive format and tagged with its
+/// `WeaveVM:Format-Version`, see [`wevm_borsh::format`]. Items are compressed with the configured
+/// [`Codec`] and tagged with its `WeaveVM:Encoding`. A Zstandard dictionary is archived once,
+/// before the first item compressed with it.
+///
+/// With batching enabled consecutive blocks are uploaded as one [`BlockBatch`], tagged with the
+/// `Block-Range-Start` and `Block-Range-End` of the batch.
+///
+/// Receipts and state changes of a block can be archived as well, as a [`BlockOutcome`] item
+/// tagged with `WeaveVM:Type` `Block-Outcome` and the `Block-Number` and `Block-Hash` of the block.
+///
+/// Archived blocks that are reverted get a revert marker, an item tagged with the `Block-Hash` of
+/// the reverted block and the hash of the block it was `Reverted-By`. Markers are published
+/// through the same storage backend as blocks and thus signed by the same key.
+///
+/// With a [`ReadSidecarOutbox`] the read sidecar a committed block commits to is archived before
+/// the block, as an item tagged with `WeaveVM:Type` `Read-Sidecar`, the `Block-Number` and the
+/// `WeaveVM:Sidecar-Hash`. Nodes that did not build the block replay its reads from that item.
+///
+/// Items can be archived to mirrors next to the primary storage, see [`Archiver::with_mirrors`].
+/// They are uploaded to all destinations at once and count as archived once the primary storage
+/// and a quorum of all destinations stored the item. The id at the primary storage is the id of
+/// the item, the ids of a block at every destination are recorded in the index. A block short of
+/// the quorum keeps the ids of its successful uploads in the outbox, so the retry only uploads to
+/// the missing destinations, but mirrors that miss an item once it is archived don't get it later.
+pub struct Archiver {
+    outbox: ArchiveOutbox,
+    /// The primary storage, where archived dictionaries are looked up.
+    storage: Arc<dyn PermanentStorageBackend>,
+    /// All destinations in order of preference, the primary storage first.
+    destinations: Vec<ArchiveDestination>,
+    /// Number of destinations an item has to be stored at, all of them if not set.
+    quorum: Option<usize>,
+    index: Arc<dyn ArchiveIndex>,
+    network_tag: String,
+    backoff: Backoff,
+    retry_at: Option<Instant>,
+    batch: BatchConfig,
+    /// When the currently filling batch received its first block.
+    batch_started: Option<Instant>,
+    outcomes: OutcomeConfig,
+    codec: Codec,
+    /// Whether the dictionary of the codec is known to be archived.
+    dictionary_archived: bool,
+    sidecars: Option<ReadSidecarOutbox>,
+    /// First block archived when nothing was archived yet.
+    start_block: u64,
+}
+
+impl Archiver {
+    pub fn new(
+        outbox: ArchiveOutbox,
+        storage: Arc<dyn PermanentStorageBackend>,
+        index: Arc<dyn ArchiveIndex>,
+        network_tag: &str,
+    ) -> Self {
+        Archiver {
+            outbox,
+            destinations: vec![ArchiveDestination::new(PRIMARY_DESTINATION, storage.clone())],
+            quorum: None,
+            storage,
+            index,
+            network_tag: network_tag.to_string(),
+            backoff: Backoff::default(),
+            retry_at: None,
+            batch: BatchConfig::default(),
+            batch_started: None,
+            outcomes: OutcomeConfig::default(),
+            codec: Codec::default(),
+            dictionary_archived: false,
+            sidecars: None,
+            start_block: 0,
+        }
+    }
+
+    pub fn with_backoff(mut self, backoff: Backoff) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    pub fn with_batch(mut self, batch: BatchConfig) -> Self {
+        self.batch = batch;
+        self
+    }
+
+    pub fn with_outcomes(mut self, outcomes: OutcomeConfig) -> Self {
+        self.outcomes = outcomes;
+        self
+    }
+
+    pub fn with_codec(mut self, codec: Codec) -> Self {
+        self.codec = codec;
+        self.dictionary_archived = false;
+        self
+    }
+
+    /// Archives the read sidecars of committed blocks, staged by the payload builder.
+    pub fn with_read_sidecars(mut self, sidecars: ReadSidecarOutbox) -> Self {
+        self.sidecars = Some(sidecars);
+        self
+    }
+
+    /// Backfills the archive from `block` on the very first start, from genesis by default.
+    pub fn with_start_block(mut self, block: u64) -> Self {
+        self.start_block = block;
+        self
+    }
+
+    /// Archives every item to `mirrors` as well, next to the primary storage.
+    pub fn with_mirrors(mut self, mirrors: Vec<ArchiveDestination>) -> Self {
+        self.destinations.extend(mirrors);
+        self
+    }
+
+    /// Sets the number of destinations an item has to be stored at before it counts as archived,
+    /// the primary storage included. An item is never archived without the primary storage.
+    pub fn with_quorum(mut self, quorum: usize) -> Self {
+        self.quorum = Some(quorum);
+        self
+    }
+
+    fn quorum(&self) -> usize {
+        self.quorum.unwrap_or(self.destinations.len()).clamp(1, self.destinations.len())
+    }
+
+    pub fn outbox(&self) -> &ArchiveOutbox {
+        &self.outbox
+    }
+
+    /// Stages every block of a committed chain.
+    pub fn stage_chain(&self, chain: &Chain) -> eyre::Result<()> {
+        for block in chain.blocks_iter() {
+            self.confirm_read_sidecar(block)?;
+            self.outbox.push_with_outcome(block, self.chain_outcome(chain, block))?;
+        }
+
+        Ok(())
+    }
+
+    /// Queues the read sidecar a committed block commits to for archival, if this node built the
+    /// block.
+    pub fn confirm_read_sidecar(&self, block: &SealedBlockWithSenders) -> eyre::Result<()> {
+        let Some(sidecars) = &self.sidecars else { return Ok(()) };
+
+        // blocks without a commitment still drop the sidecars of orphaned payloads
+        let commitment = sidecar_commitment(&block.header.extra_data).unwrap_or_default();
+        if sidecars.confirm_block(block.number, commitment)? {
+            info!(block_number = block.number, %commitment, "Confirmed read sidecar");
+        }
+
+        Ok(())
+    }
+
+    fn chain_outcome(&self, chain: &Chain, block: &SealedBlockWithSenders) -> Option<BlockOutcome> {
+        if !self.outcomes.is_enabled() {
+            return None
+        }
+
+        let receipts = if self.outcomes.receipts {
+            chain.execution_outcome().receipts_by_block(block.number).to_vec()
+        } else {
+            vec![]
+        };
+
+        // the last reverts of the outcome at a block are the changes made by that block
+        let state_diff = if self.outcomes.state_diffs {
+            chain
+                .execution_outcome_at_block(block.number)
+                .map(|outcome| StateDiff::from_execution_outcome(&outcome))
+        } else {
+            None
+        };
+
+        Some(BlockOutcome {
+            block_number: block.number,
+            block_hash: block.hash(),
+            receipts,
+            state_diff,
+        })
+    }
+
+    /// Stages revert markers for the blocks of a reverted chain.
+    ///
+    /// `new` is the chain that replaced `old` in case of a reorg.
+    pub fn stage_reverted(&self, old: &Chain, new: Option<&Chain>) -> eyre::Result<()> {
+        // highest first, every revert rolls back the last archived height
+        for block in old.blocks().values().rev() {
+            let reverted_by = match new {
+                Some(new) => new.blocks().get(&block.number).unwrap_or_else(|| new.tip()).hash(),
+                None => old.first().parent_hash,
+            };
+
+            if self.outbox.revert(block, reverted_by)? {
+                info!(block_number = block.number, block_hash = %block.hash(), "Staged revert marker");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Stages the blocks committed since the last archived block, up to `head`.
+    ///
+    /// On the very first start nothing has been archived yet and the archive is backfilled from
+    /// the start block, see [`Archiver::with_start_block`]. An archive that is ahead of the node,
+    /// e.g. after its database was unwound, resumes at `head`. Returns the height of the last
+    /// archived block, `None` if nothing was archived yet.
+    pub fn resume<P: BlockReader>(&self, provider: &P, head: u64) -> eyre::Result<Option<u64>> {
+        let mut last_archived = self.outbox.last_archived()?;
+        if let Some(archived) = last_archived.filter(|archived| *archived > head) {
+            warn!(last_archived = archived, head, "Archive is ahead of the node, resuming at head");
+            self.outbox.set_last_archived(head)?;
+            last_archived = Some(head);
+        }
+
+        let from = last_archived.map_or(self.start_block, |last_archived| last_archived + 1);
+        for block_number in from..=head {
+            if !self.outbox.contains(block_number) {
+                self.stage_stored_block(provider, block_number)?;
+            }
+        }
+
+        if head >= from {
+            info!(from, to = head, "Resuming archival");
+        }
+
+        Ok(last_archived)
+    }
+
+    /// Stages the blocks of items a destination dropped after their upload succeeded, see
+    /// [`PermanentStorageBackend::dropped_uploads`], so they are archived again.
+    ///
+    /// Blocks, batches and outcomes are staged again from the canonical blocks at their heights
+    /// and a dropped dictionary is archived again with the next item. Other dropped items are
+    /// only reported, they have to be archived again manually.
+    pub async fn stage_dropped<P: BlockReader>(&mut self, provider: &P) -> eyre::Result<()> {
+        let mut dictionary_dropped = false;
+        for destination in &self.destinations {
+            for dropped in destination.storage.dropped_uploads().await? {
+                if let Some(block_numbers) = dropped_blocks(&dropped) {
+                    for &block_number in &block_numbers {
+                        self.stage_stored_block(provider, block_number)?;
+                    }
+                    warn!(
+                        destination = %destination.name,
+                        id = %dropped.id,
+                        ?block_numbers,
+                        "Staged the blocks of a dropped item again"
+                    );
+                } else if dropped.tag("WeaveVM:Type") == Some(ZSTD_DICTIONARY_TYPE) {
+                    dictionary_dropped = true;
+                } else {
+                    error!(
+                        destination = %destination.name,
+                        id = %dropped.id,
+                        tags = ?dropped.tags,
+                        "Dropped item has to be archived again manually"
+                    );
+                }
+                destination.storage.forget_dropped_upload(dropped.id.as_str()).await?;
+            }
+        }
+
+        if dictionary_dropped {
+            self.dictionary_archived = false;
+        }
+
+        Ok(())
+    }
+
+    /// Stages the canonical block at `block_number` with its outcome.
+    fn stage_stored_block<P: BlockReader>(
+        &self,
+        provider: &P,
+        block_number: u64,
+    ) -> eyre::Result<()> {
+        let block = provider
+            .sealed_block_with_senders(block_number.into(), TransactionVariant::WithHash)?
+            .ok_or_else(|| eyre::eyre!("block {} not found", block_number))?;
+
+        let outcome = if self.outcomes.receipts {
+            let receipts = provider.receipts_by_block(block_number.into())?.unwrap_or_default();
+            Some(BlockOutcome {
+                block_number,
+                block_hash: block.hash(),
+                receipts: receipts.into_iter().map(Some).collect(),
+                state_diff: None,
+            })
+        } else {
+            None
+        };
+        self.outbox.push_with_outcome(&block, outcome)?;
+
+        Ok(())
+    }
+
+    /// Returns when the archiver has to be polled again, either because a retry is due or
+    /// because a batch waited long enough.
+    pub fn wake_at(&self) -> Option<Instant> {
+        let flush_at = self.batch_started.map(|started| started + self.batch.max_delay);
+        match self.retry_at {
+            Some(retry_at) if retry_at > Instant::now() => Some(retry_at),
+            retry_at => flush_at.or(retry_at),
+        }
+    }
+
+    /// Publishes all queued revert markers and archives all pending blocks, lowest first, until
+    /// the first failure.
+    ///
+    /// Does nothing while a retry is not due yet. Returns the height of the last block archived
+    /// by this call.
+    pub async fn archive_pending(&mut self) -> eyre::Result<Option<u64>> {
+        if self.retry_at.is_some_and(|retry_at| Instant::now() < retry_at) {
+            return Ok(None)
+        }
+
+        let mut archived = None;
+        let result = self.archive_all(&mut archived).await;
+        // blocks staged again below the last archived block don't lower the finished height
+        if archived.is_some() {
+            archived = self.outbox.last_archived()?;
+        }
+
+        if let Err(err) = result {
+            let delay = self.backoff.fail();
+            self.retry_at = Some(Instant::now() + delay);
+            warn!(
+                %err,
+                failures = self.backoff.failures(),
+                retry_in = ?delay,
+                "Failed to archive blocks"
+            );
+
+            return Ok(archived)
+        }
+
+        self.backoff.reset();
+        self.retry_at = None;
+
+        Ok(archived)
+    }
+
+    async fn archive_all(&mut self, archived: &mut Option<u64>) -> eyre::Result<()> {
+        for entry in self.outbox.reverted()? {
+            self.publish_revert(&entry).await?;
+        }
+
+        let pending = self.outbox.pending()?;
+        let sidecars = match &self.sidecars {
+            Some(sidecars) => sidecars.ready()?,
+            None => vec![],
+        };
+        if (!pending.is_empty() || !sidecars.is_empty()) && !self.dictionary_archived {
+            if let Some(dictionary) = self.codec.dictionary() {
+                self.archive_dictionary(dictionary).await?;
+            }
+            self.dictionary_archived = true;
+        }
+
+        // other nodes can only execute a block once its sidecar is archived, so sidecars go first
+        for (key, block_number, sidecar) in sidecars {
+            let sidecar_id = self.upload_read_sidecar(block_number, &sidecar).await?;
+            if let Some(sidecars) = &self.sidecars {
+                sidecars.complete(key.as_str())?;
+            }
+            info!(block_number, %sidecar_id, "Archived read sidecar");
+        }
+
+        let mut remaining = pending.as_slice();
+
+        while let Some(&block_number) = remaining.first() {
+            let entry = self.outbox.read(block_number)?;
+
+            // blocks that were uploaded before only have to be recorded
+            if entry.arweave_id.is_some() || self.batch.max_blocks == 1 {
+                self.archive_block(entry).await?;
+                *archived = Some(block_number);
+                remaining = &remaining[1..];
+                continue
+            }
+
+            // a batch short of the quorum is retried with the same blocks, so the ids of its
+            // successful uploads stay valid
+            let stored_ids = entry.archive_ids;
+            let mut batch = BlockBatch::new();
+            let mut entries = vec![];
+            for &block_number in remaining {
+                if batch.len() >= self.batch.max_blocks ||
+                    batch.blocks_size() >= self.batch.max_bytes
+                {
+                    break
+                }
+
+                let entry = self.outbox.read(block_number)?;
+                if entry.arweave_id.is_some() || entry.archive_ids != stored_ids {
+                    break
+                }
+
+                batch.push(&entry.block)?;
+                entries.push(entry);
+            }
+
+            let is_full = entries.len() < remaining.len() ||
+                batch.len() >= self.batch.max_blocks ||
+                batch.blocks_size() >= self.batch.max_bytes;
+            if !is_full {
+                let started = *self.batch_started.get_or_insert_with(Instant::now);
+                if started.elapsed() < self.batch.max_delay {
+                    return Ok(())
+                }
+            }
+
+            let mut archive_ids = stored_ids.clone();
+            let uploaded = self.upload_batch(&batch, &mut archive_ids).await;
+            // successful uploads are kept even if the quorum was missed
+            if archive_ids != stored_ids {
+                for entry in entries.iter() {
+                    self.outbox.set_archive_ids(entry.block.number, &archive_ids)?;
+                }
+            }
+
+            let arweave_id = uploaded?;
+            self.batch_started = None;
+
+            for entry in entries.iter() {
+                self.outbox.set_arweave_id(entry.block.number, arweave_id.as_str())?;
+            }
+
+            for entry in entries.iter() {
+                self.record_block(entry, arweave_id.as_str(), &archive_ids).await?;
+                *archived = Some(entry.block.number);
+            }
+
+            remaining = &remaining[entries.len()..];
+        }
+
+        self.batch_started = None;
+
+        Ok(())
+    }
+
+    async fn archive_block(&self, entry: ArchiveEntry) -> eyre::Result<()> {
+        let mut archive_ids = entry.archive_ids.clone();
+        let arweave_id = match &entry.arweave_id {
+            Some(arweave_id) => arweave_id.clone(),
+            None => {
+                let uploaded = self.upload_block(&entry.block, &mut archive_ids).await;
+                // successful uploads are kept even if the quorum was missed
+                if archive_ids != entry.archive_ids {
+                    self.outbox.set_archive_ids(entry.block.number, &archive_ids)?;
+                }
+
+                let arweave_id = uploaded?;
+                self.outbox.set_arweave_id(entry.block.number, arweave_id.as_str())?;
+                arweave_id
+            }
+        };
+
+        self.record_block(&entry, arweave_id.as_str(), &archive_ids).await
+    }
+
+    async fn record_block(
+        &self,
+        entry: &ArchiveEntry,
+        arweave_id: &str,
+        archive_ids: &BTreeMap<String, String>,
+    ) -> eyre::Result<()> {
+        let block_number = entry.block.number;
+
+        if let (Some(outcome), None) = (&entry.outcome, &entry.outcome_id) {
+            let outcome_id = self.upload_outcome(outcome).await?;
+            self.outbox.set_outcome_id(block_number, outcome_id.as_str())?;
+            info!(block_number, %outcome_id, "Archived block outcome");
+        }
+
+        self.index
+            .record(ExecutionTipState {
+                block_number,
+                block_hash: entry.block.hash(),
+                arweave_id: arweave_id.to_string(),
+                archive_ids: archive_ids.clone(),
+                sealed_block_with_senders_serialized: serde_json::to_string(&entry.block)?,
+            })
+            .await?;
+
+        self.outbox.complete(block_number)?;
+        info!(block_number, %arweave_id, destinations = archive_ids.len(), "Archived block");
+
+        Ok(())
+    }
+
+    async fn publish_revert(&self, entry: &RevertEntry) -> eyre::Result<()> {
+        let marker_id = match &entry.marker_id {
+            Some(marker_id) => marker_id.clone(),
+            None => {
+                let marker_id = self.upload_revert_marker(entry).await?;
+                self.outbox.set_marker_id(entry, marker_id.as_str())?;
+                marker_id
+            }
+        };
+
+        self.index.mark_non_canonical(entry.block_number, entry.block_hash).await?;
+
+        self.outbox.complete_revert(entry)?;
+        info!(
+            block_number = entry.block_number,
+            block_hash = %entry.block_hash,
+            %marker_id,
+            "Published revert marker"
+        );
+
+        Ok(())
+    }
+
+    async fn upload_revert_marker(&self, entry: &RevertEntry) -> eyre::Result<String> {
+        let marker = serde_json::json!({
+            "block_number": entry.block_number,
+            "block_hash": entry.block_hash,
+            "reverted_by": entry.reverted_by,
+        });
+
+        let mut request = IrysRequest::new();
+        request
+            .set_tag("Content-Type", "application/json")
+            .set_tag("WeaveVM:Type", "Revert-Marker")
+            .set_tag("Block-Number", entry.block_number.to_string().as_str())
+            .set_tag("Block-Hash", entry.block_hash.to_string().as_str())
+            .set_tag("Reverted-By", entry.reverted_by.to_string().as_str())
+            .set_tag("Network", self.network_tag.as_str())
+            .set_data(serde_json::to_vec(&marker)?);
+
+        self.publish(&request, &mut BTreeMap::new()).await
+    }
+
+    async fn archive_dictionary(&self, dictionary: &ZstdDictionary) -> eyre::Result<()> {
+        let hash = dictionary.hash().to_string();
+        let archived = self
+            .storage
+            .query(
+                &TagQuery::new()
+                    .with_tag("WeaveVM:Type", ZSTD_DICTIONARY_TYPE)
+                    .with_tag(ZSTD_DICTIONARY_TAG, hash.as_str())
+                    .with_first(1),
+            )
+            .await;
+        match archived {
+            Ok(archived) if !archived.is_empty() => return Ok(()),
+            Ok(_) => {}
+            // e.g. object stores can't be queried, the dictionary is archived again instead
+            Err(err) => warn!(dictionary = %hash, %err, "Failed to look up zstd dictionary"),
+        }
+
+        let mut request = IrysRequest::new();
+        request
+            .set_tag("Content-Type", "application/octet-stream")
+            .set_tag("WeaveVM:Type", ZSTD_DICTIONARY_TYPE)
+            .set_tag(ZSTD_DICTIONARY_TAG, hash.as_str())
+            .set_tag("Network", self.network_tag.as_str())
+            .set_data(dictionary.raw().to_vec());
+        let dictionary_id = self.publish(&request, &mut BTreeMap::new()).await?;
+        info!(dictionary = %hash, %dictionary_id, "Archived zstd dictionary");
+
+        Ok(())
+    }
+
+    /// Starts the upload of an encoded payload, compressed with the configured codec.
+    fn payload_request(&self, payload: Vec<u8>) -> eyre::Result<IrysRequest> {
+        let mut request = IrysRequest::new();
+        request
+            .set_tag("Content-Type", "application/octet-stream")
+            .set_tag(ENCODING_TAG, self.codec.encoding())
+            .set_tag(FORMAT_VERSION_TAG, CURRENT_FORMAT_VERSION.to_string().as_str())
+            .set_data(self.codec.compress(payload.as_slice())?);
+
+        if let Some(dictionary) = self.codec.dictionary() {
+            request.set_tag(ZSTD_DICTIONARY_TAG, dictionary.hash().to_string().as_str());
+        }
+
+        Ok(request)
+    }
+
+    async fn upload_batch(
+        &self,
+        batch: &BlockBatch,
+        archive_ids: &mut BTreeMap<String, String>,
+    ) -> eyre::Result<String> {
+        let (start, end) =
+            batch.block_range().ok_or_else(|| eyre::eyre!("can't archive an empty batch"))?;
+
+        let mut request = self.payload_request(encode_payload(batch)?)?;
+        request
+            .set_tag("WeaveVM:Type", "Block-Batch")
+            .set_tag("Block-Range-Start", start.to_string().as_str())
+            .set_tag("Block-Range-End", end.to_string().as_str())
+            .set_tag("Network", self.network_tag.as_str());
+
+        // every block is tagged, so blocks in a batch are found by their number like single blocks
+        for entry in batch.entries() {
+            request.set_tag("Block-Number", entry.block_number.to_string().as_str());
+        }
+
+        self.publish(&request, archive_ids).await
+    }
+
+    async fn upload_outcome(&self, outcome: &BlockOutcome) -> eyre::Result<String> {
+        let mut request = self.payload_request(encode_payload(outcome)?)?;
+        request
+            .set_tag("WeaveVM:Type", "Block-Outcome")
+            .set_tag("Block-Number", outcome.block_number.to_string().as_str())
+            .set_tag("Block-Hash", outcome.block_hash.to_string().as_str())
+            .set_tag("Network", self.network_tag.as_str());
+
+        self.publish(&request, &mut BTreeMap::new()).await
+    }
+
+    async fn upload_read_sidecar(
+        &self,
+        block_number: u64,
+        sidecar: &ReadSidecar,
+    ) -> eyre::Result<String> {
+        let mut request = self.payload_request(encode_payload(sidecar)?)?;
+        request
+            .set_tag("WeaveVM:Type", READ_SIDECAR_TYPE)
+            .set_tag("Block-Number", block_number.to_string().as_str())
+            .set_tag(SIDECAR_HASH_TAG, sidecar.commitment().to_string().as_str())
+            .set_tag("Network", self.network_tag.as_str());
+
+        self.publish(&request, &mut BTreeMap::new()).await
+    }
+
+    async fn upload_block(
+        &self,
+        block: &SealedBlockWithSenders,
+        archive_ids: &mut BTreeMap<String, String>,
+    ) -> eyre::Result<String> {
+        let borsh_data = encode_payload(&BorshSealedBlockWithSenders(block.clone()))?;
+
+        let mut request = self.payload_request(borsh_data)?;
+        request
+            .set_tag("Block-Number", block.number.to_string().as_str())
+            .set_tag("Block-Hash", block.hash().to_string().as_str())
+            .set_tag("Network", self.network_tag.as_str());
+
+        self.publish(&request, archive_ids).await
+    }
+
+    /// Uploads an item to every destination without an id in `archive_ids` at once and adds the
+    /// ids of the successful uploads.
+    ///
+    /// Succeeds once the item is stored at the primary storage and a quorum of the destinations and
+    /// returns its id at the primary storage.
+    async fn publish(
+        &self,
+        request: &IrysRequest,
+        archive_ids: &mut BTreeMap<String, String>,
+    ) -> eyre::Result<String> {
+        let missing = self
+            .destinations
+            .iter()
+            .filter(|destination| !archive_ids.contains_key(&destination.name))
+            .collect::<Vec<_>>();
+        let uploads = join_all(
+            missing
+                .iter()
+                .map(|destination| request.send_with_provider(destination.storage.as_ref())),
+        )
+        .await;
+
+        let mut failure = None;
+        for (destination, upload) in missing.into_iter().zip(uploads) {
+            match upload {
+                Ok(id) => {
+                    archive_ids.insert(destination.name.clone(), id);
+                }
+                Err(err) => {
+                    warn!(destination = %destination.name, %err, "Failed to archive item");
+                    failure = Some(err);
+                }
+            }
+        }
+
+        let stored = self
+            .destinations
+            .iter()
+            .filter(|destination| archive_ids.contains_key(&destination.name))
+            .count();
+        let primary_id = match archive_ids.get(PRIMARY_DESTINATION) {
+            Some(primary_id) if stored >= self.quorum() => return Ok(primary_id.clone()),
+            primary_id => primary_id,
+        };
+
+        let err = format!(
+            "item stored at {} of {} destinations, {} required{}",
+            stored,
+            self.destinations.len(),
+            self.quorum(),
+            if primary_id.is_none() { " with the primary storage" } else { "" }
+        );
+        Err(match failure {
+            Some(failure) => failure.wrap_err(err),
+            None => eyre::eyre!(err),
+        })
+    }
+}
+
+/// Returns the heights of the blocks a dropped item archived, `None` for items that archive no
+/// block.
+fn dropped_blocks(dropped: &DroppedUpload) -> Option<Vec<u64>> {
+    let archives_blocks = match dropped.tag("WeaveVM:Type") {
+        Some("Block-Batch") | Some("Block-Outcome") => true,
+        // single blocks carry no type, unlike every other item but precompile uploads
+        None => dropped.tag("WeaveVM:Precompile").is_none() && dropped.tag("Block-Hash").is_some(),
+        Some(_) => false,
+    };
+    if !archives_blocks {
+        return None
+    }
+
+    // batches carry a tag for every block they contain
+    let block_numbers = dropped
+        .tags
+        .iter()
+        .filter(|tag| tag.name == "Block-Number")
+        .filter_map(|tag| tag.value.parse().ok())
+        .collect::<Vec<u64>>();
+
+    Some(block_numbers).filter(|block_numbers| !block_numbers.is_empty())
+}
+
+#[cfg(test)]
+mod archiver_tests {
+    use crate::archiver::{
+        dropped_blocks, ArchiveDestination, ArchiveIndex, Archiver, Backoff, BatchConfig,
+    };
+    use irys::{
+        backend::{DroppedUpload, PermanentStorageBackend, Tag, TagQuery},
+        local::LocalStorage,
+        test_utils::PaidStorage,
+    };
+    use outbox::{archive::ArchiveOutbox, sidecar::ReadSidecarOutbox};
+    use reth::primitives::{
+        BlockHash, Bytes, Header, Receipt, SealedBlock, SealedBlockWithSenders, B256,
+    };
+    use std::{
+        collections::BTreeMap,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc, Mutex,
+        },
+        time::Duration,
+    };
+    use types::types::ExecutionTipState;
+    use wevm_borsh::{
+        block::BorshSealedBlockWithSenders,
+        codec::{Codec, ZstdDictionary, BORSH_ZSTD_ENCODING, ZSTD_DICTIONARY_TAG},
+        format::{encode_payload, DecoderRegistry, PayloadTags, ENCODING_TAG},
+        outcome::BlockOutcome,
+        sidecar::{ReadSidecar, RecordedRead, SIDECAR_HASH_TAG},
+        test_utils::block,
+    };
+
+    #[derive(Default)]
+    struct TestIndex {
+        fail: AtomicBool,
+        recorded: Mutex<Vec<(u64, String)>>,
+        archive_ids: Mutex<Vec<BTreeMap<String, String>>>,
+        non_canonical: Mutex<Vec<BlockHash>>,
+    }
+
+    #[async_trait::async_trait]
+    impl ArchiveIndex for TestIndex {
+        async fn record(&self, state: ExecutionTipState) -> eyre::Result<()> {
+            if self.fail.load(Ordering::SeqCst) {
+                return Err(eyre::eyre!("index unavailable"))
+            }
+
+            self.recorded.lock().unwrap().push((state.block_number, state.arweave_id));
+            self.archive_ids.lock().unwrap().push(state.archive_ids);
+            Ok(())
+        }
+
+        async fn mark_non_canonical(
+            &self,
+            _block_number: u64,
+            block_hash: BlockHash,
+        ) -> eyre::Result<()> {
+            if self.fail.load(Ordering::SeqCst) {
+                return Err(eyre::eyre!("index unavailable"))
+            }
+
+            self.non_canonical.lock().unwrap().push(block_hash);
+            Ok(())
+        }
+    }
+
+    #[test]
+    pub fn test_backoff() {
+        let mut backoff = Backoff::new(Duration::from_secs(1), Duration::from_secs(5));
+        assert_eq!(backoff.fail(), Duration::from_secs(1));
+        assert_eq!(backoff.fail(), Duration::from_secs(2));
+        assert_eq!(backoff.fail(), Duration::from_secs(4));
+        assert_eq!(backoff.fail(), Duration::from_secs(5));
+
+        backoff.reset();
+        assert_eq!(backoff.fail(), Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    pub async fn test_archive_pending_retries_without_reupload() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = Arc::new(LocalStorage::new(dir.path().join("storage")).unwrap());
+        let index = Arc::new(TestIndex::default());
+        let outbox = ArchiveOutbox::new(&dir.path().join("outbox")).unwrap();
+        let mut archiver = Archiver::new(outbox, storage.clone(), index.clone(), "Test")
+            .with_backoff(Backoff::new(Duration::ZERO, Duration::ZERO));
+
+        for number in 1..=2 {
+            archiver.outbox().push(&block(number)).unwrap();
+        }
+
+        index.fail.store(true, Ordering::SeqCst);
+        assert_eq!(archiver.archive_pending().await.unwrap(), None);
+        assert_eq!(archiver.outbox().pending().unwrap(), vec![1, 2]);
+        assert!(archiver.outbox().read(1).unwrap().arweave_id.is_some());
+
+        index.fail.store(false, Ordering::SeqCst);
+        assert_eq!(archiver.archive_pending().await.unwrap(), Some(2));
+        assert!(archiver.outbox().pending().unwrap().is_empty());
+        assert_eq!(archiver.outbox().last_archived().unwrap(), Some(2));
+
+        let recorded = index.recorded.lock().unwrap().clone();
+        assert_eq!(recorded.iter().map(|(number, _)| *number).collect::<Vec<_>>(), vec![1, 2]);
+
+        // the index points at the uploaded block
+        let items = storage.query(&TagQuery::new().with_tag("Block-Number", "1")).await.unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].id, recorded[0].1);
+
+        let data = storage.fetch(items[0].id.as_str()).await.unwrap();
+        let payload = DecoderRegistry::default()
+            .open(PayloadTags::from_fn(|tag| items[0].tag(tag)), &data)
+            .unwrap();
+        assert_eq!(payload.version, 1);
+        assert_eq!(payload.block().unwrap(), block(1));
+    }
+
+    #[tokio::test]
+    pub async fn test_archive_mirrors() {
+        let dir = tempfile::tempdir().unwrap();
+        let primary = Arc::new(PaidStorage::unlimited(&dir.path().join("primary")));
+        let local = Arc::new(PaidStorage::unlimited(&dir.path().join("local")));
+        let s3 = Arc::new(PaidStorage::unlimited(&dir.path().join("s3")));
+        let index = Arc::new(TestIndex::default());
+        let outbox = ArchiveOutbox::new(&dir.path().join("outbox")).unwrap();
+        let mut archiver = Archiver::new(outbox, primary.clone(), index.clone(), "Test")
+            .with_mirrors(vec![
+                ArchiveDestination::new("local", local.clone()),
+                ArchiveDestination::new("s3", s3.clone()),
+            ])
+            .with_quorum(2)
+            .with_backoff(Backoff::new(Duration::ZERO, Duration::ZERO));
+
+        // the mirrors reach the quorum, but items are never archived without the primary storage
+        primary.set_failing(true);
+        archiver.outbox().push(&block(1)).unwrap();
+        assert_eq!(archiver.archive_pending().await.unwrap(), None);
+
+        let entry = archiver.outbox().read(1).unwrap();
+        assert_eq!(entry.arweave_id, None);
+        assert_eq!(entry.archive_ids.keys().collect::<Vec<_>>(), vec!["local", "s3"]);
+        assert!(index.recorded.lock().unwrap().is_empty());
+
+        // the retry only uploads to the primary storage
+        primary.set_failing(false);
+        local.set_failing(true);
+        s3.set_failing(true);
+        assert_eq!(archiver.archive_pending().await.unwrap(), Some(1));
+
+        let archive_ids = index.archive_ids.lock().unwrap()[0].clone();
+        assert_eq!(archive_ids.keys().collect::<Vec<_>>(), vec!["local", "primary", "s3"]);
+        assert_eq!(archive_ids["local"], entry.archive_ids["local"]);
+        assert_eq!(archive_ids["s3"], entry.archive_ids["s3"]);
+        assert_eq!(index.recorded.lock().unwrap()[0].1, archive_ids["primary"]);
+        let items = primary.query(&TagQuery::new().with_tag("Block-Number", "1")).await.unwrap();
+        assert_eq!(items[0].id, archive_ids["primary"]);
+
+        // the primary storage and a mirror reach the quorum
+        local.set_failing(false);
+        archiver.outbox().push(&block(2)).unwrap();
+        assert_eq!(archiver.archive_pending().await.unwrap(), Some(2));
+
+        let archive_ids = index.archive_ids.lock().unwrap()[1].clone();
+        assert_eq!(archive_ids.keys().collect::<Vec<_>>(), vec!["local", "primary"]);
+        assert_eq!(index.recorded.lock().unwrap()[1].1, archive_ids["primary"]);
+        assert!(archiver.outbox().pending().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    pub async fn test_archive_outcome() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = Arc::new(LocalStorage::new(dir.path().join("storage")).unwrap());
+        let outbox = ArchiveOutbox::new(&dir.path().join("outbox")).unwrap();
+        let mut archiver =
+            Archiver::new(outbox, storage.clone(), Arc::new(TestIndex::default()), "Test");
+
+        let block = block(1);
+        let outcome = BlockOutcome {
+            block_number: 1,
+            block_hash: block.hash(),
+            receipts: vec![Some(Receipt::default())],
+            state_diff: None,
+        };
+        archiver.outbox().push_with_outcome(&block, Some(outcome.clone())).unwrap();
+
+        assert_eq!(archiver.archive_pending().await.unwrap(), Some(1));
+
+        let items = storage
+            .query(&TagQuery::new().with_tag("WeaveVM:Type", "Block-Outcome"))
+            .await
+            .unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].tag("Block-Number"), Some("1"));
+
+        let data = storage.fetch(items[0].id.as_str()).await.unwrap();
+        let payload = DecoderRegistry::default()
+            .open(PayloadTags::from_fn(|tag| items[0].tag(tag)), &data)
+            .unwrap();
+        assert_eq!(payload.outcome().unwrap(), outcome);
+    }
+
+    #[tokio::test]
+    pub async fn test_archive_batches() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = Arc::new(LocalStorage::new(dir.path().join("storage")).unwrap());
+        let index = Arc::new(TestIndex::default());
+        let outbox = ArchiveOutbox::new(&dir.path().join("outbox")).unwrap();
+        let mut archiver = Archiver::new(outbox, storage.clone(), index.clone(), "Test")
+            .with_batch(BatchConfig {
+                max_blocks: 3,
+                max_bytes: usize::MAX,
+                max_delay: Duration::from_secs(60 * 60),
+            });
+
+        for number in 1..=4 {
+            archiver.outbox().push(&block(number)).unwrap();
+        }
+
+        // the second batch waits for more blocks
+        assert_eq!(archiver.archive_pending().await.unwrap(), Some(3));
+        assert_eq!(archiver.outbox().pending().unwrap(), vec![4]);
+        assert!(archiver.wake_at().is_some());
+
+        let recorded = index.recorded.lock().unwrap().clone();
+        assert_eq!(recorded.len(), 3);
+        assert!(recorded.iter().all(|(_, id)| *id == recorded[0].1));
+
+        let items = storage.query(&TagQuery::new().with_tag("Block-Number", "2")).await.unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].tag("WeaveVM:Type"), Some("Block-Batch"));
+        assert_eq!(items[0].tag("Block-Range-Start"), Some("1"));
+        assert_eq!(items[0].tag("Block-Range-End"), Some("3"));
+
+        let data = storage.fetch(items[0].id.as_str()).await.unwrap();
+        let payload = DecoderRegistry::default()
+            .open(PayloadTags::from_fn(|tag| items[0].tag(tag)), &data)
+            .unwrap();
+        assert_eq!(payload.batch_block(2).unwrap(), Some(block(2)));
+    }
+
+    #[tokio::test]
+    pub async fn test_archive_batch_retries_missing_destinations() {
+        let dir = tempfile::tempdir().unwrap();
+        let primary = Arc::new(PaidStorage::unlimited(&dir.path().join("primary")));
+        let local = Arc::new(PaidStorage::unlimited(&dir.path().join("local")));
+        let index = Arc::new(TestIndex::default());
+        let outbox = ArchiveOutbox::new(&dir.path().join("outbox")).unwrap();
+        let mut archiver = Archiver::new(outbox, primary.clone(), index.clone(), "Test")
+            .with_mirrors(vec![ArchiveDestination::new("local", local.clone())])
+            .with_batch(BatchConfig {
+                max_blocks: 2,
+                max_bytes: usize::MAX,
+                max_delay: Duration::ZERO,
+            })
+            .with_backoff(Backoff::new(Duration::ZERO, Duration::ZERO));
+
+        for number in 1..=2 {
+            archiver.outbox().push(&block(number)).unwrap();
+        }
+
+        // the batch is short of the quorum, but its upload to the primary storage is kept
+        local.set_failing(true);
+        assert_eq!(archiver.archive_pending().await.unwrap(), None);
+        let stored_ids = archiver.outbox().read(1).unwrap().archive_ids;
+        assert_eq!(stored_ids.keys().collect::<Vec<_>>(), vec!["primary"]);
+        assert_eq!(archiver.outbox().read(2).unwrap().archive_ids, stored_ids);
+        let spent = u128::MAX - primary.remaining();
+
+        // the retry only uploads to the mirror, blocks staged since don't change the batch
+        archiver.outbox().push(&block(3)).unwrap();
+        local.set_failing(false);
+        primary.set_failing(true);
+        assert_eq!(archiver.archive_pending().await.unwrap(), Some(2));
+        assert_eq!(u128::MAX - primary.remaining(), spent);
+        assert_eq!(archiver.outbox().pending().unwrap(), vec![3]);
+
+        let archive_ids = index.archive_ids.lock().unwrap()[0].clone();
+        assert_eq!(archive_ids.keys().collect::<Vec<_>>(), vec!["local", "primary"]);
+        assert_eq!(archive_ids["primary"], stored_ids["primary"]);
+        assert_eq!(index.recorded.lock().unwrap()[1].1, stored_ids["primary"]);
+
+        let items = local.query(&TagQuery::new().with_tag("Block-Number", "2")).await.unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].tag("Block-Range-End"), Some("2"));
+    }
+
+    #[tokio::test]
+    pub async fn test_archive_zstd_dictionary() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = Arc::new(LocalStorage::new(dir.path().join("storage")).unwrap());
+        let outbox = ArchiveOutbox::new(&dir.path().join("outbox")).unwrap();
+
+        let samples = (0..100)
+            .map(|number| encode_payload(&BorshSealedBlockWithSenders(block(number))).unwrap());
+        let dictionary = Arc::new(ZstdDictionary::train(samples, 2048).unwrap());
+        let mut archiver =
+            Archiver::new(outbox, storage.clone(), Arc::new(TestIndex::default()), "Test")
+                .with_codec(Codec::Zstd { level: 3, dictionary: Some(dictionary.clone()) });
+
+        for number in 1..=2 {
+            archiver.outbox().push(&block(number)).unwrap();
+            assert_eq!(archiver.archive_pending().await.unwrap(), Some(number));
+        }
+
+        // the dictionary is archived once, before the first block
+        let dictionaries = storage
+            .query(&TagQuery::new().with_tag("WeaveVM:Type", "Zstd-Dictionary"))
+            .await
+            .unwrap();
+        assert_eq!(dictionaries.len(), 1);
+        let hash = dictionary.hash().to_string();
+        assert_eq!(dictionaries[0].tag(ZSTD_DICTIONARY_TAG), Some(hash.as_str()));
+
+        let items = storage.query(&TagQuery::new().with_tag("Block-Number", "2")).await.unwrap();
+        assert_eq!(items[0].tag(ENCODING_TAG), Some(BORSH_ZSTD_ENCODING));
+        assert_eq!(items[0].tag(ZSTD_DICTIONARY_TAG), Some(hash.as_str()));
+
+        let registry = DecoderRegistry::default();
+        let raw = storage.fetch(dictionaries[0].id.as_str()).await.unwrap();
+        registry.add_dictionary(Arc::new(ZstdDictionary::new(raw)));
+
+        let data = storage.fetch(items[0].id.as_str()).await.unwrap();
+        let payload = registry.open(PayloadTags::from_fn(|tag| items[0].tag(tag)), &data).unwrap();
+        assert_eq!(payload.block().unwrap(), block(2));
+    }
+
+    #[tokio::test]
+    pub async fn test_archive_read_sidecar() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = Arc::new(LocalStorage::new(dir.path().join("storage")).unwrap());
+        let outbox = ArchiveOutbox::new(&dir.path().join("outbox")).unwrap();
+        let sidecars = ReadSidecarOutbox::new(&dir.path().join("sidecars")).unwrap();
+        let mut archiver =
+            Archiver::new(outbox, storage.clone(), Arc::new(TestIndex::default()), "Test")
+                .with_read_sidecars(sidecars.clone());
+
+        let sidecar = ReadSidecar {
+            reads: vec![RecordedRead {
+                precompile: "arweave-read".to_string(),
+                input: b"tx".to_vec(),
+                output: Ok(b"data".to_vec()),
+            }],
+        };
+        let commitment = sidecars.stage(1, &sidecar).unwrap();
+        let header = Header {
+            number: 1,
+            extra_data: Bytes::copy_from_slice(commitment.as_slice()),
+            ..Default::default()
+        };
+        let block = SealedBlockWithSenders {
+            block: SealedBlock { header: header.seal_slow(), ..Default::default() },
+            senders: vec![],
+        };
+
+        archiver.confirm_read_sidecar(&block).unwrap();
+        archiver.outbox().push(&block).unwrap();
+        assert_eq!(archiver.archive_pending().await.unwrap(), Some(1));
+        assert!(sidecars.ready().unwrap().is_empty());
+
+        let items =
+            storage.query(&TagQuery::new().with_tag("WeaveVM:Type", "Read-Sidecar")).await.unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].tag("Block-Number"), Some("1"));
+        assert_eq!(items[0].tag(SIDECAR_HASH_TAG), Some(commitment.to_string().as_str()));
+
+        let data = storage.fetch(items[0].id.as_str()).await.unwrap();
+        let payload = DecoderRegistry::default()
+            .open(PayloadTags::from_fn(|tag| items[0].tag(tag)), &data)
+            .unwrap();
+        assert_eq!(payload.read_sidecar().unwrap(), sidecar);
+    }
+
+    #[tokio::test]
+    pub async fn test_revert_marker() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = Arc::new(LocalStorage::new(dir.path().join("storage")).unwrap());
+        let index = Arc::new(TestIndex::default());
+        let outbox = ArchiveOutbox::new(&dir.path().join("outbox")).unwrap();
+        let mut archiver = Archiver::new(outbox, storage.clone(), index.clone(), "Test")
+            .with_backoff(Backoff::new(Duration::ZERO, Duration::ZERO));
+
+        let reverted = block(1);
+        archiver.outbox().push(&reverted).unwrap();
+        assert_eq!(archiver.archive_pending().await.unwrap(), Some(1));
+
+        let reverted_by = B256::random();
+        archiver.outbox().revert(&reverted, reverted_by).unwrap();
+        assert_eq!(archiver.archive_pending().await.unwrap(), None);
+        assert!(archiver.outbox().reverted().unwrap().is_empty());
+        assert_eq!(index.non_canonical.lock().unwrap().clone(), vec![reverted.hash()]);
+
+        let markers = storage
+            .query(&TagQuery::new().with_tag("WeaveVM:Type", "Revert-Marker"))
+            .await
+            .unwrap();
+        assert_eq!(markers.len(), 1);
+        assert_eq!(markers[0].tag("Block-Number"), Some("1"));
+        assert_eq!(markers[0].tag("Block-Hash"), Some(reverted.hash().to_string().as_str()));
+        assert_eq!(markers[0].tag("Reverted-By"), Some(reverted_by.to_string().as_str()));
+    }
+
+    #[test]
+    pub fn test_dropped_blocks() {
+        let dropped = |tags: &[(&str, &str)]| DroppedUpload {
+            id: "id".to_string(),
+            tags: tags.iter().map(|(name, value)| Tag::new(name, value)).collect(),
+        };
+
+        let single = dropped(&[("Block-Number", "7"), ("Block-Hash", "0x01")]);
+        assert_eq!(dropped_blocks(&single), Some(vec![7]));
+
+        let batch = dropped(&[
+            ("WeaveVM:Type", "Block-Batch"),
+            ("Block-Number", "7"),
+            ("Block-Number", "8"),
+        ]);
+        assert_eq!(dropped_blocks(&batch), Some(vec![7, 8]));
+
+        let outcome = dropped(&[("WeaveVM:Type", "Block-Outcome"), ("Block-Number", "7")]);
+        assert_eq!(dropped_blocks(&outcome), Some(vec![7]));
+
+        let precompile = dropped(&[("WeaveVM:Precompile", "true"), ("Block-Number", "7")]);
+        assert_eq!(dropped_blocks(&precompile), None);
+
+        let marker = dropped(&[
+            ("WeaveVM:Type", "Revert-Marker"),
+            ("Block-Number", "7"),
+            ("Block-Hash", "0x01"),
+        ]);
+        assert_eq!(dropped_blocks(&marker), None);
+    }
+
+    #[tokio::test]
+    pub async fn test_archive_again_keeps_finished_height() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = Arc::new(LocalStorage::new(dir.path().join("storage")).unwrap());
+        let index = Arc::new(TestIndex::default());
+        let outbox = ArchiveOutbox::new(&dir.path().join("outbox")).unwrap();
+        let mut archiver = Archiver::new(outbox, storage, index.clone(), "Test")
+            .with_backoff(Backoff::new(Duration::ZERO, Duration::ZERO));
+
+        for number in 1..=3 {
+            archiver.outbox().push(&block(number)).unwrap();
+        }
+        assert_eq!(archiver.archive_pending().await.unwrap(), Some(3));
+
+        // a block whose upload was dropped is staged again
+        archiver.outbox().push(&block(2)).unwrap();
+        assert_eq!(archiver.archive_pending().await.unwrap(), Some(3));
+        assert_eq!(archiver.outbox().last_archived().unwrap(), Some(3));
+
+        let recorded = index.recorded.lock().unwrap().clone();
+        let numbers = recorded.iter().map(|(number, _)| *number).collect::<Vec<_>>();
+        assert_eq!(numbers, vec![1, 2, 3, 2]);
+        assert!(archiver.outbox().pending().unwrap().is_empty());
+    }
+}
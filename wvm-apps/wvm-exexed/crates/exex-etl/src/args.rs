@@ -0,0 +1,356 @@
+use crate::archiver::{ArchiveDestination, BatchConfig, OutcomeConfig, MAX_BATCH_BLOCKS};
+use clap::{Args, ValueEnum};
+use irys::{
+    args::{StorageArgs, StorageBackendKind},
+    signer::KeyType,
+};
+use reth_cli_util::parse_duration_from_secs;
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+use wevm_borsh::codec::{Codec, ZstdDictionary};
+
+/// Compression of archived items.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ArchiveCodec {
+    #[default]
+    Brotli,
+    Zstd,
+    None,
+}
+
+/// Parameters of the archive ExEx.
+#[derive(Debug, Clone, Args)]
+#[command(next_help_heading = "WVM Archive")]
+pub struct ArchiveArgs {
+    /// Maximum number of blocks archived as one item, `1` archives every block on its own.
+    #[arg(
+        long = "wvm.archive.batch-blocks",
+        value_name = "BLOCKS",
+        default_value_t = 1,
+        value_parser = clap::value_parser!(u64).range(1..=MAX_BATCH_BLOCKS as u64)
+    )]
+    pub batch_blocks: u64,
+
+    /// Size budget of the encoded blocks of one batch, the batch is closed once it is reached.
+    #[arg(long = "wvm.archive.batch-bytes", value_name = "BYTES", default_value_t = 4 * 1024 * 1024)]
+    pub batch_bytes: usize,
+
+    /// Maximum time a block waits for its batch to fill up.
+    #[arg(
+        long = "wvm.archive.batch-delay",
+        value_name = "SECONDS",
+        value_parser = parse_duration_from_secs,
+        default_value = "30"
+    )]
+    pub batch_delay: Duration,
+
+    /// Archive the receipts and logs of every block.
+    #[arg(long = "wvm.archive.receipts")]
+    pub receipts: bool,
+
+    /// Archive the account and storage changes of every block.
+    #[arg(long = "wvm.archive.state-diffs")]
+    pub state_diffs: bool,
+
+    /// Compression of archived items.
+    #[arg(long = "wvm.archive.codec", value_name = "CODEC", value_enum, default_value_t)]
+    pub codec: ArchiveCodec,
+
+    /// Brotli quality, from 0 to 11.
+    #[arg(
+        long = "wvm.archive.brotli-quality",
+        value_name = "QUALITY",
+        default_value_t = rbrotli::DEFAULT_QUALITY,
+        value_parser = clap::value_parser!(u8).range(0..=11)
+    )]
+    pub brotli_quality: u8,
+
+    /// Base two logarithm of the Brotli window size, from 10 to 24.
+    #[arg(
+        long = "wvm.archive.brotli-window",
+        value_name = "BITS",
+        default_value_t = rbrotli::DEFAULT_WINDOW,
+        value_parser = clap::value_parser!(u8).range(10..=24)
+    )]
+    pub brotli_window: u8,
+
+    /// Zstandard compression level, `0` uses the default level of zstd.
+    #[arg(long = "wvm.archive.zstd-level", value_name = "LEVEL", default_value_t = 0)]
+    pub zstd_level: i32,
+
+    /// Zstandard dictionary to compress with, e.g. trained on previously archived blocks.
+    ///
+    /// It is archived before the first item compressed with it, so readers can fetch it.
+    #[arg(long = "wvm.archive.zstd-dictionary", value_name = "PATH")]
+    pub zstd_dictionary: Option<PathBuf>,
+
+    /// Backends every item is archived to next to `--wvm.storage`, configured by the same
+    /// `--wvm.storage.*` parameters.
+    ///
+    /// All destinations sign with the one configured key, so `irys` and `arweave` can only be
+    /// combined with an Arweave key (`--wvm.storage.key-type arweave`).
+    #[arg(long = "wvm.archive.mirror", value_name = "BACKEND", value_enum, value_delimiter = ',')]
+    pub mirrors: Vec<StorageBackendKind>,
+
+    /// Number of destinations an item has to be stored at before it counts as archived, defaults
+    /// to all of them. `--wvm.storage` is always one of them.
+    #[arg(
+        long = "wvm.archive.quorum",
+        value_name = "DESTINATIONS",
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    pub quorum: Option<u64>,
+
+    /// First block archived on the very first start, the archive is backfilled from it.
+    #[arg(long = "wvm.archive.start-block", value_name = "BLOCK", default_value_t = 0)]
+    pub start_block: u64,
+}
+
+impl Default for ArchiveArgs {
+    fn default() -> Self {
+        let batch = BatchConfig::default();
+        ArchiveArgs {
+            batch_blocks: batch.max_blocks as u64,
+            batch_bytes: batch.max_bytes,
+            batch_delay: batch.max_delay,
+            receipts: false,
+            state_diffs: false,
+            codec: ArchiveCodec::default(),
+            brotli_quality: rbrotli::DEFAULT_QUALITY,
+            brotli_window: rbrotli::DEFAULT_WINDOW,
+            zstd_level: 0,
+            zstd_dictionary: None,
+            mirrors: vec![],
+            quorum: None,
+            start_block: 0,
+        }
+    }
+}
+
+impl ArchiveArgs {
+    pub fn batch_config(&self) -> BatchConfig {
+        BatchConfig {
+            max_blocks: self.batch_blocks as usize,
+            max_bytes: self.batch_bytes,
+            max_delay: self.batch_delay,
+        }
+    }
+
+    pub fn outcome_config(&self) -> OutcomeConfig {
+        OutcomeConfig { receipts: self.receipts, state_diffs: self.state_diffs }
+    }
+
+    /// Returns the configured codec, loading the Zstandard dictionary if one is set.
+    pub fn codec(&self) -> eyre::Result<Codec> {
+        let codec = match self.codec {
+            ArchiveCodec::None => Codec::None,
+            ArchiveCodec::Brotli => {
+                Codec::Brotli { quality: self.brotli_quality, window: self.brotli_window }
+            }
+            ArchiveCodec::Zstd => {
+                let dictionary = match &self.zstd_dictionary {
+                    Some(path) => {
+                        let raw = std::fs::read(path).map_err(|err| {
+                            eyre::eyre!("failed to read zstd dictionary {}: {err}", path.display())
+                        })?;
+                        Some(Arc::new(ZstdDictionary::new(raw)))
+                    }
+                    None => None,
+                };
+                Codec::Zstd { level: self.zstd_level, dictionary }
+            }
+        };
+
+        Ok(codec)
+    }
+
+    /// Creates the mirrors of the archive, named by their backend.
+    pub fn mirrors(
+        &self,
+        storage: &StorageArgs,
+        datadir: &Path,
+    ) -> eyre::Result<Vec<ArchiveDestination>> {
+        let archives_to = |kind| storage.backend == kind || self.mirrors.contains(&kind);
+        if archives_to(StorageBackendKind::Irys) &&
+            archives_to(StorageBackendKind::Arweave) &&
+            storage.key_type != KeyType::Arweave
+        {
+            return Err(eyre::eyre!(
+                "irys and arweave share the upload key, which requires \
+                 `--wvm.storage.key-type arweave`"
+            ))
+        }
+
+        let mut mirrors: Vec<ArchiveDestination> = vec![];
+        for kind in self.mirrors.iter().copied() {
+            if kind == storage.backend || mirrors.iter().any(|mirror| mirror.name == kind.name()) {
+                return Err(eyre::eyre!("{} is archived to more than once", kind.name()))
+            }
+            mirrors.push(ArchiveDestination::new(kind.name(), storage.backend_of(kind, datadir)?));
+        }
+
+        Ok(mirrors)
+    }
+
+    /// Returns the quorum of destinations, all of them by default.
+    pub fn quorum(&self) -> eyre::Result<usize> {
+        let destinations = self.mirrors.len() + 1;
+        match self.quorum {
+            Some(quorum) if quorum as usize > destinations => Err(eyre::eyre!(
+                "quorum of {} exceeds the {} archive destinations",
+                quorum,
+                destinations
+            )),
+            Some(quorum) => Ok(quorum as usize),
+            None => Ok(destinations),
+        }
+    }
+}
+
+#[cfg(test)]
+mod args_tests {
+    use crate::args::ArchiveArgs;
+    use clap::{Args, Parser};
+    use irys::{
+        args::{StorageArgs, StorageBackendKind},
+        signer::KeyType,
+    };
+    use std::time::Duration;
+    use wevm_borsh::codec::Codec;
+
+    #[derive(Parser)]
+    struct CommandParser<T: Args> {
+        #[command(flatten)]
+        args: T,
+    }
+
+    #[test]
+    pub fn test_parse_archive_args() {
+        let args = CommandParser::<ArchiveArgs>::parse_from(["reth"]).args;
+        assert_eq!(args.batch_config().max_blocks, 1);
+        assert!(!args.outcome_config().is_enabled());
+        assert_eq!(args.start_block, 0);
+
+        let args = CommandParser::<ArchiveArgs>::parse_from([
+            "reth",
+            "--wvm.archive.batch-blocks",
+            "10",
+            "--wvm.archive.batch-delay",
+            "5",
+            "--wvm.archive.receipts",
+            "--wvm.archive.start-block",
+            "100",
+        ])
+        .args;
+        assert_eq!(args.start_block, 100);
+        assert_eq!(args.batch_config().max_blocks, 10);
+        assert_eq!(args.batch_config().max_delay, Duration::from_secs(5));
+        assert!(args.outcome_config().receipts);
+        assert!(!args.outcome_config().state_diffs);
+
+        assert!(CommandParser::<ArchiveArgs>::try_parse_from([
+            "reth",
+            "--wvm.archive.batch-blocks",
+            "1000",
+        ])
+        .is_err());
+    }
+
+    #[test]
+    pub fn test_parse_codec_args() {
+        let args = CommandParser::<ArchiveArgs>::parse_from(["reth"]).args;
+        assert!(matches!(args.codec().unwrap(), Codec::Brotli { quality: 11, window: 22 }));
+
+        let args = CommandParser::<ArchiveArgs>::parse_from([
+            "reth",
+            "--wvm.archive.codec",
+            "brotli",
+            "--wvm.archive.brotli-quality",
+            "5",
+        ])
+        .args;
+        assert!(matches!(args.codec().unwrap(), Codec::Brotli { quality: 5, window: 22 }));
+
+        let args =
+            CommandParser::<ArchiveArgs>::parse_from(["reth", "--wvm.archive.codec", "none"]).args;
+        assert!(matches!(args.codec().unwrap(), Codec::None));
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("blocks.dict");
+        std::fs::write(&path, b"dictionary").unwrap();
+        let args = CommandParser::<ArchiveArgs>::parse_from([
+            "reth",
+            "--wvm.archive.codec",
+            "zstd",
+            "--wvm.archive.zstd-level",
+            "19",
+            "--wvm.archive.zstd-dictionary",
+            path.to_str().unwrap(),
+        ])
+        .args;
+        let codec = args.codec().unwrap();
+        assert!(matches!(codec, Codec::Zstd { level: 19, .. }));
+        assert_eq!(codec.dictionary().unwrap().raw(), b"dictionary");
+
+        assert!(CommandParser::<ArchiveArgs>::try_parse_from([
+            "reth",
+            "--wvm.archive.brotli-quality",
+            "12",
+        ])
+        .is_err());
+    }
+
+    #[test]
+    pub fn test_parse_mirror_args() {
+        let args = CommandParser::<ArchiveArgs>::parse_from(["reth"]).args;
+        assert!(args.mirrors.is_empty());
+        assert_eq!(args.quorum().unwrap(), 1);
+
+        let args = CommandParser::<ArchiveArgs>::parse_from([
+            "reth",
+            "--wvm.archive.mirror",
+            "local,arweave",
+            "--wvm.archive.quorum",
+            "2",
+        ])
+        .args;
+        assert_eq!(args.mirrors, vec![StorageBackendKind::Local, StorageBackendKind::Arweave]);
+        assert_eq!(args.quorum().unwrap(), 2);
+
+        let dir = tempfile::tempdir().unwrap();
+        let storage = StorageArgs {
+            backend: StorageBackendKind::Local,
+            local_dir: Some(dir.path().to_path_buf()),
+            ..Default::default()
+        };
+        let args =
+            CommandParser::<ArchiveArgs>::parse_from(["reth", "--wvm.archive.mirror", "arweave"])
+                .args;
+        let mirrors = args.mirrors(&storage, dir.path()).unwrap();
+        assert_eq!(mirrors.len(), 1);
+        assert_eq!(mirrors[0].name, "arweave");
+        assert_eq!(args.quorum().unwrap(), 2);
+
+        // the configured backend is no mirror of itself
+        let args =
+            CommandParser::<ArchiveArgs>::parse_from(["reth", "--wvm.archive.mirror", "local"])
+                .args;
+        assert!(args.mirrors(&storage, dir.path()).is_err());
+
+        // irys and arweave share the key, which has to be an arweave key
+        let args =
+            CommandParser::<ArchiveArgs>::parse_from(["reth", "--wvm.archive.mirror", "arweave"])
+                .args;
+        let storage = StorageArgs { backend: StorageBackendKind::Irys, ..Default::default() };
+        assert!(args.mirrors(&storage, dir.path()).is_err());
+        let storage = StorageArgs { key_type: KeyType::Arweave, ..storage };
+        assert_eq!(args.mirrors(&storage, dir.path()).unwrap().len(), 1);
+
+        let args =
+            CommandParser::<ArchiveArgs>::parse_from(["reth", "--wvm.archive.quorum", "2"]).args;
+        assert!(args.quorum().is_err());
+    }
+}
@@ -1 +1,4 @@
+pub mod archiver;
+pub mod args;
+pub mod precompile_uploads;
 pub mod state_processor;
@@ -0,0 +1,63 @@
+use irys::{backend::PermanentStorageBackend, irys::IrysRequest};
+use outbox::upload::{UploadOutbox, UPLOAD_PRECOMPILE_ADDRESS};
+use reth::{
+    primitives::{Address, SealedBlockWithSenders},
+    providers::Chain,
+};
+use reth_tracing::tracing::{info, warn};
+use std::collections::HashSet;
+
+/// Confirms the staged precompile uploads of every block in a committed chain and uploads all
+/// confirmed payloads.
+///
+/// Uploads stop at the first failure, the failed and the remaining uploads stay in the outbox and
+/// are retried with the next committed chain.
+pub async fn process_committed_chain(
+    outbox: &UploadOutbox,
+    chain: &Chain,
+    storage: &dyn PermanentStorageBackend,
+    network_tag: &str,
+) -> eyre::Result<()> {
+    for block in chain.blocks_iter() {
+        let confirmed =
+            outbox.confirm_block(block.number, block.hash(), &included_transactions(block))?;
+        if confirmed > 0 {
+            info!(block_number = block.number, confirmed, "Confirmed precompile uploads");
+        }
+    }
+
+    for (key, record) in outbox.ready()? {
+        let res = IrysRequest::new()
+            .set_tag("Content-Type", "application/octet-stream")
+            .set_tag("WeaveVM:Precompile", "true")
+            .set_tag("WeaveVM:Precompile-Address", UPLOAD_PRECOMPILE_ADDRESS.to_string().as_str())
+            .set_tag("WeaveVM:Content-Id", record.content_id.to_string().as_str())
+            .set_tag("Block-Number", record.block_number.to_string().as_str())
+            .set_tag("Network", network_tag)
+            .set_data(record.data)
+            .send_with_provider(storage)
+            .await;
+
+        match res {
+            Ok(arweave_id) => {
+                info!(content_id = %record.content_id, %arweave_id, "Uploaded precompile data");
+                outbox.complete(key.as_str())?;
+            }
+            Err(err) => {
+                // the storage is likely unavailable or out of budget, the rest waits as well
+                warn!(
+                    content_id = %record.content_id,
+                    %err,
+                    "Failed to upload precompile data, will retry"
+                );
+                break
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn included_transactions(block: &SealedBlockWithSenders) -> HashSet<(Address, u64)> {
+    block.transactions_with_sender().map(|(sender, tx)| (*sender, tx.nonce())).collect()
+}
@@ -0,0 +1,383 @@
+use crate::{
+    arweave::{ArweaveProvider, ARWEAVE_PENDING_FILE},
+    backend::PermanentStorageBackend,
+    budget::{BudgetedStorage, UploadBudget, UPLOAD_SPENDS_DIR},
+    gateway::{ArweaveGateway, DEFAULT_ARWEAVE_GATEWAY},
+    irys::{IrysProvider, DEFAULT_IRYS_NODE},
+    local::LocalStorage,
+    s3::{S3Storage, DEFAULT_S3_REGION},
+    signer::{KeyType, LocalSigner, UploadSigner},
+};
+use clap::{Args, ValueEnum};
+use reth_cli_util::parse_duration_from_secs;
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+/// Permanent storage backends selectable from the command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum StorageBackendKind {
+    /// Upload through Irys, read through an Arweave gateway.
+    Irys,
+    /// Upload layer 1 transactions through an Arweave gateway and read through it, requires an
+    /// Arweave key.
+    Arweave,
+    /// Keep all items in a local directory, no network access.
+    Local,
+    /// Keep all items in a bucket of an S3 compatible object store, which can't be queried by
+    /// tags, so it only mirrors the archive. Credentials are read from `AWS_ACCESS_KEY_ID` and
+    /// `AWS_SECRET_ACCESS_KEY`.
+    S3,
+}
+
+impl StorageBackendKind {
+    /// Name of the kind on the command line.
+    pub fn name(&self) -> &'static str {
+        match self {
+            StorageBackendKind::Irys => "irys",
+            StorageBackendKind::Arweave => "arweave",
+            StorageBackendKind::Local => "local",
+            StorageBackendKind::S3 => "s3",
+        }
+    }
+}
+
+/// Parameters for the permanent storage the node archives to and reads from.
+#[derive(Debug, Clone, Args)]
+#[command(next_help_heading = "WVM Storage")]
+pub struct StorageArgs {
+    /// Backend used to store archived data and to serve the read precompiles, any but `s3`.
+    #[arg(long = "wvm.storage", value_enum, default_value_t = StorageBackendKind::Irys)]
+    pub backend: StorageBackendKind,
+
+    /// Directory of the local backend, defaults to `<DATADIR>/wvm/local-storage`.
+    #[arg(long = "wvm.storage.local-dir", value_name = "PATH")]
+    pub local_dir: Option<PathBuf>,
+
+    /// Endpoint of the object store of the s3 backend, e.g. `https://s3.us-east-1.amazonaws.com`.
+    #[arg(long = "wvm.storage.s3-endpoint", value_name = "URL")]
+    pub s3_endpoint: Option<String>,
+
+    /// Bucket of the s3 backend.
+    #[arg(long = "wvm.storage.s3-bucket", value_name = "BUCKET")]
+    pub s3_bucket: Option<String>,
+
+    /// Region requests to the object store are signed for.
+    #[arg(
+        long = "wvm.storage.s3-region",
+        value_name = "REGION",
+        default_value = DEFAULT_S3_REGION
+    )]
+    pub s3_region: String,
+
+    /// Prefix of the keys of all objects stored by the s3 backend.
+    #[arg(long = "wvm.storage.s3-prefix", value_name = "PREFIX", default_value = "")]
+    pub s3_prefix: String,
+
+    /// Irys node uploads are sent to.
+    #[arg(
+        long = "wvm.storage.irys-node",
+        value_name = "URL",
+        default_value = DEFAULT_IRYS_NODE
+    )]
+    pub irys_node: String,
+
+    /// Arweave gateway used to fetch and query items, and to post transactions with the
+    /// `arweave` backend.
+    #[arg(
+        long = "wvm.storage.gateway",
+        value_name = "URL",
+        default_value = DEFAULT_ARWEAVE_GATEWAY
+    )]
+    pub gateway: String,
+
+    /// Type of the key uploads are signed with, Irys charges in the currency of the key.
+    #[arg(long = "wvm.storage.key-type", value_enum, default_value_t = KeyType::Solana)]
+    pub key_type: KeyType,
+
+    /// File with the key uploads are signed with, must only be readable by its owner.
+    #[arg(
+        long = "wvm.storage.key-file",
+        value_name = "PATH",
+        conflicts_with_all = ["key_env", "remote_signer"]
+    )]
+    pub key_file: Option<PathBuf>,
+
+    /// Environment variable holding the key uploads are signed with.
+    #[arg(
+        long = "wvm.storage.key-env",
+        value_name = "VAR",
+        conflicts_with_all = ["key_file", "remote_signer"]
+    )]
+    pub key_env: Option<String>,
+
+    /// Unix socket of a remote signer uploads are signed by, the node never sees its key.
+    #[arg(
+        long = "wvm.storage.remote-signer",
+        value_name = "SOCKET",
+        conflicts_with_all = ["key_file", "key_env"]
+    )]
+    pub remote_signer: Option<PathBuf>,
+
+    /// Maximum spend on uploads within any hour, in the smallest unit of the currency of the key.
+    ///
+    /// Uploads beyond the budget are queued locally until it allows them again. Spends are kept
+    /// below the datadir, so a restart does not reset the budget.
+    #[arg(long = "wvm.storage.budget-hourly", value_name = "AMOUNT")]
+    pub budget_hourly: Option<u128>,
+
+    /// Maximum spend on uploads within any day, in the smallest unit of the currency of the key.
+    #[arg(long = "wvm.storage.budget-daily", value_name = "AMOUNT")]
+    pub budget_daily: Option<u128>,
+
+    /// Balance kept at the bundler, uploads that would spend it are queued locally instead.
+    #[arg(long = "wvm.storage.min-balance", value_name = "AMOUNT", default_value_t = 0)]
+    pub min_balance: u128,
+
+    /// How often the balance at the bundler is queried.
+    #[arg(
+        long = "wvm.storage.balance-interval",
+        value_name = "SECONDS",
+        value_parser = parse_duration_from_secs,
+        default_value = "60"
+    )]
+    pub balance_interval: Duration,
+}
+
+impl Default for StorageArgs {
+    fn default() -> Self {
+        StorageArgs {
+            backend: StorageBackendKind::Irys,
+            local_dir: None,
+            s3_endpoint: None,
+            s3_bucket: None,
+            s3_region: DEFAULT_S3_REGION.to_string(),
+            s3_prefix: String::new(),
+            irys_node: DEFAULT_IRYS_NODE.to_string(),
+            gateway: DEFAULT_ARWEAVE_GATEWAY.to_string(),
+            key_type: KeyType::Solana,
+            key_file: None,
+            key_env: None,
+            remote_signer: None,
+            budget_hourly: None,
+            budget_daily: None,
+            min_balance: 0,
+            balance_interval: UploadBudget::default().balance_ttl,
+        }
+    }
+}
+
+impl StorageArgs {
+    /// Creates the configured backend, `datadir` is used to resolve default paths.
+    ///
+    /// Uploads of every backend go through a [`BudgetedStorage`] enforcing the configured budget.
+    pub fn backend(&self, datadir: &Path) -> eyre::Result<Arc<dyn PermanentStorageBackend>> {
+        // archived items are looked up by their tags
+        if self.backend == StorageBackendKind::S3 {
+            return Err(eyre::eyre!(
+                "the s3 backend can't be queried and only mirrors the archive, see \
+                 `--wvm.archive.mirror`"
+            ))
+        }
+
+        self.backend_of(self.backend, datadir)
+    }
+
+    /// Creates a backend of another kind from the same parameters, e.g. to mirror the archive.
+    ///
+    /// The upload metrics of backends other than the configured one are labeled with their kind.
+    pub fn backend_of(
+        &self,
+        kind: StorageBackendKind,
+        datadir: &Path,
+    ) -> eyre::Result<Arc<dyn PermanentStorageBackend>> {
+        // the gateway serves the data items the node uploaded through irys
+        let gateway = ArweaveGateway::new(self.gateway.as_str()).with_trusted_data_items(true);
+        let backend: Arc<dyn PermanentStorageBackend> = match kind {
+            StorageBackendKind::Irys => {
+                let mut irys = IrysProvider::new()
+                    .with_node_url(self.irys_node.as_str())
+                    .with_gateway(gateway);
+                if let Some(signer) = self.signer()? {
+                    irys = irys.with_signer(signer);
+                }
+                Arc::new(irys)
+            }
+            StorageBackendKind::Arweave => {
+                // dropped transactions are reported after a restart as well
+                let mut arweave = ArweaveProvider::new(gateway)
+                    .with_pending_file(datadir.join(ARWEAVE_PENDING_FILE))?;
+                if let Some(signer) = self.signer()? {
+                    // remote signers report their key type with the first upload
+                    if self.remote_signer.is_none() && self.key_type != KeyType::Arweave {
+                        return Err(eyre::eyre!(
+                            "the arweave backend requires `--wvm.storage.key-type arweave`"
+                        ))
+                    }
+                    arweave = arweave.with_signer(signer);
+                }
+                Arc::new(arweave)
+            }
+            StorageBackendKind::Local => Arc::new(match &self.local_dir {
+                Some(dir) => LocalStorage::new(dir)?,
+                None => LocalStorage::at_datadir(datadir)?,
+            }),
+            StorageBackendKind::S3 => {
+                let (Some(endpoint), Some(bucket)) = (&self.s3_endpoint, &self.s3_bucket) else {
+                    return Err(eyre::eyre!(
+                        "the s3 backend requires `--wvm.storage.s3-endpoint` and \
+                         `--wvm.storage.s3-bucket`"
+                    ))
+                };
+                Arc::new(
+                    S3Storage::from_env(endpoint, bucket)?
+                        .with_region(self.s3_region.as_str())
+                        .with_prefix(self.s3_prefix.as_str()),
+                )
+            }
+        };
+
+        let budget = self.budget();
+        let mut storage = BudgetedStorage::new(backend, budget);
+        if budget.hourly.is_some() || budget.daily.is_some() {
+            // a restart must not reset the spends of the last day
+            let spends_file = datadir.join(UPLOAD_SPENDS_DIR).join(format!("{}.json", kind.name()));
+            storage = storage.with_spends_file(spends_file)?;
+        }
+        if kind == self.backend {
+            return Ok(Arc::new(storage))
+        }
+
+        Ok(Arc::new(storage.with_destination(kind.name())))
+    }
+
+    pub fn budget(&self) -> UploadBudget {
+        UploadBudget {
+            hourly: self.budget_hourly,
+            daily: self.budget_daily,
+            min_balance: self.min_balance,
+            balance_ttl: self.balance_interval,
+        }
+    }
+
+    /// Creates the signer of uploads, if a key is configured.
+    ///
+    /// Without a signer the node can still read, but uploads fail.
+    pub fn signer(&self) -> eyre::Result<Option<Arc<dyn UploadSigner>>> {
+        if let Some(socket) = &self.remote_signer {
+            #[cfg(unix)]
+            return Ok(Some(Arc::new(crate::signer::RemoteSigner::new(socket))));
+            #[cfg(not(unix))]
+            return Err(eyre::eyre!("remote signer {} requires unix sockets", socket.display()))
+        }
+
+        let signer = match (&self.key_file, &self.key_env) {
+            (Some(path), _) => LocalSigner::from_file(self.key_type, path)?,
+            (None, Some(var)) => LocalSigner::from_env(self.key_type, var)?,
+            (None, None) => return Ok(None),
+        };
+
+        Ok(Some(Arc::new(signer)))
+    }
+}
+
+#[cfg(test)]
+mod args_tests {
+    use crate::{
+        args::{StorageArgs, StorageBackendKind},
+        signer::KeyType,
+    };
+    use clap::{Args, Parser};
+    use std::{path::Path, time::Duration};
+
+    #[derive(Parser)]
+    struct CommandParser<T: Args> {
+        #[command(flatten)]
+        args: T,
+    }
+
+    #[test]
+    pub fn test_parse_storage_args() {
+        let args = CommandParser::<StorageArgs>::parse_from(["reth"]).args;
+        assert_eq!(args.backend, StorageBackendKind::Irys);
+
+        let args = CommandParser::<StorageArgs>::parse_from([
+            "reth",
+            "--wvm.storage",
+            "local",
+            "--wvm.storage.local-dir",
+            "/tmp/wvm",
+        ])
+        .args;
+        assert_eq!(args.backend, StorageBackendKind::Local);
+        assert_eq!(args.local_dir.unwrap().to_str(), Some("/tmp/wvm"));
+        assert!(args.signer().unwrap().is_none());
+
+        let args = CommandParser::<StorageArgs>::parse_from([
+            "reth",
+            "--wvm.storage.key-type",
+            "arweave",
+            "--wvm.storage.key-file",
+            "/etc/wvm/wallet.json",
+        ])
+        .args;
+        assert_eq!(args.key_type, KeyType::Arweave);
+        assert_eq!(args.key_file.unwrap().to_str(), Some("/etc/wvm/wallet.json"));
+
+        let args =
+            CommandParser::<StorageArgs>::parse_from(["reth", "--wvm.storage", "arweave"]).args;
+        assert_eq!(args.backend, StorageBackendKind::Arweave);
+
+        let args = CommandParser::<StorageArgs>::parse_from([
+            "reth",
+            "--wvm.storage",
+            "s3",
+            "--wvm.storage.s3-endpoint",
+            "http://localhost:9000",
+            "--wvm.storage.s3-bucket",
+            "wvm-archive",
+        ])
+        .args;
+        assert_eq!(args.backend, StorageBackendKind::S3);
+        assert_eq!(args.s3_bucket.as_deref(), Some("wvm-archive"));
+        assert_eq!(args.s3_region, "us-east-1");
+        // s3 only mirrors the archive
+        assert!(args.backend(Path::new("/tmp/wvm")).is_err());
+
+        let args = CommandParser::<StorageArgs>::parse_from([
+            "reth",
+            "--wvm.storage.key-type",
+            "ethereum",
+            "--wvm.storage.key-env",
+            "WVM_UPLOAD_KEY",
+        ])
+        .args;
+        assert_eq!(args.key_type, KeyType::Ethereum);
+        assert_eq!(args.key_env.as_deref(), Some("WVM_UPLOAD_KEY"));
+
+        let args = CommandParser::<StorageArgs>::parse_from([
+            "reth",
+            "--wvm.storage.budget-daily",
+            "5000000000",
+            "--wvm.storage.min-balance",
+            "100",
+        ])
+        .args;
+        let budget = args.budget();
+        assert_eq!(budget.hourly, None);
+        assert_eq!(budget.daily, Some(5_000_000_000));
+        assert_eq!(budget.min_balance, 100);
+        assert_eq!(budget.balance_ttl, Duration::from_secs(60));
+
+        assert!(CommandParser::<StorageArgs>::try_parse_from([
+            "reth",
+            "--wvm.storage.key-env",
+            "WVM_UPLOAD_KEY",
+            "--wvm.storage.remote-signer",
+            "/run/wvm/signer.sock",
+        ])
+        .is_err());
+    }
+}
@@ -0,0 +1,499 @@
+use crate::{
+    backend::{DroppedUpload, PermanentStorageBackend, StoredItem, Tag, TagQuery},
+    chunk::{merkle_tree, ChunkProof},
+    gateway::ArweaveGateway,
+    signer::UploadSigner,
+    state_file::{millis, now_millis, StateFile},
+    tx::TxHeader,
+};
+use base64::{prelude::BASE64_URL_SAFE_NO_PAD, Engine};
+use eyre::eyre;
+use reqwest::StatusCode;
+use reth_tracing::tracing::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    ops::Range,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Location of the pending transactions of the provider, relative to the node's datadir.
+pub const ARWEAVE_PENDING_FILE: &str = "wvm/arweave-pending.json";
+
+/// Confirmations after which an uploaded transaction is no longer tracked.
+pub const DEFAULT_CONFIRMATIONS: u64 = 10;
+
+/// Attempts to post a chunk before the upload fails.
+const CHUNK_ATTEMPTS: usize = 3;
+
+/// Status of a transaction, as returned by `/tx/{id}/status`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TxStatus {
+    /// The transaction is in the mempool.
+    Pending,
+    Confirmed {
+        block_height: u64,
+        confirmations: u64,
+    },
+    /// The gateway does not know the transaction.
+    NotFound,
+}
+
+/// A chunk as posted to `/chunk`, binary fields are base64url encoded.
+#[derive(Debug, Serialize)]
+struct ChunkUpload<'a> {
+    data_root: &'a str,
+    data_size: &'a str,
+    data_path: String,
+    /// Offset of the last byte of the chunk in the data.
+    offset: String,
+    chunk: String,
+}
+
+/// A transaction header as posted to `/tx`, the data follows in chunks.
+#[derive(Debug, Serialize)]
+struct TxUpload<'a> {
+    #[serde(flatten)]
+    header: &'a TxHeader,
+    data: &'a str,
+}
+
+/// A transaction uploaded by a provider that is not confirmed yet.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PendingTx {
+    /// When the transaction was posted, in milliseconds since the unix epoch.
+    posted_at: u64,
+    /// Tags of the upload, so its data can be found again if the transaction is dropped.
+    tags: Vec<Tag>,
+}
+
+/// Transactions uploaded by a provider that are not confirmed yet, and the ones that were dropped.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct ConfirmationTracker {
+    pending: BTreeMap<String, PendingTx>,
+    /// Tags of the dropped transactions whose data was not queued for another upload yet.
+    dropped: BTreeMap<String, Vec<Tag>>,
+    #[serde(skip)]
+    checked_at: Option<Instant>,
+}
+
+impl ConfirmationTracker {
+    /// Applies the `status` of pending transaction `id`, returns whether it is no longer pending.
+    fn update(
+        &mut self,
+        id: &str,
+        status: TxStatus,
+        confirmations: u64,
+        drop_timeout: Duration,
+    ) -> bool {
+        let Some(tx) = self.pending.get(id) else { return false };
+        match status {
+            TxStatus::Confirmed { block_height, confirmations: confirmed }
+                if confirmed >= confirmations =>
+            {
+                info!(
+                    %id,
+                    block_height,
+                    confirmations = confirmed,
+                    "Arweave transaction confirmed"
+                );
+            }
+            TxStatus::NotFound
+                if now_millis().saturating_sub(tx.posted_at) >= millis(drop_timeout) =>
+            {
+                error!(%id, "Arweave transaction was dropped, its data has to be uploaded again");
+                self.dropped.insert(id.to_string(), tx.tags.clone());
+            }
+            _ => return false,
+        }
+
+        self.pending.remove(id);
+        true
+    }
+}
+
+/// Uploads layer 1 transactions through an Arweave gateway and reads through the same gateway.
+///
+/// Every upload is a format 2 transaction signed by the configured [`UploadSigner`], which has to
+/// hold an Arweave key. The header is posted first, the data follows chunk by chunk with the
+/// Merkle proof of every chunk.
+///
+/// Uploaded transactions are tracked until they have [`DEFAULT_CONFIRMATIONS`] confirmations.
+/// Pending transactions are checked at most once per check interval, before an upload. A
+/// transaction the gateway still does not know after the drop timeout is reported as dropped by
+/// [`PermanentStorageBackend::dropped_uploads`], so the caller uploads its data again. Pending and
+/// dropped transactions survive restarts with a pending file, see
+/// [`ArweaveProvider::with_pending_file`].
+#[derive(Debug, Clone)]
+pub struct ArweaveProvider {
+    signer: Option<Arc<dyn UploadSigner>>,
+    gateway: ArweaveGateway,
+    client: reqwest::Client,
+    confirmations: u64,
+    check_interval: Duration,
+    drop_timeout: Duration,
+    tracker: Arc<Mutex<ConfirmationTracker>>,
+    pending_file: Option<Arc<StateFile>>,
+}
+
+impl Default for ArweaveProvider {
+    fn default() -> Self {
+        Self::new(ArweaveGateway::default().with_trusted_data_items(true))
+    }
+}
+
+impl ArweaveProvider {
+    pub fn new(gateway: ArweaveGateway) -> Self {
+        ArweaveProvider {
+            signer: None,
+            gateway,
+            client: reqwest::Client::new(),
+            confirmations: DEFAULT_CONFIRMATIONS,
+            check_interval: Duration::from_secs(2 * 60),
+            drop_timeout: Duration::from_secs(2 * 60 * 60),
+            tracker: Arc::default(),
+            pending_file: None,
+        }
+    }
+
+    pub fn with_signer(mut self, signer: Arc<dyn UploadSigner>) -> Self {
+        self.signer = Some(signer);
+        self
+    }
+
+    pub fn with_confirmations(mut self, confirmations: u64) -> Self {
+        self.confirmations = confirmations;
+        self
+    }
+
+    pub fn with_check_interval(mut self, check_interval: Duration) -> Self {
+        self.check_interval = check_interval;
+        self
+    }
+
+    pub fn with_drop_timeout(mut self, drop_timeout: Duration) -> Self {
+        self.drop_timeout = drop_timeout;
+        self
+    }
+
+    /// Persists the pending and dropped transactions to the file at `path`, loading them from it.
+    pub fn with_pending_file(mut self, path: impl Into<PathBuf>) -> eyre::Result<Self> {
+        let file = StateFile::new(path);
+        if let Some(tracker) = file.load()? {
+            self.tracker = Arc::new(Mutex::new(tracker));
+        }
+        self.pending_file = Some(Arc::new(file));
+        Ok(self)
+    }
+
+    /// Returns the ids of the uploaded transactions that are not confirmed yet.
+    pub fn pending_transactions(&self) -> Vec<String> {
+        self.tracker.lock().unwrap().pending.keys().cloned().collect()
+    }
+
+    async fn persist_tracker(&self) -> eyre::Result<()> {
+        let Some(file) = &self.pending_file else { return Ok(()) };
+        file.store(|| self.tracker.lock().unwrap().clone()).await
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.gateway.url(), path)
+    }
+
+    async fn get_text(&self, path: &str) -> eyre::Result<String> {
+        Ok(self.client.get(self.url(path)).send().await?.error_for_status()?.text().await?)
+    }
+
+    /// Returns the fee of a transaction of `bytes` bytes, in winston.
+    pub async fn get_price(&self, bytes: usize) -> eyre::Result<u128> {
+        let price = self.get_text(&format!("/price/{}", bytes)).await?;
+        price.trim().parse().map_err(|e| eyre!("invalid price of gateway {}: {}", price, e))
+    }
+
+    /// Returns the balance of the wallet of the signer, in winston.
+    pub async fn get_balance(&self) -> eyre::Result<Option<u128>> {
+        let Some(signer) = &self.signer else { return Ok(None) };
+        let address = signer.public_key().await?.address();
+
+        let balance = self.get_text(&format!("/wallet/{}/balance", address)).await?;
+        let balance = balance
+            .trim()
+            .parse()
+            .map_err(|e| eyre!("invalid balance of wallet {}: {}", address, e))?;
+
+        Ok(Some(balance))
+    }
+
+    /// Returns the status of transaction `id`.
+    pub async fn status(&self, id: &str) -> eyre::Result<TxStatus> {
+        #[derive(Deserialize)]
+        struct ConfirmedStatus {
+            block_height: u64,
+            number_of_confirmations: u64,
+        }
+
+        let res = self.client.get(self.url(&format!("/tx/{}/status", id))).send().await?;
+        match res.status() {
+            StatusCode::ACCEPTED => Ok(TxStatus::Pending),
+            StatusCode::NOT_FOUND => Ok(TxStatus::NotFound),
+            _ => {
+                let status: ConfirmedStatus = res.error_for_status()?.json().await?;
+                Ok(TxStatus::Confirmed {
+                    block_height: status.block_height,
+                    confirmations: status.number_of_confirmations,
+                })
+            }
+        }
+    }
+
+    /// Checks the status of the pending transactions, if the check interval passed.
+    pub async fn track_confirmations(&self) -> eyre::Result<()> {
+        let pending = {
+            let mut tracker = self.tracker.lock().unwrap();
+            if tracker
+                .checked_at
+                .is_some_and(|checked_at| checked_at.elapsed() < self.check_interval)
+            {
+                return Ok(())
+            }
+            tracker.checked_at = Some(Instant::now());
+            tracker.pending.clone()
+        };
+
+        let mut checked = Ok(());
+        let mut changed = false;
+        for id in pending.keys() {
+            let status = match self.status(id.as_str()).await {
+                Ok(status) => status,
+                Err(err) => {
+                    checked = Err(err);
+                    break
+                }
+            };
+            changed |= self.tracker.lock().unwrap().update(
+                id,
+                status,
+                self.confirmations,
+                self.drop_timeout,
+            );
+        }
+
+        // the statuses checked before a failure are kept
+        if changed {
+            self.persist_tracker().await?;
+        }
+
+        checked
+    }
+
+    pub async fn upload_transaction(
+        &self,
+        data: Vec<u8>,
+        param_tags: Vec<Tag>,
+    ) -> eyre::Result<String> {
+        let Some(signer) = &self.signer else {
+            return Err(eyre!("no upload signer configured, see `--wvm.storage.key-type`"))
+        };
+
+        // tracking is best effort, it must not stop uploads
+        if let Err(err) = self.track_confirmations().await {
+            warn!(%err, "Failed to check Arweave transactions");
+        }
+
+        let mut tags = vec![
+            Tag::new("Protocol", "WeaveVM-ExEx"),
+            Tag::new("ExEx-Type", "Arweave-Data-Uploader"),
+        ];
+        tags.extend(param_tags.iter().cloned());
+
+        let anchor = self.get_text("/tx_anchor").await?;
+        let reward = self.get_price(data.len()).await?;
+        let header = TxHeader::sign(signer.as_ref(), &data, &tags, anchor.trim(), reward)
+            .await
+            .map_err(|e| eyre!("failed to sign transaction: {}", e))?;
+
+        self.client
+            .post(self.url("/tx"))
+            .json(&TxUpload { header: &header, data: "" })
+            .send()
+            .await
+            .and_then(|res| res.error_for_status())
+            .map_err(|e| eyre!("failed to post transaction {}: {}", header.id, e))?;
+
+        let (_, proofs) = merkle_tree(&data);
+        for proof in proofs.iter().filter(|proof| !proof.range.is_empty()) {
+            self.post_chunk(&header, &data, proof).await?;
+        }
+
+        let tx = PendingTx { posted_at: now_millis(), tags: param_tags };
+        self.tracker.lock().unwrap().pending.insert(header.id.clone(), tx);
+        // the transaction is posted either way, failing the upload would only post it again
+        if let Err(err) = self.persist_tracker().await {
+            error!(id = %header.id, %err, "Failed to persist pending Arweave transactions");
+        }
+
+        Ok(header.id)
+    }
+
+    async fn post_chunk(
+        &self,
+        header: &TxHeader,
+        data: &[u8],
+        proof: &ChunkProof,
+    ) -> eyre::Result<()> {
+        let chunk = ChunkUpload {
+            data_root: header.data_root.as_str(),
+            data_size: header.data_size.as_str(),
+            data_path: BASE64_URL_SAFE_NO_PAD.encode(&proof.data_path),
+            offset: (proof.range.end - 1).to_string(),
+            chunk: BASE64_URL_SAFE_NO_PAD.encode(&data[proof.range.clone()]),
+        };
+
+        let mut attempt = 1;
+        loop {
+            let res = self
+                .client
+                .post(self.url("/chunk"))
+                .json(&chunk)
+                .send()
+                .await
+                .and_then(|res| res.error_for_status());
+            match res {
+                Ok(_) => return Ok(()),
+                Err(err) if attempt < CHUNK_ATTEMPTS => {
+                    warn!(
+                        id = %header.id,
+                        offset = %chunk.offset,
+                        %err,
+                        "Failed to post chunk, retrying"
+                    );
+                    tokio::time::sleep(Duration::from_secs(attempt as u64)).await;
+                    attempt += 1;
+                }
+                Err(err) => {
+                    return Err(eyre!(
+                        "failed to post chunk at offset {} of transaction {}: {}",
+                        chunk.offset,
+                        header.id,
+                        err
+                    ))
+                }
+            }
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl PermanentStorageBackend for ArweaveProvider {
+    async fn upload(&self, data: Vec<u8>, tags: Vec<Tag>) -> eyre::Result<String> {
+        self.upload_transaction(data, tags).await
+    }
+
+    async fn fetch(&self, id: &str) -> eyre::Result<Vec<u8>> {
+        self.gateway.fetch(id).await
+    }
+
+    async fn fetch_range(&self, id: &str, range: Range<usize>) -> eyre::Result<Vec<u8>> {
+        self.gateway.fetch_range(id, range).await
+    }
+
+    async fn query(&self, query: &TagQuery) -> eyre::Result<Vec<StoredItem>> {
+        self.gateway.query(query).await
+    }
+
+    async fn upload_price(&self, bytes: usize) -> eyre::Result<Option<u128>> {
+        self.get_price(bytes).await.map(Some)
+    }
+
+    async fn balance(&self) -> eyre::Result<Option<u128>> {
+        self.get_balance().await
+    }
+
+    async fn dropped_uploads(&self) -> eyre::Result<Vec<DroppedUpload>> {
+        let tracker = self.tracker.lock().unwrap();
+        Ok(tracker
+            .dropped
+            .iter()
+            .map(|(id, tags)| DroppedUpload { id: id.clone(), tags: tags.clone() })
+            .collect())
+    }
+
+    async fn forget_dropped_upload(&self, id: &str) -> eyre::Result<()> {
+        if self.tracker.lock().unwrap().dropped.remove(id).is_some() {
+            self.persist_tracker().await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod arweave_tests {
+    use crate::{
+        arweave::{ArweaveProvider, PendingTx, TxStatus, TxUpload},
+        backend::{DroppedUpload, PermanentStorageBackend, Tag},
+        signer::{signer_tests::ARWEAVE_JWK, KeyType, LocalSigner},
+        state_file::now_millis,
+        tx::TxHeader,
+    };
+    use std::time::Duration;
+
+    #[tokio::test]
+    pub async fn test_tx_upload() {
+        let signer = LocalSigner::from_key(KeyType::Arweave, ARWEAVE_JWK).unwrap();
+        let anchor = "AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8AAQIDBAUGBwgJCgsMDQ4P";
+        let tags = vec![Tag::new("Content-Type", "text/plain")];
+        let header = TxHeader::sign(&signer, b"Hello world", &tags, anchor, 1000).await.unwrap();
+
+        let upload = serde_json::to_value(TxUpload { header: &header, data: "" }).unwrap();
+        assert_eq!(upload["format"], 2);
+        assert_eq!(upload["id"], header.id.as_str());
+        assert_eq!(upload["data_size"], "11");
+        assert_eq!(upload["data"], "");
+    }
+
+    #[tokio::test]
+    pub async fn test_upload_without_signer() {
+        let provider = ArweaveProvider::default();
+        assert!(provider.upload(b"data".to_vec(), vec![]).await.is_err());
+        assert_eq!(provider.balance().await.unwrap(), None);
+        assert!(provider.pending_transactions().is_empty());
+    }
+
+    #[tokio::test]
+    pub async fn test_dropped_transactions() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("arweave-pending.json");
+        let provider = ArweaveProvider::default().with_pending_file(&path).unwrap();
+        let tags = vec![Tag::new("Block-Number", "1")];
+        {
+            let mut tracker = provider.tracker.lock().unwrap();
+            let pending = |posted_at| PendingTx { posted_at, tags: tags.clone() };
+            tracker.pending.insert("new".to_string(), pending(now_millis()));
+            tracker.pending.insert("old".to_string(), pending(0));
+            tracker.pending.insert("mined".to_string(), pending(0));
+
+            // unknown transactions are only dropped after the timeout
+            let timeout = Duration::from_secs(60);
+            assert!(!tracker.update("new", TxStatus::NotFound, 10, timeout));
+            assert!(tracker.update("old", TxStatus::NotFound, 10, timeout));
+            let mined = |confirmations| TxStatus::Confirmed { block_height: 1, confirmations };
+            assert!(!tracker.update("mined", mined(9), 10, timeout));
+            assert!(tracker.update("mined", mined(10), 10, timeout));
+        }
+        provider.persist_tracker().await.unwrap();
+        let dropped = vec![DroppedUpload { id: "old".to_string(), tags }];
+        assert_eq!(provider.dropped_uploads().await.unwrap(), dropped);
+
+        // a restart keeps the pending transactions and the dropped ones until they are forgotten
+        let provider = ArweaveProvider::default().with_pending_file(&path).unwrap();
+        assert_eq!(provider.pending_transactions(), vec!["new".to_string()]);
+        assert_eq!(provider.dropped_uploads().await.unwrap(), dropped);
+        provider.forget_dropped_upload("old").await.unwrap();
+
+        let provider = ArweaveProvider::default().with_pending_file(&path).unwrap();
+        assert!(provider.dropped_uploads().await.unwrap().is_empty());
+        assert_eq!(provider.pending_transactions(), vec!["new".to_string()]);
+    }
+}
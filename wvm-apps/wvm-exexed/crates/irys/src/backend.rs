@@ -0,0 +1,211 @@
+use base64::{prelude::BASE64_URL_SAFE_NO_PAD, Engine};
+use reth::primitives::B256;
+use serde::{Deserialize, Serialize};
+use std::{fmt::Debug, ops::Range};
+
+/// A name/value pair attached to a stored item.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Tag {
+    pub name: String,
+    pub value: String,
+}
+
+impl Tag {
+    pub fn new(name: &str, value: &str) -> Self {
+        Tag { name: name.to_string(), value: value.to_string() }
+    }
+}
+
+/// Matches items that carry a tag `name` with any of the given `values`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagFilter {
+    pub name: String,
+    pub values: Vec<String>,
+}
+
+impl TagFilter {
+    pub fn new(name: &str, values: Vec<String>) -> Self {
+        TagFilter { name: name.to_string(), values }
+    }
+
+    pub fn matches(&self, tags: &[Tag]) -> bool {
+        tags.iter().any(|tag| tag.name == self.name && self.values.contains(&tag.value))
+    }
+}
+
+/// A GraphQL-style query over stored items.
+///
+/// All filters have to match. Empty `ids` and `owners` lists do not restrict the result.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TagQuery {
+    pub ids: Vec<String>,
+    /// Arweave addresses of the wallets that signed the items. Backends that do not know the
+    /// owner of their items match no item if set.
+    pub owners: Vec<String>,
+    pub tags: Vec<TagFilter>,
+    /// Maximum number of items to return, `None` uses the backend default.
+    pub first: Option<usize>,
+    /// Cursor of the item to continue the query after, see [`StoredItem::cursor`].
+    pub after: Option<String>,
+}
+
+impl TagQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_id(mut self, id: &str) -> Self {
+        self.ids.push(id.to_string());
+        self
+    }
+
+    pub fn with_tag(mut self, name: &str, value: &str) -> Self {
+        self.tags.push(TagFilter::new(name, vec![value.to_string()]));
+        self
+    }
+
+    /// Matches items with a tag `name` of any of `values`.
+    pub fn with_tag_values(mut self, name: &str, values: &[&str]) -> Self {
+        self.tags.push(TagFilter::new(name, values.iter().map(|v| v.to_string()).collect()));
+        self
+    }
+
+    pub fn with_owner(mut self, owner: &str) -> Self {
+        self.owners.push(owner.to_string());
+        self
+    }
+
+    pub fn with_first(mut self, first: usize) -> Self {
+        self.first = Some(first);
+        self
+    }
+
+    pub fn with_after(mut self, cursor: &str) -> Self {
+        self.after = Some(cursor.to_string());
+        self
+    }
+
+    /// Returns true if an item with `id` and `tags` satisfies the query.
+    pub fn matches(&self, id: &str, tags: &[Tag]) -> bool {
+        (self.ids.is_empty() || self.ids.iter().any(|i| i == id)) &&
+            self.tags.iter().all(|filter| filter.matches(tags))
+    }
+}
+
+/// Metadata of a stored item, as returned by [`PermanentStorageBackend::query`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StoredItem {
+    pub id: String,
+    pub size: usize,
+    pub tags: Vec<Tag>,
+    /// Position of the item in the results of a query, opaque to callers.
+    #[serde(default)]
+    pub cursor: Option<String>,
+}
+
+impl StoredItem {
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter().find(|tag| tag.name == name).map(|tag| tag.value.as_str())
+    }
+}
+
+/// An upload that succeeded, but never made it to permanent storage, see
+/// [`PermanentStorageBackend::dropped_uploads`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DroppedUpload {
+    pub id: String,
+    /// Tags of the upload, without the ones the backend adds.
+    pub tags: Vec<Tag>,
+}
+
+impl DroppedUpload {
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter().find(|tag| tag.name == name).map(|tag| tag.value.as_str())
+    }
+}
+
+/// Encodes a raw 32-byte Arweave id or address, base64url without padding.
+pub fn arweave_id(raw: &B256) -> String {
+    BASE64_URL_SAFE_NO_PAD.encode(raw)
+}
+
+/// Returns the bytes in `range` of `data`, the range is clamped to the size of the data.
+pub fn data_range(mut data: Vec<u8>, range: Range<usize>) -> Vec<u8> {
+    let end = range.end.min(data.len());
+    data.truncate(end);
+    data.split_off(range.start.min(end))
+}
+
+/// Permanent storage the node archives to and reads from.
+///
+/// The default implementation uploads through Irys and reads through an Arweave gateway, the
+/// local implementation keeps everything in a directory so devnets and CI can run offline.
+#[async_trait::async_trait]
+pub trait PermanentStorageBackend: Debug + Send + Sync {
+    /// Stores `data` with `tags` and returns the id of the new item.
+    async fn upload(&self, data: Vec<u8>, tags: Vec<Tag>) -> eyre::Result<String>;
+
+    /// Returns the data of the item with `id`.
+    async fn fetch(&self, id: &str) -> eyre::Result<Vec<u8>>;
+
+    /// Returns the bytes in `range` of the data of the item with `id`, the range is clamped to the
+    /// size of the item.
+    ///
+    /// The default implementation fetches the whole item.
+    async fn fetch_range(&self, id: &str, range: Range<usize>) -> eyre::Result<Vec<u8>> {
+        Ok(data_range(self.fetch(id).await?, range))
+    }
+
+    /// Returns the items matching `query`, newest first, in an order that is the same for every
+    /// call.
+    async fn query(&self, query: &TagQuery) -> eyre::Result<Vec<StoredItem>>;
+
+    /// Returns the price of uploading `bytes` bytes, in the smallest unit of the currency uploads
+    /// are paid in. `None` if uploads are free.
+    async fn upload_price(&self, _bytes: usize) -> eyre::Result<Option<u128>> {
+        Ok(None)
+    }
+
+    /// Returns the size an upload of `bytes` bytes of data with `tags` is charged for, the size
+    /// [`PermanentStorageBackend::upload_price`] is quoted for.
+    ///
+    /// The default implementation charges the data only.
+    async fn upload_size(&self, bytes: usize, _tags: &[Tag]) -> eyre::Result<usize> {
+        Ok(bytes)
+    }
+
+    /// Returns the balance uploads are paid from, in the unit of
+    /// [`PermanentStorageBackend::upload_price`]. `None` if uploads are free.
+    async fn balance(&self) -> eyre::Result<Option<u128>> {
+        Ok(None)
+    }
+
+    /// Returns the uploads that were dropped after they succeeded, e.g. layer 1 transactions that
+    /// never made it into a block. Their data has to be uploaded again, after which they are
+    /// forgotten with [`PermanentStorageBackend::forget_dropped_upload`].
+    ///
+    /// The default implementation never drops uploads.
+    async fn dropped_uploads(&self) -> eyre::Result<Vec<DroppedUpload>> {
+        Ok(vec![])
+    }
+
+    /// Forgets the dropped upload with `id`, once its data was queued for another upload.
+    async fn forget_dropped_upload(&self, _id: &str) -> eyre::Result<()> {
+        Ok(())
+    }
+
+    /// Returns the id of the item whose raw 32-byte id is `raw`, as callers of the ABI
+    /// interfaces pass it.
+    ///
+    /// Arweave ids are the base64url encoding of the raw id.
+    fn encode_id(&self, raw: &B256) -> String {
+        arweave_id(raw)
+    }
+
+    /// Returns the raw 32-byte id of the item with `id`, the inverse of
+    /// [`PermanentStorageBackend::encode_id`]. `None` if `id` is no valid id of the backend.
+    fn decode_id(&self, id: &str) -> Option<B256> {
+        let raw = BASE64_URL_SAFE_NO_PAD.decode(id).ok()?;
+        B256::try_from(raw.as_slice()).ok()
+    }
+}
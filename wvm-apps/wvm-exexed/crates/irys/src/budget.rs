@@ -0,0 +1,497 @@
+use crate::{
+    backend::{DroppedUpload, PermanentStorageBackend, StoredItem, Tag, TagQuery},
+    state_file::{millis, now_millis, StateFile},
+};
+use eyre::eyre;
+use reth::primitives::B256;
+use reth_metrics::{
+    metrics::{Counter, Gauge},
+    Metrics,
+};
+use reth_tracing::tracing::{error, info};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    ops::Range,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Location of the spends files of the budgeted storages, relative to the node's datadir.
+pub const UPLOAD_SPENDS_DIR: &str = "wvm/upload-spends";
+
+const HOUR: Duration = Duration::from_secs(60 * 60);
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Limits on what uploads may cost, in the smallest unit of the currency they are paid in.
+#[derive(Debug, Clone, Copy)]
+pub struct UploadBudget {
+    /// Maximum spend within any hour.
+    pub hourly: Option<u128>,
+    /// Maximum spend within any day.
+    pub daily: Option<u128>,
+    /// Balance that is never spent, uploads that would go below it are refused.
+    pub min_balance: u128,
+    /// How long a queried balance is trusted, it is lowered by the cost of every upload in the
+    /// meantime.
+    pub balance_ttl: Duration,
+}
+
+impl Default for UploadBudget {
+    fn default() -> Self {
+        UploadBudget {
+            hourly: None,
+            daily: None,
+            min_balance: 0,
+            balance_ttl: Duration::from_secs(60),
+        }
+    }
+}
+
+#[derive(Metrics)]
+#[metrics(scope = "wvm.storage")]
+struct UploadMetrics {
+    /// Number of uploaded items.
+    uploads: Counter,
+    /// Number of uploads that failed at the backend.
+    failed_uploads: Counter,
+    /// Number of uploads refused because of the budget or the balance.
+    refused_uploads: Counter,
+    /// Total size of the uploaded items.
+    uploaded_bytes: Counter,
+    /// Total price paid for uploads.
+    upload_cost: Counter,
+    /// Last known balance uploads are paid from.
+    balance: Gauge,
+    /// Whether uploads are paused, `1` while the node only queues data.
+    degraded: Gauge,
+}
+
+/// Price of an upload and when it was reserved, in milliseconds since the unix epoch.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct Spend {
+    /// Tells the spends of uploads in flight apart, only unique within the running node.
+    #[serde(skip)]
+    id: u64,
+    at: u64,
+    price: u128,
+}
+
+/// The spend reserved for an upload in flight.
+#[derive(Debug)]
+struct Reservation {
+    id: u64,
+    price: u128,
+    /// When the balance the price was deducted from was queried.
+    balance_queried_at: Option<Instant>,
+}
+
+#[derive(Debug, Default)]
+struct BudgetState {
+    /// Spends of the last day, oldest first, including the ones of uploads in flight.
+    spends: VecDeque<Spend>,
+    next_spend_id: u64,
+    /// The last known balance and when it was queried.
+    balance: Option<(Instant, u128)>,
+    /// Why uploads are paused, if they are.
+    degraded: Option<String>,
+}
+
+impl BudgetState {
+    fn push_spend(&mut self, at: u64, price: u128) -> u64 {
+        let id = self.next_spend_id;
+        self.next_spend_id += 1;
+        self.spends.push_back(Spend { id, at, price });
+        id
+    }
+
+    /// Drops the spends older than a day.
+    fn prune(&mut self, now: u64) {
+        while self.spends.front().is_some_and(|spend| now.saturating_sub(spend.at) >= millis(DAY)) {
+            self.spends.pop_front();
+        }
+    }
+
+    fn spent_since(&self, now: u64, since: Duration) -> u128 {
+        self.spends
+            .iter()
+            .filter(|spend| now.saturating_sub(spend.at) < millis(since))
+            .fold(0u128, |spent, spend| spent.saturating_add(spend.price))
+    }
+}
+
+/// Storage backend that checks the [`UploadBudget`] and the balance of the inner backend before
+/// every upload and records metrics of all uploads.
+///
+/// Uploads that don't fit the budget or the balance are refused with an error, so they stay
+/// queued in the outboxes of their callers and are retried. The storage is then degraded: it
+/// logs an error once and sets the `wvm_storage_degraded` gauge, which alerts can be defined on,
+/// until an upload succeeds again.
+///
+/// The price of an upload is reserved before it is sent, so concurrent uploads can't exceed the
+/// budget together, and released if the upload fails. Spends are persisted to a spends file if
+/// one is set, see [`BudgetedStorage::with_spends_file`], otherwise a restart starts a new
+/// budget.
+#[derive(Debug)]
+pub struct BudgetedStorage {
+    inner: Arc<dyn PermanentStorageBackend>,
+    budget: UploadBudget,
+    state: Mutex<BudgetState>,
+    spends_file: Option<StateFile>,
+    metrics: UploadMetrics,
+}
+
+impl BudgetedStorage {
+    pub fn new(inner: Arc<dyn PermanentStorageBackend>, budget: UploadBudget) -> Self {
+        BudgetedStorage {
+            inner,
+            budget,
+            state: Mutex::new(BudgetState::default()),
+            spends_file: None,
+            metrics: UploadMetrics::default(),
+        }
+    }
+
+    /// Labels the metrics with the `destination` the storage uploads to, so several budgeted
+    /// backends can be told apart.
+    pub fn with_destination(mut self, destination: &str) -> Self {
+        self.metrics = UploadMetrics::new_with_labels(&[("destination", destination.to_string())]);
+        self
+    }
+
+    /// Persists the spends to the file at `path`, loading the spends of the last day from it.
+    ///
+    /// Spends are written before the upload they are reserved for is sent, so an upload in flight
+    /// when the node stops counts as spent.
+    pub fn with_spends_file(mut self, path: impl Into<PathBuf>) -> eyre::Result<Self> {
+        let file = StateFile::new(path);
+        let spends: Vec<Spend> = file.load()?.unwrap_or_default();
+        let state = self.state.get_mut().unwrap();
+        for spend in spends {
+            state.push_spend(spend.at, spend.price);
+        }
+        state.prune(now_millis());
+
+        self.spends_file = Some(file);
+        Ok(self)
+    }
+
+    /// Returns why uploads are paused, `None` while they are not.
+    pub fn degraded(&self) -> Option<String> {
+        self.state.lock().unwrap().degraded.clone()
+    }
+
+    /// Returns the balance, queried from the inner backend once the known balance expired.
+    async fn current_balance(&self) -> eyre::Result<Option<u128>> {
+        let known = self.state.lock().unwrap().balance;
+        if let Some((queried_at, balance)) = known {
+            if queried_at.elapsed() < self.budget.balance_ttl {
+                return Ok(Some(balance))
+            }
+        }
+
+        let Some(balance) = self.inner.balance().await? else { return Ok(None) };
+        self.state.lock().unwrap().balance = Some((Instant::now(), balance));
+        self.metrics.balance.set(balance as f64);
+
+        Ok(Some(balance))
+    }
+
+    /// Reserves the spend of an upload costing `price`, if it fits the budget and the balance.
+    async fn check(&self, price: u128) -> Result<Reservation, String> {
+        if let Err(err) = self.current_balance().await {
+            return Err(format!("failed to query balance: {err}"))
+        }
+
+        // checked and reserved under one lock, concurrent uploads see each other's spends
+        let now = now_millis();
+        let mut state = self.state.lock().unwrap();
+        state.prune(now);
+
+        for (limit, window, name) in
+            [(self.budget.hourly, HOUR, "hourly"), (self.budget.daily, DAY, "daily")]
+        {
+            let Some(limit) = limit else { continue };
+            let spent = state.spent_since(now, window);
+            if spent.saturating_add(price) > limit {
+                return Err(format!(
+                    "{name} upload budget of {limit} exhausted, {spent} spent, upload costs {price}"
+                ))
+            }
+        }
+
+        if let Some((_, balance)) = state.balance {
+            if balance < price.saturating_add(self.budget.min_balance) {
+                return Err(format!(
+                    "balance of {balance} is too low, upload costs {price} and {} are kept",
+                    self.budget.min_balance
+                ))
+            }
+        }
+
+        let id = state.push_spend(now, price);
+        let balance_queried_at = state.balance.as_mut().map(|(queried_at, balance)| {
+            *balance = balance.saturating_sub(price);
+            self.metrics.balance.set(*balance as f64);
+            *queried_at
+        });
+
+        Ok(Reservation { id, price, balance_queried_at })
+    }
+
+    /// Releases the spend of an upload that failed, and was not paid.
+    fn release(&self, reservation: Reservation) {
+        let mut state = self.state.lock().unwrap();
+        state.spends.retain(|spend| spend.id != reservation.id);
+        // a balance queried since the reservation never had the price deducted
+        if let Some((queried_at, balance)) = &mut state.balance {
+            if Some(*queried_at) == reservation.balance_queried_at {
+                *balance = balance.saturating_add(reservation.price);
+                self.metrics.balance.set(*balance as f64);
+            }
+        }
+    }
+
+    /// Writes the spends to the spends file, if one is set.
+    async fn persist_spends(&self) -> eyre::Result<()> {
+        let Some(file) = &self.spends_file else { return Ok(()) };
+        file.store(|| self.state.lock().unwrap().spends.clone()).await
+    }
+
+    fn enter_degraded(&self, reason: String) {
+        let mut state = self.state.lock().unwrap();
+        if state.degraded.is_none() {
+            error!(%reason, "Uploads are paused, data is queued locally until they can resume");
+            self.metrics.degraded.set(1.0);
+        }
+        state.degraded = Some(reason);
+    }
+
+    fn record_upload(&self, bytes: usize, price: u128) {
+        if let Some(reason) = self.state.lock().unwrap().degraded.take() {
+            info!(%reason, "Uploads resumed");
+            self.metrics.degraded.set(0.0);
+        }
+
+        self.metrics.uploads.increment(1);
+        self.metrics.uploaded_bytes.increment(bytes as u64);
+        self.metrics.upload_cost.increment(price.try_into().unwrap_or(u64::MAX));
+    }
+}
+
+#[async_trait::async_trait]
+impl PermanentStorageBackend for BudgetedStorage {
+    async fn upload(&self, data: Vec<u8>, tags: Vec<Tag>) -> eyre::Result<String> {
+        let bytes = data.len();
+        // quoted for what the inner backend is charged for, e.g. the signed data item
+        let size = self.inner.upload_size(bytes, &tags).await;
+        let price = match size {
+            Ok(size) => self.inner.upload_price(size).await,
+            Err(err) => Err(err),
+        };
+        let price = match price {
+            Ok(price) => price.unwrap_or_default(),
+            Err(err) => {
+                self.metrics.failed_uploads.increment(1);
+                return Err(eyre!("failed to query upload price: {}", err))
+            }
+        };
+
+        let reservation = match self.check(price).await {
+            Ok(reservation) => reservation,
+            Err(reason) => {
+                self.metrics.refused_uploads.increment(1);
+                self.enter_degraded(reason.clone());
+                return Err(eyre!("upload refused: {}", reason))
+            }
+        };
+        if let Err(err) = self.persist_spends().await {
+            self.release(reservation);
+            self.metrics.failed_uploads.increment(1);
+            return Err(eyre!("failed to persist upload spends: {}", err))
+        }
+
+        match self.inner.upload(data, tags).await {
+            Ok(id) => {
+                self.record_upload(bytes, price);
+                Ok(id)
+            }
+            Err(err) => {
+                self.metrics.failed_uploads.increment(1);
+                self.release(reservation);
+                if let Err(persist_err) = self.persist_spends().await {
+                    // the released spend still counts until the next write
+                    error!(err = %persist_err, "Failed to persist upload spends");
+                }
+                Err(err)
+            }
+        }
+    }
+
+    async fn upload_size(&self, bytes: usize, tags: &[Tag]) -> eyre::Result<usize> {
+        self.inner.upload_size(bytes, tags).await
+    }
+
+    async fn fetch(&self, id: &str) -> eyre::Result<Vec<u8>> {
+        self.inner.fetch(id).await
+    }
+
+    async fn fetch_range(&self, id: &str, range: Range<usize>) -> eyre::Result<Vec<u8>> {
+        self.inner.fetch_range(id, range).await
+    }
+
+    async fn query(&self, query: &TagQuery) -> eyre::Result<Vec<StoredItem>> {
+        self.inner.query(query).await
+    }
+
+    async fn upload_price(&self, bytes: usize) -> eyre::Result<Option<u128>> {
+        self.inner.upload_price(bytes).await
+    }
+
+    async fn balance(&self) -> eyre::Result<Option<u128>> {
+        self.inner.balance().await
+    }
+
+    async fn dropped_uploads(&self) -> eyre::Result<Vec<DroppedUpload>> {
+        self.inner.dropped_uploads().await
+    }
+
+    async fn forget_dropped_upload(&self, id: &str) -> eyre::Result<()> {
+        self.inner.forget_dropped_upload(id).await
+    }
+
+    fn encode_id(&self, raw: &B256) -> String {
+        self.inner.encode_id(raw)
+    }
+
+    fn decode_id(&self, id: &str) -> Option<B256> {
+        self.inner.decode_id(id)
+    }
+}
+
+#[cfg(test)]
+mod budget_tests {
+    use crate::{
+        backend::{PermanentStorageBackend, Tag},
+        budget::{BudgetedStorage, UploadBudget},
+        local::LocalStorage,
+        test_utils::PaidStorage,
+    };
+    use std::{sync::Arc, time::Duration};
+
+    fn paid_storage(balance: u128) -> (tempfile::TempDir, Arc<PaidStorage>) {
+        let dir = tempfile::tempdir().unwrap();
+        let paid = Arc::new(PaidStorage::new(dir.path(), balance));
+        (dir, paid)
+    }
+
+    #[tokio::test]
+    pub async fn test_upload_budget() {
+        let (_dir, paid) = paid_storage(1_000);
+        let budget = UploadBudget { hourly: Some(10), daily: Some(15), ..Default::default() };
+        let storage = BudgetedStorage::new(paid.clone(), budget);
+
+        storage.upload(vec![0; 6], vec![]).await.unwrap();
+        assert!(storage.degraded().is_none());
+
+        // 6 + 6 exceeds the hourly budget
+        assert!(storage.upload(vec![0; 6], vec![]).await.is_err());
+        assert!(storage.degraded().unwrap().contains("hourly"));
+
+        storage.upload(vec![0; 4], vec![]).await.unwrap();
+        assert!(storage.degraded().is_none());
+
+        // 10 + 6 exceeds the daily budget
+        let storage = BudgetedStorage::new(paid, UploadBudget { hourly: None, ..budget });
+        storage.upload(vec![0; 10], vec![]).await.unwrap();
+        assert!(storage.upload(vec![0; 6], vec![]).await.is_err());
+        assert!(storage.degraded().unwrap().contains("daily"));
+    }
+
+    #[tokio::test]
+    pub async fn test_upload_balance() {
+        let (_dir, paid) = paid_storage(20);
+        let budget = UploadBudget {
+            min_balance: 5,
+            balance_ttl: Duration::from_secs(60 * 60),
+            ..Default::default()
+        };
+        let storage = BudgetedStorage::new(paid.clone(), budget);
+
+        storage.upload(vec![0; 10], vec![]).await.unwrap();
+        // the balance is not queried again, the upload is deducted from the known balance
+        assert!(storage.upload(vec![0; 6], vec![]).await.is_err());
+        assert!(storage.degraded().unwrap().contains("balance"));
+        assert_eq!(paid.remaining(), 10);
+
+        storage.upload(vec![0; 5], vec![]).await.unwrap();
+        assert!(storage.degraded().is_none());
+        assert_eq!(paid.remaining(), 5);
+    }
+
+    #[tokio::test]
+    pub async fn test_upload_reservations() {
+        let (_dir, paid) = paid_storage(1_000);
+        let budget = UploadBudget { hourly: Some(10), ..Default::default() };
+        let storage = BudgetedStorage::new(paid.clone(), budget);
+
+        // concurrent uploads can't exceed the budget together
+        let (first, second) =
+            tokio::join!(storage.upload(vec![0; 6], vec![]), storage.upload(vec![1; 6], vec![]));
+        assert!(first.is_ok() != second.is_ok());
+
+        // the spend of a failed upload is released
+        paid.set_failing(true);
+        assert!(storage.upload(vec![0; 4], vec![]).await.is_err());
+        paid.set_failing(false);
+        storage.upload(vec![0; 4], vec![]).await.unwrap();
+        assert_eq!(paid.remaining(), 990);
+    }
+
+    #[tokio::test]
+    pub async fn test_upload_price_of_item() {
+        let (_dir, paid) = paid_storage(1_000);
+        let budget = UploadBudget { hourly: Some(10), ..Default::default() };
+        let storage = BudgetedStorage::new(paid.clone(), budget);
+
+        // the tags are charged with the data
+        let tags = vec![Tag::new("a", "bc")];
+        storage.upload(vec![0; 4], tags.clone()).await.unwrap();
+        assert_eq!(paid.remaining(), 993);
+        assert!(storage.upload(vec![0; 1], tags).await.is_err());
+        storage.upload(vec![0; 3], vec![]).await.unwrap();
+    }
+
+    #[tokio::test]
+    pub async fn test_persisted_spends() {
+        let (dir, paid) = paid_storage(1_000);
+        let spends = dir.path().join("spends").join("irys.json");
+        let budget = UploadBudget { daily: Some(10), ..Default::default() };
+
+        let storage = BudgetedStorage::new(paid.clone(), budget).with_spends_file(&spends).unwrap();
+        storage.upload(vec![0; 6], vec![]).await.unwrap();
+        paid.set_failing(true);
+        assert!(storage.upload(vec![0; 2], vec![]).await.is_err());
+        paid.set_failing(false);
+
+        // a restart keeps the spends of successful uploads
+        let storage = BudgetedStorage::new(paid.clone(), budget).with_spends_file(&spends).unwrap();
+        assert!(storage.upload(vec![0; 6], vec![]).await.is_err());
+        storage.upload(vec![0; 4], vec![]).await.unwrap();
+
+        std::fs::write(&spends, b"not json").unwrap();
+        assert!(BudgetedStorage::new(paid, budget).with_spends_file(&spends).is_err());
+    }
+
+    #[tokio::test]
+    pub async fn test_free_uploads() {
+        let dir = tempfile::tempdir().unwrap();
+        let budget = UploadBudget { hourly: Some(0), ..Default::default() };
+        let storage =
+            BudgetedStorage::new(Arc::new(LocalStorage::new(dir.path()).unwrap()), budget);
+
+        let id = storage.upload(b"data".to_vec(), vec![]).await.unwrap();
+        assert_eq!(storage.fetch(id.as_str()).await.unwrap(), b"data");
+    }
+}
@@ -0,0 +1,297 @@
+use eyre::{bail, eyre};
+use sha2::{Digest, Sha256};
+use std::ops::Range;
+
+const HASH_SIZE: usize = 32;
+const NOTE_SIZE: usize = 32;
+const LEAF_SIZE: usize = HASH_SIZE + NOTE_SIZE;
+const BRANCH_SIZE: usize = HASH_SIZE * 2 + NOTE_SIZE;
+
+/// Maximum size of a chunk.
+pub const MAX_CHUNK_SIZE: usize = 256 * 1024;
+/// Minimum size of a chunk, only the last chunk of the data may be smaller.
+pub const MIN_CHUNK_SIZE: usize = 32 * 1024;
+
+fn sha256(data: &[u8]) -> [u8; HASH_SIZE] {
+    Sha256::digest(data).into()
+}
+
+/// Hash of a node of the Merkle tree, the hash of the hashes of its parts.
+fn node_id(parts: &[&[u8]]) -> [u8; HASH_SIZE] {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update(sha256(part));
+    }
+    hasher.finalize().into()
+}
+
+/// Reads a note, a 256-bit big-endian byte offset.
+fn note(note: &[u8]) -> eyre::Result<u64> {
+    let (high, low) = note.split_at(NOTE_SIZE - 8);
+    if high.iter().any(|byte| *byte != 0) {
+        bail!("data path offset out of range")
+    }
+
+    Ok(u64::from_be_bytes(low.try_into()?))
+}
+
+/// Encodes `offset` as a note.
+fn encode_note(offset: u64) -> [u8; NOTE_SIZE] {
+    let mut note = [0; NOTE_SIZE];
+    note[NOTE_SIZE - 8..].copy_from_slice(&offset.to_be_bytes());
+    note
+}
+
+/// A chunk of data and its proof, as posted to `/chunk`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkProof {
+    /// Bytes of the data in the chunk.
+    pub range: Range<usize>,
+    /// Path from the data root to the chunk, see [`validate_path`].
+    pub data_path: Vec<u8>,
+}
+
+/// Splits `data` into chunks the way Arweave clients chunk it.
+///
+/// Chunks are [`MAX_CHUNK_SIZE`] bytes, except for the last two that are split evenly if the last
+/// one would be smaller than [`MIN_CHUNK_SIZE`]. Like in the reference client, data of a multiple
+/// of [`MAX_CHUNK_SIZE`] ends with an empty chunk.
+pub fn chunk_ranges(data: &[u8]) -> Vec<Range<usize>> {
+    let mut ranges = vec![];
+    let mut start = 0;
+    while data.len() - start >= MAX_CHUNK_SIZE {
+        let next = data.len() - start - MAX_CHUNK_SIZE;
+        let size = if next > 0 && next < MIN_CHUNK_SIZE {
+            (data.len() - start).div_ceil(2)
+        } else {
+            MAX_CHUNK_SIZE
+        };
+        ranges.push(start..start + size);
+        start += size;
+    }
+    ranges.push(start..data.len());
+
+    ranges
+}
+
+/// Computes the root of the Merkle tree of `data` and the proofs of all its chunks, see
+/// [`chunk_ranges`].
+///
+/// Nodes without a sibling are carried up to the next layer of the tree.
+pub fn merkle_tree(data: &[u8]) -> ([u8; HASH_SIZE], Vec<ChunkProof>) {
+    let mut proofs = vec![];
+    // ids of the nodes of the current layer, the offset they end at and the chunks below them
+    let mut nodes = vec![];
+    for range in chunk_ranges(data) {
+        let (data_hash, end) = (sha256(&data[range.clone()]), encode_note(range.end as u64));
+        nodes.push((
+            node_id(&[&data_hash, &end]),
+            range.end as u64,
+            proofs.len()..proofs.len() + 1,
+        ));
+        proofs.push(ChunkProof { range, data_path: [data_hash, end].concat() });
+    }
+
+    while nodes.len() > 1 {
+        nodes = nodes
+            .chunks(2)
+            .map(|pair| match pair {
+                [(left, split, left_chunks), (right, end, right_chunks)] => {
+                    let branch = [left.as_slice(), right, &encode_note(*split)].concat();
+                    for proof in &mut proofs[left_chunks.start..right_chunks.end] {
+                        proof.data_path.splice(0..0, branch.iter().copied());
+                    }
+                    (
+                        node_id(&[left, right, &encode_note(*split)]),
+                        *end,
+                        left_chunks.start..right_chunks.end,
+                    )
+                }
+                _ => pair[0].clone(),
+            })
+            .collect();
+    }
+
+    (nodes[0].0, proofs)
+}
+
+/// Computes the root of the Merkle tree of `data`, see [`merkle_tree`].
+pub fn data_root(data: &[u8]) -> [u8; HASH_SIZE] {
+    merkle_tree(data).0
+}
+
+/// Walks the Merkle `data_path` from `data_root` to the chunk holding byte `offset` of data of
+/// `data_size` bytes.
+///
+/// The path is a list of branches, each the ids of both children and the offset they are split
+/// at, followed by the leaf, the hash of the chunk and the offset it ends at. Returns the hash of
+/// the chunk and the bytes it covers.
+pub fn validate_path(
+    data_root: &[u8; HASH_SIZE],
+    data_size: u64,
+    offset: u64,
+    data_path: &[u8],
+) -> eyre::Result<([u8; HASH_SIZE], Range<u64>)> {
+    if offset >= data_size {
+        bail!("offset {} is outside of the data ({} bytes)", offset, data_size)
+    }
+
+    let mut id = *data_root;
+    let mut bounds = 0..data_size;
+    let mut path = data_path;
+    while path.len() > LEAF_SIZE {
+        if path.len() < BRANCH_SIZE {
+            bail!("invalid data path length")
+        }
+        let (branch, rest) = path.split_at(BRANCH_SIZE);
+        let (left, right, split) =
+            (&branch[..HASH_SIZE], &branch[HASH_SIZE..HASH_SIZE * 2], &branch[HASH_SIZE * 2..]);
+        if node_id(&[left, right, split]) != id {
+            bail!("data path does not match the data root")
+        }
+
+        let split = note(split)?;
+        if offset < split {
+            id = left.try_into()?;
+            bounds.end = bounds.end.min(split);
+        } else {
+            id = right.try_into()?;
+            bounds.start = bounds.start.max(split);
+        }
+        path = rest;
+    }
+
+    if path.len() != LEAF_SIZE {
+        bail!("invalid data path length")
+    }
+    let (data_hash, end) = path.split_at(HASH_SIZE);
+    if node_id(&[data_hash, end]) != id {
+        bail!("data path does not match the data root")
+    }
+
+    bounds.end = bounds.end.min(note(end)?);
+    if !bounds.contains(&offset) {
+        bail!("data path does not lead to offset {}", offset)
+    }
+
+    Ok((data_hash.try_into()?, bounds))
+}
+
+/// Verifies that `chunk` is the chunk holding byte `offset` of data of `data_size` bytes with
+/// `data_root`, and returns the bytes it covers.
+pub fn verify_chunk(
+    data_root: &[u8; HASH_SIZE],
+    data_size: u64,
+    offset: u64,
+    chunk: &[u8],
+    data_path: &[u8],
+) -> eyre::Result<Range<u64>> {
+    let (data_hash, bounds) = validate_path(data_root, data_size, offset, data_path)?;
+    if sha256(chunk) != data_hash {
+        return Err(eyre!("chunk at offset {} does not match its data path", offset))
+    }
+    if chunk.len() as u64 != bounds.end - bounds.start {
+        return Err(eyre!("chunk at offset {} has an unexpected size", offset))
+    }
+
+    Ok(bounds)
+}
+
+#[cfg(test)]
+mod chunk_tests {
+    use crate::chunk::{
+        chunk_ranges, data_root, encode_note as note, merkle_tree, node_id, sha256, verify_chunk,
+        MAX_CHUNK_SIZE, MIN_CHUNK_SIZE,
+    };
+
+    fn leaf(chunk: &[u8], end: u64) -> ([u8; 32], Vec<u8>) {
+        let path = [sha256(chunk), note(end)].concat();
+        (node_id(&[&path[..32], &path[32..]]), path)
+    }
+
+    fn branch(left: [u8; 32], right: [u8; 32], split: u64) -> ([u8; 32], Vec<u8>) {
+        let path = [left, right, note(split)].concat();
+        (node_id(&[&left, &right, &note(split)]), path)
+    }
+
+    #[test]
+    pub fn test_verify_chunk() {
+        let chunks: [&[u8]; 3] = [b"abcd", b"efgh", b"ij"];
+        let (leaf_0, leaf_0_path) = leaf(chunks[0], 4);
+        let (leaf_1, leaf_1_path) = leaf(chunks[1], 8);
+        let (leaf_2, leaf_2_path) = leaf(chunks[2], 10);
+        let (left, left_path) = branch(leaf_0, leaf_1, 4);
+        let (root, root_path) = branch(left, leaf_2, 8);
+
+        let path_0 = [root_path.as_slice(), &left_path, &leaf_0_path].concat();
+        let path_1 = [root_path.as_slice(), &left_path, &leaf_1_path].concat();
+        let path_2 = [root_path.as_slice(), &leaf_2_path].concat();
+
+        assert_eq!(verify_chunk(&root, 10, 0, chunks[0], &path_0).unwrap(), 0..4);
+        assert_eq!(verify_chunk(&root, 10, 3, chunks[0], &path_0).unwrap(), 0..4);
+        assert_eq!(verify_chunk(&root, 10, 5, chunks[1], &path_1).unwrap(), 4..8);
+        assert_eq!(verify_chunk(&root, 10, 9, chunks[2], &path_2).unwrap(), 8..10);
+
+        // the path has to lead to the offset
+        assert!(verify_chunk(&root, 10, 5, chunks[0], &path_0).is_err());
+        assert!(verify_chunk(&root, 10, 10, chunks[2], &path_2).is_err());
+        // and the chunk has to match it
+        assert!(verify_chunk(&root, 10, 0, b"abce", &path_0).is_err());
+        assert!(verify_chunk(&root, 10, 8, chunks[2], &path_1).is_err());
+        assert!(verify_chunk(&[0; 32], 10, 0, chunks[0], &path_0).is_err());
+        assert!(verify_chunk(&root, 10, 0, chunks[0], &path_0[1..]).is_err());
+    }
+
+    #[test]
+    pub fn test_data_root() {
+        // a single chunk
+        assert_eq!(data_root(b"abcd"), leaf(b"abcd", 4).0);
+
+        // the last chunk would be too small, the last two are split evenly
+        let data = vec![7; MAX_CHUNK_SIZE + 10];
+        let half = (MAX_CHUNK_SIZE + 10) / 2;
+        let (left, _) = leaf(&data[..half], half as u64);
+        let (right, _) = leaf(&data[half..], data.len() as u64);
+        assert_eq!(data_root(&data), branch(left, right, half as u64).0);
+
+        // a multiple of the chunk size ends with an empty chunk, carried up to the root
+        let data = vec![7; MAX_CHUNK_SIZE * 2];
+        let size = MAX_CHUNK_SIZE as u64;
+        let (leaf_0, _) = leaf(&data[..MAX_CHUNK_SIZE], size);
+        let (leaf_1, _) = leaf(&data[MAX_CHUNK_SIZE..], size * 2);
+        let (leaf_2, _) = leaf(&[], size * 2);
+        let (left, _) = branch(leaf_0, leaf_1, size);
+        assert_eq!(data_root(&data), branch(left, leaf_2, size * 2).0);
+    }
+
+    #[test]
+    pub fn test_merkle_tree() {
+        assert_eq!(chunk_ranges(b""), vec![0..0]);
+        assert_eq!(
+            chunk_ranges(&vec![0; MAX_CHUNK_SIZE * 2 + MIN_CHUNK_SIZE]),
+            vec![
+                0..MAX_CHUNK_SIZE,
+                MAX_CHUNK_SIZE..MAX_CHUNK_SIZE * 2,
+                MAX_CHUNK_SIZE * 2..MAX_CHUNK_SIZE * 2 + MIN_CHUNK_SIZE
+            ]
+        );
+
+        // every proof leads from the root to its chunk
+        let data = (0..MAX_CHUNK_SIZE * 4 + 100).map(|i| i as u8).collect::<Vec<_>>();
+        let (root, proofs) = merkle_tree(&data);
+        assert_eq!(proofs.len(), 5);
+        for proof in &proofs {
+            let chunk = &data[proof.range.clone()];
+            for offset in [proof.range.start, proof.range.end - 1] {
+                let bounds =
+                    verify_chunk(&root, data.len() as u64, offset as u64, chunk, &proof.data_path)
+                        .unwrap();
+                assert_eq!(bounds, proof.range.start as u64..proof.range.end as u64);
+            }
+        }
+
+        let (root, proofs) = merkle_tree(b"abcd");
+        assert_eq!(root, leaf(b"abcd", 4).0);
+        assert_eq!(proofs[0].data_path, leaf(b"abcd", 4).1);
+    }
+}
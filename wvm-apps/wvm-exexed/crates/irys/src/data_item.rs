@@ -0,0 +1,181 @@
+use crate::{
+    backend::Tag,
+    signer::{KeyType, UploadSigner},
+    tx::{deep_hash, DeepHashItem},
+};
+use base64::{prelude::BASE64_URL_SAFE_NO_PAD, Engine};
+use eyre::bail;
+use sha2::{Digest, Sha256};
+
+/// A signed ANS-104 data item, the unit bundlers like Irys accept uploads in.
+///
+/// Items are created without target and anchor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataItem {
+    pub signature_type: u16,
+    pub signature: Vec<u8>,
+    pub owner: Vec<u8>,
+    pub tags: Vec<Tag>,
+    pub data: Vec<u8>,
+}
+
+impl DataItem {
+    /// Creates a data item of `data` and `tags` signed by `signer`.
+    pub async fn sign(
+        signer: &dyn UploadSigner,
+        data: Vec<u8>,
+        tags: Vec<Tag>,
+    ) -> eyre::Result<Self> {
+        let public_key = signer.public_key().await?;
+        let (signature_length, owner_length) = public_key.key_type.lengths();
+        if public_key.owner.len() != owner_length {
+            bail!(
+                "invalid {:?} public key: expected {} bytes, got {}",
+                public_key.key_type,
+                owner_length,
+                public_key.owner.len()
+            )
+        }
+
+        let mut item = DataItem {
+            signature_type: public_key.key_type.signature_type(),
+            signature: vec![],
+            owner: public_key.owner,
+            tags,
+            data,
+        };
+        item.signature = signer.sign(&item.signature_data()).await?;
+        if item.signature.len() != signature_length {
+            bail!(
+                "invalid {:?} signature: expected {} bytes, got {}",
+                public_key.key_type,
+                signature_length,
+                item.signature.len()
+            )
+        }
+
+        Ok(item)
+    }
+
+    /// Returns the message the signature of the item is over.
+    pub fn signature_data(&self) -> Vec<u8> {
+        deep_hash(&DeepHashItem::List(vec![
+            DeepHashItem::Blob(b"dataitem".to_vec()),
+            DeepHashItem::Blob(b"1".to_vec()),
+            DeepHashItem::Blob(self.signature_type.to_string().into_bytes()),
+            DeepHashItem::Blob(self.owner.clone()),
+            // target
+            DeepHashItem::Blob(vec![]),
+            // anchor
+            DeepHashItem::Blob(vec![]),
+            DeepHashItem::Blob(encode_tags(&self.tags)),
+            DeepHashItem::Blob(self.data.clone()),
+        ]))
+        .to_vec()
+    }
+
+    /// Returns the id of the item, the hash of its signature.
+    pub fn id(&self) -> String {
+        BASE64_URL_SAFE_NO_PAD.encode(Sha256::digest(&self.signature))
+    }
+
+    /// Returns the size of the serialized item of `data_len` bytes of data with `tags`, signed with
+    /// a key of `key_type`.
+    pub fn size(key_type: KeyType, tags: &[Tag], data_len: usize) -> usize {
+        let (signature_length, owner_length) = key_type.lengths();
+        2 + signature_length + owner_length + 2 + 16 + encode_tags(tags).len() + data_len
+    }
+
+    /// Serializes the item in the binary format of ANS-104.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let tags = encode_tags(&self.tags);
+        let mut bytes = Vec::with_capacity(
+            2 + self.signature.len() + self.owner.len() + 2 + 16 + tags.len() + self.data.len(),
+        );
+
+        bytes.extend_from_slice(&self.signature_type.to_le_bytes());
+        bytes.extend_from_slice(&self.signature);
+        bytes.extend_from_slice(&self.owner);
+        // no target and no anchor
+        bytes.extend_from_slice(&[0, 0]);
+        bytes.extend_from_slice(&(self.tags.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(&(tags.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(&tags);
+        bytes.extend_from_slice(&self.data);
+
+        bytes
+    }
+}
+
+/// Encodes tags as an Avro array of `{name: bytes, value: bytes}` records.
+fn encode_tags(tags: &[Tag]) -> Vec<u8> {
+    let mut bytes = vec![];
+    if tags.is_empty() {
+        return bytes
+    }
+
+    encode_avro_long(&mut bytes, tags.len() as i64);
+    for tag in tags {
+        for field in [&tag.name, &tag.value] {
+            encode_avro_long(&mut bytes, field.len() as i64);
+            bytes.extend_from_slice(field.as_bytes());
+        }
+    }
+    // end of the array
+    bytes.push(0);
+
+    bytes
+}
+
+/// Appends `value` as a zigzag encoded varint.
+fn encode_avro_long(bytes: &mut Vec<u8>, value: i64) {
+    let mut value = ((value << 1) ^ (value >> 63)) as u64;
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+#[cfg(test)]
+mod data_item_tests {
+    use crate::{
+        backend::Tag,
+        data_item::{encode_avro_long, encode_tags, DataItem},
+        signer::{signer_tests::SOLANA_KEY, KeyType, LocalSigner},
+    };
+    use ring::signature::{UnparsedPublicKey, ED25519};
+
+    #[test]
+    pub fn test_encode_tags() {
+        assert_eq!(encode_tags(&[]), Vec::<u8>::new());
+        assert_eq!(encode_tags(&[Tag::new("a", "b")]), vec![2, 2, 97, 2, 98, 0]);
+
+        let mut bytes = vec![];
+        encode_avro_long(&mut bytes, 64);
+        assert_eq!(bytes, vec![0x80, 0x01]);
+    }
+
+    #[tokio::test]
+    pub async fn test_sign_data_item() {
+        let signer = LocalSigner::from_key(KeyType::Solana, SOLANA_KEY).unwrap();
+        let item =
+            DataItem::sign(&signer, b"hello".to_vec(), vec![Tag::new("a", "b")]).await.unwrap();
+
+        UnparsedPublicKey::new(&ED25519, &item.owner)
+            .verify(&item.signature_data(), &item.signature)
+            .unwrap();
+        assert_eq!(item.id().len(), 43);
+
+        let bytes = item.to_bytes();
+        assert_eq!(bytes[..2], [2, 0]);
+        assert_eq!(bytes[2..66], item.signature);
+        assert_eq!(bytes[66..98], item.owner);
+        assert_eq!(bytes[98..100], [0, 0]);
+        assert_eq!(bytes[100..108], 1u64.to_le_bytes());
+        assert_eq!(bytes[108..116], 6u64.to_le_bytes());
+        assert_eq!(bytes[116..122], [2, 2, 97, 2, 98, 0]);
+        assert_eq!(&bytes[122..], b"hello");
+        assert_eq!(DataItem::size(KeyType::Solana, &item.tags, 5), bytes.len());
+    }
+}
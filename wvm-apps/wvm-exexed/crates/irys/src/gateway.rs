@@ -0,0 +1,382 @@
+use crate::{
+    backend::{PermanentStorageBackend, StoredItem, Tag, TagQuery},
+    chunk::verify_chunk,
+    tx::{decode_base64, TxHeader},
+};
+use eyre::{bail, eyre, Error};
+use reqwest::{header::RANGE, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+pub const DEFAULT_ARWEAVE_GATEWAY: &str = "https://arweave.net";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Response {
+    pub data: Data,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Data {
+    pub transactions: Transactions,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Transactions {
+    pub edges: Vec<Edge>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Edge {
+    pub cursor: Option<String>,
+    pub node: Node,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Node {
+    pub id: String,
+    pub data: NodeData,
+    pub tags: Option<Vec<Tag>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NodeData {
+    pub size: String,
+}
+
+impl TryFrom<Node> for StoredItem {
+    type Error = Error;
+
+    fn try_from(node: Node) -> Result<Self, Self::Error> {
+        let size = node
+            .data
+            .size
+            .parse::<usize>()
+            .map_err(|e| eyre!("invalid size for item {}: {}", node.id, e))?;
+
+        Ok(StoredItem { id: node.id, size, tags: node.tags.unwrap_or_default(), cursor: None })
+    }
+}
+
+impl TryFrom<Edge> for StoredItem {
+    type Error = Error;
+
+    fn try_from(edge: Edge) -> Result<Self, Self::Error> {
+        Ok(StoredItem { cursor: edge.cursor, ..StoredItem::try_from(edge.node)? })
+    }
+}
+
+/// Position of a transaction in the weave, as returned by `/tx/{id}/offset`.
+#[derive(Debug, Clone, Deserialize)]
+struct TxOffset {
+    /// Offset of the last byte of the transaction.
+    offset: String,
+    size: String,
+}
+
+/// A chunk and its Merkle proof, as returned by `/chunk/{offset}`.
+#[derive(Debug, Clone, Deserialize)]
+struct TxChunk {
+    chunk: String,
+    data_path: String,
+}
+
+/// Removes a trailing `/` from a gateway url.
+pub fn clean_gateway_url(gateway: &str) -> String {
+    let clean_gateway =
+        if gateway.ends_with('/') { &gateway[..gateway.len() - 1] } else { gateway };
+
+    clean_gateway.to_string()
+}
+
+/// Builds the GraphQL document for `query`.
+///
+/// Values are encoded as JSON strings, which are valid GraphQL string literals, so user provided
+/// values cannot alter the query. Items are sorted newest first by block height.
+pub fn graphql_query(query: &TagQuery) -> String {
+    let mut args = vec![];
+
+    if !query.ids.is_empty() {
+        args.push(format!("ids: {}", string_list(&query.ids)));
+    }
+
+    if !query.owners.is_empty() {
+        args.push(format!("owners: {}", string_list(&query.owners)));
+    }
+
+    if !query.tags.is_empty() {
+        let tags: Vec<String> = query
+            .tags
+            .iter()
+            .map(|filter| {
+                format!(
+                    "{{name: {}, values: {}}}",
+                    serde_json::Value::from(filter.name.as_str()),
+                    string_list(&filter.values)
+                )
+            })
+            .collect();
+        args.push(format!("tags: [{}]", tags.join(", ")));
+    }
+
+    if let Some(first) = query.first {
+        args.push(format!("first: {}", first));
+    }
+
+    if let Some(after) = &query.after {
+        args.push(format!("after: {}", serde_json::Value::from(after.as_str())));
+    }
+
+    args.push("sort: HEIGHT_DESC".to_string());
+    let args = format!("({})", args.join(", "));
+
+    format!(
+        "{{\n  transactions{} {{\n    edges {{\n      cursor\n      node {{\n        id\n        tags {{\n          name\n          value\n        }}\n        data {{\n          size\n        }}\n      }}\n    }}\n  }}\n}}\n",
+        args
+    )
+}
+
+fn string_list(values: &[String]) -> String {
+    let values: Vec<String> =
+        values.iter().map(|v| serde_json::Value::from(v.as_str()).to_string()).collect();
+    format!("[{}]", values.join(", "))
+}
+
+pub async fn send_graphql(gateway: &str, query: &str) -> Result<Response, Error> {
+    let query = serde_json::json!({
+        "variables": {},
+        "query": query
+    });
+
+    // Create a client
+    let client = reqwest::Client::new();
+
+    // Send the request
+    let res = client
+        .post(format!("{}/{}", gateway, "graphql"))
+        .header("Content-Type", "application/json")
+        .json(&query)
+        .send()
+        .await?;
+
+    Ok(res.json::<Response>().await?)
+}
+
+/// Read-only access to Arweave through an HTTP gateway.
+///
+/// The data of layer 1 transactions is verified against their signed header, so the gateway does
+/// not have to be trusted for them. Data items of bundles have no header on layer 1 and can't be
+/// verified, reading them fails unless the gateway is trusted for them with
+/// [`ArweaveGateway::with_trusted_data_items`].
+#[derive(Debug, Clone)]
+pub struct ArweaveGateway {
+    url: String,
+    trusted_data_items: bool,
+}
+
+impl Default for ArweaveGateway {
+    fn default() -> Self {
+        Self::new(DEFAULT_ARWEAVE_GATEWAY)
+    }
+}
+
+impl ArweaveGateway {
+    pub fn new(url: &str) -> Self {
+        ArweaveGateway { url: clean_gateway_url(url), trusted_data_items: false }
+    }
+
+    /// Returns data items of bundles as the gateway serves them if `trusted`, e.g. for the
+    /// gateway of the node's own storage backend.
+    pub fn with_trusted_data_items(mut self, trusted: bool) -> Self {
+        self.trusted_data_items = trusted;
+        self
+    }
+
+    pub fn url(&self) -> &str {
+        self.url.as_str()
+    }
+
+    /// Returns the header of transaction `id`, verified against its signature, `None` if it is
+    /// not a layer 1 transaction, e.g. a data item of a bundle.
+    ///
+    /// Fails for items without a header unless data items are trusted.
+    async fn tx_header(&self, id: &str) -> eyre::Result<Option<TxHeader>> {
+        let res = reqwest::get(format!("{}/tx/{}", self.url, id)).await?;
+        if res.status() == StatusCode::NOT_FOUND {
+            if !self.trusted_data_items {
+                bail!("{} has no layer 1 transaction {} to verify its data against", self.url, id)
+            }
+            return Ok(None)
+        }
+
+        let header = res.error_for_status()?.json::<TxHeader>().await?;
+        header.verify(id)?;
+        Ok(Some(header))
+    }
+
+    /// Reads `range` of layer 1 transaction `id` chunk by chunk, verifying every chunk against
+    /// the data root of the transaction.
+    async fn fetch_chunks(
+        &self,
+        id: &str,
+        data_root: &[u8; 32],
+        data_size: u64,
+        range: Range<u64>,
+    ) -> eyre::Result<Vec<u8>> {
+        let end = range.end.min(data_size);
+        let mut position = range.start.min(end);
+        let mut data = Vec::with_capacity((end - position) as usize);
+        if position == end {
+            return Ok(data)
+        }
+
+        let offset = reqwest::get(format!("{}/tx/{}/offset", self.url, id))
+            .await?
+            .error_for_status()?
+            .json::<TxOffset>()
+            .await?;
+        let (Ok(last_byte), Ok(size)) = (offset.offset.parse::<u64>(), offset.size.parse::<u64>())
+        else {
+            bail!("invalid offset for transaction {}", id)
+        };
+        if size != data_size || last_byte < size {
+            bail!("offset of transaction {} does not match its data size", id)
+        }
+        let first_byte = last_byte + 1 - size;
+
+        while position < end {
+            let chunk = reqwest::get(format!("{}/chunk/{}", self.url, first_byte + position))
+                .await?
+                .error_for_status()?
+                .json::<TxChunk>()
+                .await?;
+            let chunk_data = decode_base64(chunk.chunk.as_str())?;
+            let data_path = decode_base64(chunk.data_path.as_str())?;
+            let bounds = verify_chunk(data_root, data_size, position, &chunk_data, &data_path)?;
+
+            let chunk_end = bounds.end.min(end);
+            data.extend_from_slice(
+                &chunk_data
+                    [(position - bounds.start) as usize..(chunk_end - bounds.start) as usize],
+            );
+            position = chunk_end;
+        }
+
+        Ok(data)
+    }
+
+    /// Reads `range` of item `id` with an HTTP range request.
+    ///
+    /// Data items of bundles have no chunks of their own, their data is returned as the gateway
+    /// serves it, like by [`PermanentStorageBackend::fetch`]. Only used for trusted data items.
+    async fn fetch_http_range(&self, id: &str, range: Range<u64>) -> eyre::Result<Vec<u8>> {
+        if range.is_empty() {
+            return Ok(vec![])
+        }
+
+        let res = reqwest::Client::new()
+            .get(format!("{}/{}", self.url, id))
+            .header(RANGE, format!("bytes={}-{}", range.start, range.end - 1))
+            .send()
+            .await?;
+        if res.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+            return Ok(vec![])
+        }
+
+        let res = res.error_for_status()?;
+        let partial = res.status() == StatusCode::PARTIAL_CONTENT;
+        let data = res.bytes().await?;
+
+        // gateways that ignore the range return the whole item
+        let data =
+            if partial { &data[..] } else { &data[(range.start as usize).min(data.len())..] };
+        Ok(data[..data.len().min((range.end - range.start) as usize)].to_vec())
+    }
+}
+
+#[async_trait::async_trait]
+impl PermanentStorageBackend for ArweaveGateway {
+    async fn upload(&self, _data: Vec<u8>, _tags: Vec<Tag>) -> eyre::Result<String> {
+        Err(eyre!("arweave gateway {} does not accept uploads", self.url))
+    }
+
+    async fn fetch(&self, id: &str) -> eyre::Result<Vec<u8>> {
+        let header = self.tx_header(id).await?;
+        if header.as_ref().is_some_and(|header| header.data_root.is_empty()) {
+            return Ok(vec![])
+        }
+
+        let res = reqwest::get(format!("{}/{}", self.url, id)).await?.error_for_status()?;
+        let data = res.bytes().await?.to_vec();
+        if let Some(header) = header {
+            header.verify_data(&data)?;
+        }
+
+        Ok(data)
+    }
+
+    /// Reads the chunks of layer 1 transactions, verified against their data root, and trusted
+    /// data items of bundles with HTTP range requests.
+    async fn fetch_range(&self, id: &str, range: Range<usize>) -> eyre::Result<Vec<u8>> {
+        let range = range.start as u64..range.end as u64;
+        let Some(header) = self.tx_header(id).await? else {
+            return self.fetch_http_range(id, range).await
+        };
+
+        match header.data_root()? {
+            Some(data_root) => self.fetch_chunks(id, &data_root, header.data_size()?, range).await,
+            None => Ok(vec![]),
+        }
+    }
+
+    async fn query(&self, query: &TagQuery) -> eyre::Result<Vec<StoredItem>> {
+        let res = send_graphql(self.url.as_str(), graphql_query(query).as_str()).await?;
+        res.data.transactions.edges.into_iter().map(StoredItem::try_from).collect()
+    }
+}
+
+#[cfg(test)]
+mod gateway_tests {
+    use crate::{
+        backend::{PermanentStorageBackend, TagQuery},
+        gateway::{graphql_query, ArweaveGateway},
+    };
+    use reth::primitives::b256;
+
+    #[test]
+    pub fn test_graphql_query() {
+        let query = TagQuery::new().with_tag("Block-Number", "141550").with_first(1);
+        let gql = graphql_query(&query);
+        assert!(gql.contains(
+            r#"transactions(tags: [{name: "Block-Number", values: ["141550"]}], first: 1, sort: HEIGHT_DESC)"#
+        ));
+
+        let query = TagQuery::new().with_owner("owner").with_first(10).with_after("cursor");
+        let gql = graphql_query(&query);
+        assert!(gql.contains(
+            r#"transactions(owners: ["owner"], first: 10, after: "cursor", sort: HEIGHT_DESC)"#
+        ));
+        assert!(gql.contains("cursor\n"));
+    }
+
+    #[test]
+    pub fn test_graphql_query_escapes_values() {
+        let query = TagQuery::new().with_id("a\"]) { evil }");
+        let gql = graphql_query(&query);
+        assert!(gql.contains(r#"ids: ["a\"]) { evil }"]"#));
+    }
+
+    #[tokio::test]
+    #[ignore = "requires access to arweave.net"]
+    async fn test_fetch_range() {
+        let gateway = ArweaveGateway::default();
+        let data = gateway.fetch_range("bs318IdjLWQK7pF_bNIbJnpade8feD7yGAS8xIffJDI", 6..100).await;
+        assert_eq!(data.unwrap(), b"world".to_vec());
+    }
+
+    #[test]
+    pub fn test_encode_id() {
+        let gateway = ArweaveGateway::new("https://arweave.net");
+        let raw = b256!("6ecdf5f087632d640aee917f6cd21b267a5a75ef1f783ef21804bcc487df2432");
+        assert_eq!(gateway.encode_id(&raw), "bs318IdjLWQK7pF_bNIbJnpade8feD7yGAS8xIffJDI");
+    }
+}
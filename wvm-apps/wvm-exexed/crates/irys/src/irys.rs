@@ -1,50 +1,55 @@
-use bundlr_sdk::{
-    currency::solana::{Solana, SolanaBuilder},
-    tags::Tag,
-    Bundlr, BundlrBuilder,
+use crate::{
+    backend::{PermanentStorageBackend, StoredItem, Tag, TagQuery},
+    data_item::DataItem,
+    gateway::ArweaveGateway,
+    signer::UploadSigner,
 };
-use dotenv::dotenv;
 use eyre::eyre;
-use reqwest::Url;
-use std::env;
+use serde::Deserialize;
+use std::{ops::Range, sync::Arc};
 
+pub const DEFAULT_IRYS_NODE: &str = "https://node1.bundlr.network";
+
+/// Uploads through an Irys bundler node and reads through an Arweave gateway.
+///
+/// Uploads are signed by the configured [`UploadSigner`] and paid in the currency of its key.
 #[derive(Clone, Debug)]
 pub struct IrysProvider {
-    private_key: Option<String>,
-}
-
-pub fn get_irys_pk() -> Result<String, env::VarError> {
-    dotenv().ok();
-    let key = "irys_pk";
-    env::var(key)
+    signer: Option<Arc<dyn UploadSigner>>,
+    node_url: String,
+    gateway: ArweaveGateway,
+    client: reqwest::Client,
 }
 
-async fn init_bundlr(private_key: Option<String>) -> eyre::Result<Bundlr<Solana>> {
-    let irys_wallet_pk: String = get_irys_pk().unwrap_or_else(|e| private_key.unwrap());
-    let url = Url::parse("https://node1.bundlr.network").unwrap();
-
-    let currency = SolanaBuilder::new().wallet(&irys_wallet_pk).build().map_err(|e| {
-        eyre::eyre!(
-            "failed to initialize irys provider, failed to create bundlr wallet instance: {}",
-            e
-        )
-    })?;
-
-    let bundlr = BundlrBuilder::new()
-        .url(url)
-        .currency(currency)
-        .fetch_pub_info()
-        .await
-        .map_err(|e| eyre::eyre!("failed to fetch bundlr public info: {}", e))?
-        .build()
-        .map_err(|e| eyre::eyre!("bundlr successfully initialized: {}", e))?;
-
-    Ok(bundlr)
+impl Default for IrysProvider {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl IrysProvider {
-    pub fn new(private_key: Option<String>) -> IrysProvider {
-        IrysProvider { private_key }
+    pub fn new() -> IrysProvider {
+        IrysProvider {
+            signer: None,
+            node_url: DEFAULT_IRYS_NODE.to_string(),
+            gateway: ArweaveGateway::default().with_trusted_data_items(true),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    pub fn with_signer(mut self, signer: Arc<dyn UploadSigner>) -> Self {
+        self.signer = Some(signer);
+        self
+    }
+
+    pub fn with_node_url(mut self, node_url: &str) -> Self {
+        self.node_url = node_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    pub fn with_gateway(mut self, gateway: ArweaveGateway) -> Self {
+        self.gateway = gateway;
+        self
     }
 
     pub async fn upload_data_to_irys(
@@ -52,37 +57,128 @@ impl IrysProvider {
         data: Vec<u8>,
         param_tags: Vec<Tag>,
     ) -> eyre::Result<String> {
-        let mut tags = vec![
-            Tag::new("Protocol", "WeaveVM-ExEx"),
-            Tag::new("ExEx-Type", "Arweave-Data-Uploader"),
-        ];
+        #[derive(Deserialize)]
+        struct UploadResponse {
+            id: String,
+        }
 
-        tags.extend(param_tags);
+        let Some(signer) = &self.signer else {
+            return Err(eyre!("no upload signer configured, see `--wvm.storage.key-type`"))
+        };
 
-        let bundlr = init_bundlr(self.private_key.clone())
+        let item = DataItem::sign(signer.as_ref(), data, upload_tags(param_tags))
+            .await
+            .map_err(|e| eyre!("failed to sign data item: {}", e))?;
+        let currency = signer.public_key().await?.key_type.currency();
+
+        let response: UploadResponse = self
+            .client
+            .post(format!("{}/tx/{}", self.node_url, currency))
+            .header(reqwest::header::CONTENT_TYPE, "application/octet-stream")
+            .body(item.to_bytes())
+            .send()
             .await
-            .map_err(|e| eyre!("failed to initialize bundlr: {}", e))?;
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| eyre!("failed to send transaction: {}", e))?
+            .json()
+            .await
+            .map_err(|e| eyre!("invalid response of irys node: {}", e))?;
 
-        let mut tx = bundlr
-            .create_transaction(data, tags)
-            .map_err(|e| eyre!("failed to create transaction: {}", e))?;
+        if response.id != item.id() {
+            return Err(eyre!("irys node returned id {} for data item {}", response.id, item.id()))
+        }
 
-        bundlr
-            .sign_transaction(&mut tx)
-            .await
-            .map_err(|e| eyre!("failed to sign transaction: {}", e))?;
+        eyre::Ok(response.id)
+    }
 
-        let result = bundlr
-            .send_transaction(tx)
-            .await
-            .map_err(|e| eyre!("failed to send transaction: {}", e))?;
+    /// Returns the price of uploading `bytes` bytes quoted by the node.
+    pub async fn get_price(&self, bytes: usize) -> eyre::Result<Option<u128>> {
+        let Some(signer) = &self.signer else { return Ok(None) };
+        let currency = signer.public_key().await?.key_type.currency();
+
+        let price = self
+            .client
+            .get(format!("{}/price/{}/{}", self.node_url, currency, bytes))
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        let price = price
+            .trim()
+            .parse()
+            .map_err(|e| eyre!("invalid price of irys node {}: {}", price, e))?;
+
+        Ok(Some(price))
+    }
+
+    /// Returns the balance of the account of the signer at the node.
+    pub async fn get_balance(&self) -> eyre::Result<Option<u128>> {
+        #[derive(Deserialize)]
+        struct BalanceResponse {
+            balance: String,
+        }
+
+        let Some(signer) = &self.signer else { return Ok(None) };
+        let public_key = signer.public_key().await?;
+
+        let response: BalanceResponse = self
+            .client
+            .get(format!("{}/account/balance/{}", self.node_url, public_key.key_type.currency()))
+            .query(&[("address", public_key.address())])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let balance = response
+            .balance
+            .parse()
+            .map_err(|e| eyre!("invalid balance of irys account {}: {}", response.balance, e))?;
+
+        Ok(Some(balance))
+    }
+}
+
+/// Returns `tags` after the tags every upload of the node carries.
+fn upload_tags(tags: Vec<Tag>) -> Vec<Tag> {
+    let mut upload_tags =
+        vec![Tag::new("Protocol", "WeaveVM-ExEx"), Tag::new("ExEx-Type", "Arweave-Data-Uploader")];
+    upload_tags.extend(tags);
+    upload_tags
+}
 
-        let id = result["id"]
-            .as_str()
-            .ok_or_else(|| eyre!("missing 'id' field in response"))?
-            .to_string();
+#[async_trait::async_trait]
+impl PermanentStorageBackend for IrysProvider {
+    async fn upload(&self, data: Vec<u8>, tags: Vec<Tag>) -> eyre::Result<String> {
+        self.upload_data_to_irys(data, tags).await
+    }
+
+    async fn fetch(&self, id: &str) -> eyre::Result<Vec<u8>> {
+        self.gateway.fetch(id).await
+    }
 
-        eyre::Ok(id)
+    async fn fetch_range(&self, id: &str, range: Range<usize>) -> eyre::Result<Vec<u8>> {
+        self.gateway.fetch_range(id, range).await
+    }
+
+    async fn query(&self, query: &TagQuery) -> eyre::Result<Vec<StoredItem>> {
+        self.gateway.query(query).await
+    }
+
+    async fn upload_price(&self, bytes: usize) -> eyre::Result<Option<u128>> {
+        self.get_price(bytes).await
+    }
+
+    /// Uploads are charged for the signed data item.
+    async fn upload_size(&self, bytes: usize, tags: &[Tag]) -> eyre::Result<usize> {
+        let Some(signer) = &self.signer else { return Ok(bytes) };
+        let key_type = signer.public_key().await?.key_type;
+        Ok(DataItem::size(key_type, &upload_tags(tags.to_vec()), bytes))
+    }
+
+    async fn balance(&self) -> eyre::Result<Option<u128>> {
+        self.get_balance().await
     }
 }
 
@@ -90,12 +186,11 @@ impl IrysProvider {
 pub struct IrysRequest {
     tags: Vec<Tag>,
     data: Vec<u8>,
-    private_key: Option<String>,
 }
 
 impl IrysRequest {
     pub fn new() -> Self {
-        IrysRequest { tags: vec![], data: vec![], private_key: None }
+        IrysRequest { tags: vec![], data: vec![] }
     }
 
     pub fn set_tag(&mut self, name: &str, value: &str) -> &mut IrysRequest {
@@ -108,17 +203,10 @@ impl IrysRequest {
         self
     }
 
-    pub fn set_private_key(&mut self, data: String) -> &mut IrysRequest {
-        self.private_key = Some(data);
-        self
-    }
-
-    pub async fn send(&self) -> eyre::Result<String> {
-        let provider = IrysProvider::new(self.private_key.clone());
-        self.send_with_provider(&provider).await
-    }
-
-    pub async fn send_with_provider(&self, provider: &IrysProvider) -> eyre::Result<String> {
-        provider.upload_data_to_irys(self.data.clone(), self.tags.clone()).await
+    pub async fn send_with_provider(
+        &self,
+        provider: &dyn PermanentStorageBackend,
+    ) -> eyre::Result<String> {
+        provider.upload(self.data.clone(), self.tags.clone()).await
     }
 }
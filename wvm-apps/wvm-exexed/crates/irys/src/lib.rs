@@ -1 +1,16 @@
+pub mod args;
+pub mod arweave;
+pub mod backend;
+pub mod budget;
+pub mod chunk;
+pub mod data_item;
+pub mod gateway;
 pub mod irys;
+pub mod local;
+pub mod s3;
+pub mod signer;
+mod state_file;
+#[cfg(any(test, feature = "test-utils"))]
+/// Common fixtures of tests.
+pub mod test_utils;
+pub mod tx;
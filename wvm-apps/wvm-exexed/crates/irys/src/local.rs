@@ -0,0 +1,226 @@
+use crate::backend::{PermanentStorageBackend, StoredItem, Tag, TagQuery};
+use eyre::eyre;
+use reth::primitives::{hex, keccak256, B256};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File},
+    io::{Read, Seek, SeekFrom},
+    ops::Range,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Location of the local storage backend, relative to the node's datadir.
+pub const LOCAL_STORAGE_DIR: &str = "wvm/local-storage";
+
+const DATA_EXTENSION: &str = "data";
+const META_EXTENSION: &str = "json";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ItemMeta {
+    size: usize,
+    tags: Vec<Tag>,
+    /// Upload time in nanoseconds, used to order query results.
+    created_at: u128,
+}
+
+/// Stand-in for Arweave that keeps every item in a local directory.
+///
+/// Each item is stored as `<id>.data` next to a `<id>.json` file holding its tags. Ids are the hex
+/// encoded hash of data and tags, so uploading the same item twice yields the same id.
+#[derive(Debug, Clone)]
+pub struct LocalStorage {
+    dir: PathBuf,
+}
+
+impl LocalStorage {
+    pub fn new(dir: impl Into<PathBuf>) -> eyre::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(LocalStorage { dir })
+    }
+
+    /// Opens the local storage below the node's datadir.
+    pub fn at_datadir(datadir: &Path) -> eyre::Result<Self> {
+        Self::new(datadir.join(LOCAL_STORAGE_DIR))
+    }
+
+    pub(crate) fn item_id(data: &[u8], tags: &[Tag]) -> String {
+        let mut buf = data.to_vec();
+        for tag in tags {
+            buf.extend_from_slice(keccak256(tag.name.as_bytes()).as_slice());
+            buf.extend_from_slice(keccak256(tag.value.as_bytes()).as_slice());
+        }
+        hex::encode(keccak256(buf))
+    }
+
+    fn path(&self, id: &str, extension: &str) -> eyre::Result<PathBuf> {
+        // ids end up in file names, never let them escape the storage directory
+        if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+            return Err(eyre!("invalid item id: {}", id))
+        }
+
+        Ok(self.dir.join(format!("{}.{}", id, extension)))
+    }
+}
+
+#[async_trait::async_trait]
+impl PermanentStorageBackend for LocalStorage {
+    async fn upload(&self, data: Vec<u8>, tags: Vec<Tag>) -> eyre::Result<String> {
+        let id = Self::item_id(data.as_slice(), tags.as_slice());
+        let created_at = SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos();
+        let meta = ItemMeta { size: data.len(), tags, created_at };
+
+        fs::write(self.path(id.as_str(), DATA_EXTENSION)?, data)?;
+        fs::write(self.path(id.as_str(), META_EXTENSION)?, serde_json::to_vec(&meta)?)?;
+
+        Ok(id)
+    }
+
+    async fn fetch(&self, id: &str) -> eyre::Result<Vec<u8>> {
+        fs::read(self.path(id, DATA_EXTENSION)?)
+            .map_err(|e| eyre!("item {} not found in local storage: {}", id, e))
+    }
+
+    async fn fetch_range(&self, id: &str, range: Range<usize>) -> eyre::Result<Vec<u8>> {
+        let mut file = File::open(self.path(id, DATA_EXTENSION)?)
+            .map_err(|e| eyre!("item {} not found in local storage: {}", id, e))?;
+        let size = file.metadata()?.len() as usize;
+        let end = range.end.min(size);
+        let start = range.start.min(end);
+
+        let mut data = vec![0; end - start];
+        file.seek(SeekFrom::Start(start as u64))?;
+        file.read_exact(&mut data)?;
+        Ok(data)
+    }
+
+    /// The cursor of an item is its id. Local items have no owner.
+    async fn query(&self, query: &TagQuery) -> eyre::Result<Vec<StoredItem>> {
+        if !query.owners.is_empty() {
+            return Ok(vec![])
+        }
+
+        let mut items = vec![];
+
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some(META_EXTENSION) {
+                continue
+            }
+
+            let Some(id) = path.file_stem().and_then(|s| s.to_str()).map(String::from) else {
+                continue
+            };
+
+            let meta: ItemMeta = serde_json::from_slice(fs::read(&path)?.as_slice())?;
+            if query.matches(id.as_str(), meta.tags.as_slice()) {
+                let item =
+                    StoredItem { cursor: Some(id.clone()), id, size: meta.size, tags: meta.tags };
+                items.push((meta.created_at, item));
+            }
+        }
+
+        // newest first, ties broken by id to keep the order deterministic
+        items.sort_by(|(a_time, a), (b_time, b)| b_time.cmp(a_time).then_with(|| a.id.cmp(&b.id)));
+
+        let mut items = items.into_iter().map(|(_, item)| item);
+        if let Some(after) = &query.after {
+            if !items.by_ref().any(|item| &item.id == after) {
+                return Err(eyre!("unknown cursor: {}", after))
+            }
+        }
+
+        Ok(match query.first {
+            Some(first) => items.take(first).collect(),
+            None => items.collect(),
+        })
+    }
+
+    /// Local ids are the hex encoding of the raw id.
+    fn encode_id(&self, raw: &B256) -> String {
+        hex::encode(raw)
+    }
+
+    fn decode_id(&self, id: &str) -> Option<B256> {
+        let raw = hex::decode(id).ok()?;
+        B256::try_from(raw.as_slice()).ok()
+    }
+}
+
+#[cfg(test)]
+mod local_tests {
+    use crate::{
+        backend::{PermanentStorageBackend, Tag, TagQuery},
+        local::LocalStorage,
+    };
+
+    #[tokio::test]
+    async fn test_upload_fetch_query() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = LocalStorage::new(dir.path()).unwrap();
+
+        let id = storage
+            .upload(b"Hello world".to_vec(), vec![Tag::new("Block-Number", "1")])
+            .await
+            .unwrap();
+        storage.upload(b"other".to_vec(), vec![Tag::new("Block-Number", "2")]).await.unwrap();
+
+        assert_eq!(storage.fetch(id.as_str()).await.unwrap(), b"Hello world".to_vec());
+
+        let items = storage.query(&TagQuery::new().with_tag("Block-Number", "1")).await.unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].id, id);
+        assert_eq!(items[0].size, 11);
+        assert_eq!(items[0].tag("Block-Number"), Some("1"));
+
+        let items = storage.query(&TagQuery::new().with_id(id.as_str())).await.unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(storage.decode_id(id.as_str()).map(|raw| storage.encode_id(&raw)), Some(id));
+    }
+
+    #[tokio::test]
+    async fn test_query_pages() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = LocalStorage::new(dir.path()).unwrap();
+        let mut ids = vec![];
+        for i in 0..5 {
+            let tags = vec![Tag::new("App-Name", "test"), Tag::new("Index", &i.to_string())];
+            ids.push(storage.upload(vec![i], tags).await.unwrap());
+        }
+        ids.reverse();
+
+        let query = TagQuery::new().with_tag("App-Name", "test").with_first(2);
+        let mut pages = vec![];
+        let mut page = storage.query(&query).await.unwrap();
+        while let Some(last) = page.last() {
+            let cursor = last.cursor.clone().unwrap();
+            pages.push(page.into_iter().map(|item| item.id).collect::<Vec<_>>());
+            page = storage.query(&query.clone().with_after(&cursor)).await.unwrap();
+        }
+        assert_eq!(pages, vec![ids[..2].to_vec(), ids[2..4].to_vec(), ids[4..].to_vec()]);
+
+        assert!(storage.query(&query.clone().with_after("unknown")).await.is_err());
+        assert!(storage.query(&query.with_owner("owner")).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_range() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = LocalStorage::new(dir.path()).unwrap();
+        let id = storage.upload(b"Hello world".to_vec(), vec![]).await.unwrap();
+
+        assert_eq!(storage.fetch_range(id.as_str(), 6..100).await.unwrap(), b"world".to_vec());
+        assert_eq!(storage.fetch_range(id.as_str(), 0..5).await.unwrap(), b"Hello".to_vec());
+        assert!(storage.fetch_range(id.as_str(), 20..30).await.unwrap().is_empty());
+        assert!(storage.fetch_range("unknown", 0..5).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_rejects_path_ids() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = LocalStorage::new(dir.path()).unwrap();
+
+        assert!(storage.fetch("../secret").await.is_err());
+    }
+}
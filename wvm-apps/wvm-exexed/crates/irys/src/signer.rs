@@ -0,0 +1,518 @@
+use crate::tx::decode_base64;
+use base64::{prelude::BASE64_URL_SAFE_NO_PAD, Engine};
+use clap::ValueEnum;
+use eyre::{bail, eyre};
+use reth::primitives::{keccak256, Address};
+use ring::{
+    rand::SystemRandom,
+    rsa::{KeyPairComponents, PublicKeyComponents},
+    signature::{Ed25519KeyPair, RsaKeyPair, RSA_PSS_SHA256},
+};
+use secp256k1::{Message, PublicKey, SecretKey, SECP256K1};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    fmt::{self, Debug},
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+#[cfg(unix)]
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::UnixStream,
+};
+
+/// Kinds of keys data items can be signed with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyType {
+    /// An Ed25519 keypair, base58 encoded like Solana wallets export it.
+    Solana,
+    /// A secp256k1 secret key, hex encoded.
+    Ethereum,
+    /// An RSA key in the JWK format of Arweave wallets.
+    Arweave,
+}
+
+impl KeyType {
+    /// Returns the ANS-104 signature type of data items signed with the key.
+    pub const fn signature_type(&self) -> u16 {
+        match self {
+            KeyType::Arweave => 1,
+            KeyType::Solana => 2,
+            KeyType::Ethereum => 3,
+        }
+    }
+
+    /// Returns the lengths of the signature and the owner of data items signed with the key.
+    pub const fn lengths(&self) -> (usize, usize) {
+        match self {
+            KeyType::Arweave => (512, 512),
+            KeyType::Solana => (64, 32),
+            KeyType::Ethereum => (65, 65),
+        }
+    }
+
+    /// Returns the currency uploads signed with the key are paid in on Irys.
+    pub const fn currency(&self) -> &'static str {
+        match self {
+            KeyType::Solana => "solana",
+            KeyType::Ethereum => "ethereum",
+            KeyType::Arweave => "arweave",
+        }
+    }
+}
+
+/// The public key a signer signs data items as.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignerPublicKey {
+    pub key_type: KeyType,
+    /// Owner of the data items, the raw public key.
+    pub owner: Vec<u8>,
+}
+
+impl SignerPublicKey {
+    /// Returns the address of the key in the format of its chain, the account uploads are paid
+    /// from.
+    pub fn address(&self) -> String {
+        match self.key_type {
+            KeyType::Solana => bs58::encode(&self.owner).into_string(),
+            KeyType::Ethereum => {
+                let hash = keccak256(self.owner.get(1..).unwrap_or_default());
+                Address::from_slice(&hash[12..]).to_string()
+            }
+            KeyType::Arweave => BASE64_URL_SAFE_NO_PAD.encode(Sha256::digest(&self.owner)),
+        }
+    }
+}
+
+/// Signs the data items the node uploads.
+///
+/// Signers never expose their secret key, so it can be kept in a separate process, see
+/// [`RemoteSigner`].
+#[async_trait::async_trait]
+pub trait UploadSigner: Debug + Send + Sync {
+    /// Returns the public key data items are signed as.
+    async fn public_key(&self) -> eyre::Result<SignerPublicKey>;
+
+    /// Signs `message`, the deep hash of a data item.
+    async fn sign(&self, message: &[u8]) -> eyre::Result<Vec<u8>>;
+}
+
+enum LocalKey {
+    Solana(Ed25519KeyPair),
+    Ethereum(SecretKey),
+    Arweave(RsaKeyPair),
+}
+
+/// A signer holding its key in memory, loaded from a key file or an environment variable.
+pub struct LocalSigner {
+    key: LocalKey,
+    public_key: SignerPublicKey,
+}
+
+/// An Arweave wallet, every field is base64url encoded.
+#[derive(Deserialize)]
+struct Jwk {
+    n: String,
+    e: String,
+    d: String,
+    p: String,
+    q: String,
+    dp: String,
+    dq: String,
+    qi: String,
+}
+
+impl LocalSigner {
+    /// Creates a signer from `key`, encoded as described by [`KeyType`].
+    pub fn from_key(key_type: KeyType, key: &str) -> eyre::Result<Self> {
+        let key = key.trim();
+        let (key, owner) = match key_type {
+            KeyType::Solana => {
+                let keypair = bs58::decode(key)
+                    .into_vec()
+                    .map_err(|e| eyre!("invalid Solana keypair: {}", e))?;
+                if keypair.len() != 64 {
+                    bail!("invalid Solana keypair: expected 64 bytes, got {}", keypair.len())
+                }
+                let (seed, public_key) = keypair.split_at(32);
+                let keypair = Ed25519KeyPair::from_seed_and_public_key(seed, public_key)
+                    .map_err(|e| eyre!("invalid Solana keypair: {}", e))?;
+                (LocalKey::Solana(keypair), public_key.to_vec())
+            }
+            KeyType::Ethereum => {
+                let secret = reth::primitives::hex::decode(key)
+                    .map_err(|e| eyre!("invalid Ethereum key: {}", e))?;
+                let secret = SecretKey::from_slice(&secret)
+                    .map_err(|e| eyre!("invalid Ethereum key: {}", e))?;
+                let owner = PublicKey::from_secret_key(SECP256K1, &secret).serialize_uncompressed();
+                (LocalKey::Ethereum(secret), owner.to_vec())
+            }
+            KeyType::Arweave => {
+                let jwk: Jwk =
+                    serde_json::from_str(key).map_err(|e| eyre!("invalid Arweave JWK: {}", e))?;
+                let n = decode_base64(&jwk.n)?;
+                let components = KeyPairComponents {
+                    public_key: PublicKeyComponents { n: n.clone(), e: decode_base64(&jwk.e)? },
+                    d: decode_base64(&jwk.d)?,
+                    p: decode_base64(&jwk.p)?,
+                    q: decode_base64(&jwk.q)?,
+                    dP: decode_base64(&jwk.dp)?,
+                    dQ: decode_base64(&jwk.dq)?,
+                    qInv: decode_base64(&jwk.qi)?,
+                };
+                let keypair = RsaKeyPair::from_components(&components)
+                    .map_err(|e| eyre!("invalid Arweave JWK: {}", e))?;
+                // data items and transactions only fit the owner and signature of 4096-bit keys
+                let modulus_len = keypair.public().modulus_len();
+                if modulus_len != KeyType::Arweave.lengths().1 {
+                    bail!(
+                        "invalid Arweave JWK: expected a 4096-bit key, got {} bits",
+                        modulus_len * 8
+                    )
+                }
+                (LocalKey::Arweave(keypair), n)
+            }
+        };
+
+        Ok(LocalSigner { key, public_key: SignerPublicKey { key_type, owner } })
+    }
+
+    /// Loads the key from the file at `path`.
+    ///
+    /// On unix the file must not be accessible by other users.
+    pub fn from_file(key_type: KeyType, path: &Path) -> eyre::Result<Self> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            let mode = std::fs::metadata(path)?.permissions().mode();
+            if mode & 0o077 != 0 {
+                bail!(
+                    "key file {} is accessible by other users (mode {:o}), restrict it to the \
+                     owner with `chmod 600`",
+                    path.display(),
+                    mode & 0o777
+                )
+            }
+        }
+
+        let key = std::fs::read_to_string(path)
+            .map_err(|e| eyre!("failed to read key file {}: {}", path.display(), e))?;
+        Self::from_key(key_type, &key)
+    }
+
+    /// Loads the key from the environment variable `var`.
+    pub fn from_env(key_type: KeyType, var: &str) -> eyre::Result<Self> {
+        let key = std::env::var(var)
+            .map_err(|e| eyre!("failed to read key from environment variable {}: {}", var, e))?;
+        Self::from_key(key_type, &key)
+    }
+}
+
+impl Debug for LocalSigner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // the key is never printed
+        f.debug_struct("LocalSigner")
+            .field("key_type", &self.public_key.key_type)
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait::async_trait]
+impl UploadSigner for LocalSigner {
+    async fn public_key(&self) -> eyre::Result<SignerPublicKey> {
+        Ok(self.public_key.clone())
+    }
+
+    async fn sign(&self, message: &[u8]) -> eyre::Result<Vec<u8>> {
+        match &self.key {
+            LocalKey::Solana(keypair) => Ok(keypair.sign(message).as_ref().to_vec()),
+            LocalKey::Ethereum(secret) => {
+                // signed like `personal_sign`, as Ethereum wallets sign data items
+                let mut prefixed =
+                    format!("\x19Ethereum Signed Message:\n{}", message.len()).into_bytes();
+                prefixed.extend_from_slice(message);
+                let digest = Message::from_digest(keccak256(prefixed).0);
+
+                let (recovery_id, signature) =
+                    SECP256K1.sign_ecdsa_recoverable(&digest, secret).serialize_compact();
+                let mut signature = signature.to_vec();
+                signature.push(recovery_id.to_i32() as u8 + 27);
+                Ok(signature)
+            }
+            LocalKey::Arweave(keypair) => {
+                let mut signature = vec![0; keypair.public().modulus_len()];
+                keypair
+                    .sign(&RSA_PSS_SHA256, &SystemRandom::new(), message, &mut signature)
+                    .map_err(|e| eyre!("failed to sign with Arweave key: {}", e))?;
+                Ok(signature)
+            }
+        }
+    }
+}
+
+/// A request to a [`RemoteSigner`].
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "method", rename_all = "camelCase")]
+pub enum RemoteSignerRequest {
+    PublicKey,
+    /// Signs `message`, base64url encoded.
+    Sign {
+        message: String,
+    },
+}
+
+/// A response of a [`RemoteSigner`], binary fields are base64url encoded.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RemoteSignerResponse {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_type: Option<KeyType>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Signs through a signer process listening on a local unix socket, so the node never holds the
+/// key.
+///
+/// Every request is a connection with a single line of JSON, a [`RemoteSignerRequest`], answered
+/// by a single line of JSON, a [`RemoteSignerResponse`].
+#[cfg(unix)]
+#[derive(Debug)]
+pub struct RemoteSigner {
+    socket: PathBuf,
+    public_key: OnceLock<SignerPublicKey>,
+}
+
+#[cfg(unix)]
+impl RemoteSigner {
+    pub fn new(socket: impl Into<PathBuf>) -> Self {
+        RemoteSigner { socket: socket.into(), public_key: OnceLock::new() }
+    }
+
+    async fn request(&self, request: &RemoteSignerRequest) -> eyre::Result<RemoteSignerResponse> {
+        let stream = UnixStream::connect(&self.socket).await.map_err(|e| {
+            eyre!("failed to connect to remote signer at {}: {}", self.socket.display(), e)
+        })?;
+        let (reader, mut writer) = stream.into_split();
+
+        let mut line = serde_json::to_vec(request)?;
+        line.push(b'\n');
+        writer.write_all(&line).await?;
+
+        let mut response = String::new();
+        BufReader::new(reader).read_line(&mut response).await?;
+        let response: RemoteSignerResponse = serde_json::from_str(&response)
+            .map_err(|e| eyre!("invalid response of remote signer: {}", e))?;
+        if let Some(error) = response.error {
+            bail!("remote signer failed: {}", error)
+        }
+
+        Ok(response)
+    }
+}
+
+#[cfg(unix)]
+#[async_trait::async_trait]
+impl UploadSigner for RemoteSigner {
+    async fn public_key(&self) -> eyre::Result<SignerPublicKey> {
+        if let Some(public_key) = self.public_key.get() {
+            return Ok(public_key.clone())
+        }
+
+        let response = self.request(&RemoteSignerRequest::PublicKey).await?;
+        let (Some(key_type), Some(owner)) = (response.key_type, response.owner) else {
+            bail!("remote signer did not return its public key")
+        };
+        let owner = decode_base64(&owner)?;
+        if owner.len() != key_type.lengths().1 {
+            bail!("remote signer returned a {} byte {:?} public key", owner.len(), key_type)
+        }
+        let public_key = SignerPublicKey { key_type, owner };
+
+        Ok(self.public_key.get_or_init(|| public_key).clone())
+    }
+
+    async fn sign(&self, message: &[u8]) -> eyre::Result<Vec<u8>> {
+        let request = RemoteSignerRequest::Sign { message: BASE64_URL_SAFE_NO_PAD.encode(message) };
+        let Some(signature) = self.request(&request).await?.signature else {
+            bail!("remote signer did not return a signature")
+        };
+
+        decode_base64(&signature)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod signer_tests {
+    use crate::signer::{
+        KeyType, LocalSigner, RemoteSigner, RemoteSignerRequest, RemoteSignerResponse, UploadSigner,
+    };
+    use base64::{prelude::BASE64_URL_SAFE_NO_PAD, Engine};
+    use reth::primitives::{hex, keccak256};
+    use ring::signature::{
+        RsaPublicKeyComponents, UnparsedPublicKey, ED25519, RSA_PSS_2048_8192_SHA256,
+    };
+    use secp256k1::{
+        ecdsa::{RecoverableSignature, RecoveryId},
+        Message, SECP256K1,
+    };
+    use std::sync::Arc;
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+        net::UnixListener,
+    };
+
+    /// Keypair of the Ed25519 seed `1..=32`.
+    pub(crate) const SOLANA_KEY: &str =
+        "2Ana1pUpv2ZbMVkwF5FXapYeBEjdxDatLn7nvJkhgTSdZd8hbDHTd21as7EAsg7ypityqfsw2pMQKJcVDVcAEsd";
+
+    const ETHEREUM_KEY: &str = "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+
+    /// A throwaway 4096-bit wallet.
+    pub(crate) const ARWEAVE_JWK: &str = r#"{"kty": "RSA", "n": "pAvXFjuVWwhmyDI9lBWrmso_P2oOT5qTbV5hQeIiORpFmZbbJ7gx1lAuVLy5P6E-LTYWIA9afCMe2SJGXNhpwkauVanT2fKESCm3yu2oJwBMpEsjoIJCUBIL6uMoPk4rYW0mc6zGrmdhPIINx4P8Y61fDyuK06lOnSljifF2nzVbWL6dO8hTyM9exvN24Xo-l5zt_XyBRcRh1Ir2m0dw9IttYG-RalZmpYELCXlKIvevN3vhyJRD6VJuPmBhJ6daQpzsNzJKwJI0PBFrvLJ_pcHm9pDjCd5QQSB4yAzvoCtIRwjVLLruDTM7NC6_xFfeftBCko7poUjLL64cd1d2t77WoFzMpG7m_FhzHozLENZ2vD79bS8bp2xKSAVicLSHAunTDoiIj8SeX1_SCTDhQAFa9jlzeHitqilXsB7H4CH3lTRPVulnbt3ybB0SwcwH9CAmP3zAItPKVW6M_NHPjdLtFfR69doOfHVw9DgqPL0bxJqAcfP--kCYfCgROn8mdulW0CW9HvSIFFpN40p0yMTO6qzoD6HWzfh8FAaGSRs_qGxMYKpVWQInvhs4Fo_OvhfJdcmSwA6XBU-lmfXCBWEL49_8WYQZ0YRQvQ6qTQDREmknStUsAg1LNL4cuuCINbaNdrFEJ3Rr3jsqYuXphYQNj82FtaZCMujbVcTbDms", "e": "AQAB", "d": "DS3KVMXqpMqS_0hR_CCVjbTrydtqVOjVKTco1xMkrrO2nbUp3BIHJvGAF1ymAT-h8B3lPyQcn9ZudwEy5-iW7n2hKc6f72V1TRrU4h-fn6p9fcFvlkiArVAV1wPyyraa85PCQA7EU2SIwkSseLfYweU-ushDuedjImsdzQox_m5Z7VI67X-Vg49tTMxoY4rflmyMDg4_kGxLc7jKQQVHRM4itlmgNAvG6UrpqRQuaYq-b9Wq-OM4ZXky1B4IEBh4vts-ety2dqwIOD8qCZnJeXaPaIWRR8ytY94I9gFcCuEPSG2kOpi14SeZfQK4czyf6k4u2vlSOZvQfv8c2FWVeuJZxwAC3NKqvWjhVhAHDRVhfap9SpSYRR7T86ehh3ZvdswPcNH6F0Fdkm7aE7H6aP7cckmJSx1Q-RMlln3E_SEDGLvld1cSiMTr-4RPYYoqr6wjtgNwJSobgdiuQpjC_UVIybZL4XoZ85fYintmGQ-JG0Or34nyChNdS2E6Rq_zZ7JWRZXmeQVQAYIJGFX7XS-bkb0PpJMRn9nxJgSmzVLoRAiwzNTmXcIasQnPk0nog0vbSh0F0VeMr_NBPRPP9URULfIY_e1e0t_OvU0saMNk0a6FFebXef1qcoaE8uOvNK5O4ZqbyG3rlR8tho1fmOiPC3hfyYb7x9n-LOj6oQk", "p": "5mWCjKYR1S0NiGDwAjKLaSCmwbDr_valS9VQOWUUP2bE73I6cW1fKh940_3QhyOxLmAC6aPSkp5BnjqN6VHD-iM3YUkXjkt5c2v6cAzj3CAWrkhsGEwAgfRIYc4QArNoHzIMfbDuxXx1G7gsA4AUhXWfDSu2d6oO87FXSp5lcb2-fepto2y4H9ugqhIcJ5azeDTft0VxHgHGcnJBDqggL6-xCvbDK32c_XdKf5KTsVLI_oSYaKQqlm3gyZ13QqtXCH8yYFe-ZesCjlRAKsAQbkgV8SbV6sFbblKvQZW-og7_EvQUuAjW7GP5F6xaqHyV5fwM8-jvqv0lt_jKeLfWTQ", "q": "tka_sHSF-zyUPyNlqYO0hZI6caGj3J4X57XbLZ2eMpCMxTjE2dh3w-5OPQjzX2QLVZvWL_vEHM4vmZ6uzyKhFzz3hZtc2AcbasfFoi9ZgpccLUoaCMqnVwGSHysx5rZURQNjXSLTAYm6oh1qajgQs6El86z5DRpT0fdMnP3AkjGlzz91_5zXQe2djQBLPbjUcALbbJjdG1TpZf7vOlgbg6zNeCWM4Ympna8VTbkBEiGnk2GmqaBt0uPIMPsjFgjeRjJvjWgFPod8IBBejcTlsVVxMjDEd2ek-Ri2c46-wPkS-TJnTFuyFWCU0zugVmkUwgDIjhagmgJAZudczmvDlw", "dp": "VjJuKD7Ndyr24CocQUY1wCwWF2sgr_AJIZj5_a8iZyIE1eyvjtYzf2fs5asVBCpHVKlZDZ5b3rzUoeUYSM1te70HMB-Pb44vLw6b0KYR7b4Xnja8Mk7k9bm2Btl4RfpnHxyzbabvffU1PxylYt3dT50U13GeP6DhHS0hEmq2W1p41VUKldTMAidCMb66x9jq5jMp68BQNMDHRPQYAOrHt--MViTCGhhRzsZ9mcL1_KaTSh3Dg4q_4C3VpriB8PB3jFdCOqc5NZo6B5Pfud7HhurzQpib-ctDcMAAj5H9k1X2-llVExFDj4dLnx69gEG0NyoYknx4hklVfkHXRljJFQ", "dq": "FbMSevVERgaON34FSkrtoMEpyAFQOqFvgxw5PZL4FFj1CT2a4bxNdbvXAJkiMAb--nAU7Z1xWWSKciVb9jjPKK8PYI6AXQ9riijCNG6in1NR_WpG8yj9BvSe6qr_w9GM8rJWj-3nZNV6rHYIByEpi9jxZbL_6zlpJSPlsQLcfAsBDE0nxQhs7-JT0Udpra1lTPhqGuGP4QMYzhWtcSqwC9r0kwsVXHxA_w6q4JfXssri4j7L0oHQ0TxgxsP_B06C96Rld1Yc2bbTZhXMy9_W4GellC4OFZD3KNhyUAt8VgjQ423GsmZwKu9WiFce3WqhF_9z4MZ81BjUheuhNsnJfQ", "qi": "BeET56l6bhxvvkpfV0veHRa3Iq-opPYAsRcza3ZRz9TNAXFgysdVCrOJFZvrL64hnq_SAkHqIbHmKbLSDAsZDRthL_oOPsU9RuY6kIVkKYqpiSpYI030u2XZ2C5tDmQhtA-z16rUqtDjaBN-I3up05Fa9g1w7pYO4KGc-n0q0aiAN082RKma6dODzk94Hk-Si4VLxCG5VLK9ld8FwcHcVUE2go5ksv-VPbk-IaSmoDSBp58hVol5Ajkk7BELqyjUttd1qXD3aBk1F6t3SzpblWh_VGqaYOaDGmcXgAlJlN_fhO8NUs5dY5eKNU1Yo4aXJZ4OpOXIEgk6K3T7tV7Y2A"}"#;
+
+    /// A throwaway 2048-bit wallet, real Arweave wallets are 4096-bit.
+    const ARWEAVE_JWK_2048: &str = r#"{"kty": "RSA", "n": "u75eUC2CRz75KeM5akMZ9ke8js_Uz6ieS41CkF2aPCZjz3zvQjW2zjXaVElRVyYrgBFd7-f9mZe6IL8Kz28Y9YdxJgaoT16Ngv_ZyFvfvpv7hjC_bIvVQir4BmyYY4atpg1jI-T_oWrmTmfDWtgD2JdhHj5LICRzfNMmM6Q-thgtcwve-SO6wimt6ZkNs6rGjtvYpQsn0knJuaqeR6D9thrs6X5at16qSuxNN7RDYXwMwa_-OFC6oq0AoiLbT03zyIMZ0UVIstEhrsy92YAa28ATq4ydrAF47XEvnBvrt3tjscplS2ljTNun6FPqP4DI3a8pqja-TaLOFHs4YT_Q8w", "e": "AQAB", "d": "L2mDet8LS__Iv73M5Zt9hncWVMCCn1CXbUAGw9uHlFIjmaYU7BURjeEGuotrecU_bg-0t93P6wZosFVenwUQ-gyS1AYRYj3gIemH77PX_NR6gCh2ZyynFhcw3Lp2_4dm-gzecRWnQvXypHFK4fm0evG7yn3zzfG2oprWRmtflO91_mHVvGMUSXVuevDnAis_6teSBxptJC5D1Proepvd5U-5uAwjxWOxPanszwmaaM2E2nqjLvSA4DXX6nxjqjBGRnMQniPfZpe-NTLPiraSOvp3HYH2i3SrbA8aWUkwqUK8cYDd_dXdPhm1qakpNsFlwS-Ffb1avwPzmbNHvdvObQ", "p": "72KqUXPfgaAOFxqh05lt3bJp0LmbojaxLKkSkpvFu5EXcQc-uVjR6XD5gf9vn_RNtYcTce4lByaIWrF_hyu9N-2KksutpPOZiDdsPFBdo1-Z-m1clmPPoQA9LnbrqWLaAqyA9xmzVv-qM-ilFSGQEiu6Re7P6o2CXpnL56R7GLU", "q": "yMYlXaD44m-BXEm4V-Zupxsk9u7unADnbfgitxPdStWxW2uc1Z6AhiVAOeKEFJcnbpPgWzttT3l4uLZ7Z2cjLwCDfzcU7aMgG2GhAsLZ3JjmhEfUoNr9F6aB0w-GdketfLRO3BcAHU0b3Ry98FGCHGUL3zwOvPnlWrgWDbCkFAc", "dp": "1ewwFBYemDI2JUNxdizYV5QGp7YWdMG3VxfINyfpbhLdBe7ZiLzs6VZCQn_IewLRJvWM1PViNZ-jnpicRGk78OzgNwq5JH1kqKwOl1-iLktSyM5OPT743wHdqtYvBvIFrPoHTcqzz30t1rGCRP33R6W4EqK1PwAyjuOVqm38azk", "dq": "F41nBLzFdYqvGixru6QEhwXgKDePNi3E-FmLeP6TjxvSWm8dTtHxMctc5W11IRU6QvaF8y3dfN07FDAT-eVXc0QUKPBFPDtOoOerA6_n7sDhCdODVftahVO7Fw9TLrzp1R8xwQ_Rq55IDoA4p-Bn6vzPj7hz4idOiH3f2GtIVDE", "qi": "C1mMUBHzrmoulv9oUmZnPttDUucgBFShXi52HoWpukXN_OXhAvT2yQb1ifqw7ZNym77diOY-0JRwIjJfq0Q-pW7wo87MKYjcPYwyLUg5Ke_6yhM2IBO4YV088pNlsPlVyfolS0xZOOE42Nh_jZXMDsNPJh9olEdxr1zjOuV2aMc"}"#;
+
+    #[tokio::test]
+    pub async fn test_solana_key() {
+        let keypair = bs58::decode(SOLANA_KEY).into_vec().unwrap();
+        assert_eq!(keypair[..32], (1..=32).collect::<Vec<u8>>());
+        assert_eq!(
+            hex::encode(&keypair[32..]),
+            "79b5562e8fe654f94078b112e8a98ba7901f853ae695bed7e0e3910bad049664"
+        );
+
+        let signer = LocalSigner::from_key(KeyType::Solana, SOLANA_KEY).unwrap();
+        assert_eq!(signer.public_key().await.unwrap().owner, keypair[32..]);
+        // `0` is not in the base58 alphabet
+        assert!(LocalSigner::from_key(KeyType::Solana, &SOLANA_KEY.replace('2', "0")).is_err());
+    }
+
+    #[tokio::test]
+    pub async fn test_local_signers() {
+        let message = b"data item";
+
+        let signer = LocalSigner::from_key(KeyType::Solana, SOLANA_KEY).unwrap();
+        let owner = signer.public_key().await.unwrap().owner;
+        let signature = signer.sign(message).await.unwrap();
+        assert_eq!(signature.len(), KeyType::Solana.lengths().0);
+        UnparsedPublicKey::new(&ED25519, &owner).verify(message, &signature).unwrap();
+        assert_eq!(
+            signer.public_key().await.unwrap().address(),
+            "9C6hybhQ6Aycep9jaUnP6uL9ZYvDjUp1aSkFWPUFJtpj"
+        );
+
+        let signer = LocalSigner::from_key(KeyType::Ethereum, ETHEREUM_KEY).unwrap();
+        let owner = signer.public_key().await.unwrap().owner;
+        assert_eq!(owner.len(), KeyType::Ethereum.lengths().1);
+        assert_eq!(
+            signer.public_key().await.unwrap().address(),
+            "0x2c7536E3605D9C16a7a3D7b1898e529396a65c23"
+        );
+        let signature = signer.sign(message).await.unwrap();
+        let digest = keccak256([b"\x19Ethereum Signed Message:\n9".as_slice(), message].concat());
+        let recovery_id = RecoveryId::from_i32(signature[64] as i32 - 27).unwrap();
+        let recovered = SECP256K1
+            .recover_ecdsa(
+                &Message::from_digest(digest.0),
+                &RecoverableSignature::from_compact(&signature[..64], recovery_id).unwrap(),
+            )
+            .unwrap();
+        assert_eq!(recovered.serialize_uncompressed().to_vec(), owner);
+
+        let signer = LocalSigner::from_key(KeyType::Arweave, ARWEAVE_JWK).unwrap();
+        let owner = signer.public_key().await.unwrap().owner;
+        assert_eq!(owner.len(), KeyType::Arweave.lengths().1);
+        let signature = signer.sign(message).await.unwrap();
+        assert_eq!(signature.len(), KeyType::Arweave.lengths().0);
+        RsaPublicKeyComponents { n: owner, e: vec![1, 0, 1] }
+            .verify(&RSA_PSS_2048_8192_SHA256, message, &signature)
+            .unwrap();
+
+        assert!(LocalSigner::from_key(KeyType::Solana, ETHEREUM_KEY).is_err());
+        assert!(LocalSigner::from_key(KeyType::Ethereum, SOLANA_KEY).is_err());
+        assert!(LocalSigner::from_key(KeyType::Arweave, SOLANA_KEY).is_err());
+        // only 4096-bit keys fit data items and transactions
+        assert!(LocalSigner::from_key(KeyType::Arweave, ARWEAVE_JWK_2048).is_err());
+        // keys are never printed
+        assert!(!format!("{signer:?}").contains(&ARWEAVE_JWK[..16]));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    pub fn test_key_file_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("key");
+        std::fs::write(&path, SOLANA_KEY).unwrap();
+
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+        assert!(LocalSigner::from_file(KeyType::Solana, &path).is_err());
+
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).unwrap();
+        assert!(LocalSigner::from_file(KeyType::Solana, &path).is_ok());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    pub async fn test_remote_signer() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("signer.sock");
+        let listener = UnixListener::bind(&socket).unwrap();
+        let key = Arc::new(LocalSigner::from_key(KeyType::Solana, SOLANA_KEY).unwrap());
+
+        let server_key = key.clone();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let (reader, mut writer) = stream.into_split();
+                let mut line = String::new();
+                BufReader::new(reader).read_line(&mut line).await.unwrap();
+
+                let response = match serde_json::from_str(&line).unwrap() {
+                    RemoteSignerRequest::PublicKey => {
+                        let public_key = server_key.public_key().await.unwrap();
+                        RemoteSignerResponse {
+                            key_type: Some(public_key.key_type),
+                            owner: Some(BASE64_URL_SAFE_NO_PAD.encode(public_key.owner)),
+                            ..Default::default()
+                        }
+                    }
+                    RemoteSignerRequest::Sign { message } if message.is_empty() => {
+                        RemoteSignerResponse {
+                            error: Some("empty message".to_string()),
+                            ..Default::default()
+                        }
+                    }
+                    RemoteSignerRequest::Sign { message } => {
+                        let message = BASE64_URL_SAFE_NO_PAD.decode(message).unwrap();
+                        let signature = server_key.sign(&message).await.unwrap();
+                        RemoteSignerResponse {
+                            signature: Some(BASE64_URL_SAFE_NO_PAD.encode(signature)),
+                            ..Default::default()
+                        }
+                    }
+                };
+                let mut response = serde_json::to_vec(&response).unwrap();
+                response.push(b'\n');
+                writer.write_all(&response).await.unwrap();
+            }
+        });
+
+        let remote = RemoteSigner::new(&socket);
+        assert_eq!(remote.public_key().await.unwrap(), key.public_key().await.unwrap());
+        assert_eq!(remote.sign(b"data item").await.unwrap(), key.sign(b"data item").await.unwrap());
+        assert!(remote.sign(b"").await.is_err());
+
+        assert!(RemoteSigner::new(dir.path().join("missing.sock")).public_key().await.is_err());
+    }
+}
@@ -0,0 +1,80 @@
+use eyre::eyre;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    io::ErrorKind,
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// A JSON file a backend keeps its state in, so it survives restarts.
+#[derive(Debug)]
+pub(crate) struct StateFile {
+    path: PathBuf,
+    /// Held while the file is written, so snapshots are written in the order they are taken.
+    write: tokio::sync::Mutex<()>,
+}
+
+impl StateFile {
+    pub(crate) fn new(path: impl Into<PathBuf>) -> Self {
+        StateFile { path: path.into(), write: tokio::sync::Mutex::new(()) }
+    }
+
+    /// Reads the state, `None` if the file does not exist yet.
+    pub(crate) fn load<T: DeserializeOwned>(&self) -> eyre::Result<Option<T>> {
+        let data = match std::fs::read(&self.path) {
+            Ok(data) => data,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(eyre!("failed to read {}: {}", self.path.display(), err)),
+        };
+
+        serde_json::from_slice(&data)
+            .map(Some)
+            .map_err(|e| eyre!("invalid state file {}: {}", self.path.display(), e))
+    }
+
+    /// Writes the state `snapshot` returns, the file is replaced atomically.
+    pub(crate) async fn store<T: Serialize>(
+        &self,
+        snapshot: impl FnOnce() -> T,
+    ) -> eyre::Result<()> {
+        let _write = self.write.lock().await;
+        let data = serde_json::to_vec(&snapshot())?;
+
+        if let Some(dir) = self.path.parent() {
+            tokio::fs::create_dir_all(dir).await?;
+        }
+        let tmp = self.path.with_extension("tmp");
+        tokio::fs::write(&tmp, data).await?;
+        tokio::fs::rename(&tmp, &self.path).await?;
+
+        Ok(())
+    }
+}
+
+pub(crate) fn millis(duration: Duration) -> u64 {
+    duration.as_millis().try_into().unwrap_or(u64::MAX)
+}
+
+/// Returns the current time in milliseconds since the unix epoch, the time state files keep.
+pub(crate) fn now_millis() -> u64 {
+    millis(SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod state_file_tests {
+    use crate::state_file::StateFile;
+
+    #[tokio::test]
+    pub async fn test_state_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state").join("file.json");
+        let file = StateFile::new(&path);
+        assert_eq!(file.load::<Vec<u64>>().unwrap(), None);
+
+        file.store(|| vec![1u64, 2]).await.unwrap();
+        assert_eq!(file.load::<Vec<u64>>().unwrap(), Some(vec![1, 2]));
+
+        std::fs::write(&path, b"not json").unwrap();
+        assert!(file.load::<Vec<u64>>().is_err());
+    }
+}
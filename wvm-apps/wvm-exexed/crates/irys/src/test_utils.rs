@@ -0,0 +1,75 @@
+use crate::{
+    backend::{PermanentStorageBackend, StoredItem, Tag, TagQuery},
+    local::LocalStorage,
+};
+use std::{
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
+
+/// Local storage charging one unit per byte of data and tags, which refuses uploads while it
+/// fails.
+#[derive(Debug)]
+pub struct PaidStorage {
+    inner: LocalStorage,
+    balance: Mutex<u128>,
+    fail: AtomicBool,
+}
+
+impl PaidStorage {
+    pub fn new(dir: &Path, balance: u128) -> Self {
+        PaidStorage {
+            inner: LocalStorage::new(dir).unwrap(),
+            balance: Mutex::new(balance),
+            fail: AtomicBool::new(false),
+        }
+    }
+
+    /// Storage that never runs out of balance.
+    pub fn unlimited(dir: &Path) -> Self {
+        Self::new(dir, u128::MAX)
+    }
+
+    pub fn set_failing(&self, fail: bool) {
+        self.fail.store(fail, Ordering::SeqCst);
+    }
+
+    pub fn remaining(&self) -> u128 {
+        *self.balance.lock().unwrap()
+    }
+}
+
+#[async_trait::async_trait]
+impl PermanentStorageBackend for PaidStorage {
+    async fn upload(&self, data: Vec<u8>, tags: Vec<Tag>) -> eyre::Result<String> {
+        if self.fail.load(Ordering::SeqCst) {
+            return Err(eyre::eyre!("storage unavailable"))
+        }
+
+        *self.balance.lock().unwrap() -= self.upload_size(data.len(), &tags).await? as u128;
+        self.inner.upload(data, tags).await
+    }
+
+    async fn fetch(&self, id: &str) -> eyre::Result<Vec<u8>> {
+        self.inner.fetch(id).await
+    }
+
+    async fn query(&self, query: &TagQuery) -> eyre::Result<Vec<StoredItem>> {
+        self.inner.query(query).await
+    }
+
+    async fn upload_price(&self, bytes: usize) -> eyre::Result<Option<u128>> {
+        Ok(Some(bytes as u128))
+    }
+
+    async fn upload_size(&self, bytes: usize, tags: &[Tag]) -> eyre::Result<usize> {
+        Ok(tags.iter().fold(bytes, |size, tag| size + tag.name.len() + tag.value.len()))
+    }
+
+    async fn balance(&self) -> eyre::Result<Option<u128>> {
+        Ok(Some(self.remaining()))
+    }
+}
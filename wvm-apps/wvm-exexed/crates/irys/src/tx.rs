@@ -0,0 +1,282 @@
+use crate::{
+    backend::Tag,
+    chunk::data_root,
+    signer::{KeyType, UploadSigner},
+};
+use base64::{prelude::BASE64_URL_SAFE_NO_PAD, Engine};
+use eyre::{bail, eyre};
+use ring::signature::{RsaPublicKeyComponents, RSA_PSS_2048_8192_SHA256};
+use serde::{Deserialize, Serialize};
+use sha2::{digest::Output, Digest, Sha256, Sha384};
+
+/// Public exponent of every Arweave wallet, a transaction only carries the modulus of its owner.
+const PUBLIC_EXPONENT: [u8; 3] = [1, 0, 1];
+
+pub(crate) fn decode_base64(value: &str) -> eyre::Result<Vec<u8>> {
+    BASE64_URL_SAFE_NO_PAD.decode(value).map_err(|e| eyre!("invalid base64url value: {}", e))
+}
+
+/// A tag of a transaction header, name and value are base64url encoded.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TxTag {
+    pub name: String,
+    pub value: String,
+}
+
+/// Header of a layer 1 transaction, as returned by `/tx/{id}`.
+///
+/// Binary fields are base64url encoded, amounts and the data size are decimal strings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TxHeader {
+    pub format: u8,
+    pub id: String,
+    pub last_tx: String,
+    pub owner: String,
+    #[serde(default)]
+    pub tags: Vec<TxTag>,
+    pub target: String,
+    pub quantity: String,
+    pub data_root: String,
+    pub data_size: String,
+    pub reward: String,
+    pub signature: String,
+}
+
+/// An item of the deep hash of a transaction or data item.
+pub(crate) enum DeepHashItem {
+    Blob(Vec<u8>),
+    List(Vec<DeepHashItem>),
+}
+
+/// Hashes nested lists of blobs, tagging every item with its type and length.
+pub(crate) fn deep_hash(item: &DeepHashItem) -> Output<Sha384> {
+    match item {
+        DeepHashItem::Blob(data) => Sha384::new()
+            .chain_update(Sha384::digest(format!("blob{}", data.len())))
+            .chain_update(Sha384::digest(data))
+            .finalize(),
+        DeepHashItem::List(items) => {
+            items.iter().fold(Sha384::digest(format!("list{}", items.len())), |acc, item| {
+                Sha384::new().chain_update(acc).chain_update(deep_hash(item)).finalize()
+            })
+        }
+    }
+}
+
+impl TxHeader {
+    /// Creates a format 2 transaction of `data` with `tags`, signed by `signer`.
+    ///
+    /// `last_tx` is the anchor of the transaction and `reward` its fee in winston, both as quoted
+    /// by a gateway. The data itself is not part of the header, it is uploaded in chunks.
+    pub async fn sign(
+        signer: &dyn UploadSigner,
+        data: &[u8],
+        tags: &[Tag],
+        last_tx: &str,
+        reward: u128,
+    ) -> eyre::Result<Self> {
+        let public_key = signer.public_key().await?;
+        if public_key.key_type != KeyType::Arweave {
+            bail!("transactions can't be signed with a {:?} key", public_key.key_type)
+        }
+
+        let encode = |value: &[u8]| BASE64_URL_SAFE_NO_PAD.encode(value);
+        let mut header = TxHeader {
+            format: 2,
+            id: String::new(),
+            last_tx: last_tx.to_string(),
+            owner: encode(&public_key.owner),
+            tags: tags
+                .iter()
+                .map(|tag| TxTag {
+                    name: encode(tag.name.as_bytes()),
+                    value: encode(tag.value.as_bytes()),
+                })
+                .collect(),
+            target: String::new(),
+            quantity: "0".to_string(),
+            data_root: if data.is_empty() { String::new() } else { encode(&data_root(data)) },
+            data_size: data.len().to_string(),
+            reward: reward.to_string(),
+            signature: String::new(),
+        };
+
+        let signature = signer.sign(&header.signature_data()?).await?;
+        header.id = encode(&Sha256::digest(&signature));
+        header.signature = encode(&signature);
+
+        Ok(header)
+    }
+
+    /// Verifies that the header is signed by its owner and that `id` is the id of the signature.
+    ///
+    /// Only format 2 transactions are supported, the signature of format 1 transactions covers
+    /// their whole data.
+    pub fn verify(&self, id: &str) -> eyre::Result<()> {
+        if self.format != 2 {
+            bail!("transaction {} has unsupported format {}", id, self.format)
+        }
+
+        let signature = decode_base64(self.signature.as_str())?;
+        if self.id != id || BASE64_URL_SAFE_NO_PAD.encode(Sha256::digest(&signature)) != id {
+            bail!("transaction {} does not match its signature", id)
+        }
+
+        let owner = decode_base64(self.owner.as_str())?;
+        RsaPublicKeyComponents { n: owner.as_slice(), e: PUBLIC_EXPONENT.as_slice() }
+            .verify(&RSA_PSS_2048_8192_SHA256, &self.signature_data()?, &signature)
+            .map_err(|_| eyre!("invalid signature of transaction {}", id))
+    }
+
+    /// Returns the data signed by the owner, the deep hash of the fields of the header.
+    fn signature_data(&self) -> eyre::Result<Output<Sha384>> {
+        let blob = |value: &str| decode_base64(value).map(DeepHashItem::Blob);
+        let tags = self
+            .tags
+            .iter()
+            .map(|tag| Ok(DeepHashItem::List(vec![blob(&tag.name)?, blob(&tag.value)?])))
+            .collect::<eyre::Result<Vec<_>>>()?;
+
+        Ok(deep_hash(&DeepHashItem::List(vec![
+            DeepHashItem::Blob(self.format.to_string().into_bytes()),
+            blob(&self.owner)?,
+            blob(&self.target)?,
+            DeepHashItem::Blob(self.quantity.clone().into_bytes()),
+            DeepHashItem::Blob(self.reward.clone().into_bytes()),
+            blob(&self.last_tx)?,
+            DeepHashItem::List(tags),
+            DeepHashItem::Blob(self.data_size.clone().into_bytes()),
+            blob(&self.data_root)?,
+        ])))
+    }
+
+    pub fn data_size(&self) -> eyre::Result<u64> {
+        self.data_size
+            .parse::<u64>()
+            .map_err(|e| eyre!("invalid data size for transaction {}: {}", self.id, e))
+    }
+
+    /// Returns the root of the Merkle tree of the data, `None` if the transaction has no data.
+    pub fn data_root(&self) -> eyre::Result<Option<[u8; 32]>> {
+        if self.data_root.is_empty() {
+            return Ok(None)
+        }
+
+        let data_root = decode_base64(self.data_root.as_str())?
+            .try_into()
+            .map_err(|_| eyre!("invalid data root for transaction {}", self.id))?;
+        Ok(Some(data_root))
+    }
+
+    /// Verifies that `data` is the data the header commits to.
+    pub fn verify_data(&self, data: &[u8]) -> eyre::Result<()> {
+        if data.len() as u64 != self.data_size()? {
+            bail!("data of transaction {} has an unexpected size", self.id)
+        }
+
+        match self.data_root()? {
+            Some(root) if root == data_root(data) => Ok(()),
+            None if data.is_empty() => Ok(()),
+            _ => Err(eyre!("data of transaction {} does not match its data root", self.id)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tx_tests {
+    use crate::{
+        backend::Tag,
+        signer::{
+            signer_tests::{ARWEAVE_JWK, SOLANA_KEY},
+            KeyType, LocalSigner,
+        },
+        tx::{deep_hash, DeepHashItem, TxHeader},
+    };
+    use sha2::{Digest, Sha384};
+
+    /// A transaction of "Hello world" signed with a throwaway 2048-bit key.
+    const TX: &str = r#"{
+        "format": 2,
+        "id": "9D10GRqykZRtAcLK2ieWZABeAU2m_-1Ur2OP7jN3IQ8",
+        "last_tx": "AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8AAQIDBAUGBwgJCgsMDQ4P",
+        "owner": "uMvbYfto8gGMmNlzaXu6AhItzq2OaQE_H4XGNZuPasnhzgtf43HsysEY_3CJ2PgleIFzsNAOAJgTDQ6-PlBpmGiogwlxYpF1pDegPKnkf3BzTDAoYdGS45en9sh7tWPaVd1aUR7QwY7fNIq0goBT_rpoacbeKUg-ZfS-dGS1jWh81NWWkJTapcbLIRlaN1dId7Y1czu5E5rZTrTRk44yQWw9SAncHjh2fAc1Ivlu_btQwsbpQUdK5HmK4I5HSuW777tO_p4oK3te_-3u2vQ5_PLqp86yEUXGYI_lDu_2uJFkM5T_mOULoye0Qos7kdheMyXznAsgyMt1cckLVOYGeQ",
+        "tags": [
+            {
+                "name": "Q29udGVudC1UeXBl",
+                "value": "dGV4dC9wbGFpbg"
+            }
+        ],
+        "target": "",
+        "quantity": "0",
+        "data": "",
+        "data_size": "11",
+        "data_tree": [],
+        "data_root": "wF19-Bs58Gm_UCT__VzOYAQmpFTxpMTh6Ml9iEGrJi0",
+        "reward": "1000",
+        "signature": "XVP8Bhp4jTyt4zXr3u1cltJy84Qq_hI-ZMteaB61_R3I2cxy4EgdKXixBLLb9Xk_ag6aXDIph5-t-tkQfqsCKociiaUqpRjBZqJ4JJ4s9ZQpkk0fodpaAmOQsyey9UaXhnka0QU4WBDBH4sanRhLqv4mmyIyFuKUaS04Gu8Jekle0b7GLz5dywIJgFAseqfebNTKqRVG9C_eu37k3oH87Yo_PXUY9YKcjZ_Blj71m-u5C6USDpJdeMWkEBKf29Gsi8-hSfQpel3mxwIr9GmitsLHV1ZNP8Qgf9wiRyEqnoBPPu_1nEmO_voCL0-PxkNuSqRZ-ZmnMNx54DIOnHZ4mQ"
+    }"#;
+
+    fn tx() -> TxHeader {
+        serde_json::from_str(TX).unwrap()
+    }
+
+    #[test]
+    pub fn test_deep_hash() {
+        let blob = |data: &[u8]| {
+            let tag = Sha384::digest(format!("blob{}", data.len()));
+            Sha384::digest([tag, Sha384::digest(data)].concat())
+        };
+        assert_eq!(deep_hash(&DeepHashItem::Blob(b"abc".to_vec())), blob(b"abc"));
+
+        let list = Sha384::digest([Sha384::digest("list2"), blob(b"a")].concat());
+        let list = Sha384::digest([list, blob(b"")].concat());
+        let items = vec![DeepHashItem::Blob(b"a".to_vec()), DeepHashItem::Blob(vec![])];
+        assert_eq!(deep_hash(&DeepHashItem::List(items)), list);
+    }
+
+    #[test]
+    pub fn test_verify_tx() {
+        let id = tx().id;
+        tx().verify(id.as_str()).unwrap();
+        tx().verify_data(b"Hello world").unwrap();
+
+        // the data has to match the header
+        assert!(tx().verify_data(b"Hello World").is_err());
+        assert!(tx().verify_data(b"Hello world!").is_err());
+        // the header has to match its id
+        assert!(tx().verify("bs318IdjLWQK7pF_bNIbJnpade8feD7yGAS8xIffJDI").is_err());
+        // and every signed field has to match the signature
+        let mut tampered = tx();
+        tampered.reward = "1".to_string();
+        assert!(tampered.verify(id.as_str()).is_err());
+        let mut tampered = tx();
+        tampered.tags[0].value = "dGV4dC9odG1s".to_string();
+        assert!(tampered.verify(id.as_str()).is_err());
+        let mut tampered = tx();
+        tampered.data_size = "12".to_string();
+        assert!(tampered.verify(id.as_str()).is_err());
+        let mut tampered = tx();
+        tampered.format = 1;
+        assert!(tampered.verify(id.as_str()).is_err());
+    }
+
+    #[tokio::test]
+    pub async fn test_sign_tx() {
+        let signer = LocalSigner::from_key(KeyType::Arweave, ARWEAVE_JWK).unwrap();
+        let tags = vec![Tag::new("Content-Type", "text/plain")];
+        let anchor = tx().last_tx;
+        let tx = TxHeader::sign(&signer, b"Hello world", &tags, &anchor, 1000).await.unwrap();
+
+        tx.verify(tx.id.as_str()).unwrap();
+        tx.verify_data(b"Hello world").unwrap();
+        assert_eq!(tx.tags[0].value, "dGV4dC9wbGFpbg");
+        assert_eq!(tx.reward, "1000");
+
+        // the header survives the JSON it is posted as
+        let posted: TxHeader = serde_json::from_slice(&serde_json::to_vec(&tx).unwrap()).unwrap();
+        posted.verify(tx.id.as_str()).unwrap();
+
+        let signer = LocalSigner::from_key(KeyType::Solana, SOLANA_KEY).unwrap();
+        assert!(TxHeader::sign(&signer, b"Hello world", &tags, &anchor, 1000).await.is_err());
+    }
+}
@@ -0,0 +1,477 @@
+use crate::file_queue::FileQueue;
+use borsh::{BorshDeserialize, BorshSerialize};
+use reth::primitives::{BlockHash, SealedBlockWithSenders};
+use std::{
+    collections::BTreeMap,
+    io::{self, ErrorKind, Read, Write},
+    path::Path,
+};
+use wevm_borsh::{b256::BorshB256, block::BorshSealedBlockWithSenders, outcome::BlockOutcome};
+
+/// Location of the archive outbox, relative to the node's datadir.
+pub const ARCHIVE_OUTBOX_DIR: &str = "wvm/archive-outbox";
+
+const LAST_ARCHIVED_KEY: &str = "last-archived";
+
+/// A committed block that still has to be archived.
+#[derive(Debug, Clone)]
+pub struct ArchiveEntry {
+    pub block: SealedBlockWithSenders,
+    /// Id of the uploaded block, set once the upload succeeded so a failure in a later archival
+    /// step does not upload the block a second time.
+    pub arweave_id: Option<String>,
+    /// Receipts and state changes of the block, if they are archived.
+    pub outcome: Option<BlockOutcome>,
+    /// Id of the uploaded outcome.
+    pub outcome_id: Option<String>,
+    /// Ids of the block at every archive destination it was uploaded to, by destination name.
+    ///
+    /// Kept while the block is short of the quorum, so retries only upload to the destinations
+    /// that are missing.
+    pub archive_ids: BTreeMap<String, String>,
+}
+
+impl BorshSerialize for ArchiveEntry {
+    fn serialize<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        BorshSealedBlockWithSenders(self.block.clone()).serialize(writer)?;
+        self.arweave_id.serialize(writer)?;
+        self.outcome.serialize(writer)?;
+        self.outcome_id.serialize(writer)?;
+        self.archive_ids.serialize(writer)?;
+
+        Ok(())
+    }
+}
+
+impl BorshDeserialize for ArchiveEntry {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let block = BorshSealedBlockWithSenders::deserialize_reader(reader)?;
+        let arweave_id: Option<String> = BorshDeserialize::deserialize_reader(reader)?;
+
+        // entries staged before outcomes were archived end here
+        let mut rest = vec![];
+        reader.read_to_end(&mut rest)?;
+        let mut rest = rest.as_slice();
+        let (outcome, outcome_id) =
+            if rest.is_empty() { (None, None) } else { BorshDeserialize::deserialize(&mut rest)? };
+
+        // entries staged before blocks were archived to several destinations end here
+        let archive_ids = if rest.is_empty() { BTreeMap::new() } else { borsh::from_slice(rest)? };
+
+        Ok(ArchiveEntry { block: block.0, arweave_id, outcome, outcome_id, archive_ids })
+    }
+}
+
+/// A block that left the canonical chain and needs a revert marker.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RevertEntry {
+    pub block_number: u64,
+    pub block_hash: BlockHash,
+    /// Hash of the canonical block that replaced the reverted one, or of the block the chain was
+    /// reverted to.
+    pub reverted_by: BlockHash,
+    /// Id of the published revert marker.
+    pub marker_id: Option<String>,
+}
+
+impl RevertEntry {
+    fn key(&self) -> String {
+        format!("{}-{}", key(self.block_number), self.block_hash)
+    }
+}
+
+impl BorshSerialize for RevertEntry {
+    fn serialize<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.block_number.serialize(writer)?;
+        BorshB256(self.block_hash).serialize(writer)?;
+        BorshB256(self.reverted_by).serialize(writer)?;
+        self.marker_id.serialize(writer)?;
+
+        Ok(())
+    }
+}
+
+impl BorshDeserialize for RevertEntry {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let block_number: u64 = BorshDeserialize::deserialize_reader(reader)?;
+        let block_hash = BorshB256::deserialize_reader(reader)?;
+        let reverted_by = BorshB256::deserialize_reader(reader)?;
+        let marker_id: Option<String> = BorshDeserialize::deserialize_reader(reader)?;
+
+        Ok(RevertEntry {
+            block_number,
+            block_hash: block_hash.0,
+            reverted_by: reverted_by.0,
+            marker_id,
+        })
+    }
+}
+
+/// Write-ahead outbox of the archive ExEx.
+///
+/// Every committed block is written to the `pending` queue before it is uploaded and stays there
+/// until it has been archived. The height of the last archived block is kept next to the queue,
+/// so the ExEx can pick up blocks that were committed while it was not running.
+///
+/// Blocks that leave the canonical chain are moved to the `reverted` queue, unless they never
+/// made it to permanent storage.
+///
+/// Archival is at-least-once: a crash between the upload and [`ArchiveOutbox::complete`] archives
+/// the block again after a restart.
+#[derive(Debug, Clone)]
+pub struct ArchiveOutbox {
+    pending: FileQueue,
+    reverted: FileQueue,
+    state: FileQueue,
+}
+
+impl ArchiveOutbox {
+    pub fn new(dir: &Path) -> io::Result<Self> {
+        Ok(ArchiveOutbox {
+            pending: FileQueue::new(dir.join("pending"))?,
+            reverted: FileQueue::new(dir.join("reverted"))?,
+            state: FileQueue::new(dir.join("state"))?,
+        })
+    }
+
+    /// Opens the outbox below the node's datadir.
+    pub fn at_datadir(datadir: &Path) -> io::Result<Self> {
+        Self::new(&datadir.join(ARCHIVE_OUTBOX_DIR))
+    }
+
+    /// Adds a block to the outbox, replacing a pending block of the same height.
+    ///
+    /// Pushing a block that is already pending is a no-op, so an upload that already happened is
+    /// not repeated.
+    pub fn push(&self, block: &SealedBlockWithSenders) -> io::Result<()> {
+        self.push_with_outcome(block, None)
+    }
+
+    /// Adds a block together with its execution outcome, see [`ArchiveOutbox::push`].
+    pub fn push_with_outcome(
+        &self,
+        block: &SealedBlockWithSenders,
+        outcome: Option<BlockOutcome>,
+    ) -> io::Result<()> {
+        let key = key(block.number);
+        if self.pending.contains(key.as_str()) &&
+            self.read(block.number)?.block.hash() == block.hash()
+        {
+            return Ok(())
+        }
+
+        self.write(&ArchiveEntry {
+            block: block.clone(),
+            arweave_id: None,
+            outcome,
+            outcome_id: None,
+            archive_ids: BTreeMap::new(),
+        })
+    }
+
+    pub fn contains(&self, block_number: u64) -> bool {
+        self.pending.contains(key(block_number).as_str())
+    }
+
+    pub fn read(&self, block_number: u64) -> io::Result<ArchiveEntry> {
+        borsh::from_slice(self.pending.read(key(block_number).as_str())?.as_slice())
+    }
+
+    /// Records the id a pending block was uploaded under.
+    pub fn set_arweave_id(&self, block_number: u64, arweave_id: &str) -> io::Result<()> {
+        let mut entry = self.read(block_number)?;
+        entry.arweave_id = Some(arweave_id.to_string());
+        self.write(&entry)
+    }
+
+    /// Records the ids a pending block was uploaded under at the archive destinations.
+    pub fn set_archive_ids(
+        &self,
+        block_number: u64,
+        archive_ids: &BTreeMap<String, String>,
+    ) -> io::Result<()> {
+        let mut entry = self.read(block_number)?;
+        entry.archive_ids = archive_ids.clone();
+        self.write(&entry)
+    }
+
+    /// Records the id the outcome of a pending block was uploaded under.
+    pub fn set_outcome_id(&self, block_number: u64, outcome_id: &str) -> io::Result<()> {
+        let mut entry = self.read(block_number)?;
+        entry.outcome_id = Some(outcome_id.to_string());
+        self.write(&entry)
+    }
+
+    /// Returns the heights of all pending blocks, lowest first.
+    pub fn pending(&self) -> io::Result<Vec<u64>> {
+        self.pending
+            .keys()?
+            .into_iter()
+            .map(|key| {
+                key.parse().map_err(|_| {
+                    io::Error::new(ErrorKind::InvalidData, format!("invalid outbox key: {}", key))
+                })
+            })
+            .collect()
+    }
+
+    /// Marks a pending block as archived and removes it from the outbox.
+    ///
+    /// The last archived height never goes down, blocks staged again below it, e.g. because their
+    /// upload was dropped, leave it as it is.
+    pub fn complete(&self, block_number: u64) -> io::Result<()> {
+        if self.last_archived()?.map_or(true, |last_archived| block_number > last_archived) {
+            self.set_last_archived(block_number)?;
+        }
+        self.pending.remove(key(block_number).as_str())
+    }
+
+    /// Records that `block` is no longer canonical.
+    ///
+    /// A pending block is dropped. A revert marker is only queued for blocks that were uploaded,
+    /// i.e. archived blocks and pending blocks that are stored at some destination already, and
+    /// the last archived height is rolled back, so the replacing blocks are archived after a
+    /// restart. Blocks have to be reverted highest first. Returns whether a marker was queued.
+    pub fn revert(
+        &self,
+        block: &SealedBlockWithSenders,
+        reverted_by: BlockHash,
+    ) -> io::Result<bool> {
+        let archived =
+            self.last_archived()?.is_some_and(|last_archived| last_archived >= block.number);
+        let mut uploaded = archived;
+        if self.contains(block.number) {
+            let entry = self.read(block.number)?;
+            if entry.block.hash() == block.hash() {
+                self.pending.remove(key(block.number).as_str())?;
+                // blocks short of the quorum may be stored at some destinations already
+                uploaded |= entry.arweave_id.is_some() || !entry.archive_ids.is_empty();
+            }
+        }
+        if !uploaded {
+            return Ok(false)
+        }
+
+        let entry = RevertEntry {
+            block_number: block.number,
+            block_hash: block.hash(),
+            reverted_by,
+            marker_id: None,
+        };
+        self.write_revert(&entry)?;
+
+        if archived {
+            self.set_last_archived(block.number.saturating_sub(1))?;
+        }
+
+        Ok(true)
+    }
+
+    /// Returns all queued revert markers, lowest block first.
+    pub fn reverted(&self) -> io::Result<Vec<RevertEntry>> {
+        let mut entries = vec![];
+        for key in self.reverted.keys()? {
+            entries.push(borsh::from_slice(self.reverted.read(key.as_str())?.as_slice())?);
+        }
+
+        Ok(entries)
+    }
+
+    /// Records the id a revert marker was published under.
+    pub fn set_marker_id(&self, entry: &RevertEntry, marker_id: &str) -> io::Result<()> {
+        self.write_revert(&RevertEntry { marker_id: Some(marker_id.to_string()), ..entry.clone() })
+    }
+
+    /// Removes a revert marker once it has been published and recorded.
+    pub fn complete_revert(&self, entry: &RevertEntry) -> io::Result<()> {
+        self.reverted.remove(entry.key().as_str())
+    }
+
+    /// Returns the height of the last archived block, `None` if nothing was archived yet.
+    pub fn last_archived(&self) -> io::Result<Option<u64>> {
+        if !self.state.contains(LAST_ARCHIVED_KEY) {
+            return Ok(None)
+        }
+
+        let bytes = self.state.read(LAST_ARCHIVED_KEY)?;
+        let bytes: [u8; 8] = bytes.as_slice().try_into().map_err(|_| {
+            io::Error::new(ErrorKind::InvalidData, "invalid last archived block number")
+        })?;
+
+        Ok(Some(u64::from_be_bytes(bytes)))
+    }
+
+    pub fn set_last_archived(&self, block_number: u64) -> io::Result<()> {
+        self.state.push(LAST_ARCHIVED_KEY, &block_number.to_be_bytes())
+    }
+
+    fn write(&self, entry: &ArchiveEntry) -> io::Result<()> {
+        self.pending.push(key(entry.block.number).as_str(), borsh::to_vec(entry)?.as_slice())
+    }
+
+    fn write_revert(&self, entry: &RevertEntry) -> io::Result<()> {
+        self.reverted.push(entry.key().as_str(), borsh::to_vec(entry)?.as_slice())
+    }
+}
+
+fn key(block_number: u64) -> String {
+    format!("{:020}", block_number)
+}
+
+#[cfg(test)]
+mod archive_tests {
+    use crate::archive::{ArchiveEntry, ArchiveOutbox};
+    use borsh::BorshSerialize;
+    use reth::primitives::B256;
+    use std::collections::BTreeMap;
+    use wevm_borsh::{
+        block::BorshSealedBlockWithSenders,
+        outcome::BlockOutcome,
+        test_utils::{block, block_with_extra_data},
+    };
+
+    #[test]
+    pub fn test_push_and_complete() {
+        let dir = tempfile::tempdir().unwrap();
+        let outbox = ArchiveOutbox::new(dir.path()).unwrap();
+        assert_eq!(outbox.last_archived().unwrap(), None);
+
+        outbox.push(&block(2)).unwrap();
+        outbox.push(&block(1)).unwrap();
+        assert_eq!(outbox.pending().unwrap(), vec![1, 2]);
+
+        outbox.set_arweave_id(1, "arweave-id").unwrap();
+        assert_eq!(outbox.read(1).unwrap().arweave_id.as_deref(), Some("arweave-id"));
+
+        // pushing the same block again keeps the upload
+        outbox.push(&block(1)).unwrap();
+        assert_eq!(outbox.read(1).unwrap().arweave_id.as_deref(), Some("arweave-id"));
+
+        outbox.complete(1).unwrap();
+        assert_eq!(outbox.pending().unwrap(), vec![2]);
+        assert_eq!(outbox.last_archived().unwrap(), Some(1));
+        assert!(!outbox.contains(1));
+
+        // blocks archived again don't lower the last archived height
+        outbox.complete(2).unwrap();
+        outbox.push(&block(1)).unwrap();
+        outbox.complete(1).unwrap();
+        assert_eq!(outbox.last_archived().unwrap(), Some(2));
+        assert!(outbox.pending().unwrap().is_empty());
+    }
+
+    #[test]
+    pub fn test_push_replaces_block_at_same_height() {
+        let dir = tempfile::tempdir().unwrap();
+        let outbox = ArchiveOutbox::new(dir.path()).unwrap();
+
+        outbox.push(&block_with_extra_data(1, b"old")).unwrap();
+        outbox.set_arweave_id(1, "arweave-id").unwrap();
+
+        let new = block_with_extra_data(1, b"new");
+        outbox.push(&new).unwrap();
+        let entry = outbox.read(1).unwrap();
+        assert_eq!(entry.block.hash(), new.hash());
+        assert_eq!(entry.arweave_id, None);
+    }
+
+    #[test]
+    pub fn test_push_with_outcome() {
+        let dir = tempfile::tempdir().unwrap();
+        let outbox = ArchiveOutbox::new(dir.path()).unwrap();
+
+        let block = block(1);
+        let outcome =
+            BlockOutcome { block_number: 1, block_hash: block.hash(), ..Default::default() };
+        outbox.push_with_outcome(&block, Some(outcome.clone())).unwrap();
+        outbox.set_outcome_id(1, "outcome-id").unwrap();
+
+        let entry = outbox.read(1).unwrap();
+        assert_eq!(entry.outcome, Some(outcome));
+        assert_eq!(entry.outcome_id.as_deref(), Some("outcome-id"));
+    }
+
+    #[test]
+    pub fn test_read_entry_without_outcome() {
+        // layout of entries staged before outcomes were archived
+        let mut data = vec![];
+        BorshSealedBlockWithSenders(block(1)).serialize(&mut data).unwrap();
+        Some("arweave-id".to_string()).serialize(&mut data).unwrap();
+
+        let entry: ArchiveEntry = borsh::from_slice(data.as_slice()).unwrap();
+        assert_eq!(entry.arweave_id.as_deref(), Some("arweave-id"));
+        assert_eq!(entry.outcome, None);
+        assert_eq!(entry.outcome_id, None);
+        assert!(entry.archive_ids.is_empty());
+
+        // layout of entries staged before archive destinations were tracked
+        let mut data = vec![];
+        BorshSealedBlockWithSenders(block(1)).serialize(&mut data).unwrap();
+        None::<String>.serialize(&mut data).unwrap();
+        None::<BlockOutcome>.serialize(&mut data).unwrap();
+        Some("outcome-id".to_string()).serialize(&mut data).unwrap();
+
+        let entry: ArchiveEntry = borsh::from_slice(data.as_slice()).unwrap();
+        assert_eq!(entry.outcome_id.as_deref(), Some("outcome-id"));
+        assert!(entry.archive_ids.is_empty());
+    }
+
+    #[test]
+    pub fn test_set_archive_ids() {
+        let dir = tempfile::tempdir().unwrap();
+        let outbox = ArchiveOutbox::new(dir.path()).unwrap();
+
+        outbox.push(&block(1)).unwrap();
+        let archive_ids = BTreeMap::from([
+            ("primary".to_string(), "primary-id".to_string()),
+            ("s3".to_string(), "s3-id".to_string()),
+        ]);
+        outbox.set_archive_ids(1, &archive_ids).unwrap();
+        outbox.set_arweave_id(1, "primary-id").unwrap();
+
+        let entry = outbox.read(1).unwrap();
+        assert_eq!(entry.archive_ids, archive_ids);
+        assert_eq!(entry.arweave_id.as_deref(), Some("primary-id"));
+    }
+
+    #[test]
+    pub fn test_revert() {
+        let dir = tempfile::tempdir().unwrap();
+        let outbox = ArchiveOutbox::new(dir.path()).unwrap();
+
+        // archived blocks
+        for number in [0, 1] {
+            outbox.push(&block(number)).unwrap();
+            outbox.set_arweave_id(number, "archived").unwrap();
+            outbox.complete(number).unwrap();
+        }
+        // uploaded, but not recorded yet
+        outbox.push(&block(2)).unwrap();
+        outbox.set_arweave_id(2, "uploaded").unwrap();
+        // never uploaded
+        outbox.push(&block(3)).unwrap();
+
+        let reverted_by = B256::random();
+        // never staged
+        assert!(!outbox.revert(&block(4), reverted_by).unwrap());
+        assert!(!outbox.revert(&block(3), reverted_by).unwrap());
+        // a different block than the one staged at the height
+        assert!(!outbox.revert(&block_with_extra_data(2, b"other"), reverted_by).unwrap());
+        assert!(outbox.revert(&block(2), reverted_by).unwrap());
+        assert!(outbox.revert(&block(1), reverted_by).unwrap());
+
+        assert!(outbox.pending().unwrap().is_empty());
+        assert_eq!(outbox.last_archived().unwrap(), Some(0));
+
+        let reverted = outbox.reverted().unwrap();
+        assert_eq!(reverted.len(), 2);
+        assert_eq!(reverted[0].block_hash, block(1).hash());
+        assert_eq!(reverted[0].reverted_by, reverted_by);
+
+        outbox.set_marker_id(&reverted[0], "marker").unwrap();
+        assert_eq!(outbox.reverted().unwrap()[0].marker_id.as_deref(), Some("marker"));
+
+        outbox.complete_revert(&reverted[0]).unwrap();
+        assert_eq!(outbox.reverted().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,114 @@
+use std::{
+    fs,
+    io::{self, ErrorKind, Write},
+    path::{Path, PathBuf},
+};
+use tempfile::NamedTempFile;
+
+/// A directory backed queue where every entry lives in its own file.
+///
+/// Entries are written to a temporary file first and renamed into place, so readers never observe
+/// a partially written entry, even if the node crashes mid-write.
+#[derive(Debug, Clone)]
+pub struct FileQueue {
+    dir: PathBuf,
+}
+
+impl FileQueue {
+    /// Opens the queue at `dir`, creating the directory if it does not exist yet.
+    pub fn new(dir: impl Into<PathBuf>) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Atomically writes `data` under `key`, replacing any previous entry.
+    pub fn push(&self, key: &str, data: &[u8]) -> io::Result<()> {
+        let mut tmp = NamedTempFile::new_in(&self.dir)?;
+        tmp.write_all(data)?;
+        tmp.as_file().sync_all()?;
+        tmp.persist(self.dir.join(key))?;
+        Ok(())
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.dir.join(key).is_file()
+    }
+
+    pub fn read(&self, key: &str) -> io::Result<Vec<u8>> {
+        fs::read(self.dir.join(key))
+    }
+
+    /// Removes the entry under `key`. Removing a missing entry is not an error.
+    pub fn remove(&self, key: &str) -> io::Result<()> {
+        match fs::remove_file(self.dir.join(key)) {
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(()),
+            res => res,
+        }
+    }
+
+    /// Moves the entry under `key` into `other`.
+    pub fn move_to(&self, key: &str, other: &FileQueue) -> io::Result<()> {
+        fs::rename(self.dir.join(key), other.dir.join(key))
+    }
+
+    /// Returns the keys of all entries, sorted lexicographically.
+    ///
+    /// In-flight temporary files are skipped.
+    pub fn keys(&self) -> io::Result<Vec<String>> {
+        let mut keys = vec![];
+
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            if !entry.file_type()?.is_file() {
+                continue
+            }
+
+            if let Some(key) = entry.file_name().to_str() {
+                if !key.starts_with('.') {
+                    keys.push(key.to_string());
+                }
+            }
+        }
+
+        keys.sort();
+        Ok(keys)
+    }
+}
+
+#[cfg(test)]
+mod file_queue_tests {
+    use crate::file_queue::FileQueue;
+
+    #[test]
+    pub fn test_push_read_remove() {
+        let dir = tempfile::tempdir().unwrap();
+        let queue = FileQueue::new(dir.path().join("queue")).unwrap();
+
+        queue.push("b", b"second").unwrap();
+        queue.push("a", b"first").unwrap();
+        assert_eq!(queue.keys().unwrap(), vec!["a".to_string(), "b".to_string()]);
+        assert_eq!(queue.read("a").unwrap(), b"first".to_vec());
+
+        queue.remove("a").unwrap();
+        queue.remove("a").unwrap();
+        assert_eq!(queue.keys().unwrap(), vec!["b".to_string()]);
+    }
+
+    #[test]
+    pub fn test_move_to() {
+        let dir = tempfile::tempdir().unwrap();
+        let from = FileQueue::new(dir.path().join("from")).unwrap();
+        let to = FileQueue::new(dir.path().join("to")).unwrap();
+
+        from.push("a", b"data").unwrap();
+        from.move_to("a", &to).unwrap();
+
+        assert!(from.keys().unwrap().is_empty());
+        assert!(to.contains("a"));
+    }
+}
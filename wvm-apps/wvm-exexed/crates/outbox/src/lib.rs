@@ -0,0 +1,4 @@
+pub mod archive;
+pub mod file_queue;
+pub mod sidecar;
+pub mod upload;
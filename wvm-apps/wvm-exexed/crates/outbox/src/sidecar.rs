@@ -0,0 +1,154 @@
+use crate::file_queue::FileQueue;
+use reth::primitives::B256;
+use std::{io, path::Path};
+use wevm_borsh::sidecar::ReadSidecar;
+
+/// Location of the read sidecar outbox, relative to the node's datadir.
+pub const READ_SIDECAR_OUTBOX_DIR: &str = "wvm/read-sidecars";
+
+/// Persistent outbox for the read sidecars of built blocks.
+///
+/// The payload builder stages a sidecar for every block it builds, most of which never become
+/// canonical. Staged sidecars are also where the builder's own node replays a block from, before
+/// the sidecar is archived. Once a block is committed, [`ReadSidecarOutbox::confirm_block`] moves
+/// the sidecar the block commits to to the `ready` queue and drops the rest. The archive ExEx
+/// drains the `ready` queue.
+#[derive(Debug, Clone)]
+pub struct ReadSidecarOutbox {
+    staged: FileQueue,
+    ready: FileQueue,
+}
+
+impl ReadSidecarOutbox {
+    pub fn new(dir: &Path) -> io::Result<Self> {
+        Ok(ReadSidecarOutbox {
+            staged: FileQueue::new(dir.join("staged"))?,
+            ready: FileQueue::new(dir.join("ready"))?,
+        })
+    }
+
+    /// Opens the outbox below the node's datadir.
+    pub fn at_datadir(datadir: &Path) -> io::Result<Self> {
+        Self::new(&datadir.join(READ_SIDECAR_OUTBOX_DIR))
+    }
+
+    /// Stages the sidecar of a built block, returns its commitment.
+    pub fn stage(&self, block_number: u64, sidecar: &ReadSidecar) -> io::Result<B256> {
+        let commitment = sidecar.commitment();
+        let key = sidecar_key(block_number, commitment);
+        if !self.staged.contains(key.as_str()) {
+            self.staged.push(key.as_str(), borsh::to_vec(sidecar)?.as_slice())?;
+        }
+
+        Ok(commitment)
+    }
+
+    /// Returns the sidecar of `block_number` with the given commitment, if it is staged or
+    /// waiting to be archived.
+    pub fn get(&self, block_number: u64, commitment: B256) -> io::Result<Option<ReadSidecar>> {
+        let key = sidecar_key(block_number, commitment);
+        for queue in [&self.staged, &self.ready] {
+            if queue.contains(key.as_str()) {
+                return Ok(Some(borsh::from_slice(queue.read(key.as_str())?.as_slice())?))
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Confirms the sidecar of a committed block.
+    ///
+    /// The sidecar with `commitment` is moved to the `ready` queue, every other sidecar staged for
+    /// this or an earlier block is discarded. Returns whether a sidecar was confirmed.
+    pub fn confirm_block(&self, block_number: u64, commitment: B256) -> io::Result<bool> {
+        let canonical = sidecar_key(block_number, commitment);
+        let mut confirmed = false;
+
+        for key in self.staged.keys()? {
+            match block_of_key(key.as_str()) {
+                Some(staged_block) if staged_block > block_number => continue,
+                _ if key == canonical => {
+                    self.staged.move_to(key.as_str(), &self.ready)?;
+                    confirmed = true;
+                }
+                _ => self.staged.remove(key.as_str())?,
+            }
+        }
+
+        Ok(confirmed)
+    }
+
+    /// Returns all confirmed sidecars that still have to be archived with their block number,
+    /// oldest first.
+    pub fn ready(&self) -> io::Result<Vec<(String, u64, ReadSidecar)>> {
+        let mut sidecars = vec![];
+        for key in self.ready.keys()? {
+            let Some(block_number) = block_of_key(key.as_str()) else {
+                self.ready.remove(key.as_str())?;
+                continue
+            };
+            let sidecar = borsh::from_slice(self.ready.read(key.as_str())?.as_slice())?;
+            sidecars.push((key, block_number, sidecar));
+        }
+
+        Ok(sidecars)
+    }
+
+    /// Removes a sidecar from the `ready` queue once it has been archived.
+    pub fn complete(&self, key: &str) -> io::Result<()> {
+        self.ready.remove(key)
+    }
+}
+
+fn sidecar_key(block_number: u64, commitment: B256) -> String {
+    format!("{block_number:020}-{commitment}")
+}
+
+fn block_of_key(key: &str) -> Option<u64> {
+    key.split_once('-')?.0.parse().ok()
+}
+
+#[cfg(test)]
+mod sidecar_tests {
+    use crate::sidecar::ReadSidecarOutbox;
+    use wevm_borsh::sidecar::{ReadSidecar, RecordedRead};
+
+    fn sidecar(data: &[u8]) -> ReadSidecar {
+        ReadSidecar {
+            reads: vec![RecordedRead {
+                precompile: "arweave-read".to_string(),
+                input: b"tx".to_vec(),
+                output: Ok(data.to_vec()),
+            }],
+        }
+    }
+
+    #[test]
+    pub fn test_confirm_block() {
+        let dir = tempfile::tempdir().unwrap();
+        let outbox = ReadSidecarOutbox::new(dir.path()).unwrap();
+
+        let canonical = outbox.stage(10, &sidecar(b"canonical")).unwrap();
+        let orphaned = outbox.stage(10, &sidecar(b"orphaned")).unwrap();
+        outbox.stage(9, &sidecar(b"stale")).unwrap();
+        let future = outbox.stage(11, &sidecar(b"future")).unwrap();
+        assert_eq!(outbox.get(10, orphaned).unwrap(), Some(sidecar(b"orphaned")));
+
+        assert!(outbox.confirm_block(10, canonical).unwrap());
+        assert_eq!(outbox.get(10, orphaned).unwrap(), None);
+        assert_eq!(outbox.get(10, canonical).unwrap(), Some(sidecar(b"canonical")));
+
+        let ready = outbox.ready().unwrap();
+        assert_eq!(ready.len(), 1);
+        assert_eq!(ready[0].1, 10);
+        assert_eq!(ready[0].2, sidecar(b"canonical"));
+
+        outbox.complete(ready[0].0.as_str()).unwrap();
+        assert!(outbox.ready().unwrap().is_empty());
+
+        // the sidecar of the next block is still staged
+        assert_eq!(outbox.get(11, future).unwrap(), Some(sidecar(b"future")));
+        assert!(!outbox.confirm_block(11, ReadSidecar::empty_commitment()).unwrap());
+        assert_eq!(outbox.get(11, future).unwrap(), None);
+    }
+}
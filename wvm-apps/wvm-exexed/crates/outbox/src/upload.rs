@@ -0,0 +1,262 @@
+use crate::file_queue::FileQueue;
+use borsh::{BorshDeserialize, BorshSerialize};
+use reth::primitives::{keccak256, Address, B256};
+use std::{
+    collections::HashSet,
+    io::{self, Read, Write},
+    path::Path,
+};
+use wevm_borsh::{address::BorshAddress, b256::BorshB256};
+
+/// Location of the upload outbox, relative to the node's datadir.
+pub const UPLOAD_OUTBOX_DIR: &str = "wvm/upload-outbox";
+/// Address of the Arweave upload precompile the records are handed to.
+pub const UPLOAD_PRECOMPILE_ADDRESS: u64 = 0x17;
+
+/// A payload handed to the Arweave upload precompile.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UploadRecord {
+    pub block_number: u64,
+    /// Origin of the transaction that called the precompile.
+    pub caller: Address,
+    /// Nonce of the transaction that called the precompile, `None` for calls that are not part of
+    /// a transaction (e.g. `eth_call`).
+    pub nonce: Option<u64>,
+    pub content_id: B256,
+    pub data: Vec<u8>,
+}
+
+impl UploadRecord {
+    pub fn new(
+        chain_id: u64,
+        block_number: u64,
+        caller: Address,
+        nonce: Option<u64>,
+        data: Vec<u8>,
+    ) -> Self {
+        let content_id = content_id(chain_id, block_number, caller, nonce, &data);
+        UploadRecord { block_number, caller, nonce, content_id, data }
+    }
+
+    /// Key of the record staged by the execution of the block with `block_hash`.
+    fn key(&self, block_hash: B256) -> String {
+        format!("{:020}-{}-{}", self.block_number, block_hash, self.content_id)
+    }
+}
+
+/// Derives the identifier returned by the upload precompile.
+///
+/// The id only depends on the call and its block context, so every node executing the block
+/// computes the same value, independent of when (or whether) the payload reaches Arweave.
+pub fn content_id(
+    chain_id: u64,
+    block_number: u64,
+    caller: Address,
+    nonce: Option<u64>,
+    data: &[u8],
+) -> B256 {
+    let mut buf = Vec::with_capacity(8 + 8 + 20 + 9 + 32);
+    buf.extend_from_slice(&chain_id.to_be_bytes());
+    buf.extend_from_slice(&block_number.to_be_bytes());
+    buf.extend_from_slice(caller.as_slice());
+    match nonce {
+        Some(nonce) => {
+            buf.push(1);
+            buf.extend_from_slice(&nonce.to_be_bytes());
+        }
+        None => buf.push(0),
+    }
+    buf.extend_from_slice(keccak256(data).as_slice());
+
+    keccak256(buf)
+}
+
+impl BorshSerialize for UploadRecord {
+    fn serialize<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.block_number.serialize(writer)?;
+        BorshAddress(self.caller).serialize(writer)?;
+        self.nonce.serialize(writer)?;
+        BorshB256(self.content_id).serialize(writer)?;
+        self.data.serialize(writer)?;
+
+        Ok(())
+    }
+}
+
+impl BorshDeserialize for UploadRecord {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let block_number: u64 = BorshDeserialize::deserialize_reader(reader)?;
+        let caller = BorshAddress::deserialize_reader(reader)?;
+        let nonce: Option<u64> = BorshDeserialize::deserialize_reader(reader)?;
+        let content_id = BorshB256::deserialize_reader(reader)?;
+        let data = Vec::<u8>::deserialize_reader(reader)?;
+
+        Ok(UploadRecord { block_number, caller: caller.0, nonce, content_id: content_id.0, data })
+    }
+}
+
+/// Persistent outbox for precompile uploads.
+///
+/// The block executor stages the payloads of every block it executes in the `staged` queue, keyed
+/// by the hash of the block, while `eth_call`s and payload building attempts stage nothing. A block
+/// may still be executed without becoming canonical, so once a block is committed,
+/// [`UploadOutbox::confirm_block`] moves the records of that block which belong to one of its
+/// transactions to the `ready` queue and drops the rest. The archive ExEx drains the `ready`
+/// queue.
+#[derive(Debug, Clone)]
+pub struct UploadOutbox {
+    staged: FileQueue,
+    ready: FileQueue,
+}
+
+impl UploadOutbox {
+    pub fn new(dir: &Path) -> io::Result<Self> {
+        Ok(UploadOutbox {
+            staged: FileQueue::new(dir.join("staged"))?,
+            ready: FileQueue::new(dir.join("ready"))?,
+        })
+    }
+
+    /// Opens the outbox below the node's datadir.
+    pub fn at_datadir(datadir: &Path) -> io::Result<Self> {
+        Self::new(&datadir.join(UPLOAD_OUTBOX_DIR))
+    }
+
+    /// Stages the records of an execution of the block with `block_hash`.
+    ///
+    /// Executing the same block again stages the same keys, so records are never duplicated.
+    pub fn stage(&self, block_hash: B256, records: &[UploadRecord]) -> io::Result<()> {
+        for record in records {
+            self.staged.push(record.key(block_hash).as_str(), borsh::to_vec(record)?.as_slice())?;
+        }
+
+        Ok(())
+    }
+
+    /// Confirms the staged records of a committed block.
+    ///
+    /// `included` holds the `(sender, nonce)` pair of every transaction in the block. Records
+    /// staged for the block with `block_hash` that match are moved to the `ready` queue, every
+    /// other record staged for this or an earlier block, e.g. by the execution of a block that
+    /// lost a reorg, is discarded. Returns the number of confirmed records.
+    pub fn confirm_block(
+        &self,
+        block_number: u64,
+        block_hash: B256,
+        included: &HashSet<(Address, u64)>,
+    ) -> io::Result<usize> {
+        let block_prefix = format!("{:020}-{}-", block_number, block_hash);
+        let mut confirmed = 0;
+
+        for key in self.staged.keys()? {
+            let Some(staged_block) = block_of_key(key.as_str()) else {
+                self.staged.remove(key.as_str())?;
+                continue
+            };
+
+            if staged_block > block_number {
+                continue
+            }
+
+            let record = self.read_staged(key.as_str())?;
+            let is_included = key.starts_with(block_prefix.as_str()) &&
+                record.nonce.is_some_and(|nonce| included.contains(&(record.caller, nonce)));
+
+            if is_included {
+                self.staged.move_to(key.as_str(), &self.ready)?;
+                confirmed += 1;
+            } else {
+                self.staged.remove(key.as_str())?;
+            }
+        }
+
+        Ok(confirmed)
+    }
+
+    /// Returns all confirmed records that still have to be uploaded, oldest first.
+    pub fn ready(&self) -> io::Result<Vec<(String, UploadRecord)>> {
+        let mut records = vec![];
+        for key in self.ready.keys()? {
+            let record = borsh::from_slice(self.ready.read(key.as_str())?.as_slice())?;
+            records.push((key, record));
+        }
+
+        Ok(records)
+    }
+
+    /// Removes a record from the `ready` queue once it has been uploaded.
+    pub fn complete(&self, key: &str) -> io::Result<()> {
+        self.ready.remove(key)
+    }
+
+    fn read_staged(&self, key: &str) -> io::Result<UploadRecord> {
+        borsh::from_slice(self.staged.read(key)?.as_slice())
+    }
+}
+
+fn block_of_key(key: &str) -> Option<u64> {
+    key.split_once('-')?.0.parse().ok()
+}
+
+#[cfg(test)]
+mod upload_tests {
+    use crate::upload::{content_id, UploadOutbox, UploadRecord};
+    use reth::primitives::{Address, B256};
+    use std::collections::HashSet;
+
+    #[test]
+    pub fn test_content_id_is_deterministic() {
+        let caller = Address::random();
+        let id = content_id(1, 10, caller, Some(0), b"Hello world");
+        assert_eq!(id, content_id(1, 10, caller, Some(0), b"Hello world"));
+        assert_ne!(id, content_id(1, 11, caller, Some(0), b"Hello world"));
+        assert_ne!(id, content_id(1, 10, caller, None, b"Hello world"));
+        assert_ne!(id, content_id(1, 10, caller, Some(0), b"Hello world!"));
+    }
+
+    #[test]
+    pub fn test_record_borsh() {
+        let record = UploadRecord::new(1, 10, Address::random(), Some(3), b"data".to_vec());
+        let to_borsh = borsh::to_vec(&record).unwrap();
+        let from_borsh: UploadRecord = borsh::from_slice(to_borsh.as_slice()).unwrap();
+        assert_eq!(record, from_borsh);
+    }
+
+    #[test]
+    pub fn test_confirm_block() {
+        let dir = tempfile::tempdir().unwrap();
+        let outbox = UploadOutbox::new(dir.path()).unwrap();
+        let caller = Address::random();
+        let (canonical, uncle) = (B256::with_last_byte(1), B256::with_last_byte(2));
+
+        let included = UploadRecord::new(1, 10, caller, Some(0), b"included".to_vec());
+        let second = UploadRecord::new(1, 10, caller, Some(0), b"second call".to_vec());
+        let simulated = UploadRecord::new(1, 10, caller, None, b"eth_call".to_vec());
+        let stale = UploadRecord::new(1, 9, caller, Some(5), b"stale".to_vec());
+        let future = UploadRecord::new(1, 11, caller, Some(1), b"future".to_vec());
+        outbox.stage(canonical, &[included.clone(), second.clone(), simulated]).unwrap();
+        outbox.stage(canonical, &[stale, future]).unwrap();
+        // re-executing the block stages nothing new
+        outbox.stage(canonical, &[included.clone()]).unwrap();
+        // the same transaction in a block that loses the reorg
+        let reorged = UploadRecord::new(1, 10, caller, Some(0), b"reorged".to_vec());
+        outbox.stage(uncle, &[reorged]).unwrap();
+
+        let confirmed = outbox.confirm_block(10, canonical, &HashSet::from([(caller, 0)])).unwrap();
+        assert_eq!(confirmed, 2);
+
+        let ready = outbox.ready().unwrap();
+        assert_eq!(ready.len(), 2);
+        assert!(ready.iter().any(|(_, record)| *record == included));
+        assert!(ready.iter().any(|(_, record)| *record == second));
+
+        for (key, _) in ready {
+            outbox.complete(key.as_str()).unwrap();
+        }
+        assert!(outbox.ready().unwrap().is_empty());
+
+        // the record of the next block is still staged
+        let included = HashSet::from([(caller, 1)]);
+        assert_eq!(outbox.confirm_block(11, canonical, &included).unwrap(), 1);
+    }
+}
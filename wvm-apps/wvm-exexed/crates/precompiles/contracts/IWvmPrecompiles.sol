@@ -0,0 +1,149 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.0;
+
+/// Fields of an archived WVM block, as read by `IWvmBlockRead.blockField`.
+enum BlockField {
+    Hash,
+    ParentHash,
+    Beneficiary,
+    StateRoot,
+    TransactionsRoot,
+    ReceiptsRoot,
+    MixHash,
+    ParentBeaconBlockRoot,
+    Number,
+    Timestamp,
+    GasLimit,
+    GasUsed,
+    BaseFeePerGas,
+    BlobGasUsed,
+    ExcessBlobGas,
+    Difficulty,
+    Nonce,
+    Size,
+    WithdrawalsRoot
+}
+
+/// Fields of a blob sidecar, as read by `IKyveRead.blobField`.
+enum BlobField {
+    Blob,
+    KzgCommitment,
+    KzgProof
+}
+
+/// A withdrawal of a WVM block, as read by `IWvmBlockRead.blockWithdrawals`.
+struct BlockWithdrawal {
+    uint64 index;
+    uint64 validatorIndex;
+    address recipient;
+    uint64 amountGwei;
+}
+
+/// Matches items carrying tag `name` with any of `values`, as passed to `IArweaveQuery.query`.
+struct TagFilter {
+    string name;
+    string[] values;
+}
+
+/// An item found by `IArweaveQuery.query`.
+struct QueryItem {
+    bytes32 id;
+    uint256 size;
+}
+
+/// Stages data for upload to Arweave, at address `0x17`.
+interface IArweaveUpload {
+    /// Returns the content id the data is archived under once the block is committed.
+    function upload(bytes calldata data) external returns (bytes32 contentId);
+}
+
+/// Reads the data of Arweave transactions, at address `0x18`.
+///
+/// Reads are charged by the size of the returned data. Only the requested range is transferred.
+/// The data of layer 1 transactions is verified against their signed header, data items of
+/// bundles are trusted as served by the gateway.
+interface IArweaveRead {
+    /// Returns at most `len` bytes of the data of transaction `id`, starting at `offset`.
+    function readTx(bytes32 id, uint256 offset, uint256 len) external view returns (bytes memory data);
+
+    /// Like `readTx`, but reads through a custom gateway instead of the node's storage. The call
+    /// fails unless the node allows the gateway.
+    function readTxFrom(string calldata gateway, bytes32 id, uint256 offset, uint256 len)
+        external
+        view
+        returns (bytes memory data);
+}
+
+/// Returns `Hello World`, at address `0x19`.
+interface IHelloWorld {
+    function hello() external view returns (string memory message);
+}
+
+/// Reads archived WVM blocks, at address `0x20`.
+///
+/// Reads are charged by the size of the returned data. Reading a field the block does not have,
+/// like the base fee of a block before London, fails instead of returning zero.
+interface IWvmBlockRead {
+    /// Returns a `BlockField` of block `number`, numbers are big-endian and addresses are
+    /// left-padded.
+    function blockField(uint64 number, uint8 field) external view returns (bytes32 value);
+
+    /// Returns the hashes of the transactions of block `number`.
+    function blockTransactions(uint64 number) external view returns (bytes32[] memory hashes);
+
+    /// Returns the RLP-encoded header of block `number`, its hash is the block hash.
+    function blockHeader(uint64 number) external view returns (bytes memory header);
+
+    /// Returns the EIP-2718 encoded transaction at `index` of block `number` and its sender.
+    function blockTransaction(uint64 number, uint32 index)
+        external
+        view
+        returns (bytes memory transaction, address sender);
+
+    /// Returns the senders of the transactions of block `number`, in order.
+    function blockSenders(uint64 number) external view returns (address[] memory senders);
+
+    /// Returns the withdrawals of block `number`, empty before Shanghai.
+    function blockWithdrawals(uint64 number) external view returns (BlockWithdrawal[] memory withdrawals);
+
+    /// Returns the EIP-2718 encoded receipt of the transaction at `index` of block `number`. The
+    /// call fails unless the outcome of the block is archived.
+    function transactionReceipt(uint64 number, uint32 index) external view returns (bytes memory receipt);
+}
+
+/// Reads the blob sidecars of Ethereum blocks, at address `0x21`.
+///
+/// Blobs are read from the blob source of the node, KYVE by default, and checked against their
+/// own KZG commitment: reading a blob that does not match its commitment fails. They are not
+/// checked against the versioned hashes of the Ethereum block, which blobs a block has is trusted
+/// to the blob source. Reads of blobs are charged like other reads of the returned data, plus the
+/// KZG proof verification.
+interface IKyveRead {
+    /// Returns a `BlobField` of the blob at `index` of Ethereum block `blockNumber`.
+    function blobField(uint64 blockNumber, uint8 index, uint8 field) external view returns (bytes memory value);
+
+    /// Returns the blob at `index` of Ethereum block `blockNumber` with its commitment, proof and
+    /// the versioned hash its transaction references it by.
+    function blob(uint64 blockNumber, uint8 index)
+        external
+        view
+        returns (bytes memory blob, bytes memory kzgCommitment, bytes memory kzgProof, bytes32 versionedHash);
+
+    /// Returns the beacon slot of Ethereum block `blockNumber`.
+    function blobSlot(uint64 blockNumber) external view returns (uint64 slot);
+}
+
+/// Finds Arweave items by their tags, at address `0x22`, once activated in the chain spec.
+///
+/// Reads are charged like reads of the returned data. Queries are only served in blocks that
+/// commit to a read sidecar, calls outside of them, e.g. `eth_call`, fail.
+interface IArweaveQuery {
+    /// Returns at most `first` items carrying every tag of `tags` and signed by any of `owners`,
+    /// newest first, at most 100 per call. Owners are raw Arweave addresses, an empty list matches
+    /// any owner. `cursor` continues the query after the last item, pass it as `after` to read the
+    /// next items, it is empty if there are none.
+    function query(TagFilter[] calldata tags, bytes32[] calldata owners, uint32 first, string calldata after)
+        external
+        view
+        returns (QueryItem[] memory items, string memory cursor);
+}
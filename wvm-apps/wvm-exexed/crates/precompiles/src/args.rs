@@ -0,0 +1,225 @@
+use crate::inner::{
+    blobs::{BeaconBlobSource, BlobSource, KyveBlobSource, LocalBlobSource, KYVE_API_URL},
+    io::{
+        PrecompileIo, PrecompileIoConfig, DEFAULT_PRECOMPILE_IO_THREADS, DEFAULT_PRECOMPILE_TIMEOUT,
+    },
+    read_cache::{
+        ReadCache, ReadCacheConfig, DEFAULT_READ_CACHE_MAX_ITEM_SIZE, DEFAULT_READ_CACHE_SIZE,
+    },
+};
+use clap::{Args, ValueEnum};
+use reth_cli_util::parse_duration_from_secs;
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+const MEGABYTE: u64 = 1024 * 1024;
+
+/// Sources of blob sidecars selectable from the command line.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum BlobSourceKind {
+    /// The KYVE data pipeline.
+    #[default]
+    Kyve,
+    /// The API of a beacon node, with an execution node to look up blocks.
+    Beacon,
+    /// A local directory of `<block number>.json` files.
+    Local,
+}
+
+/// Parameters of the WVM precompiles.
+#[derive(Debug, Clone, Args)]
+#[command(next_help_heading = "WVM Precompiles")]
+pub struct PrecompileArgs {
+    /// Maximum time a precompile waits for its network requests before it fails.
+    #[arg(
+        long = "wvm.precompiles.timeout",
+        value_name = "SECONDS",
+        value_parser = parse_duration_from_secs,
+        default_value = "30"
+    )]
+    pub timeout: Duration,
+
+    /// Number of threads the precompiles do their network requests on.
+    #[arg(
+        long = "wvm.precompiles.io-threads",
+        value_name = "THREADS",
+        default_value_t = DEFAULT_PRECOMPILE_IO_THREADS
+    )]
+    pub io_threads: usize,
+
+    /// RPC URLs of the nodes asked for the read sidecars of their blocks before the sidecars are
+    /// archived. Sidecars are verified against the commitment of the block, whatever the peer.
+    #[arg(long = "wvm.precompiles.sidecar-peers", value_name = "URL", value_delimiter = ',')]
+    pub sidecar_peers: Vec<String>,
+
+    /// Maximum size of the on-disk cache of data read from Arweave, in megabytes. The least
+    /// recently read items are evicted first, 0 disables the cache.
+    #[arg(
+        long = "wvm.precompiles.read-cache-mb",
+        value_name = "MB",
+        default_value_t = DEFAULT_READ_CACHE_SIZE / MEGABYTE
+    )]
+    pub read_cache_mb: u64,
+
+    /// Items larger than this many megabytes are never cached.
+    #[arg(
+        long = "wvm.precompiles.read-cache-max-item-mb",
+        value_name = "MB",
+        default_value_t = DEFAULT_READ_CACHE_MAX_ITEM_SIZE / MEGABYTE
+    )]
+    pub read_cache_max_item_mb: u64,
+
+    /// Source the blob precompile reads the blob sidecars of Ethereum blocks from. Blobs are
+    /// checked against their own KZG commitment, the source is trusted for which blobs a block
+    /// has.
+    #[arg(
+        long = "wvm.precompiles.blob-source",
+        value_name = "SOURCE",
+        value_enum,
+        default_value_t
+    )]
+    pub blob_source: BlobSourceKind,
+
+    /// URL of the KYVE data API, or of the beacon node API for the beacon source.
+    #[arg(long = "wvm.precompiles.blob-url", value_name = "URL")]
+    pub blob_url: Option<String>,
+
+    /// JSON-RPC URL of an Ethereum execution node, required by the beacon source.
+    #[arg(long = "wvm.precompiles.blob-execution-url", value_name = "URL")]
+    pub blob_execution_url: Option<String>,
+
+    /// Directory of the local source, defaults to `<DATADIR>/wvm/blobs`.
+    #[arg(long = "wvm.precompiles.blob-dir", value_name = "PATH")]
+    pub blob_dir: Option<PathBuf>,
+}
+
+impl Default for PrecompileArgs {
+    fn default() -> Self {
+        PrecompileArgs {
+            timeout: DEFAULT_PRECOMPILE_TIMEOUT,
+            io_threads: DEFAULT_PRECOMPILE_IO_THREADS,
+            sidecar_peers: vec![],
+            read_cache_mb: DEFAULT_READ_CACHE_SIZE / MEGABYTE,
+            read_cache_max_item_mb: DEFAULT_READ_CACHE_MAX_ITEM_SIZE / MEGABYTE,
+            blob_source: BlobSourceKind::Kyve,
+            blob_url: None,
+            blob_execution_url: None,
+            blob_dir: None,
+        }
+    }
+}
+
+impl PrecompileArgs {
+    pub fn io_config(&self) -> PrecompileIoConfig {
+        PrecompileIoConfig { worker_threads: self.io_threads, timeout: self.timeout }
+    }
+
+    /// Starts the runtime the precompiles do their network requests on.
+    pub fn io(&self) -> eyre::Result<PrecompileIo> {
+        Ok(PrecompileIo::new(self.io_config())?)
+    }
+
+    pub fn read_cache_config(&self) -> ReadCacheConfig {
+        ReadCacheConfig {
+            max_bytes: self.read_cache_mb * MEGABYTE,
+            max_item_bytes: self.read_cache_max_item_mb * MEGABYTE,
+        }
+    }
+
+    /// Opens the read cache below the node's datadir.
+    pub fn read_cache(&self, datadir: &Path) -> eyre::Result<ReadCache> {
+        Ok(ReadCache::at_datadir(datadir, self.read_cache_config())?)
+    }
+
+    /// Creates the configured blob source, `datadir` is used to resolve default paths.
+    pub fn blob_source(&self, datadir: &Path) -> eyre::Result<Arc<dyn BlobSource>> {
+        Ok(match self.blob_source {
+            BlobSourceKind::Kyve => {
+                Arc::new(KyveBlobSource::new(self.blob_url.as_deref().unwrap_or(KYVE_API_URL)))
+            }
+            BlobSourceKind::Beacon => {
+                let (Some(beacon_url), Some(execution_url)) =
+                    (&self.blob_url, &self.blob_execution_url)
+                else {
+                    eyre::bail!(
+                        "the beacon blob source requires --wvm.precompiles.blob-url and \
+                         --wvm.precompiles.blob-execution-url"
+                    )
+                };
+                Arc::new(BeaconBlobSource::new(beacon_url, execution_url))
+            }
+            BlobSourceKind::Local => Arc::new(match &self.blob_dir {
+                Some(dir) => LocalBlobSource::new(dir),
+                None => LocalBlobSource::at_datadir(datadir),
+            }),
+        })
+    }
+}
+
+#[cfg(test)]
+mod args_tests {
+    use crate::{
+        args::{BlobSourceKind, PrecompileArgs},
+        inner::read_cache::ReadCacheConfig,
+    };
+    use clap::{Args, Parser};
+    use std::{path::Path, time::Duration};
+
+    #[derive(Parser)]
+    struct CommandParser<T: Args> {
+        #[command(flatten)]
+        args: T,
+    }
+
+    #[test]
+    pub fn test_parse_precompile_args() {
+        let args = CommandParser::<PrecompileArgs>::parse_from(["reth"]).args;
+        assert_eq!(args.io_config().timeout, Duration::from_secs(30));
+        assert!(args.sidecar_peers.is_empty());
+        assert_eq!(args.read_cache_config(), ReadCacheConfig::default());
+        assert_eq!(args.blob_source, BlobSourceKind::Kyve);
+
+        let args = CommandParser::<PrecompileArgs>::parse_from([
+            "reth",
+            "--wvm.precompiles.timeout",
+            "5",
+            "--wvm.precompiles.io-threads",
+            "8",
+            "--wvm.precompiles.sidecar-peers",
+            "http://builder:8545",
+            "--wvm.precompiles.read-cache-mb",
+            "0",
+        ])
+        .args;
+        assert_eq!(args.io_config().timeout, Duration::from_secs(5));
+        assert_eq!(args.io_config().worker_threads, 8);
+        assert_eq!(args.sidecar_peers, vec!["http://builder:8545"]);
+        assert_eq!(args.read_cache_config().max_bytes, 0);
+
+        let args = CommandParser::<PrecompileArgs>::parse_from([
+            "reth",
+            "--wvm.precompiles.blob-source",
+            "beacon",
+            "--wvm.precompiles.blob-url",
+            "http://localhost:5052",
+        ])
+        .args;
+        assert_eq!(args.blob_source, BlobSourceKind::Beacon);
+        // the beacon source also needs an execution node
+        assert!(args.blob_source(Path::new("/tmp")).is_err());
+
+        let args = CommandParser::<PrecompileArgs>::parse_from([
+            "reth",
+            "--wvm.precompiles.blob-source",
+            "local",
+            "--wvm.precompiles.blob-dir",
+            "/tmp/blobs",
+        ])
+        .args;
+        assert_eq!(args.blob_dir.as_deref(), Some(Path::new("/tmp/blobs")));
+        assert!(args.blob_source(Path::new("/tmp")).is_ok());
+    }
+}
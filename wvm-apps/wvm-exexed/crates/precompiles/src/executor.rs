@@ -0,0 +1,180 @@
+use crate::inner::{
+    arweave_precompile::collect_uploads,
+    sidecar::{replay_reads, ReadSidecar, ReadSidecars, SidecarError},
+};
+use outbox::upload::UploadOutbox;
+use reth::primitives::{BlockNumber, BlockWithSenders, Receipt};
+use reth_evm::execute::{
+    BatchExecutor, BlockExecutionError, BlockExecutionInput, BlockExecutionOutput,
+    BlockExecutorProvider, BlockValidationError, ExecutionOutcome, Executor, ProviderError,
+};
+use reth_prune_types::PruneModes;
+use revm_primitives::{db::Database, EVMError};
+use std::{fmt::Display, sync::Arc};
+
+/// Block executor provider that replays the reads of the precompiles from the sidecar each block
+/// commits to, see [`ReadSidecars`], and stages the payloads of the upload precompile of every
+/// executed block in the [`UploadOutbox`].
+#[derive(Debug, Clone)]
+pub struct WvmExecutorProvider<P> {
+    inner: P,
+    sidecars: ReadSidecars,
+    uploads: Arc<UploadOutbox>,
+}
+
+impl<P> WvmExecutorProvider<P> {
+    pub fn new(inner: P, sidecars: ReadSidecars, uploads: Arc<UploadOutbox>) -> Self {
+        WvmExecutorProvider { inner, sidecars, uploads }
+    }
+}
+
+impl<P> BlockExecutorProvider for WvmExecutorProvider<P>
+where
+    P: BlockExecutorProvider,
+{
+    type Executor<DB: Database<Error: Into<ProviderError> + Display>> =
+        WvmExecutor<P::Executor<DB>>;
+    type BatchExecutor<DB: Database<Error: Into<ProviderError> + Display>> =
+        WvmExecutor<P::BatchExecutor<DB>>;
+
+    fn executor<DB>(&self, db: DB) -> Self::Executor<DB>
+    where
+        DB: Database<Error: Into<ProviderError> + Display>,
+    {
+        WvmExecutor {
+            inner: self.inner.executor(db),
+            sidecars: self.sidecars.clone(),
+            uploads: self.uploads.clone(),
+        }
+    }
+
+    fn batch_executor<DB>(&self, db: DB) -> Self::BatchExecutor<DB>
+    where
+        DB: Database<Error: Into<ProviderError> + Display>,
+    {
+        WvmExecutor {
+            inner: self.inner.batch_executor(db),
+            sidecars: self.sidecars.clone(),
+            uploads: self.uploads.clone(),
+        }
+    }
+}
+
+/// Single block and batch executor of a [`WvmExecutorProvider`].
+#[derive(Debug)]
+pub struct WvmExecutor<E> {
+    inner: E,
+    sidecars: ReadSidecars,
+    uploads: Arc<UploadOutbox>,
+}
+
+/// Executes a block with `execute`, staging its uploads once it executed successfully.
+///
+/// `sidecar` looks up the read sidecar the block commits to.
+fn execute_block<R>(
+    sidecar: impl FnOnce(&BlockWithSenders) -> Result<Option<ReadSidecar>, SidecarError>,
+    uploads: &UploadOutbox,
+    block: &BlockWithSenders,
+    execute: impl FnOnce() -> Result<R, BlockExecutionError>,
+) -> Result<R, BlockExecutionError> {
+    let (output, records) = collect_uploads(|| execute_pinned(sidecar, block, execute));
+    let output = output?;
+    if !records.is_empty() {
+        // a node that cannot persist the payloads must not keep executing as if it did
+        uploads.stage(block.header.hash_slow(), &records).map_err(|err| {
+            BlockExecutionError::msg(format!("failed to stage arweave uploads: {err}"))
+        })?;
+    }
+
+    Ok(output)
+}
+
+/// Executes a block with `execute`, replaying its reads if it commits to a sidecar.
+///
+/// A block whose sidecar is not available yet fails with an internal error, which does not mark
+/// the block as invalid, so it is executed again once it is received again. A block that does not
+/// commit to a sidecar or whose reads don't match it is invalid.
+fn execute_pinned<R>(
+    sidecar: impl FnOnce(&BlockWithSenders) -> Result<Option<ReadSidecar>, SidecarError>,
+    block: &BlockWithSenders,
+    execute: impl FnOnce() -> Result<R, BlockExecutionError>,
+) -> Result<R, BlockExecutionError> {
+    let sidecar = match sidecar(block) {
+        Ok(Some(sidecar)) => sidecar,
+        Ok(None) => return execute(),
+        Err(err) if err.is_retryable() => return Err(BlockExecutionError::msg(err)),
+        Err(err) => return Err(invalid_block(block, err.to_string())),
+    };
+
+    let (output, matched) = replay_reads(sidecar, execute);
+    let output = output?;
+    if !matched {
+        let message = format!("reads of block {} do not match its read sidecar", block.number);
+        return Err(invalid_block(block, message))
+    }
+
+    Ok(output)
+}
+
+fn invalid_block(block: &BlockWithSenders, message: String) -> BlockExecutionError {
+    let error = Box::new(EVMError::Custom(message));
+    BlockValidationError::EVM { hash: block.header.hash_slow(), error }.into()
+}
+
+impl<E, DB> Executor<DB> for WvmExecutor<E>
+where
+    E: for<'a> Executor<
+        DB,
+        Input<'a> = BlockExecutionInput<'a, BlockWithSenders>,
+        Output = BlockExecutionOutput<Receipt>,
+        Error = BlockExecutionError,
+    >,
+{
+    type Input<'a> = BlockExecutionInput<'a, BlockWithSenders>;
+    type Output = BlockExecutionOutput<Receipt>;
+    type Error = BlockExecutionError;
+
+    fn execute(self, input: Self::Input<'_>) -> Result<Self::Output, Self::Error> {
+        let WvmExecutor { inner, sidecars, uploads } = self;
+        let sidecar = |block: &BlockWithSenders| sidecars.sidecar(&block.header);
+        execute_block(sidecar, &uploads, input.block, || inner.execute(input))
+    }
+}
+
+impl<E, DB> BatchExecutor<DB> for WvmExecutor<E>
+where
+    E: for<'a> BatchExecutor<
+        DB,
+        Input<'a> = BlockExecutionInput<'a, BlockWithSenders>,
+        Output = ExecutionOutcome,
+        Error = BlockExecutionError,
+    >,
+{
+    type Input<'a> = BlockExecutionInput<'a, BlockWithSenders>;
+    type Output = ExecutionOutcome;
+    type Error = BlockExecutionError;
+
+    fn execute_and_verify_one(&mut self, input: Self::Input<'_>) -> Result<(), Self::Error> {
+        let inner = &mut self.inner;
+        let block = input.block;
+        // a failed block stops the whole pipeline, so missing sidecars are waited for a while
+        let sidecar = |block: &BlockWithSenders| self.sidecars.wait_for_sidecar(&block.header);
+        execute_block(sidecar, &self.uploads, block, || inner.execute_and_verify_one(input))
+    }
+
+    fn finalize(self) -> Self::Output {
+        self.inner.finalize()
+    }
+
+    fn set_tip(&mut self, tip: BlockNumber) {
+        self.inner.set_tip(tip);
+    }
+
+    fn set_prune_modes(&mut self, prune_modes: PruneModes) {
+        self.inner.set_prune_modes(prune_modes);
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.inner.size_hint()
+    }
+}
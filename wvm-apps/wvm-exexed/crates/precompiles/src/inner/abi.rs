@@ -0,0 +1,71 @@
+use alloy_sol_types::{sol, SolInterface};
+use reth::primitives::revm_primitives::{PrecompileError, PrecompileErrors};
+
+// Solidity interfaces of the WVM precompiles, see `contracts/IWvmPrecompiles.sol`.
+sol!("contracts/IWvmPrecompiles.sol");
+
+/// Decodes an ABI-encoded call to interface `I`.
+///
+/// Returns `None` if the input does not start with a selector of `I`, the precompiles then read
+/// it as their legacy string input. An input that starts with a selector has to be a valid call.
+pub fn decode_call<I: SolInterface>(input: &[u8]) -> Result<Option<I>, PrecompileErrors> {
+    let Some(selector) = input.first_chunk::<4>() else { return Ok(None) };
+    if !I::valid_selector(*selector) {
+        return Ok(None)
+    }
+
+    I::abi_decode(input, true).map(Some).map_err(|err| {
+        PrecompileErrors::Error(PrecompileError::Other(format!("Invalid ABI input: {err}")))
+    })
+}
+
+/// Returns the size of `bytes` of `len` bytes returned by a call, ABI-encoded.
+pub const fn abi_bytes_size(len: usize) -> usize {
+    // offset and length words, then the data padded to whole words
+    64 + len.div_ceil(32) * 32
+}
+
+#[cfg(test)]
+mod abi_tests {
+    use crate::inner::abi::{
+        abi_bytes_size, decode_call,
+        IArweaveRead::{self, IArweaveReadCalls},
+        IWvmBlockRead::IWvmBlockReadCalls,
+    };
+    use alloy_sol_types::SolCall;
+    use reth::primitives::{Bytes, B256, U256};
+
+    #[test]
+    pub fn test_decode_call() {
+        let call = IArweaveRead::readTxCall {
+            id: B256::repeat_byte(1),
+            offset: U256::from(2),
+            len: U256::from(3),
+        };
+        let input = call.abi_encode();
+        let Some(IArweaveReadCalls::readTx(decoded)) =
+            decode_call::<IArweaveReadCalls>(&input).unwrap()
+        else {
+            panic!("expected a readTx call")
+        };
+        assert_eq!((decoded.id, decoded.offset, decoded.len), (call.id, call.offset, call.len));
+
+        // the legacy string inputs are never decoded as calls
+        for input in ["bs318IdjLWQK7pF_bNIbJnpade8feD7yGAS8xIffJDI", "0;hash", ""] {
+            assert!(decode_call::<IArweaveReadCalls>(input.as_bytes()).unwrap().is_none());
+            assert!(decode_call::<IWvmBlockReadCalls>(input.as_bytes()).unwrap().is_none());
+        }
+
+        // a call with a known selector has to be valid
+        assert!(decode_call::<IArweaveReadCalls>(&input[..40]).is_err());
+    }
+
+    #[test]
+    pub fn test_abi_bytes_size() {
+        for len in [0, 1, 32, 33, 1000] {
+            let encoded =
+                IArweaveRead::readTxCall::abi_encode_returns(&(Bytes::from(vec![1; len]),));
+            assert_eq!(abi_bytes_size(len), encoded.len());
+        }
+    }
+}
@@ -1,24 +1,87 @@
-use irys::irys::IrysRequest;
-use reth::{
-    primitives::{
-        hex,
-        revm_primitives::{Precompile, PrecompileError, PrecompileOutput, PrecompileResult},
-        Bytes,
+use crate::inner::{
+    abi::{
+        decode_call,
+        IArweaveUpload::{self, IArweaveUploadCalls},
     },
-    revm::precompile::{u64_to_address, PrecompileWithAddress},
+    registry::fork_reached,
 };
-use reth_revm::{precompile::PrecompileErrors, primitives::B256};
-use std::str::FromStr;
+use alloy_sol_types::SolCall;
+use outbox::upload::{UploadRecord, UPLOAD_PRECOMPILE_ADDRESS};
+use reth::primitives::{
+    revm_primitives::{
+        Env, Precompile, PrecompileError, PrecompileOutput, PrecompileResult, StatefulPrecompile,
+    },
+    Bytes,
+};
+use reth_chainspec::ForkCondition;
+use reth_revm::precompile::PrecompileErrors;
+use std::{cell::RefCell, sync::Arc};
 
-pub const PC_ADDRESS: u64 = 0x17;
+pub const PC_ADDRESS: u64 = UPLOAD_PRECOMPILE_ADDRESS;
 pub const ARWEAVE_PC_BASE: u64 = 3_450;
 
-pub const ARWEAVE_UPLOAD_PC: Precompile = Precompile::Standard(arweave_upload);
+/// Name of the precompile in the chain spec.
+pub const ARWEAVE_UPLOAD: &str = "arweave-upload";
+
+thread_local! {
+    static UPLOAD_SESSION: RefCell<Option<Vec<UploadRecord>>> = const { RefCell::new(None) };
+}
+
+/// Restores the session that was active before a nested one, also if the execution panics.
+struct UploadSessionGuard(Option<Vec<UploadRecord>>);
+
+impl Drop for UploadSessionGuard {
+    fn drop(&mut self) {
+        UPLOAD_SESSION.set(self.0.take());
+    }
+}
+
+/// Runs `f`, collecting the payloads of every upload precompile call on this thread.
+///
+/// Only the block executor collects uploads, calls outside of a session (e.g. `eth_call`, gas
+/// estimation or payload building) are never staged.
+pub fn collect_uploads<R>(f: impl FnOnce() -> R) -> (R, Vec<UploadRecord>) {
+    let guard = UploadSessionGuard(UPLOAD_SESSION.replace(Some(vec![])));
+    let result = f();
+    let records = UPLOAD_SESSION.take().unwrap_or_default();
+    drop(guard);
+
+    (result, records)
+}
+
+/// Stages data for upload to Arweave.
+///
+/// Uploading from within block execution would make the result depend on the network, so the
+/// precompile only returns a content id derived from the call and its block context. The payload
+/// is collected by the block executor, see [`collect_uploads`], which stages it in the upload
+/// outbox of the executed block. The archive ExEx uploads it once the block is committed.
+///
+/// The input is the raw payload, returning the content id as a hex string. From the activation
+/// of ABI calls on, an input that starts with the selector of [`IArweaveUpload`] is a call
+/// returning it as `bytes32` instead.
+#[derive(Debug, Clone)]
+pub struct ArweaveUploadPrecompile {
+    /// Blocks ABI-encoded calls are accepted at.
+    abi_calls: ForkCondition,
+}
+
+impl ArweaveUploadPrecompile {
+    pub fn new(abi_calls: ForkCondition) -> Self {
+        ArweaveUploadPrecompile { abi_calls }
+    }
 
-pub const SOLANA_SILLY_PRIVATE_KEY: &str =
-    "kNykCXNxgePDjFbDWjPNvXQRa8U12Ywc19dFVaQ7tebUj3m7H4sF4KKdJwM7yxxb3rqxchdjezX9Szh8bLcQAjb";
+    pub fn into_precompile(self) -> Precompile {
+        Precompile::Stateful(Arc::new(self))
+    }
+}
 
-fn arweave_upload(input: &Bytes, gas_limit: u64) -> PrecompileResult {
+impl StatefulPrecompile for ArweaveUploadPrecompile {
+    fn call(&self, input: &Bytes, gas_limit: u64, env: &Env) -> PrecompileResult {
+        arweave_upload(input, gas_limit, env, fork_reached(&self.abi_calls, env))
+    }
+}
+
+fn arweave_upload(input: &Bytes, gas_limit: u64, env: &Env, abi_calls: bool) -> PrecompileResult {
     let data_size = input.len();
     let gas_used: u64 = (10_000 + data_size * 3) as u64;
 
@@ -26,7 +89,15 @@ fn arweave_upload(input: &Bytes, gas_limit: u64) -> PrecompileResult {
         return Err(PrecompileErrors::Error(PrecompileError::OutOfGas));
     }
 
-    if input.is_empty() {
+    // before the activation payloads that happen to start with the selector are raw payloads
+    let call = if abi_calls { decode_call::<IArweaveUploadCalls>(input)? } else { None };
+    let data = match &call {
+        Some(IArweaveUploadCalls::upload(call)) => &call.data,
+        None => input,
+    };
+    let data_size = data.len();
+
+    if data.is_empty() {
         return Err(PrecompileErrors::Error(PrecompileError::Other(
             "Data cannot be empty when uploading to arweave".to_string(),
         )));
@@ -39,37 +110,108 @@ fn arweave_upload(input: &Bytes, gas_limit: u64) -> PrecompileResult {
         )));
     }
 
-    let res = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap().block_on(
-        async {
-            IrysRequest::new()
-                .set_private_key(SOLANA_SILLY_PRIVATE_KEY.to_string())
-                .set_tag("Content-Type", "application/octet-stream")
-                .set_tag("WeaveVM:Precompile", "true")
-                .set_tag("WeaveVM:Precompile-Address", PC_ADDRESS.to_string().as_str())
-                .set_data(input.0.to_vec())
-                .send()
-                .await
-        },
+    let record = UploadRecord::new(
+        env.cfg.chain_id,
+        env.block.number.saturating_to(),
+        env.tx.caller,
+        env.tx.nonce,
+        data.to_vec(),
     );
 
-    let byte_resp = if let Ok(tx_id) = res { tx_id.into_bytes() } else { vec![] };
+    UPLOAD_SESSION.with_borrow_mut(|session| {
+        if let Some(records) = session {
+            records.push(record.clone());
+        }
+    });
+
+    let output = match call {
+        Some(_) => IArweaveUpload::uploadCall::abi_encode_returns(&(record.content_id,)),
+        None => record.content_id.to_string().into_bytes(),
+    };
 
-    let out = PrecompileOutput::new(gas_used, byte_resp.into());
+    let out = PrecompileOutput::new(gas_used, output.into());
     Ok(out)
 }
 
 #[cfg(test)]
 mod irys_pc_tests {
-    use crate::inner::arweave_precompile::{arweave_upload, SOLANA_SILLY_PRIVATE_KEY};
-    use reth::primitives::{revm_primitives::PrecompileOutput, Bytes};
-    use std::env;
+    use crate::inner::{
+        abi::IArweaveUpload,
+        arweave_precompile::{arweave_upload, collect_uploads},
+    };
+    use alloy_sol_types::SolCall;
+    use reth::primitives::{
+        revm_primitives::{Env, PrecompileOutput},
+        Address, Bytes, U256,
+    };
+
+    fn block_env(number: u64) -> Env {
+        let mut env = Env::default();
+        env.block.number = U256::from(number);
+        env.tx.caller = Address::with_last_byte(1);
+        env.tx.nonce = Some(0);
+        env
+    }
 
     #[test]
     pub fn test_arweave_precompile() {
         let input = Bytes::from("Hello world".as_bytes());
-        env::set_var("irys_pk", SOLANA_SILLY_PRIVATE_KEY);
-        let PrecompileOutput { gas_used, bytes } = arweave_upload(&input, 100_000).unwrap();
-        let tx_id = unsafe { String::from_utf8_unchecked(bytes.to_vec()) };
-        println!("{}", tx_id)
+
+        let PrecompileOutput { bytes, .. } =
+            arweave_upload(&input, 100_000, &block_env(1), true).unwrap();
+
+        // re-executing the same call yields the same id
+        let replay = arweave_upload(&input, 100_000, &block_env(1), true).unwrap();
+        assert_eq!(bytes, replay.bytes);
+        assert_eq!(bytes.len(), 66);
+
+        let next_block = arweave_upload(&input, 100_000, &block_env(2), true).unwrap();
+        assert_ne!(bytes, next_block.bytes);
+    }
+
+    #[test]
+    pub fn test_collect_uploads() {
+        let input = Bytes::from("Hello world".as_bytes());
+
+        let (output, records) = collect_uploads(|| {
+            arweave_upload(&input, 100_000, &block_env(1), true).unwrap();
+            arweave_upload(&Bytes::from("second"), 100_000, &block_env(1), true).unwrap()
+        });
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].data, b"second".to_vec());
+        assert_eq!(output.bytes.to_vec(), records[1].content_id.to_string().into_bytes());
+
+        // calls outside of a session are not collected
+        let (_, records) = collect_uploads(|| ());
+        arweave_upload(&input, 100_000, &block_env(1), true).unwrap();
+        assert!(records.is_empty());
+    }
+
+    #[test]
+    pub fn test_arweave_precompile_abi() {
+        let input = Bytes::from("Hello world".as_bytes());
+        let call = IArweaveUpload::uploadCall { data: input.clone() };
+
+        let PrecompileOutput { bytes, .. } =
+            arweave_upload(&call.abi_encode().into(), 100_000, &block_env(1), true).unwrap();
+        let content_id =
+            IArweaveUpload::uploadCall::abi_decode_returns(&bytes, true).unwrap().contentId;
+
+        // the same payload gets the same id through either interface
+        let raw = arweave_upload(&input, 100_000, &block_env(1), true).unwrap();
+        assert_eq!(raw.bytes.to_vec(), content_id.to_string().into_bytes());
+
+        // before the activation of ABI calls the encoded call is uploaded as it is
+        let (output, records) = collect_uploads(|| {
+            arweave_upload(&call.abi_encode().into(), 100_000, &block_env(1), false).unwrap()
+        });
+        assert_eq!(records[0].data, call.abi_encode());
+        assert_eq!(output.bytes.to_vec(), records[0].content_id.to_string().into_bytes());
+    }
+
+    #[test]
+    pub fn test_arweave_precompile_empty_input() {
+        let res = arweave_upload(&Bytes::new(), 100_000, &block_env(1), true);
+        assert!(res.is_err());
     }
 }
@@ -0,0 +1,244 @@
+use crate::inner::{
+    abi::{
+        decode_call,
+        IArweaveQuery::{self, IArweaveQueryCalls},
+        QueryItem,
+    },
+    gas::{ReadGasSchedule, ReadGasSchedules},
+    io::PrecompileIo,
+    sidecar::{in_read_session, oracle_read},
+    util::paid_output,
+};
+use alloy_sol_types::SolCall;
+use irys::backend::{arweave_id, PermanentStorageBackend, StoredItem, TagFilter, TagQuery};
+use reth::primitives::{
+    revm_primitives::{
+        Env, Precompile, PrecompileError, PrecompileErrors, PrecompileResult, StatefulPrecompile,
+    },
+    Bytes, U256,
+};
+use std::sync::Arc;
+
+/// Name of the precompile in read sidecars.
+pub const ARWEAVE_QUERY: &str = "arweave-query";
+
+/// Maximum number of items returned by a single query.
+pub const MAX_QUERY_ITEMS: u32 = 100;
+
+/// Finds items in the node's storage backend by their tags and owners, through the
+/// [`IArweaveQuery`] interface.
+///
+/// Items are returned newest first, at most [`MAX_QUERY_ITEMS`] per call, together with the cursor
+/// that continues the query after the last of them. Queries are charged like reads of the
+/// ABI-encoded result, see [`ReadGasSchedules`], and pinned by the read sidecars of the blocks
+/// like any other read. Results change as items are uploaded, so queries are only served while a
+/// block that commits to a read sidecar is built or validated, any other call fails, e.g. an
+/// `eth_call` or a block before the activation of read sidecars.
+#[derive(Debug, Clone)]
+pub struct ArweaveQueryPrecompile {
+    storage: Arc<dyn PermanentStorageBackend>,
+    gas: ReadGasSchedules,
+    io: PrecompileIo,
+}
+
+impl ArweaveQueryPrecompile {
+    pub fn new(
+        storage: Arc<dyn PermanentStorageBackend>,
+        gas: ReadGasSchedules,
+        io: PrecompileIo,
+    ) -> Self {
+        ArweaveQueryPrecompile { storage, gas, io }
+    }
+
+    pub fn into_precompile(self) -> Precompile {
+        Precompile::Stateful(Arc::new(self))
+    }
+}
+
+impl StatefulPrecompile for ArweaveQueryPrecompile {
+    fn call(&self, input: &Bytes, gas_limit: u64, env: &Env) -> PrecompileResult {
+        let gas = self.gas.at(env);
+        arweave_query(&self.io, &self.storage, gas, input, gas_limit)
+    }
+}
+
+/// Builds the query of a call, bounded to [`MAX_QUERY_ITEMS`].
+fn tag_query(call: IArweaveQuery::queryCall) -> TagQuery {
+    TagQuery {
+        ids: vec![],
+        owners: call.owners.iter().map(arweave_id).collect(),
+        tags: call
+            .tags
+            .into_iter()
+            .map(|tag| TagFilter { name: tag.name, values: tag.values })
+            .collect(),
+        first: Some(call.first.min(MAX_QUERY_ITEMS) as usize),
+        after: Some(call.after).filter(|after| !after.is_empty()),
+    }
+}
+
+/// ABI-encodes the items found by a query and the cursor after the last of them.
+fn encode_items(
+    storage: &dyn PermanentStorageBackend,
+    items: Vec<StoredItem>,
+) -> Result<Vec<u8>, PrecompileErrors> {
+    let cursor = items.last().and_then(|item| item.cursor.clone()).unwrap_or_default();
+    let items = items
+        .into_iter()
+        .map(|item| {
+            let Some(id) = storage.decode_id(item.id.as_str()) else {
+                return Err(PrecompileErrors::Error(PrecompileError::Other(format!(
+                    "Invalid Arweave id {}",
+                    item.id
+                ))))
+            };
+            Ok(QueryItem { id, size: U256::from(item.size) })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(IArweaveQuery::queryCall::abi_encode_returns(&(items, cursor)))
+}
+
+fn arweave_query(
+    io: &PrecompileIo,
+    storage: &Arc<dyn PermanentStorageBackend>,
+    gas: ReadGasSchedule,
+    input: &Bytes,
+    gas_limit: u64,
+) -> PrecompileResult {
+    if gas.input_gas(input.len()) > gas_limit {
+        return Err(PrecompileErrors::Error(PrecompileError::OutOfGas));
+    }
+
+    if !in_read_session() {
+        return Err(PrecompileErrors::Error(PrecompileError::Other(
+            "Queries are only served in blocks with a read sidecar".to_string(),
+        )))
+    }
+
+    let Some(IArweaveQueryCalls::query(call)) = decode_call::<IArweaveQueryCalls>(input)? else {
+        return Err(PrecompileErrors::Error(PrecompileError::Other(
+            "A query call must be provided".to_string(),
+        )))
+    };
+    let query = tag_query(call);
+    let input_len = input.len();
+    let read_gas = move |output: &[u8]| gas.read_gas(input_len, output.len());
+
+    oracle_read(ARWEAVE_QUERY, input, read_gas, || {
+        let items = {
+            let storage = storage.clone();
+            io.run(async move { storage.query(&query).await })?
+        };
+        let items = items.map_err(|err| {
+            PrecompileErrors::Error(PrecompileError::Other(format!("Arweave query failed: {err}")))
+        })?;
+
+        let output = encode_items(storage.as_ref(), items)?;
+        paid_output(read_gas(&output), output, gas_limit)
+    })
+}
+
+#[cfg(test)]
+mod arweave_query_pc_tests {
+    use crate::{
+        inner::{
+            abi::{IArweaveQuery, TagFilter},
+            arweave_query_precompile::{arweave_query, MAX_QUERY_ITEMS},
+            gas::ReadGasSchedule,
+            sidecar::record_reads,
+        },
+        test_utils::io,
+    };
+    use alloy_sol_types::SolCall;
+    use irys::{
+        backend::{PermanentStorageBackend, Tag},
+        local::LocalStorage,
+    };
+    use reth::primitives::{
+        revm_primitives::{PrecompileError, PrecompileErrors, PrecompileResult},
+        Bytes, B256, U256,
+    };
+    use std::sync::Arc;
+
+    const LEGACY: ReadGasSchedule = ReadGasSchedule::LEGACY;
+
+    /// Queries `storage` while a block is built.
+    fn query(
+        storage: &Arc<dyn PermanentStorageBackend>,
+        gas: ReadGasSchedule,
+        input: &Bytes,
+        gas_limit: u64,
+    ) -> PrecompileResult {
+        record_reads(|| arweave_query(&io(), storage, gas, input, gas_limit)).0
+    }
+
+    fn query_call(app: &str, first: u32, after: String) -> Bytes {
+        let tags = vec![TagFilter { name: "App-Name".to_string(), values: vec![app.to_string()] }];
+        IArweaveQuery::queryCall { tags, owners: vec![], first, after }.abi_encode().into()
+    }
+
+    #[tokio::test]
+    pub async fn test_arweave_query_precompile() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage: Arc<dyn PermanentStorageBackend> =
+            Arc::new(LocalStorage::new(dir.path()).unwrap());
+        let mut ids = vec![];
+        for i in 0..3 {
+            let tags = vec![Tag::new("App-Name", "test"), Tag::new("Index", &i.to_string())];
+            ids.push(storage.upload(vec![i; 10], tags).await.unwrap());
+        }
+        storage.upload(vec![], vec![Tag::new("App-Name", "other")]).await.unwrap();
+        // newest first
+        let ids: Vec<B256> = ids.iter().rev().map(|id| storage.decode_id(id).unwrap()).collect();
+
+        let output = query(&storage, LEGACY, &query_call("test", 2, "".into()), 100_000).unwrap();
+        let page = IArweaveQuery::queryCall::abi_decode_returns(&output.bytes, true).unwrap();
+        let found: Vec<_> = page.items.iter().map(|item| (item.id, item.size)).collect();
+        assert_eq!(found, vec![(ids[0], U256::from(10)), (ids[1], U256::from(10))]);
+
+        let output = query(&storage, LEGACY, &query_call("test", 2, page.cursor), 100_000).unwrap();
+        let page = IArweaveQuery::queryCall::abi_decode_returns(&output.bytes, true).unwrap();
+        let found: Vec<_> = page.items.iter().map(|item| item.id).collect();
+        assert_eq!(found, vec![ids[2]]);
+
+        let output = query(&storage, LEGACY, &query_call("test", 2, page.cursor), 100_000).unwrap();
+        let page = IArweaveQuery::queryCall::abi_decode_returns(&output.bytes, true).unwrap();
+        assert!(page.items.is_empty());
+        assert!(page.cursor.is_empty());
+    }
+
+    #[tokio::test]
+    pub async fn test_arweave_query_precompile_bounded() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage: Arc<dyn PermanentStorageBackend> =
+            Arc::new(LocalStorage::new(dir.path()).unwrap());
+        for i in 0..MAX_QUERY_ITEMS + 1 {
+            let tags = vec![Tag::new("App-Name", "test"), Tag::new("Index", &i.to_string())];
+            storage.upload(vec![], tags).await.unwrap();
+        }
+
+        let input = query_call("test", u32::MAX, "".into());
+        let output = query(&storage, LEGACY, &input, 100_000).unwrap();
+        let page = IArweaveQuery::queryCall::abi_decode_returns(&output.bytes, true).unwrap();
+        assert_eq!(page.items.len(), MAX_QUERY_ITEMS as usize);
+
+        // the result is charged by its size
+        let gas = ReadGasSchedule { base: 10_000, per_input_byte: 3, per_word: 16 };
+        let expected = gas.read_gas(input.len(), output.bytes.len());
+        assert_eq!(query(&storage, gas, &input, expected).unwrap().gas_used, expected);
+        assert!(matches!(
+            query(&storage, gas, &input, expected - 1),
+            Err(PrecompileErrors::Error(PrecompileError::OutOfGas))
+        ));
+
+        // queries are only served in blocks with a read sidecar
+        assert!(matches!(
+            arweave_query(&io(), &storage, LEGACY, &input, 100_000),
+            Err(PrecompileErrors::Error(PrecompileError::Other(_)))
+        ));
+
+        // only query calls are accepted
+        assert!(query(&storage, LEGACY, &Bytes::from("test".as_bytes()), 100_000).is_err());
+    }
+}
@@ -1,46 +1,80 @@
-use crate::inner::graphql_util::send_graphql;
-use crate::inner::util::{clean_gateway_url, download_tx, DEFAULT_ARWEAVE_TX_ENDPOINT};
+use crate::inner::{
+    abi::{
+        abi_bytes_size, decode_call,
+        IArweaveRead::{self, IArweaveReadCalls},
+    },
+    gas::{ReadGasSchedule, ReadGasSchedules},
+    io::PrecompileIo,
+    read_cache::ReadCache,
+    registry::fork_reached,
+    sidecar::oracle_read,
+    util::{is_default_gateway, paid_output, ReadGateways, DEFAULT_ARWEAVE_TX_ENDPOINT},
+};
+use alloy_sol_types::SolCall;
+use irys::backend::{data_range, PermanentStorageBackend, TagQuery};
 use reth::primitives::{
-    revm_primitives::{Precompile, PrecompileError, PrecompileErrors, PrecompileResult},
-    Bytes,
+    revm_primitives::{
+        Env, Precompile, PrecompileError, PrecompileErrors, PrecompileResult, StatefulPrecompile,
+    },
+    Bytes, B256, U256,
 };
-use serde::{Deserialize, Serialize};
-
-pub const ARWEAVE_PC_READ_BASE: u64 = 10_000;
+use reth_chainspec::ForkCondition;
+use std::{ops::Range, sync::Arc};
 
 pub const TX_MAX_SIZE: usize = 18_874_368; // 18MB
 
-pub const ARWEAVE_READ_PC: Precompile = Precompile::Standard(arweave_read);
-
-#[derive(Debug, Serialize, Deserialize)]
-struct Response {
-    data: Data,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-struct Data {
-    transactions: Transactions,
-}
+/// Name of the precompile in read sidecars.
+pub const ARWEAVE_READ: &str = "arweave-read";
 
-#[derive(Debug, Serialize, Deserialize)]
-struct Transactions {
-    edges: Vec<Edge>,
+/// Reads the data of an Arweave transaction from the node's storage backend, or from one of the
+/// [`ReadGateways`] allowed by the chain spec at the block, given in the input. Before the
+/// allow-list is activated any gateway is read from.
+///
+/// The input is either a `gateway;txid` string, returning the raw data, or, from the activation of
+/// ABI calls on, an ABI-encoded call of [`IArweaveRead`], returning a range of the data
+/// ABI-encoded.
+///
+/// Reads are charged by the size of the returned data, see [`ReadGasSchedules`]. Reads from the
+/// node's storage backend go through the [`ReadCache`]. Data read from a custom
+/// gateway is never cached, so a gateway can not change what other reads of the same id return.
+/// Gateways verify layer 1 transactions against their signed header, data items of bundles can't
+/// be verified and are only read from the node's storage backend.
+#[derive(Debug, Clone)]
+pub struct ArweaveReadPrecompile {
+    storage: Arc<dyn PermanentStorageBackend>,
+    gateways: ReadGateways,
+    cache: ReadCache,
+    gas: ReadGasSchedules,
+    io: PrecompileIo,
+    /// Blocks ABI-encoded calls are accepted at.
+    abi_calls: ForkCondition,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct Edge {
-    node: Node,
-}
+impl ArweaveReadPrecompile {
+    pub fn new(
+        storage: Arc<dyn PermanentStorageBackend>,
+        gateways: ReadGateways,
+        cache: ReadCache,
+        gas: ReadGasSchedules,
+        io: PrecompileIo,
+        abi_calls: ForkCondition,
+    ) -> Self {
+        ArweaveReadPrecompile { storage, gateways, cache, gas, io, abi_calls }
+    }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct Node {
-    id: String,
-    data: NodeData,
+    pub fn into_precompile(self) -> Precompile {
+        Precompile::Stateful(Arc::new(self))
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct NodeData {
-    size: String,
+impl StatefulPrecompile for ArweaveReadPrecompile {
+    fn call(&self, input: &Bytes, gas_limit: u64, env: &Env) -> PrecompileResult {
+        let gas = self.gas.at(env);
+        let abi_calls = fork_reached(&self.abi_calls, env);
+        let gateways = self.gateways.active_at(env);
+        let Self { io, storage, cache, .. } = self;
+        arweave_read(io, storage, &gateways, cache, gas, input, gas_limit, abi_calls)
+    }
 }
 
 pub fn parse_gateway_content(input: &str) -> (String, String) {
@@ -54,89 +88,654 @@ pub fn parse_gateway_content(input: &str) -> (String, String) {
     (endpoint.to_string(), second_part.to_string())
 }
 
-fn arweave_read(input: &Bytes, gas_limit: u64) -> PrecompileResult {
-    let data_size = input.len();
-    let gas_used: u64 = (ARWEAVE_PC_READ_BASE as usize + data_size * 3) as u64;
+/// Output of a read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ReadOutput {
+    /// The raw data, for legacy string inputs.
+    Raw,
+    /// A range of the data, ABI-encoded as `bytes`. The range is clamped to the data.
+    Abi(Range<usize>),
+}
 
-    if gas_used > gas_limit {
-        return Err(PrecompileErrors::Error(PrecompileError::OutOfGas));
+impl ReadOutput {
+    fn abi(offset: U256, len: U256) -> Self {
+        let start = offset.saturating_to::<usize>();
+        ReadOutput::Abi(start..start.saturating_add(len.saturating_to()))
     }
 
-    if input.is_empty() {
-        return Err(PrecompileErrors::Error(PrecompileError::Other(
-            "Arweave Transaction Id cannot be empty".to_string(),
-        )));
-    }
-
-    let id_str = unsafe { String::from_utf8(input.0.to_vec()) };
-
-    let res = match id_str {
-        Ok(id) => {
-            let (gateway, tx_id) = parse_gateway_content(id.as_str());
-            tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap().block_on(
-                async {
-                    let clean_gateway = clean_gateway_url(gateway.as_str());
-                    let query = {
-                        let mut query = "{\n  transactions(ids: [\"$id\"]) {\n    edges {\n      node {\n        id\n        data {\n          size\n        }\n      }\n    }\n  }\n}\n";
-                        let query = query.replace("$id", tx_id.as_str());
-                        query
-                    };
-                    let data = send_graphql(clean_gateway.as_str(), query.as_str()).await;
-
-                    let tx_size = if let Ok(data) = data {
-                        let resp = data.data;
-                        let tx = resp.transactions.edges.get(0);
-                        if let Some(&ref tx) = tx {
-                            let tx_size = tx.clone().node.data.size;
-                            let tx_size = tx_size.parse::<usize>().unwrap();
-                            tx_size
-                        } else {
-                            0
-                        }
-                    } else {
-                        0
-                    };
-
-                    if TX_MAX_SIZE >= tx_size {
-                        download_tx(gas_used, clean_gateway, tx_id).await
-                    } else {
-                        Err(PrecompileErrors::Error(PrecompileError::Other(
-                            "Arweave Transaction size is greater than allowed (18mb)".to_string(),
-                        )))
-                    }
-                },
-            )
+    /// Returns the range of the data that is read.
+    fn range(&self) -> Range<usize> {
+        match self {
+            ReadOutput::Raw => 0..usize::MAX,
+            ReadOutput::Abi(range) => range.clone(),
+        }
+    }
+
+    /// Returns the size of the output for `len` bytes read.
+    fn size(&self, len: usize) -> usize {
+        match self {
+            ReadOutput::Raw => len,
+            ReadOutput::Abi(_) => abi_bytes_size(len),
+        }
+    }
+
+    /// Encodes the bytes read.
+    fn encode(&self, data: Vec<u8>) -> Vec<u8> {
+        match self {
+            ReadOutput::Raw => data,
+            ReadOutput::Abi(_) => IArweaveRead::readTxCall::abi_encode_returns(&(data.into(),)),
+        }
+    }
+}
+
+/// Id of the transaction a read is for.
+#[derive(Debug)]
+enum TxId {
+    /// The raw id passed to an ABI call, encoded by the storage the read goes to.
+    Raw(B256),
+    Encoded(String),
+}
+
+/// A read, decoded from the input of the precompile.
+#[derive(Debug)]
+struct TxRead {
+    gateway: String,
+    tx_id: TxId,
+    output: ReadOutput,
+}
+
+impl TxRead {
+    fn decode(input: &Bytes, abi_calls: bool) -> Result<Self, PrecompileErrors> {
+        let call = if abi_calls { decode_call::<IArweaveReadCalls>(input)? } else { None };
+        match call {
+            Some(IArweaveReadCalls::readTx(call)) => {
+                return Ok(TxRead {
+                    gateway: DEFAULT_ARWEAVE_TX_ENDPOINT.to_string(),
+                    tx_id: TxId::Raw(call.id),
+                    output: ReadOutput::abi(call.offset, call.len),
+                })
+            }
+            Some(IArweaveReadCalls::readTxFrom(call)) => {
+                return Ok(TxRead {
+                    gateway: call.gateway,
+                    tx_id: TxId::Raw(call.id),
+                    output: ReadOutput::abi(call.offset, call.len),
+                })
+            }
+            None => {}
+        }
+
+        if input.is_empty() {
+            return Err(PrecompileErrors::Error(PrecompileError::Other(
+                "Arweave Transaction Id cannot be empty".to_string(),
+            )));
         }
-        Err(_) => Err(PrecompileErrors::Error(PrecompileError::Other(
-            "Transaction id could not be parsed".to_string(),
-        ))),
+
+        let Ok(id) = String::from_utf8(input.to_vec()) else {
+            return Err(PrecompileErrors::Error(PrecompileError::Other(
+                "Transaction id could not be parsed".to_string(),
+            )))
+        };
+        let (gateway, tx_id) = parse_gateway_content(id.as_str());
+
+        Ok(TxRead { gateway, tx_id: TxId::Encoded(tx_id), output: ReadOutput::Raw })
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn arweave_read(
+    io: &PrecompileIo,
+    storage: &Arc<dyn PermanentStorageBackend>,
+    gateways: &ReadGateways,
+    cache: &ReadCache,
+    gas: ReadGasSchedule,
+    input: &Bytes,
+    gas_limit: u64,
+    abi_calls: bool,
+) -> PrecompileResult {
+    if gas.input_gas(input.len()) > gas_limit {
+        return Err(PrecompileErrors::Error(PrecompileError::OutOfGas));
+    }
+
+    let TxRead { gateway, tx_id, output } = TxRead::decode(input, abi_calls)?;
+    let storage = gateways.storage(storage, gateway.as_str())?;
+    let tx_id = match tx_id {
+        TxId::Raw(raw) => storage.encode_id(&raw),
+        TxId::Encoded(tx_id) => tx_id,
     };
+    let cache =
+        if is_default_gateway(gateway.as_str()) { cache.clone() } else { ReadCache::disabled() };
+    let input_len = input.len();
+    let read_gas = move |output: &[u8]| gas.read_gas(input_len, output.len());
+
+    oracle_read(ARWEAVE_READ, input, read_gas, || {
+        let range = output.range();
+        if let Some(data) = cache.get(tx_id.as_str()) {
+            let data = output.encode(data_range(data, range));
+            return paid_output(read_gas(&data), data, gas_limit)
+        }
+
+        let id = tx_id.clone();
+        let read = range.clone();
+        let output_size = output.clone();
+        let (data, whole) = io.run(async move {
+            // the size is looked up first, so the data is only transferred once it is paid for
+            let tx_size = storage
+                .query(&TagQuery::new().with_id(id.as_str()))
+                .await
+                .map_err(query_error)?
+                .first()
+                .map(|item| item.size);
+            if tx_size.is_none() && gas.is_size_priced() {
+                return Err(tx_not_found())
+            }
+
+            // only the range that is read is transferred and paid for
+            let read_size = tx_size.map_or(0, |size| read.end.min(size).saturating_sub(read.start));
+            if read_size > TX_MAX_SIZE {
+                return Err(tx_too_large())
+            }
+            if gas.read_gas(input_len, output_size.size(read_size)) > gas_limit {
+                return Err(PrecompileErrors::Error(PrecompileError::OutOfGas))
+            }
+
+            let data = match tx_size {
+                Some(size) if read.start > 0 || read.end < size => {
+                    storage.fetch_range(id.as_str(), read).await.map(|data| (data, false))
+                }
+                _ => storage.fetch(id.as_str()).await.map(|data| (data, true)),
+            };
+            let (data, whole) = data.map_err(|_| tx_not_found())?;
+            // data of an unknown size is only checked once it is there
+            if tx_size.is_none() && data.len() > TX_MAX_SIZE {
+                return Err(tx_too_large())
+            }
+
+            Ok((data, whole))
+        })??;
+
+        let data = if whole {
+            // the cache is best effort, the read succeeded either way
+            let _ = cache.insert(tx_id.as_str(), data.as_slice());
+            data_range(data, range)
+        } else {
+            data_range(data, 0..range.len())
+        };
 
-    res
+        // the storage may return more than it announced
+        let data = output.encode(data);
+        paid_output(read_gas(&data), data, gas_limit)
+    })
+}
+
+fn tx_not_found() -> PrecompileErrors {
+    PrecompileErrors::Error(PrecompileError::Other("Arweave Transaction was not found".to_string()))
+}
+
+fn tx_too_large() -> PrecompileErrors {
+    PrecompileErrors::Error(PrecompileError::Other(
+        "Arweave read size is greater than allowed (18mb)".to_string(),
+    ))
+}
+
+/// The node failed to look up the transaction, which says nothing about the call itself, so the
+/// block is not executed instead of failing the call, like on a timeout of the [`PrecompileIo`].
+fn query_error(err: eyre::Report) -> PrecompileErrors {
+    PrecompileErrors::Fatal { msg: format!("Looking up the Arweave transaction failed: {err}") }
 }
 
 #[cfg(test)]
 mod arweave_read_pc_tests {
-    use crate::inner::arweave_read_precompile::{arweave_read, parse_gateway_content};
-    use reth::primitives::{revm_primitives::PrecompileOutput, Bytes};
+    use crate::{
+        inner::{
+            abi::IArweaveRead,
+            arweave_read_precompile::{arweave_read, parse_gateway_content, TX_MAX_SIZE},
+            gas::ReadGasSchedule,
+            read_cache::ReadCache,
+            util::{AllowedGateway, ReadGateways},
+        },
+        test_utils::io,
+    };
+    use alloy_sol_types::SolCall;
+    use irys::{
+        backend::{PermanentStorageBackend, StoredItem, Tag, TagQuery},
+        irys::IrysProvider,
+        local::LocalStorage,
+    };
+    use reth::primitives::{
+        revm_primitives::{PrecompileError, PrecompileErrors, PrecompileOutput},
+        Bytes, B256, U256,
+    };
+    use reth_chainspec::ForkCondition;
+    use std::{str::FromStr, sync::Arc};
+
+    const LEGACY: ReadGasSchedule = ReadGasSchedule::LEGACY;
+
+    /// Storage that doesn't know the size of its items, or fails to look them up.
+    #[derive(Debug)]
+    struct UnindexedStorage {
+        inner: LocalStorage,
+        failing: bool,
+    }
+
+    #[async_trait::async_trait]
+    impl PermanentStorageBackend for UnindexedStorage {
+        async fn upload(&self, data: Vec<u8>, tags: Vec<Tag>) -> eyre::Result<String> {
+            self.inner.upload(data, tags).await
+        }
+
+        async fn fetch(&self, id: &str) -> eyre::Result<Vec<u8>> {
+            self.inner.fetch(id).await
+        }
+
+        async fn query(&self, _query: &TagQuery) -> eyre::Result<Vec<StoredItem>> {
+            if self.failing {
+                return Err(eyre::eyre!("gateway unavailable"))
+            }
+            Ok(vec![])
+        }
+    }
+
+    #[test]
+    pub fn test_arweave_read_precompile_local_storage() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage: Arc<dyn PermanentStorageBackend> =
+            Arc::new(LocalStorage::new(dir.path()).unwrap());
+        let tx_id = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(storage.upload(b"Hello world".to_vec(), vec![Tag::new("Test", "true")]))
+            .unwrap();
+
+        let input = Bytes::from(tx_id.into_bytes());
+        let PrecompileOutput { bytes, .. } = arweave_read(
+            &io(),
+            &storage,
+            &ReadGateways::default(),
+            &ReadCache::disabled(),
+            LEGACY,
+            &input,
+            100_000,
+            true,
+        )
+        .unwrap();
+        assert_eq!(bytes.len(), 11);
+        assert_eq!(bytes.to_vec(), "Hello world".as_bytes().to_vec());
+    }
+
+    #[test]
+    pub fn test_arweave_read_precompile_cached() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage: Arc<dyn PermanentStorageBackend> =
+            Arc::new(LocalStorage::new(dir.path().join("storage")).unwrap());
+        let tx_id = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(storage.upload(b"Hello world".to_vec(), vec![]))
+            .unwrap();
+        let cache = ReadCache::new(&dir.path().join("cache"), Default::default()).unwrap();
+
+        let input = Bytes::from(tx_id.into_bytes());
+        arweave_read(
+            &io(),
+            &storage,
+            &ReadGateways::default(),
+            &cache,
+            LEGACY,
+            &input,
+            100_000,
+            true,
+        )
+        .unwrap();
+
+        // served from the cache once read, even if the storage no longer has it
+        let empty: Arc<dyn PermanentStorageBackend> =
+            Arc::new(LocalStorage::new(dir.path().join("empty")).unwrap());
+        let PrecompileOutput { bytes, .. } = arweave_read(
+            &io(),
+            &empty,
+            &ReadGateways::default(),
+            &cache,
+            LEGACY,
+            &input,
+            100_000,
+            true,
+        )
+        .unwrap();
+        assert_eq!(bytes.to_vec(), "Hello world".as_bytes().to_vec());
+    }
+
+    #[test]
+    pub fn test_arweave_read_precompile_size_priced() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage: Arc<dyn PermanentStorageBackend> =
+            Arc::new(LocalStorage::new(dir.path()).unwrap());
+        let tx_id = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(storage.upload(vec![1; 1000], vec![]))
+            .unwrap();
+        let gas = ReadGasSchedule { base: 10_000, per_input_byte: 3, per_word: 100 };
+
+        // 32 words of data
+        let input = Bytes::from(tx_id.into_bytes());
+        let expected = gas.input_gas(input.len()) + 32 * 100;
+        let PrecompileOutput { gas_used, bytes } = arweave_read(
+            &io(),
+            &storage,
+            &ReadGateways::default(),
+            &ReadCache::disabled(),
+            gas,
+            &input,
+            expected,
+            true,
+        )
+        .unwrap();
+        assert_eq!(gas_used, expected);
+        assert_eq!(bytes.len(), 1000);
+
+        // the size is known before the download
+        assert_eq!(
+            arweave_read(
+                &io(),
+                &storage,
+                &ReadGateways::default(),
+                &ReadCache::disabled(),
+                gas,
+                &input,
+                expected - 1,
+                true
+            ),
+            Err(PrecompileErrors::Error(PrecompileError::OutOfGas))
+        );
+    }
+
+    #[test]
+    pub fn test_arweave_read_precompile_abi() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage: Arc<dyn PermanentStorageBackend> =
+            Arc::new(LocalStorage::new(dir.path()).unwrap());
+        let tx_id = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(storage.upload(b"Hello world".to_vec(), vec![]))
+            .unwrap();
+        let id = B256::from_str(tx_id.as_str()).unwrap();
+
+        let read = |offset: U256, len: U256| {
+            let call = IArweaveRead::readTxCall { id, offset, len };
+            let input = Bytes::from(call.abi_encode());
+            let PrecompileOutput { bytes, .. } = arweave_read(
+                &io(),
+                &storage,
+                &ReadGateways::default(),
+                &ReadCache::disabled(),
+                LEGACY,
+                &input,
+                100_000,
+                true,
+            )
+            .unwrap();
+            IArweaveRead::readTxCall::abi_decode_returns(&bytes, true).unwrap().data
+        };
+        assert_eq!(read(U256::ZERO, U256::MAX).as_ref(), b"Hello world");
+        assert_eq!(read(U256::from(6), U256::from(100)).as_ref(), b"world");
+        assert_eq!(read(U256::from(0), U256::from(5)).as_ref(), b"Hello");
+        assert!(read(U256::MAX, U256::MAX).is_empty());
+
+        // before the activation of ABI calls the call is read as a string input
+        let call = IArweaveRead::readTxCall { id, offset: U256::ZERO, len: U256::MAX };
+        assert!(arweave_read(
+            &io(),
+            &storage,
+            &ReadGateways::default(),
+            &ReadCache::disabled(),
+            LEGACY,
+            &Bytes::from(call.abi_encode()),
+            100_000,
+            false,
+        )
+        .is_err());
+    }
+
+    #[test]
+    pub fn test_arweave_read_precompile_abi_partial() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage: Arc<dyn PermanentStorageBackend> =
+            Arc::new(LocalStorage::new(dir.path().join("storage")).unwrap());
+        let tx_id = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(storage.upload(b"Hello world".to_vec(), vec![]))
+            .unwrap();
+        let cache = ReadCache::new(&dir.path().join("cache"), Default::default()).unwrap();
+        let id = B256::from_str(tx_id.as_str()).unwrap();
+
+        let read = |offset: u64, len: U256| {
+            let call = IArweaveRead::readTxCall { id, offset: U256::from(offset), len };
+            let input = Bytes::from(call.abi_encode());
+            let PrecompileOutput { bytes, .. } = arweave_read(
+                &io(),
+                &storage,
+                &ReadGateways::default(),
+                &cache,
+                LEGACY,
+                &input,
+                100_000,
+                true,
+            )
+            .unwrap();
+            IArweaveRead::readTxCall::abi_decode_returns(&bytes, true).unwrap().data
+        };
+
+        // only the range is transferred, so there is nothing to cache
+        assert_eq!(read(6, U256::from(5)).as_ref(), b"world");
+        assert_eq!(cache.stats().entries, 0);
+
+        assert_eq!(read(0, U256::MAX).as_ref(), b"Hello world");
+        assert_eq!(cache.stats().entries, 1);
+        assert_eq!(read(6, U256::from(5)).as_ref(), b"world");
+    }
 
     #[test]
+    pub fn test_arweave_read_precompile_abi_size_priced() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage: Arc<dyn PermanentStorageBackend> =
+            Arc::new(LocalStorage::new(dir.path()).unwrap());
+        let tx_id = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(storage.upload(vec![1; 1000], vec![]))
+            .unwrap();
+        let gas = ReadGasSchedule { base: 10_000, per_input_byte: 3, per_word: 100 };
+
+        // only the returned range is charged, 2 words of offset and length and 1 word of data
+        let call = IArweaveRead::readTxCall {
+            id: B256::from_str(tx_id.as_str()).unwrap(),
+            offset: U256::from(100),
+            len: U256::from(32),
+        };
+        let input = Bytes::from(call.abi_encode());
+        let expected = gas.input_gas(input.len()) + 3 * 100;
+        let PrecompileOutput { gas_used, .. } = arweave_read(
+            &io(),
+            &storage,
+            &ReadGateways::default(),
+            &ReadCache::disabled(),
+            gas,
+            &input,
+            expected,
+            true,
+        )
+        .unwrap();
+        assert_eq!(gas_used, expected);
+        assert_eq!(
+            arweave_read(
+                &io(),
+                &storage,
+                &ReadGateways::default(),
+                &ReadCache::disabled(),
+                gas,
+                &input,
+                expected - 1,
+                true
+            ),
+            Err(PrecompileErrors::Error(PrecompileError::OutOfGas))
+        );
+    }
+
+    #[tokio::test]
+    pub async fn test_arweave_read_precompile_in_async_context() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage: Arc<dyn PermanentStorageBackend> =
+            Arc::new(LocalStorage::new(dir.path()).unwrap());
+        let tx_id = storage.upload(b"Hello world".to_vec(), vec![]).await.unwrap();
+
+        // block execution may run on a runtime, precompiles must not start a nested one
+        let input = Bytes::from(tx_id.into_bytes());
+        let PrecompileOutput { bytes, .. } = arweave_read(
+            &io(),
+            &storage,
+            &ReadGateways::default(),
+            &ReadCache::disabled(),
+            LEGACY,
+            &input,
+            100_000,
+            true,
+        )
+        .unwrap();
+        assert_eq!(bytes.to_vec(), "Hello world".as_bytes().to_vec());
+    }
+
+    #[test]
+    pub fn test_arweave_read_precompile_unknown_tx() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage: Arc<dyn PermanentStorageBackend> =
+            Arc::new(LocalStorage::new(dir.path()).unwrap());
+
+        let input = Bytes::from("bs318IdjLWQK7pF_bNIbJnpade8feD7yGAS8xIffJDI".as_bytes());
+        assert!(arweave_read(
+            &io(),
+            &storage,
+            &ReadGateways::default(),
+            &ReadCache::disabled(),
+            LEGACY,
+            &input,
+            100_000,
+            true
+        )
+        .is_err());
+    }
+
+    #[test]
+    pub fn test_arweave_read_precompile_unknown_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let unindexed = |failing| -> Arc<dyn PermanentStorageBackend> {
+            Arc::new(UnindexedStorage { inner: LocalStorage::new(dir.path()).unwrap(), failing })
+        };
+        let upload = |data: Vec<u8>| {
+            tokio::runtime::Builder::new_current_thread()
+                .build()
+                .unwrap()
+                .block_on(unindexed(false).upload(data, vec![]))
+                .unwrap()
+        };
+        let read = |storage: &Arc<dyn PermanentStorageBackend>, tx_id: &str| {
+            arweave_read(
+                &io(),
+                storage,
+                &ReadGateways::default(),
+                &ReadCache::disabled(),
+                LEGACY,
+                &Bytes::from(tx_id.to_string().into_bytes()),
+                100_000,
+                true,
+            )
+        };
+
+        // legacy reads of an unknown size are checked once they are fetched
+        let small = upload(b"Hello world".to_vec());
+        assert_eq!(read(&unindexed(false), &small).unwrap().bytes, Bytes::from("Hello world"));
+        let large = upload(vec![0; TX_MAX_SIZE + 1]);
+        assert!(matches!(
+            read(&unindexed(false), &large),
+            Err(PrecompileErrors::Error(PrecompileError::Other(_)))
+        ));
+
+        // a failed lookup says nothing about the transaction, the block is not executed
+        assert!(matches!(read(&unindexed(true), &small), Err(PrecompileErrors::Fatal { .. })));
+    }
+
+    #[test]
+    #[ignore = "requires access to arweave.net"]
     pub fn test_arweave_read_precompile() {
+        let storage: Arc<dyn PermanentStorageBackend> = Arc::new(IrysProvider::new());
         let input = Bytes::from("bs318IdjLWQK7pF_bNIbJnpade8feD7yGAS8xIffJDI".as_bytes());
-        let PrecompileOutput { gas_used, bytes } = arweave_read(&input, 100_000).unwrap();
+        let PrecompileOutput { gas_used, bytes } = arweave_read(
+            &io(),
+            &storage,
+            &ReadGateways::default(),
+            &ReadCache::disabled(),
+            LEGACY,
+            &input,
+            100_000,
+            true,
+        )
+        .unwrap();
         assert_eq!(bytes.len(), 11);
         assert_eq!(bytes.to_vec(), "Hello world".as_bytes().to_vec());
     }
 
     #[test]
+    #[ignore = "requires access to ar-io.dev"]
     pub fn test_arweave_read_precompile_custom_gateway() {
+        let storage: Arc<dyn PermanentStorageBackend> = Arc::new(IrysProvider::new());
+        let gateways =
+            ReadGateways::new(&[AllowedGateway::new("https://ar-io.dev", ForkCondition::Block(0))]);
         let input =
             Bytes::from("https://ar-io.dev;bs318IdjLWQK7pF_bNIbJnpade8feD7yGAS8xIffJDI".as_bytes());
-        let PrecompileOutput { gas_used, bytes } = arweave_read(&input, 100_000).unwrap();
+        let PrecompileOutput { gas_used, bytes } = arweave_read(
+            &io(),
+            &storage,
+            &gateways,
+            &ReadCache::disabled(),
+            LEGACY,
+            &input,
+            100_000,
+            true,
+        )
+        .unwrap();
         assert_eq!(bytes.len(), 11);
         assert_eq!(bytes.to_vec(), "Hello world".as_bytes().to_vec());
     }
 
+    #[test]
+    pub fn test_arweave_read_precompile_gateway_not_allowed() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage: Arc<dyn PermanentStorageBackend> =
+            Arc::new(LocalStorage::new(dir.path()).unwrap());
+
+        // custom gateways are rejected before anything is requested from them
+        let input = Bytes::from(
+            "http://localhost:1;bs318IdjLWQK7pF_bNIbJnpade8feD7yGAS8xIffJDI".as_bytes(),
+        );
+        let err = arweave_read(
+            &io(),
+            &storage,
+            &ReadGateways::default(),
+            &ReadCache::disabled(),
+            LEGACY,
+            &input,
+            100_000,
+            true,
+        );
+        assert!(matches!(
+            err,
+            Err(PrecompileErrors::Error(PrecompileError::Other(message)))
+                if message == "Gateway http://localhost:1 is not allowed"
+        ));
+    }
+
     #[test]
     pub fn test_parse_url() {
         let input = "http://arweave-custom.net;bs318IdjLWQK7pF_bNIbJnpade8feD7yGAS8xIffJDI";
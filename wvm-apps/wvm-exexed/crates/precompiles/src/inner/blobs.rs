@@ -0,0 +1,311 @@
+use eyre::{bail, eyre};
+use reth::primitives::{
+    eip4844::kzg_to_versioned_hash,
+    kzg::{Blob, Bytes48, KzgProof, KzgSettings},
+    Bytes, B256,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{
+    fmt::Debug,
+    path::{Path, PathBuf},
+};
+
+pub const KYVE_API_URL: &str = "https://data.services.kyve.network";
+
+/// First Ethereum block with blob sidecars in the KYVE data pipeline.
+pub const KYVE_FIRST_BLOCK: u64 = 19_426_589;
+
+/// Directory of the local blob archive below the node's datadir.
+pub const LOCAL_BLOBS_DIR: &str = "wvm/blobs";
+
+/// A blob of an Ethereum block with its KZG commitment and proof.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlobSidecar {
+    pub blob: Bytes,
+    pub kzg_commitment: Bytes,
+    pub kzg_proof: Bytes,
+}
+
+impl BlobSidecar {
+    /// Verifies that `kzg_proof` proves `blob` to be committed to by `kzg_commitment`.
+    ///
+    /// This does not prove that the blob is part of an Ethereum block.
+    pub fn verify(&self, settings: &KzgSettings) -> eyre::Result<()> {
+        let kzg_error = |err| eyre!("invalid blob sidecar: {err:?}");
+        let blob = Blob::from_bytes(&self.blob).map_err(kzg_error)?;
+        let commitment = Bytes48::from_bytes(&self.kzg_commitment).map_err(kzg_error)?;
+        let proof = Bytes48::from_bytes(&self.kzg_proof).map_err(kzg_error)?;
+
+        if !KzgProof::verify_blob_kzg_proof(&blob, &commitment, &proof, settings)
+            .map_err(kzg_error)?
+        {
+            bail!("KZG proof of the blob is invalid")
+        }
+
+        Ok(())
+    }
+
+    /// Returns the hash the blob is referenced by in its transaction.
+    pub fn versioned_hash(&self) -> B256 {
+        kzg_to_versioned_hash(&self.kzg_commitment)
+    }
+}
+
+/// The blob sidecars of an Ethereum block, in the order of their index.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockBlobs {
+    /// Beacon slot of the block.
+    pub slot: u64,
+    pub blobs: Vec<BlobSidecar>,
+}
+
+/// Source the blob read precompile reads the blob sidecars of Ethereum blocks from.
+///
+/// The precompile checks every blob against its own KZG commitment, so a source can't return a
+/// blob with the commitment, proof or versioned hash of another. Which blobs belong to a block is
+/// not checked against the Ethereum block, the source is trusted for that.
+#[async_trait::async_trait]
+pub trait BlobSource: Debug + Send + Sync {
+    /// Returns the blob sidecars of Ethereum block `block_number`.
+    async fn blobs(&self, block_number: u64) -> eyre::Result<BlockBlobs>;
+}
+
+/// Reads blobs from the KYVE data pipeline.
+#[derive(Debug, Clone)]
+pub struct KyveBlobSource {
+    url: String,
+}
+
+impl KyveBlobSource {
+    pub fn new(url: &str) -> Self {
+        KyveBlobSource { url: url.trim_end_matches('/').to_string() }
+    }
+}
+
+impl Default for KyveBlobSource {
+    fn default() -> Self {
+        Self::new(KYVE_API_URL)
+    }
+}
+
+#[async_trait::async_trait]
+impl BlobSource for KyveBlobSource {
+    async fn blobs(&self, block_number: u64) -> eyre::Result<BlockBlobs> {
+        #[derive(Deserialize)]
+        struct KyveItem {
+            value: BlockBlobs,
+        }
+
+        let url =
+            format!("{}/ethereum/beacon/blob_sidecars?block_height={}", self.url, block_number);
+        let item: KyveItem = reqwest::get(url).await?.error_for_status()?.json().await?;
+
+        Ok(item.value)
+    }
+}
+
+/// Reads blobs from the API of a beacon node.
+///
+/// The beacon API does not index blocks by their execution block number, so the beacon block of
+/// a block is looked up by the `parentBeaconBlockRoot` of the next block, through the JSON-RPC
+/// API of an execution node. The blobs of the latest block can't be read until the next block.
+#[derive(Debug, Clone)]
+pub struct BeaconBlobSource {
+    beacon_url: String,
+    execution_url: String,
+    client: reqwest::Client,
+}
+
+impl BeaconBlobSource {
+    pub fn new(beacon_url: &str, execution_url: &str) -> Self {
+        BeaconBlobSource {
+            beacon_url: beacon_url.trim_end_matches('/').to_string(),
+            execution_url: execution_url.to_string(),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Returns the root of the beacon block that contains Ethereum block `block_number`.
+    async fn beacon_block_root(&self, block_number: u64) -> eyre::Result<B256> {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct ExecutionBlock {
+            parent_beacon_block_root: Option<B256>,
+        }
+
+        #[derive(Deserialize)]
+        struct RpcResponse {
+            result: Option<ExecutionBlock>,
+        }
+
+        let Some(next_block) = block_number.checked_add(1) else {
+            bail!("block {block_number} does not exist")
+        };
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "eth_getBlockByNumber",
+            "params": [format!("{next_block:#x}"), false],
+        });
+        let response: RpcResponse = self
+            .client
+            .post(self.execution_url.as_str())
+            .json(&request)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let Some(next_block) = response.result else {
+            bail!("block {block_number} is not followed by a block yet")
+        };
+        next_block
+            .parent_beacon_block_root
+            .ok_or_else(|| eyre!("block {block_number} is from before Cancun"))
+    }
+
+    async fn beacon_get<T: serde::de::DeserializeOwned>(&self, path: &str) -> eyre::Result<T> {
+        #[derive(Deserialize)]
+        struct BeaconResponse<T> {
+            data: T,
+        }
+
+        let url = format!("{}{}", self.beacon_url, path);
+        let response: BeaconResponse<T> =
+            self.client.get(url).send().await?.error_for_status()?.json().await?;
+
+        Ok(response.data)
+    }
+}
+
+#[async_trait::async_trait]
+impl BlobSource for BeaconBlobSource {
+    async fn blobs(&self, block_number: u64) -> eyre::Result<BlockBlobs> {
+        #[derive(Deserialize)]
+        struct HeaderMessage {
+            slot: String,
+        }
+
+        #[derive(Deserialize)]
+        struct SignedHeader {
+            message: HeaderMessage,
+        }
+
+        #[derive(Deserialize)]
+        struct BeaconHeader {
+            header: SignedHeader,
+        }
+
+        let root = self.beacon_block_root(block_number).await?;
+        let header: BeaconHeader =
+            self.beacon_get(&format!("/eth/v1/beacon/headers/{root}")).await?;
+        let blobs: Vec<BlobSidecar> =
+            self.beacon_get(&format!("/eth/v1/beacon/blob_sidecars/{root}")).await?;
+
+        Ok(BlockBlobs { slot: header.header.message.slot.parse()?, blobs })
+    }
+}
+
+/// Reads blobs from a local archive of `<block number>.json` files of [`BlockBlobs`].
+#[derive(Debug, Clone)]
+pub struct LocalBlobSource {
+    dir: PathBuf,
+}
+
+impl LocalBlobSource {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        LocalBlobSource { dir: dir.into() }
+    }
+
+    /// Reads from the archive below the node's datadir.
+    pub fn at_datadir(datadir: &Path) -> Self {
+        Self::new(datadir.join(LOCAL_BLOBS_DIR))
+    }
+
+    /// Adds the blobs of `block_number` to the archive.
+    pub fn store(&self, block_number: u64, blobs: &BlockBlobs) -> eyre::Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        std::fs::write(self.path(block_number), serde_json::to_vec(blobs)?)?;
+        Ok(())
+    }
+
+    fn path(&self, block_number: u64) -> PathBuf {
+        self.dir.join(format!("{block_number}.json"))
+    }
+}
+
+#[async_trait::async_trait]
+impl BlobSource for LocalBlobSource {
+    async fn blobs(&self, block_number: u64) -> eyre::Result<BlockBlobs> {
+        let path = self.path(block_number);
+        let Ok(data) = tokio::fs::read(&path).await else {
+            bail!("blobs of block {block_number} are not archived in {}", self.dir.display())
+        };
+
+        Ok(serde_json::from_slice(&data)?)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod blobs_tests {
+    use crate::inner::blobs::{BlobSidecar, BlobSource, BlockBlobs, LocalBlobSource};
+    use reth::primitives::{
+        kzg::{Blob, KzgCommitment, KzgProof, BYTES_PER_BLOB},
+        revm_primitives::EnvKzgSettings,
+    };
+
+    /// Returns a blob with valid proof.
+    pub(crate) fn blob_sidecar(seed: u8) -> BlobSidecar {
+        let settings = EnvKzgSettings::Default;
+        let mut data = vec![0; BYTES_PER_BLOB];
+        // the first byte of every field element stays zero, so they are all canonical
+        for (i, element) in data.chunks_mut(32).enumerate() {
+            element[31] = seed.wrapping_add(i as u8);
+        }
+        let blob = Blob::from_bytes(&data).unwrap();
+        let commitment =
+            KzgCommitment::blob_to_kzg_commitment(&blob, settings.get()).unwrap().to_bytes();
+        let proof = KzgProof::compute_blob_kzg_proof(&blob, &commitment, settings.get())
+            .unwrap()
+            .to_bytes();
+
+        BlobSidecar {
+            blob: data.into(),
+            kzg_commitment: commitment.as_slice().to_vec().into(),
+            kzg_proof: proof.as_slice().to_vec().into(),
+        }
+    }
+
+    #[test]
+    pub fn test_verify_blob() {
+        let settings = EnvKzgSettings::Default;
+        let sidecar = blob_sidecar(1);
+        sidecar.verify(settings.get()).unwrap();
+        assert_eq!(sidecar.versioned_hash()[0], 0x01);
+
+        let mut blob = sidecar.blob.to_vec();
+        blob[63] ^= 1;
+        let tampered = BlobSidecar { blob: blob.into(), ..sidecar.clone() };
+        assert!(tampered.verify(settings.get()).is_err());
+
+        let other = blob_sidecar(2);
+        let swapped = BlobSidecar { kzg_proof: other.kzg_proof, ..sidecar.clone() };
+        assert!(swapped.verify(settings.get()).is_err());
+
+        let truncated = BlobSidecar { blob: sidecar.blob.slice(..32), ..sidecar };
+        assert!(truncated.verify(settings.get()).is_err());
+    }
+
+    #[tokio::test]
+    pub async fn test_local_blob_source() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = LocalBlobSource::new(dir.path());
+        let blobs = BlockBlobs { slot: 9, blobs: vec![blob_sidecar(1)] };
+        source.store(20, &blobs).unwrap();
+
+        assert_eq!(source.blobs(20).await.unwrap(), blobs);
+        assert!(source.blobs(21).await.is_err());
+    }
+}
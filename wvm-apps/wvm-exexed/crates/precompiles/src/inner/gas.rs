@@ -0,0 +1,173 @@
+use crate::inner::registry::{fork_reached, ForkConfig};
+use reth::primitives::revm_primitives::Env;
+use reth_chainspec::{ChainSpec, ForkCondition};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Key of the read gas schedules in the `config` section of the genesis file.
+pub const READ_GAS_SCHEDULES_KEY: &str = "wvmReadGas";
+
+/// Gas charged by the read precompiles, for the input and for the data they return.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadGasSchedule {
+    /// Gas charged for every read.
+    pub base: u64,
+    /// Gas charged per byte of input.
+    pub per_input_byte: u64,
+    /// Gas charged per 32-byte word of returned data.
+    pub per_word: u64,
+}
+
+impl ReadGasSchedule {
+    /// Schedule of the blocks before the first configured schedule, the size of the returned data
+    /// is free.
+    pub const LEGACY: ReadGasSchedule =
+        ReadGasSchedule { base: 10_000, per_input_byte: 3, per_word: 0 };
+
+    /// Returns the gas charged before anything is read.
+    pub fn input_gas(&self, input_len: usize) -> u64 {
+        self.base.saturating_add(self.per_input_byte.saturating_mul(input_len as u64))
+    }
+
+    /// Returns the gas charged for a read returning `size` bytes.
+    pub fn read_gas(&self, input_len: usize, size: usize) -> u64 {
+        let words = (size as u64).div_ceil(32);
+        self.input_gas(input_len).saturating_add(self.per_word.saturating_mul(words))
+    }
+
+    /// Whether the gas depends on the size of the returned data, which then has to be known
+    /// before it is downloaded.
+    pub const fn is_size_priced(&self) -> bool {
+        self.per_word > 0
+    }
+}
+
+/// A [`ReadGasSchedule`] and the fork it activates at, as given in the chain spec.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadGasFork {
+    #[serde(flatten)]
+    pub activation: ForkConfig,
+    #[serde(flatten)]
+    pub schedule: ReadGasSchedule,
+}
+
+/// Gas schedules of the read precompiles by hardfork.
+///
+/// The schedules are configured in the chain spec, under [`READ_GAS_SCHEDULES_KEY`] in the
+/// `config` section of the genesis file, in the order they activate at, each at a block, a
+/// timestamp or an Ethereum hardfork, e.g.
+///
+/// ```json
+/// "wvmReadGas": [
+///     { "block": 1000000, "base": 10000, "perInputByte": 3, "perWord": 16 },
+///     { "hardfork": "prague", "base": 5000, "perInputByte": 1, "perWord": 8 }
+/// ]
+/// ```
+///
+/// Blocks before the first schedule are charged by [`ReadGasSchedule::LEGACY`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReadGasSchedules {
+    /// In activation order.
+    forks: Arc<Vec<(ForkCondition, ReadGasSchedule)>>,
+}
+
+impl ReadGasSchedules {
+    pub fn new(forks: Vec<(ForkCondition, ReadGasSchedule)>) -> Self {
+        ReadGasSchedules { forks: Arc::new(forks) }
+    }
+
+    pub fn from_chain_spec(chain_spec: &ChainSpec) -> eyre::Result<Self> {
+        let Some(forks) = chain_spec.genesis().config.extra_fields.get(READ_GAS_SCHEDULES_KEY)
+        else {
+            return Ok(Self::default())
+        };
+
+        let invalid = |err: eyre::Report| {
+            eyre::eyre!("invalid `{READ_GAS_SCHEDULES_KEY}` in the chain spec: {err}")
+        };
+        let forks = serde_json::from_value::<Vec<ReadGasFork>>(forks.clone())
+            .map_err(|err| invalid(eyre::Report::from(err)))?;
+        let forks = forks
+            .into_iter()
+            .map(|fork| {
+                Ok((fork.activation.condition(chain_spec).map_err(invalid)?, fork.schedule))
+            })
+            .collect::<eyre::Result<_>>()?;
+
+        Ok(Self::new(forks))
+    }
+
+    /// Returns the schedule active at the block of `env`, the one of the last fork reached.
+    pub fn at(&self, env: &Env) -> ReadGasSchedule {
+        self.forks
+            .iter()
+            .rev()
+            .find(|(activation, _)| fork_reached(activation, env))
+            .map_or(ReadGasSchedule::LEGACY, |(_, schedule)| *schedule)
+    }
+}
+
+#[cfg(test)]
+mod gas_tests {
+    use crate::inner::gas::{ReadGasSchedule, ReadGasSchedules, READ_GAS_SCHEDULES_KEY};
+    use reth::primitives::{revm_primitives::Env, Genesis, U256};
+    use reth_chainspec::ChainSpec;
+
+    #[test]
+    pub fn test_read_gas() {
+        let schedule = ReadGasSchedule { base: 10_000, per_input_byte: 3, per_word: 16 };
+        assert_eq!(schedule.input_gas(43), 10_129);
+        assert_eq!(schedule.read_gas(43, 0), 10_129);
+        assert_eq!(schedule.read_gas(43, 1), 10_145);
+        assert_eq!(schedule.read_gas(43, 64), 10_161);
+        assert_eq!(ReadGasSchedule::LEGACY.read_gas(43, 18 * 1024 * 1024), 10_129);
+    }
+
+    #[test]
+    pub fn test_read_gas_schedules_from_chain_spec() {
+        let at = |schedules: &ReadGasSchedules, block: u64, timestamp: u64| {
+            let mut env = Env::default();
+            env.block.number = U256::from(block);
+            env.block.timestamp = U256::from(timestamp);
+            schedules.at(&env)
+        };
+
+        let mut genesis = Genesis::default();
+        genesis.config.cancun_time = Some(5_000);
+        let schedules =
+            ReadGasSchedules::from_chain_spec(&ChainSpec::from(genesis.clone())).unwrap();
+        assert_eq!(at(&schedules, 100, 0), ReadGasSchedule::LEGACY);
+
+        genesis.config.extra_fields.insert(
+            READ_GAS_SCHEDULES_KEY.to_string(),
+            serde_json::json!([
+                { "block": 100, "base": 10000, "perInputByte": 3, "perWord": 16 },
+                { "time": 1000, "base": 5000, "perInputByte": 1, "perWord": 8 },
+                { "hardfork": "cancun", "base": 5000, "perInputByte": 1, "perWord": 4 },
+            ]),
+        );
+        let schedules =
+            ReadGasSchedules::from_chain_spec(&ChainSpec::from(genesis.clone())).unwrap();
+        assert_eq!(at(&schedules, 99, 0), ReadGasSchedule::LEGACY);
+        assert_eq!(at(&schedules, 100, 0).per_word, 16);
+        assert_eq!(at(&schedules, 199, 999).per_word, 16);
+        assert_eq!(at(&schedules, 200, 1000).per_word, 8);
+        assert_eq!(at(&schedules, 300, 5000).per_word, 4);
+
+        genesis
+            .config
+            .extra_fields
+            .insert(READ_GAS_SCHEDULES_KEY.to_string(), serde_json::json!({ "block": 1 }));
+        assert!(ReadGasSchedules::from_chain_spec(&ChainSpec::from(genesis.clone())).is_err());
+
+        genesis.config.extra_fields.insert(
+            READ_GAS_SCHEDULES_KEY.to_string(),
+            serde_json::json!([
+                { "hardfork": "unknown", "base": 1, "perInputByte": 1, "perWord": 1 },
+            ]),
+        );
+        assert!(ReadGasSchedules::from_chain_spec(&ChainSpec::from(genesis)).is_err());
+    }
+}
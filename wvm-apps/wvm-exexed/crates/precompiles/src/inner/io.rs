@@ -0,0 +1,170 @@
+use revm_primitives::PrecompileErrors;
+use std::{
+    future::Future,
+    io,
+    sync::{mpsc, Arc},
+    time::Duration,
+};
+use tokio::runtime::{Handle, Runtime};
+
+/// Default time a precompile waits for its network requests.
+pub const DEFAULT_PRECOMPILE_TIMEOUT: Duration = Duration::from_secs(30);
+/// Default number of threads of the precompile runtime.
+pub const DEFAULT_PRECOMPILE_IO_THREADS: usize = 2;
+
+/// Configuration of the [`PrecompileIo`] runtime.
+#[derive(Debug, Clone, Copy)]
+pub struct PrecompileIoConfig {
+    pub worker_threads: usize,
+    /// Time after which a call is cancelled.
+    pub timeout: Duration,
+}
+
+impl Default for PrecompileIoConfig {
+    fn default() -> Self {
+        PrecompileIoConfig {
+            worker_threads: DEFAULT_PRECOMPILE_IO_THREADS,
+            timeout: DEFAULT_PRECOMPILE_TIMEOUT,
+        }
+    }
+}
+
+/// Error of a call on the [`PrecompileIo`] runtime.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum PrecompileIoError {
+    #[error("precompile request timed out after {0:?}")]
+    Timeout(Duration),
+    /// The runtime was shut down or the request panicked.
+    #[error("precompile request was cancelled")]
+    Cancelled,
+}
+
+/// The node failed to do the I/O of a call, which says nothing about the call itself, so the
+/// block is not executed instead of failing the call.
+impl From<PrecompileIoError> for PrecompileErrors {
+    fn from(err: PrecompileIoError) -> Self {
+        PrecompileErrors::Fatal { msg: err.to_string() }
+    }
+}
+
+/// Shuts the runtime down without blocking, it may be dropped from within an async context.
+#[derive(Debug)]
+struct OwnedRuntime(Option<Runtime>);
+
+impl Drop for OwnedRuntime {
+    fn drop(&mut self) {
+        if let Some(runtime) = self.0.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+/// Runtime the network precompiles do their I/O on.
+///
+/// Precompiles are called synchronously during block execution, which itself may run on a tokio
+/// runtime where blocking on a future panics. All precompiles share this runtime instead: a call
+/// is spawned onto its threads and the executing thread waits for the result. Calls that take
+/// longer than their timeout are cancelled, dropping the request.
+#[derive(Debug, Clone)]
+pub struct PrecompileIo {
+    handle: Handle,
+    timeout: Duration,
+    _runtime: Arc<OwnedRuntime>,
+}
+
+impl PrecompileIo {
+    pub fn new(config: PrecompileIoConfig) -> io::Result<Self> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(config.worker_threads.max(1))
+            .thread_name("wvm-precompile-io")
+            .enable_all()
+            .build()?;
+
+        Ok(PrecompileIo {
+            handle: runtime.handle().clone(),
+            timeout: config.timeout,
+            _runtime: Arc::new(OwnedRuntime(Some(runtime))),
+        })
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Runs `request` to completion with the configured timeout.
+    pub fn run<F>(&self, request: F) -> Result<F::Output, PrecompileIoError>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        self.run_with_timeout(self.timeout, request)
+    }
+
+    /// Runs `request` to completion, cancelling it after `timeout`.
+    pub fn run_with_timeout<F>(
+        &self,
+        timeout: Duration,
+        request: F,
+    ) -> Result<F::Output, PrecompileIoError>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let (tx, rx) = mpsc::sync_channel(1);
+        self.handle.spawn(async move {
+            let result = tokio::time::timeout(timeout, request)
+                .await
+                .map_err(|_| PrecompileIoError::Timeout(timeout));
+            let _ = tx.send(result);
+        });
+
+        // the sender is dropped without a result if the task never completes
+        rx.recv().map_err(|_| PrecompileIoError::Cancelled)?
+    }
+}
+
+#[cfg(test)]
+mod io_tests {
+    use crate::inner::io::{PrecompileIo, PrecompileIoConfig, PrecompileIoError};
+    use revm_primitives::PrecompileErrors;
+    use std::time::Duration;
+
+    #[test]
+    pub fn test_run() {
+        let io = PrecompileIo::new(PrecompileIoConfig::default()).unwrap();
+        assert_eq!(io.run(async { 1 + 1 }), Ok(2));
+
+        let slow = async {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            1
+        };
+        assert_eq!(
+            io.run_with_timeout(Duration::from_millis(10), slow),
+            Err(PrecompileIoError::Timeout(Duration::from_millis(10)))
+        );
+
+        assert_eq!(
+            io.run(async { panic!("request failed") }),
+            Err::<(), _>(PrecompileIoError::Cancelled)
+        );
+    }
+
+    #[test]
+    pub fn test_io_errors_are_fatal() {
+        let err = PrecompileErrors::from(PrecompileIoError::Timeout(Duration::from_secs(1)));
+        assert!(matches!(err, PrecompileErrors::Fatal { .. }));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    pub async fn test_run_in_async_context() {
+        let io = PrecompileIo::new(PrecompileIoConfig::default()).unwrap();
+        let value = io.run(async {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+            1
+        });
+        assert_eq!(value, Ok(1));
+
+        // dropping the last handle does not block the calling runtime
+        drop(io);
+    }
+}
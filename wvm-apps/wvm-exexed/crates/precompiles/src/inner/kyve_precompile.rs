@@ -1,116 +1,302 @@
-use reth::primitives::Bytes;
+use crate::inner::{
+    abi::{
+        decode_call, BlobField,
+        IKyveRead::{self, IKyveReadCalls},
+    },
+    blobs::{BlobSidecar, BlobSource, BlockBlobs, KYVE_FIRST_BLOCK},
+    gas::{ReadGasSchedule, ReadGasSchedules},
+    io::PrecompileIo,
+    registry::fork_reached,
+    sidecar::oracle_read,
+    util::paid_output,
+};
+use alloy_sol_types::SolCall;
+use reth::primitives::{hex, Bytes};
+use reth_chainspec::ForkCondition;
 use revm_primitives::{
-    Precompile, PrecompileError, PrecompileErrors, PrecompileOutput, PrecompileResult,
+    Env, EnvKzgSettings, Precompile, PrecompileError, PrecompileErrors, PrecompileResult,
+    StatefulPrecompile,
 };
+use std::sync::Arc;
+
+/// Name of the precompile in read sidecars.
+pub const KYVE_READ: &str = "kyve-read";
+
+/// First Ethereum block whose blobs can be read.
+///
+/// The bound is part of consensus, so it is the same for every [`BlobSource`]: the first block
+/// with blob sidecars in the KYVE data pipeline, the default source.
+pub const FIRST_BLOB_BLOCK: u64 = KYVE_FIRST_BLOCK;
+
+/// Gas charged for verifying a blob against its KZG commitment, like the point evaluation
+/// precompile.
+pub const KZG_VERIFICATION_GAS: u64 = 50_000;
+
+/// Reads the blob sidecars of Ethereum blocks from the node's [`BlobSource`], KYVE by default.
+///
+/// The input is either a `blocknum;index.field` string, returning the field as a hex string, or,
+/// from the activation of ABI calls on, an ABI-encoded call of [`IKyveRead`], returning it
+/// ABI-encoded. Every blob is checked against its KZG commitment with the trusted setup of the
+/// transaction pool before it is returned, but not against the Ethereum block, see
+/// [`BlobSource`].
+///
+/// Reads are charged by the size of their output, see [`ReadGasSchedules`], reads of a blob also
+/// pay [`KZG_VERIFICATION_GAS`] under schedules that price the output.
+#[derive(Debug, Clone)]
+pub struct KyveReadPrecompile {
+    source: Arc<dyn BlobSource>,
+    kzg_settings: EnvKzgSettings,
+    gas: ReadGasSchedules,
+    io: PrecompileIo,
+    /// Blocks ABI-encoded calls are accepted at.
+    abi_calls: ForkCondition,
+}
+
+impl KyveReadPrecompile {
+    pub fn new(
+        source: Arc<dyn BlobSource>,
+        gas: ReadGasSchedules,
+        io: PrecompileIo,
+        abi_calls: ForkCondition,
+    ) -> Self {
+        KyveReadPrecompile { source, kzg_settings: EnvKzgSettings::Default, gas, io, abi_calls }
+    }
+
+    pub fn into_precompile(self) -> Precompile {
+        Precompile::Stateful(Arc::new(self))
+    }
+}
 
-pub const KYVE_PC_BASE: u64 = 10_000;
-pub const KYVE_API_URL: &str = "https://data.services.kyve.network";
+impl StatefulPrecompile for KyveReadPrecompile {
+    fn call(&self, input: &Bytes, gas_limit: u64, env: &Env) -> PrecompileResult {
+        let gas = self.gas.at(env);
+        let abi_calls = fork_reached(&self.abi_calls, env);
+        let Self { io, source, kzg_settings, .. } = self;
+        kyve_read(io, source, kzg_settings, gas, input, gas_limit, abi_calls)
+    }
+}
 
-pub const KYVE_READ_PC: Precompile = Precompile::Standard(kyve_read);
+/// Field of the blob sidecars a read is for.
+enum KyveField {
+    /// A `index.field` string input, the field is returned as a hex string.
+    Legacy(String),
+    Abi(IKyveReadCalls),
+}
 
-fn kyve_read(input: &Bytes, gas_limit: u64) -> PrecompileResult {
-    let data_size = input.len();
-    let gas_used: u64 = (KYVE_PC_BASE as usize + data_size * 3) as u64;
+impl KyveField {
+    /// Whether the read verifies a blob against its commitment, every field but the slot.
+    fn verifies_blob(&self) -> bool {
+        match self {
+            KyveField::Legacy(field) => !field.ends_with(".slot"),
+            KyveField::Abi(call) => !matches!(call, IKyveReadCalls::blobSlot(_)),
+        }
+    }
+}
 
+fn kyve_read(
+    io: &PrecompileIo,
+    source: &Arc<dyn BlobSource>,
+    kzg_settings: &EnvKzgSettings,
+    gas: ReadGasSchedule,
+    input: &Bytes,
+    gas_limit: u64,
+    abi_calls: bool,
+) -> PrecompileResult {
     if input.is_empty() {
         return Err(PrecompileErrors::Error(PrecompileError::Other(
             "A block number and field must be provided".to_string(),
         )));
     }
 
-    if gas_used > gas_limit {
+    if gas.input_gas(input.len()) > gas_limit {
         return Err(PrecompileErrors::Error(PrecompileError::OutOfGas));
     }
 
-    let input_str = unsafe { String::from_utf8(input.0.to_vec()) }.unwrap();
-    let (block_number, field) = {
-        let mut parts = input_str.split(";");
-        let block_number = parts.next();
-        let field = parts.next();
+    let call = if abi_calls { decode_call::<IKyveReadCalls>(input)? } else { None };
+    let (blk_number, field) = match call {
+        Some(call) => {
+            let blk_number = match &call {
+                IKyveReadCalls::blobField(call) => call.blockNumber,
+                IKyveReadCalls::blobSlot(call) => call.blockNumber,
+                IKyveReadCalls::blob(call) => call.blockNumber,
+            };
+            (blk_number, KyveField::Abi(call))
+        }
+        None => parse_legacy_input(input)?,
+    };
+
+    // blocks before the first size priced schedule keep their flat price
+    let kzg_gas =
+        if gas.is_size_priced() && field.verifies_blob() { KZG_VERIFICATION_GAS } else { 0 };
+    let input_len = input.len();
+    let read_gas =
+        move |output: &[u8]| gas.read_gas(input_len, output.len()).saturating_add(kzg_gas);
+    oracle_read(KYVE_READ, input, read_gas, || {
+        if blk_number < FIRST_BLOB_BLOCK {
+            return Err(kyve_error(&format!("Can only read from block {FIRST_BLOB_BLOCK}")))
+        }
+
+        let blobs = {
+            let source = source.clone();
+            io.run(async move { source.blobs(blk_number).await })?
+        };
+        let blobs = blobs.map_err(|err| {
+            kyve_error(&format!("Could not read the blobs of block {blk_number}: {err}"))
+        })?;
 
-        (block_number, field)
+        let output = match field {
+            KyveField::Legacy(field) => legacy_field(&blobs, kzg_settings, field.as_str())?,
+            KyveField::Abi(call) => abi_field(&blobs, kzg_settings, call)?,
+        };
+
+        paid_output(read_gas(&output), output, gas_limit)
+    })
+}
+
+/// Parses a `blocknum;index.field` input.
+fn parse_legacy_input(input: &Bytes) -> Result<(u64, KyveField), PrecompileErrors> {
+    let Ok(input_str) = String::from_utf8(input.to_vec()) else {
+        return Err(kyve_error("Input could not be parsed"))
     };
+    let mut parts = input_str.split(';');
 
-    if block_number.is_none() {
-        return Err(PrecompileErrors::Error(PrecompileError::Other(
-            "A block number must be provided".to_string(),
-        )));
-    } else if field.is_none() {
-        return Err(PrecompileErrors::Error(PrecompileError::Other(
-            "Field must be provided".to_string(),
-        )));
+    let Some(block_number) = parts.next() else {
+        return Err(kyve_error("A block number must be provided"))
+    };
+    let Some(field) = parts.next() else { return Err(kyve_error("Field must be provided")) };
+    let Ok(block_number) = block_number.parse::<u64>() else {
+        return Err(kyve_error("Block number could not be parsed"))
+    };
+
+    Ok((block_number, KyveField::Legacy(field.to_string())))
+}
+
+/// Returns the blob at `index`, verified against its commitment.
+fn verified_blob<'a>(
+    blobs: &'a BlockBlobs,
+    kzg_settings: &EnvKzgSettings,
+    index: usize,
+) -> Result<&'a BlobSidecar, PrecompileErrors> {
+    let Some(blob) = blobs.blobs.get(index) else {
+        return Err(kyve_error("Blob index does not exist"))
+    };
+
+    blob.verify(kzg_settings.get())
+        .map_err(|err| kyve_error(&format!("Blob {index} could not be verified: {err}")))?;
+
+    Ok(blob)
+}
+
+/// Returns `field` of `blob`.
+fn blob_field(blob: &BlobSidecar, field: BlobField) -> Result<&Bytes, PrecompileErrors> {
+    match field {
+        BlobField::Blob => Ok(&blob.blob),
+        BlobField::KzgCommitment => Ok(&blob.kzg_commitment),
+        BlobField::KzgProof => Ok(&blob.kzg_proof),
+        BlobField::__Invalid => Err(kyve_error("Field does not exist")),
     }
+}
 
-    let blk_number = block_number.unwrap();
+fn legacy_field(
+    blobs: &BlockBlobs,
+    kzg_settings: &EnvKzgSettings,
+    field: &str,
+) -> Result<Vec<u8>, PrecompileErrors> {
+    let Some((blob_indx, field)) = field.split_once('.') else {
+        return Err(kyve_error("Field must be provided"))
+    };
 
-    if !(blk_number.to_string().parse::<usize>().unwrap() >= 19426589) {
-        return Err(PrecompileErrors::Error(PrecompileError::Other(
-            "Can only read from block 19426589".to_string(),
-        )));
+    if field.eq("slot") {
+        return Ok(blobs.slot.to_string().into_bytes())
     }
 
-    let field = field.unwrap();
-
-    tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap().block_on(async {
-        let req = reqwest::get(format!(
-            "{}/ethereum/beacon/blob_sidecars?block_height={}",
-            KYVE_API_URL, blk_number
-        ))
-        .await;
-
-        match req {
-            Ok(resp) => match resp.json::<serde_json::Value>().await {
-                Ok(json_val) => {
-                    let main_val = json_val.get("value").unwrap();
-
-                    let slot = main_val.get("slot").and_then(|s| s.as_u64()).unwrap().to_string();
-
-                    let blobs = main_val.get("blobs").and_then(|b| b.as_array()).unwrap();
-
-                    let (blob_indx, field) = field.split_once('.').unwrap();
-
-                    if field.eq("slot") {
-                        return Ok(PrecompileOutput::new(gas_used, slot.into_bytes().into()));
-                    }
-
-                    match blobs.get(blob_indx.parse::<usize>().unwrap()) {
-                        Some(get_field) => {
-                            if let Some(field_val) = get_field.get(field) {
-                                Ok(PrecompileOutput::new(
-                                    gas_used,
-                                    field_val.as_str().unwrap().to_string().into_bytes().into(),
-                                ))
-                            } else {
-                                Err(PrecompileErrors::Error(PrecompileError::Other(
-                                    "Field does not exist".to_string(),
-                                )))
-                            }
-                        }
-                        None => Err(PrecompileErrors::Error(PrecompileError::Other(
-                            "Blob index does not exist".to_string(),
-                        ))),
-                    }
-                }
-                Err(_) => Err(PrecompileErrors::Error(PrecompileError::Other(
-                    "Invalid Response from server".to_string(),
-                ))),
-            },
-            Err(_) => Err(PrecompileErrors::Error(PrecompileError::Other(
-                "Could not connect with KYVE".to_string(),
-            ))),
+    let field = match field {
+        "blob" => BlobField::Blob,
+        "kzg_commitment" => BlobField::KzgCommitment,
+        "kzg_proof" => BlobField::KzgProof,
+        _ => return Err(kyve_error("Field does not exist")),
+    };
+    let Ok(blob_indx) = blob_indx.parse::<usize>() else {
+        return Err(kyve_error("Blob index does not exist"))
+    };
+
+    let blob = verified_blob(blobs, kzg_settings, blob_indx)?;
+    Ok(hex::encode_prefixed(blob_field(blob, field)?).into_bytes())
+}
+
+fn abi_field(
+    blobs: &BlockBlobs,
+    kzg_settings: &EnvKzgSettings,
+    call: IKyveReadCalls,
+) -> Result<Vec<u8>, PrecompileErrors> {
+    let output = match call {
+        IKyveReadCalls::blobSlot(_) => IKyveRead::blobSlotCall::abi_encode_returns(&(blobs.slot,)),
+        IKyveReadCalls::blobField(call) => {
+            let field =
+                BlobField::try_from(call.field).map_err(|_| kyve_error("Field does not exist"))?;
+            let blob = verified_blob(blobs, kzg_settings, call.index as usize)?;
+            IKyveRead::blobFieldCall::abi_encode_returns(&(blob_field(blob, field)?.clone(),))
         }
-    })
+        IKyveReadCalls::blob(call) => {
+            let blob = verified_blob(blobs, kzg_settings, call.index as usize)?;
+            IKyveRead::blobCall::abi_encode_returns(&(
+                blob.blob.clone(),
+                blob.kzg_commitment.clone(),
+                blob.kzg_proof.clone(),
+                blob.versioned_hash(),
+            ))
+        }
+    };
+
+    Ok(output)
+}
+
+fn kyve_error(message: &str) -> PrecompileErrors {
+    PrecompileErrors::Error(PrecompileError::Other(message.to_string()))
 }
 
 #[cfg(test)]
 mod kyve_tests {
-    use crate::inner::kyve_precompile::kyve_read;
-    use reth::primitives::Bytes;
+    use crate::{
+        inner::{
+            abi::{BlobField, IKyveRead},
+            blobs::{
+                blobs_tests::blob_sidecar, BlobSidecar, BlobSource, BlockBlobs, KyveBlobSource,
+                LocalBlobSource,
+            },
+            gas::ReadGasSchedule,
+            kyve_precompile::{kyve_read, FIRST_BLOB_BLOCK, KZG_VERIFICATION_GAS},
+        },
+        test_utils::io,
+    };
+    use alloy_sol_types::SolCall;
+    use reth::primitives::{hex, revm_primitives::EnvKzgSettings, Bytes};
+    use revm_primitives::{PrecompileError, PrecompileErrors, PrecompileResult};
+    use std::sync::Arc;
+
+    fn read(source: Arc<dyn BlobSource>, input: &Bytes) -> PrecompileResult {
+        let (kzg, gas) = (EnvKzgSettings::Default, ReadGasSchedule::LEGACY);
+        kyve_read(&io(), &source, &kzg, gas, input, 100_000, true)
+    }
+
+    /// A local source with a valid blob and a blob that doesn't match its proof at `block`.
+    fn local_source(dir: &tempfile::TempDir, block: u64) -> (Arc<dyn BlobSource>, BlobSidecar) {
+        let source = LocalBlobSource::new(dir.path());
+        let valid = blob_sidecar(1);
+        let mut tampered = blob_sidecar(2);
+        tampered.kzg_proof = valid.kzg_proof.clone();
+        source.store(block, &BlockBlobs { slot: 3, blobs: vec![valid.clone(), tampered] }).unwrap();
+        (Arc::new(source), valid)
+    }
+
+    fn kyve() -> Arc<dyn BlobSource> {
+        Arc::new(KyveBlobSource::default())
+    }
 
     #[test]
     pub fn test_kyve_precompile() {
         let input = Bytes::from("20033062;0.kzg_commitment".as_bytes());
-        let read = kyve_read(&input, 100_000).unwrap();
+        let read = read(kyve(), &input).unwrap();
         let res = read.bytes.0.to_vec();
         assert_eq!(String::from_utf8(res).unwrap(), "0x81eb4254a890fd840a6bc60de54fb6fcd3b91242153386b9e83337f00f641a12bf6ebd876134e8703edce6725e29046c");
     }
@@ -118,7 +304,7 @@ mod kyve_tests {
     #[test]
     pub fn test_kyve_precompile_before_blk() {
         let input = Bytes::from("19426588;0.kzg_commitment".as_bytes());
-        let read = kyve_read(&input, 100_000);
+        let read = read(kyve(), &input);
         assert!(read.is_err());
         assert_eq!("Can only read from block 19426589", read.err().unwrap().to_string());
     }
@@ -126,8 +312,112 @@ mod kyve_tests {
     #[test]
     pub fn test_kyve_precompile_slot() {
         let input = Bytes::from("20033062;0.slot".as_bytes());
-        let read = kyve_read(&input, 100_000).unwrap();
+        let read = read(kyve(), &input).unwrap();
         let res = read.bytes.0.to_vec();
         assert_eq!(String::from_utf8(res).unwrap(), "9238016");
     }
+
+    #[test]
+    pub fn test_kyve_precompile_abi() {
+        let call = IKyveRead::blobFieldCall {
+            blockNumber: 20033062,
+            index: 0,
+            field: BlobField::KzgCommitment as u8,
+        };
+        let read = read(kyve(), &call.abi_encode().into()).unwrap();
+        let value = IKyveRead::blobFieldCall::abi_decode_returns(&read.bytes, true).unwrap().value;
+        assert_eq!(value.to_vec(), hex::decode("0x81eb4254a890fd840a6bc60de54fb6fcd3b91242153386b9e83337f00f641a12bf6ebd876134e8703edce6725e29046c").unwrap());
+
+        let call = IKyveRead::blobSlotCall { blockNumber: 20033062 };
+        let read = read(kyve(), &call.abi_encode().into()).unwrap();
+        let slot = IKyveRead::blobSlotCall::abi_decode_returns(&read.bytes, true).unwrap().slot;
+        assert_eq!(slot, 9238016);
+    }
+
+    #[test]
+    pub fn test_kyve_precompile_abi_before_blk() {
+        let call = IKyveRead::blobSlotCall { blockNumber: 19426588 };
+        let read = read(kyve(), &call.abi_encode().into());
+        assert_eq!("Can only read from block 19426589", read.err().unwrap().to_string());
+    }
+
+    #[test]
+    pub fn test_kyve_precompile_local_source() {
+        let dir = tempfile::tempdir().unwrap();
+        let block = FIRST_BLOB_BLOCK + 7;
+        let (source, valid) = local_source(&dir, block);
+
+        let call = IKyveRead::blobCall { blockNumber: block, index: 0 };
+        let output = read(source.clone(), &call.abi_encode().into()).unwrap();
+        let blob = IKyveRead::blobCall::abi_decode_returns(&output.bytes, true).unwrap();
+        assert_eq!(blob.blob, valid.blob);
+        assert_eq!(blob.kzgCommitment, valid.kzg_commitment);
+        assert_eq!(blob.kzgProof, valid.kzg_proof);
+        assert_eq!(blob.versionedHash, valid.versioned_hash());
+
+        let input = Bytes::from(format!("{block};0.kzg_commitment").into_bytes());
+        let output = read(source.clone(), &input).unwrap();
+        assert_eq!(output.bytes.to_vec(), hex::encode_prefixed(&valid.kzg_commitment).into_bytes());
+
+        // blobs that don't match their commitment are an error
+        let call =
+            IKyveRead::blobFieldCall { blockNumber: block, index: 1, field: BlobField::Blob as u8 };
+        assert!(read(source.clone(), &call.abi_encode().into()).is_err());
+        let call = IKyveRead::blobCall { blockNumber: block, index: 2 };
+        assert!(read(source.clone(), &call.abi_encode().into()).is_err());
+
+        let call = IKyveRead::blobSlotCall { blockNumber: block };
+        let output = read(source.clone(), &call.abi_encode().into()).unwrap();
+        assert_eq!(
+            IKyveRead::blobSlotCall::abi_decode_returns(&output.bytes, true).unwrap().slot,
+            3
+        );
+
+        // blocks missing from the source are an error instead of a panic
+        let call = IKyveRead::blobSlotCall { blockNumber: block + 1 };
+        assert!(read(source.clone(), &call.abi_encode().into()).is_err());
+
+        // the first block is the same for every source, even if it has older blobs
+        local_source(&dir, FIRST_BLOB_BLOCK - 1);
+        let call = IKyveRead::blobSlotCall { blockNumber: FIRST_BLOB_BLOCK - 1 };
+        assert!(read(source.clone(), &call.abi_encode().into()).is_err());
+
+        // before the activation of ABI calls the call is read as a string input
+        let call = IKyveRead::blobSlotCall { blockNumber: block };
+        let input = call.abi_encode().into();
+        let (kzg, gas) = (EnvKzgSettings::Default, ReadGasSchedule::LEGACY);
+        assert!(kyve_read(&io(), &source, &kzg, gas, &input, 100_000, false).is_err());
+    }
+
+    #[test]
+    pub fn test_kyve_precompile_gas() {
+        let dir = tempfile::tempdir().unwrap();
+        let block = FIRST_BLOB_BLOCK + 7;
+        let (source, _) = local_source(&dir, block);
+        let kzg = EnvKzgSettings::Default;
+        let gas = ReadGasSchedule { base: 10_000, per_input_byte: 3, per_word: 16 };
+        let read =
+            |input: &Bytes, gas_limit| kyve_read(&io(), &source, &kzg, gas, input, gas_limit, true);
+
+        // blob reads pay for the returned data and the verification of the blob
+        let input = IKyveRead::blobCall { blockNumber: block, index: 0 }.abi_encode().into();
+        let output = read(&input, 1_000_000).unwrap();
+        let expected = gas.read_gas(input.len(), output.bytes.len()) + KZG_VERIFICATION_GAS;
+        assert_eq!(output.gas_used, expected);
+        assert!(matches!(
+            read(&input, expected - 1),
+            Err(PrecompileErrors::Error(PrecompileError::OutOfGas))
+        ));
+
+        // slots verify no blob
+        let input = IKyveRead::blobSlotCall { blockNumber: block }.abi_encode().into();
+        let output = read(&input, 1_000_000).unwrap();
+        assert_eq!(output.gas_used, gas.read_gas(input.len(), output.bytes.len()));
+
+        // the legacy schedule keeps the flat price
+        let input = Bytes::from(format!("{block};0.blob").into_bytes());
+        let output =
+            kyve_read(&io(), &source, &kzg, ReadGasSchedule::LEGACY, &input, 100_000, true);
+        assert_eq!(output.unwrap().gas_used, 10_000 + 3 * input.len() as u64);
+    }
 }
@@ -1,50 +1,110 @@
-use crate::inner::arweave_precompile::ARWEAVE_UPLOAD_PC;
-use crate::inner::arweave_read_precompile::ARWEAVE_READ_PC;
-use crate::inner::kyve_precompile::KYVE_READ_PC;
+use crate::inner::arweave_precompile::ArweaveUploadPrecompile;
+use crate::inner::arweave_query_precompile::ArweaveQueryPrecompile;
+use crate::inner::arweave_read_precompile::ArweaveReadPrecompile;
+use crate::inner::blobs::BlobSource;
+use crate::inner::io::PrecompileIo;
+use crate::inner::kyve_precompile::KyveReadPrecompile;
+use crate::inner::read_cache::ReadCache;
+use crate::inner::registry::{
+    PrecompileForks, ARWEAVE_QUERY_SPEC, ARWEAVE_READ_SPEC, ARWEAVE_UPLOAD_SPEC, HELLO_WORLD_SPEC,
+    KYVE_READ_SPEC, WEVM_BLOCK_READ_SPEC,
+};
 use crate::inner::test_precompile::HELLO_WORLD_PC;
-use crate::inner::wevm_block_precompile::WEVM_BLOCK_PC;
-use reth::revm::precompile::{u64_to_address, PrecompileWithAddress};
+use crate::inner::util::ReadGateways;
+use crate::inner::wevm_block_precompile::WevmBlockPrecompile;
+use irys::backend::PermanentStorageBackend;
+use reth::revm::precompile::PrecompileWithAddress;
+use std::sync::Arc;
 
+pub mod abi;
 pub mod arweave_precompile;
+mod arweave_query_precompile;
 mod arweave_read_precompile;
-mod graphql_util;
+pub mod blobs;
+pub mod gas;
+pub mod io;
 mod kyve_precompile;
+pub mod read_cache;
+pub mod registry;
+pub mod sidecar;
 mod string_block;
 mod test_precompile;
-mod util;
-mod wevm_block_precompile;
+pub mod util;
+pub mod wevm_block_precompile;
 
-fn hex_to_u64(hex_str: &str) -> u64 {
-    u64::from_str_radix(&hex_str[2..], 16).unwrap()
-}
-
-pub fn wvm_precompiles() -> impl Iterator<Item = PrecompileWithAddress> {
-    // ORDER OF THINGS MATTER
-    // ORDER OF THINGS MATTER
-
-    let pcs_funcs =
-        [ARWEAVE_UPLOAD_PC, ARWEAVE_READ_PC, HELLO_WORLD_PC, WEVM_BLOCK_PC, KYVE_READ_PC];
-    let mut pcs = vec![];
-
-    // IT MATTERS BC OF THIS
-    let mut start_addr = 17;
-
-    for pc in pcs_funcs.into_iter() {
-        let addr = hex_to_u64(format!("0x{}", start_addr).as_str());
-        pcs.push(PrecompileWithAddress(u64_to_address(addr), pc));
-        start_addr = start_addr + 1;
-    }
+pub fn wvm_precompiles(
+    storage: Arc<dyn PermanentStorageBackend>,
+    cache: ReadCache,
+    blob_source: Arc<dyn BlobSource>,
+    forks: &PrecompileForks,
+    io: PrecompileIo,
+) -> impl Iterator<Item = PrecompileWithAddress> {
+    let gateways =
+        ReadGateways::new(&forks.read_gateways).with_allow_list(forks.read_gateway_allow_list);
+    let precompiles = [
+        (ARWEAVE_UPLOAD_SPEC, ArweaveUploadPrecompile::new(forks.abi_calls).into_precompile()),
+        (
+            ARWEAVE_READ_SPEC,
+            ArweaveReadPrecompile::new(
+                storage.clone(),
+                gateways.clone(),
+                cache.clone(),
+                forks.gas.clone(),
+                io.clone(),
+                forks.abi_calls,
+            )
+            .into_precompile(),
+        ),
+        (HELLO_WORLD_SPEC, HELLO_WORLD_PC),
+        (
+            WEVM_BLOCK_READ_SPEC,
+            WevmBlockPrecompile::new(
+                cache.wrap(storage.clone()),
+                gateways,
+                forks.gas.clone(),
+                forks.archive.clone(),
+                io.clone(),
+                forks.abi_calls,
+                forks.block_fields,
+            )
+            .into_precompile(),
+        ),
+        (
+            KYVE_READ_SPEC,
+            KyveReadPrecompile::new(blob_source, forks.gas.clone(), io.clone(), forks.abi_calls)
+                .into_precompile(),
+        ),
+        (
+            ARWEAVE_QUERY_SPEC,
+            ArweaveQueryPrecompile::new(storage, forks.gas.clone(), io).into_precompile(),
+        ),
+    ];
 
-    pcs.into_iter()
+    // installed eagerly, the iterator must not borrow the forks
+    precompiles.map(|(spec, precompile)| forks.install(spec, precompile)).into_iter()
 }
 #[cfg(test)]
 mod pc_inner_tests {
-    use crate::inner::wvm_precompiles;
+    use crate::inner::{
+        blobs::KyveBlobSource, io::PrecompileIo, read_cache::ReadCache, registry::PrecompileForks,
+        wvm_precompiles,
+    };
+    use irys::local::LocalStorage;
     use reth::revm::precompile::u64_to_address;
+    use std::sync::Arc;
 
     #[test]
     pub fn wvm_precompiles_test() {
-        let mut get_pcs = wvm_precompiles();
+        let dir = tempfile::tempdir().unwrap();
+        let storage = Arc::new(LocalStorage::new(dir.path().join("storage")).unwrap());
+        let io = PrecompileIo::new(Default::default()).unwrap();
+        let mut get_pcs = wvm_precompiles(
+            storage,
+            ReadCache::disabled(),
+            Arc::new(KyveBlobSource::default()),
+            &PrecompileForks::default(),
+            io,
+        );
         let first = get_pcs.next().unwrap();
         assert_eq!(first.0, u64_to_address(0x17));
     }
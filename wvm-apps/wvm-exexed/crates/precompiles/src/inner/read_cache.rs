@@ -0,0 +1,543 @@
+use borsh::BorshDeserialize;
+use irys::backend::{
+    data_range, DroppedUpload, PermanentStorageBackend, StoredItem, Tag, TagQuery,
+};
+use outbox::file_queue::FileQueue;
+use parking_lot::Mutex;
+use reth::primitives::{keccak256, B256};
+use reth_metrics::{
+    metrics::{Counter, Gauge},
+    Metrics,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, BufReader},
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::SystemTime,
+};
+
+/// Location of the read cache, relative to the node's datadir.
+pub const READ_CACHE_DIR: &str = "wvm/read-cache";
+
+/// Default maximum size of all cached items.
+pub const DEFAULT_READ_CACHE_SIZE: u64 = 1024 * 1024 * 1024;
+/// Default maximum size of a single cached item.
+pub const DEFAULT_READ_CACHE_MAX_ITEM_SIZE: u64 = 18 * 1024 * 1024;
+
+/// Configuration of the [`ReadCache`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReadCacheConfig {
+    /// Maximum size of all cached items, the least recently read items are evicted first.
+    pub max_bytes: u64,
+    /// Items larger than this are never cached.
+    pub max_item_bytes: u64,
+}
+
+impl Default for ReadCacheConfig {
+    fn default() -> Self {
+        ReadCacheConfig {
+            max_bytes: DEFAULT_READ_CACHE_SIZE,
+            max_item_bytes: DEFAULT_READ_CACHE_MAX_ITEM_SIZE,
+        }
+    }
+}
+
+/// A cached item, as listed by [`ReadCache::entries`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadCacheEntry {
+    pub id: String,
+    pub size: u64,
+}
+
+/// Size and limits of the [`ReadCache`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadCacheStats {
+    pub entries: u64,
+    pub size_bytes: u64,
+    pub max_bytes: u64,
+}
+
+#[derive(Metrics)]
+#[metrics(scope = "wvm.read_cache")]
+struct ReadCacheMetrics {
+    /// Number of reads served from the cache
+    hits: Counter,
+    /// Number of reads that missed the cache
+    misses: Counter,
+    /// Number of items evicted to stay within the size limit
+    evictions: Counter,
+    /// Number of cached items
+    entries: Gauge,
+    /// Total size of the cached items
+    size_bytes: Gauge,
+}
+
+#[derive(Debug)]
+struct CachedItem {
+    size: u64,
+    /// Value of the access clock when the item was last read or written.
+    last_access: u64,
+}
+
+#[derive(Debug, Default)]
+struct CacheIndex {
+    items: HashMap<String, CachedItem>,
+    size: u64,
+    clock: u64,
+}
+
+impl CacheIndex {
+    fn touch(&mut self, id: &str) -> bool {
+        self.clock += 1;
+        match self.items.get_mut(id) {
+            Some(item) => {
+                item.last_access = self.clock;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn insert(&mut self, id: String, size: u64) {
+        self.clock += 1;
+        if let Some(previous) = self.items.insert(id, CachedItem { size, last_access: self.clock })
+        {
+            self.size -= previous.size;
+        }
+        self.size += size;
+    }
+
+    fn remove(&mut self, id: &str) -> Option<CachedItem> {
+        let item = self.items.remove(id)?;
+        self.size -= item.size;
+        Some(item)
+    }
+}
+
+struct ReadCacheInner {
+    dir: PathBuf,
+    config: ReadCacheConfig,
+    index: Mutex<CacheIndex>,
+    metrics: ReadCacheMetrics,
+}
+
+/// Content addressed on-disk cache of items read from permanent storage.
+///
+/// Arweave items are immutable, so the data read for an id never changes and can be kept across
+/// restarts. Every item lives in its own file, sharded by the first byte of the hash of its id,
+/// and starts with the id itself so the index can be rebuilt when the node starts. Once the
+/// cache grows past its size limit the least recently read items are evicted.
+///
+/// The default cache is disabled and caches nothing.
+#[derive(Clone, Default)]
+pub struct ReadCache {
+    inner: Option<Arc<ReadCacheInner>>,
+}
+
+impl std::fmt::Debug for ReadCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.inner {
+            Some(inner) => f
+                .debug_struct("ReadCache")
+                .field("dir", &inner.dir)
+                .field("config", &inner.config)
+                .finish_non_exhaustive(),
+            None => f.write_str("ReadCache(disabled)"),
+        }
+    }
+}
+
+impl ReadCache {
+    /// Opens the cache at `dir`, indexing the items cached by previous runs.
+    pub fn new(dir: &Path, config: ReadCacheConfig) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+
+        // items are indexed oldest first, so the recency of previous runs is kept
+        let mut files = vec![];
+        for shard in fs::read_dir(dir)? {
+            let shard = shard?;
+            if !shard.file_type()?.is_dir() {
+                continue
+            }
+
+            let queue = FileQueue::new(shard.path())?;
+            for key in queue.keys()? {
+                let path = queue.dir().join(key.as_str());
+                let metadata = fs::metadata(&path)?;
+                let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                files.push((modified, path, metadata.len()));
+            }
+        }
+        files.sort();
+
+        let mut index = CacheIndex::default();
+        for (_, path, size) in files {
+            match read_item_id(&path) {
+                Ok(id) => index.insert(id, size),
+                // a file that was not written by the cache, or is corrupt
+                Err(_) => fs::remove_file(&path)?,
+            }
+        }
+
+        let cache = ReadCache {
+            inner: Some(Arc::new(ReadCacheInner {
+                dir: dir.to_path_buf(),
+                config,
+                index: Mutex::new(index),
+                metrics: ReadCacheMetrics::default(),
+            })),
+        };
+        cache.evict_to_limit()?;
+        cache.update_gauges();
+
+        Ok(cache)
+    }
+
+    /// Opens the cache below the node's datadir, a `max_bytes` of zero disables the cache.
+    pub fn at_datadir(datadir: &Path, config: ReadCacheConfig) -> io::Result<Self> {
+        if config.max_bytes == 0 {
+            return Ok(Self::disabled())
+        }
+
+        Self::new(&datadir.join(READ_CACHE_DIR), config)
+    }
+
+    pub fn disabled() -> Self {
+        ReadCache { inner: None }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.inner.is_some()
+    }
+
+    /// Wraps `storage` so the data it fetches is read through this cache.
+    pub fn wrap(
+        &self,
+        storage: Arc<dyn PermanentStorageBackend>,
+    ) -> Arc<dyn PermanentStorageBackend> {
+        if !self.is_enabled() {
+            return storage
+        }
+
+        Arc::new(CachedStorage { inner: storage, cache: self.clone() })
+    }
+
+    /// Returns the cached data of the item with `id`.
+    pub fn get(&self, id: &str) -> Option<Vec<u8>> {
+        let inner = self.inner.as_ref()?;
+
+        if !inner.index.lock().touch(id) {
+            inner.metrics.misses.increment(1);
+            return None
+        }
+
+        match read_item(&item_path(&inner.dir, id), id) {
+            Ok(data) => {
+                inner.metrics.hits.increment(1);
+                Some(data)
+            }
+            Err(_) => {
+                // removed from disk in the meantime
+                inner.index.lock().remove(id);
+                inner.metrics.misses.increment(1);
+                self.update_gauges();
+                None
+            }
+        }
+    }
+
+    /// Caches the data of the item with `id`, items above the size limit are skipped.
+    pub fn insert(&self, id: &str, data: &[u8]) -> io::Result<()> {
+        let Some(inner) = &self.inner else { return Ok(()) };
+        if data.len() as u64 > inner.config.max_item_bytes {
+            return Ok(())
+        }
+
+        let mut encoded = Vec::with_capacity(id.len() + data.len() + 8);
+        borsh::to_writer(&mut encoded, &(id, data))?;
+
+        let (shard, key) = item_key(id);
+        FileQueue::new(inner.dir.join(shard))?.push(key.as_str(), encoded.as_slice())?;
+        inner.index.lock().insert(id.to_string(), encoded.len() as u64);
+
+        self.evict_to_limit()?;
+        self.update_gauges();
+
+        Ok(())
+    }
+
+    /// Removes the item with `id`, returns whether it was cached.
+    pub fn evict(&self, id: &str) -> io::Result<bool> {
+        let Some(inner) = &self.inner else { return Ok(false) };
+
+        let removed = inner.index.lock().remove(id).is_some();
+        if removed {
+            remove_item(&inner.dir, id)?;
+            self.update_gauges();
+        }
+
+        Ok(removed)
+    }
+
+    /// Removes every item, returns the number of removed items.
+    pub fn clear(&self) -> io::Result<usize> {
+        let Some(inner) = &self.inner else { return Ok(0) };
+
+        let items = std::mem::take(&mut *inner.index.lock());
+        for id in items.items.keys() {
+            remove_item(&inner.dir, id)?;
+        }
+        self.update_gauges();
+
+        Ok(items.items.len())
+    }
+
+    /// Returns the cached items, most recently read first.
+    pub fn entries(&self) -> Vec<ReadCacheEntry> {
+        let Some(inner) = &self.inner else { return vec![] };
+
+        let index = inner.index.lock();
+        let mut items: Vec<_> = index.items.iter().collect();
+        items.sort_by_key(|(_, item)| std::cmp::Reverse(item.last_access));
+
+        items
+            .into_iter()
+            .map(|(id, item)| ReadCacheEntry { id: id.clone(), size: item.size })
+            .collect()
+    }
+
+    pub fn stats(&self) -> ReadCacheStats {
+        let Some(inner) = &self.inner else { return ReadCacheStats::default() };
+
+        let index = inner.index.lock();
+        ReadCacheStats {
+            entries: index.items.len() as u64,
+            size_bytes: index.size,
+            max_bytes: inner.config.max_bytes,
+        }
+    }
+
+    /// Evicts the least recently read items until the cache is within its size limit.
+    fn evict_to_limit(&self) -> io::Result<()> {
+        let Some(inner) = &self.inner else { return Ok(()) };
+
+        let evicted = {
+            let mut index = inner.index.lock();
+            if index.size <= inner.config.max_bytes {
+                return Ok(())
+            }
+
+            let mut items: Vec<(u64, String)> =
+                index.items.iter().map(|(id, item)| (item.last_access, id.clone())).collect();
+            items.sort_unstable();
+
+            let mut evicted = vec![];
+            for (_, id) in items {
+                if index.size <= inner.config.max_bytes {
+                    break
+                }
+                index.remove(id.as_str());
+                evicted.push(id);
+            }
+            evicted
+        };
+
+        for id in evicted.iter() {
+            remove_item(&inner.dir, id)?;
+        }
+        inner.metrics.evictions.increment(evicted.len() as u64);
+
+        Ok(())
+    }
+
+    fn update_gauges(&self) {
+        let Some(inner) = &self.inner else { return };
+
+        let index = inner.index.lock();
+        inner.metrics.entries.set(index.items.len() as f64);
+        inner.metrics.size_bytes.set(index.size as f64);
+    }
+}
+
+/// Returns the shard and file name of the item with `id`.
+fn item_key(id: &str) -> (String, String) {
+    let hash = keccak256(id.as_bytes());
+    (format!("{:02x}", hash[0]), format!("{hash:x}"))
+}
+
+fn item_path(dir: &Path, id: &str) -> PathBuf {
+    let (shard, key) = item_key(id);
+    dir.join(shard).join(key)
+}
+
+fn remove_item(dir: &Path, id: &str) -> io::Result<()> {
+    let (shard, key) = item_key(id);
+    FileQueue::new(dir.join(shard))?.remove(key.as_str())
+}
+
+/// Reads the id an item file starts with, without reading its data.
+fn read_item_id(path: &Path) -> io::Result<String> {
+    String::deserialize_reader(&mut BufReader::new(fs::File::open(path)?))
+}
+
+fn read_item(path: &Path, id: &str) -> io::Result<Vec<u8>> {
+    let (cached_id, data): (String, Vec<u8>) = borsh::from_slice(fs::read(path)?.as_slice())?;
+    if cached_id != id {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "cached item has another id"))
+    }
+
+    Ok(data)
+}
+
+/// Storage backend that reads the data of items through a [`ReadCache`].
+///
+/// Queries are not cached, the set of items matching a query grows over time.
+#[derive(Debug)]
+pub struct CachedStorage {
+    inner: Arc<dyn PermanentStorageBackend>,
+    cache: ReadCache,
+}
+
+#[async_trait::async_trait]
+impl PermanentStorageBackend for CachedStorage {
+    async fn upload(&self, data: Vec<u8>, tags: Vec<Tag>) -> eyre::Result<String> {
+        self.inner.upload(data, tags).await
+    }
+
+    async fn fetch(&self, id: &str) -> eyre::Result<Vec<u8>> {
+        if let Some(data) = self.cache.get(id) {
+            return Ok(data)
+        }
+
+        let data = self.inner.fetch(id).await?;
+        // the cache is best effort, the fetch succeeded either way
+        let _ = self.cache.insert(id, data.as_slice());
+
+        Ok(data)
+    }
+
+    /// Ranges are served from the cache, but never cached themselves.
+    async fn fetch_range(&self, id: &str, range: Range<usize>) -> eyre::Result<Vec<u8>> {
+        match self.cache.get(id) {
+            Some(data) => Ok(data_range(data, range)),
+            None => self.inner.fetch_range(id, range).await,
+        }
+    }
+
+    async fn query(&self, query: &TagQuery) -> eyre::Result<Vec<StoredItem>> {
+        self.inner.query(query).await
+    }
+
+    async fn upload_price(&self, bytes: usize) -> eyre::Result<Option<u128>> {
+        self.inner.upload_price(bytes).await
+    }
+
+    async fn upload_size(&self, bytes: usize, tags: &[Tag]) -> eyre::Result<usize> {
+        self.inner.upload_size(bytes, tags).await
+    }
+
+    async fn balance(&self) -> eyre::Result<Option<u128>> {
+        self.inner.balance().await
+    }
+
+    async fn dropped_uploads(&self) -> eyre::Result<Vec<DroppedUpload>> {
+        self.inner.dropped_uploads().await
+    }
+
+    async fn forget_dropped_upload(&self, id: &str) -> eyre::Result<()> {
+        self.inner.forget_dropped_upload(id).await
+    }
+
+    fn encode_id(&self, raw: &B256) -> String {
+        self.inner.encode_id(raw)
+    }
+
+    fn decode_id(&self, id: &str) -> Option<B256> {
+        self.inner.decode_id(id)
+    }
+}
+
+#[cfg(test)]
+mod read_cache_tests {
+    use crate::inner::read_cache::{ReadCache, ReadCacheConfig, ReadCacheEntry};
+    use irys::{backend::PermanentStorageBackend, local::LocalStorage};
+    use std::sync::Arc;
+
+    fn config(max_bytes: u64) -> ReadCacheConfig {
+        ReadCacheConfig { max_bytes, max_item_bytes: 1024 }
+    }
+
+    #[test]
+    pub fn test_read_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = ReadCache::new(dir.path(), config(1024 * 1024)).unwrap();
+
+        assert_eq!(cache.get("tx-1"), None);
+        cache.insert("tx-1", b"Hello world").unwrap();
+        cache.insert("tx-2", b"Hello again").unwrap();
+        assert_eq!(cache.get("tx-1"), Some(b"Hello world".to_vec()));
+
+        // items above the item limit are not cached
+        cache.insert("tx-3", vec![0; 2048].as_slice()).unwrap();
+        assert_eq!(cache.get("tx-3"), None);
+
+        // the cache survives a restart
+        drop(cache);
+        let cache = ReadCache::new(dir.path(), config(1024 * 1024)).unwrap();
+        assert_eq!(cache.stats().entries, 2);
+        assert_eq!(cache.get("tx-2"), Some(b"Hello again".to_vec()));
+
+        assert!(cache.evict("tx-2").unwrap());
+        assert!(!cache.evict("tx-2").unwrap());
+        assert_eq!(cache.get("tx-2"), None);
+        assert_eq!(cache.clear().unwrap(), 1);
+        assert_eq!(cache.stats().size_bytes, 0);
+    }
+
+    #[test]
+    pub fn test_read_cache_eviction() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = ReadCache::new(dir.path(), config(1024 * 1024)).unwrap();
+        cache.insert("tx-1", vec![1; 600].as_slice()).unwrap();
+        let item_size = cache.stats().size_bytes;
+
+        let cache = ReadCache::new(dir.path(), config(item_size * 2)).unwrap();
+        cache.insert("tx-2", vec![2; 600].as_slice()).unwrap();
+        assert!(cache.get("tx-1").is_some());
+
+        // the least recently read item goes first
+        cache.insert("tx-3", vec![3; 600].as_slice()).unwrap();
+        assert_eq!(cache.get("tx-2"), None);
+        assert_eq!(
+            cache.entries(),
+            vec![
+                ReadCacheEntry { id: "tx-3".to_string(), size: item_size },
+                ReadCacheEntry { id: "tx-1".to_string(), size: item_size },
+            ]
+        );
+        assert!(cache.stats().size_bytes <= cache.stats().max_bytes);
+    }
+
+    #[tokio::test]
+    pub async fn test_cached_storage() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage: Arc<dyn PermanentStorageBackend> =
+            Arc::new(LocalStorage::new(dir.path().join("storage")).unwrap());
+        let id = storage.upload(b"Hello world".to_vec(), vec![]).await.unwrap();
+
+        let cache = ReadCache::new(&dir.path().join("cache"), config(1024 * 1024)).unwrap();
+        let cached = cache.wrap(storage);
+        assert_eq!(cached.fetch(id.as_str()).await.unwrap(), b"Hello world".to_vec());
+        assert_eq!(cache.get(id.as_str()), Some(b"Hello world".to_vec()));
+
+        // a disabled cache does not wrap the backend
+        assert!(!ReadCache::disabled().is_enabled());
+        assert_eq!(ReadCache::disabled().get(id.as_str()), None);
+    }
+}
@@ -0,0 +1,525 @@
+use crate::inner::{
+    arweave_precompile::{ARWEAVE_UPLOAD, PC_ADDRESS},
+    arweave_query_precompile::ARWEAVE_QUERY,
+    arweave_read_precompile::ARWEAVE_READ,
+    gas::ReadGasSchedules,
+    kyve_precompile::KYVE_READ,
+    test_precompile::HELLO_WORLD,
+    util::AllowedGateway,
+    wevm_block_precompile::{ArchiveFilter, WEVM_BLOCK_READ},
+};
+use reth::{
+    primitives::{
+        revm_primitives::{
+            Env, Precompile, PrecompileOutput, PrecompileResult, StatefulPrecompileMut,
+        },
+        Address, Bytes, Genesis,
+    },
+    revm::precompile::{u64_to_address, PrecompileWithAddress},
+};
+use reth_chainspec::{ChainSpec, EthereumHardfork, ForkCondition};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, str::FromStr};
+
+/// Key of the precompile activations in the `config` section of the genesis file.
+pub const PRECOMPILE_ACTIVATIONS_KEY: &str = "wvmPrecompiles";
+/// Key of the activation of read sidecars in the `config` section of the genesis file.
+pub const READ_SIDECARS_KEY: &str = "wvmReadSidecars";
+/// Key of the activation of ABI-encoded calls of the string input precompiles in the `config`
+/// section of the genesis file.
+pub const ABI_CALLS_KEY: &str = "wvmAbiCalls";
+/// Key of the activation of the fields the string input of the block precompile gained later in
+/// the `config` section of the genesis file.
+pub const BLOCK_FIELDS_KEY: &str = "wvmBlockFields";
+/// Key of the gateways the read precompiles may be pointed at in the `config` section of the
+/// genesis file.
+pub const READ_GATEWAYS_KEY: &str = "wvmReadGateways";
+/// Key of the activation of the allow-list of read gateways in the `config` section of the
+/// genesis file.
+pub const READ_GATEWAY_ALLOW_LIST_KEY: &str = "wvmReadGatewayAllowList";
+
+/// Name and address of a WVM precompile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PrecompileSpec {
+    /// Name of the precompile in the chain spec and in read sidecars.
+    pub name: &'static str,
+    pub address: Address,
+    /// Activation of the precompile if it is not configured in the chain spec.
+    pub default_activation: PrecompileActivation,
+}
+
+impl PrecompileSpec {
+    /// A precompile of the first release, active from genesis unless configured otherwise.
+    const fn genesis(name: &'static str, address: u64) -> Self {
+        PrecompileSpec {
+            name,
+            address: u64_to_address(address),
+            default_activation: PrecompileActivation::ALWAYS,
+        }
+    }
+
+    /// A precompile added later, inactive until it is activated in the chain spec.
+    const fn later(name: &'static str, address: u64) -> Self {
+        PrecompileSpec {
+            name,
+            address: u64_to_address(address),
+            default_activation: PrecompileActivation::NEVER,
+        }
+    }
+}
+
+pub const ARWEAVE_UPLOAD_SPEC: PrecompileSpec = PrecompileSpec::genesis(ARWEAVE_UPLOAD, PC_ADDRESS);
+pub const ARWEAVE_READ_SPEC: PrecompileSpec = PrecompileSpec::genesis(ARWEAVE_READ, 0x18);
+pub const HELLO_WORLD_SPEC: PrecompileSpec = PrecompileSpec::genesis(HELLO_WORLD, 0x19);
+pub const WEVM_BLOCK_READ_SPEC: PrecompileSpec = PrecompileSpec::genesis(WEVM_BLOCK_READ, 0x20);
+pub const KYVE_READ_SPEC: PrecompileSpec = PrecompileSpec::genesis(KYVE_READ, 0x21);
+pub const ARWEAVE_QUERY_SPEC: PrecompileSpec = PrecompileSpec::later(ARWEAVE_QUERY, 0x22);
+
+/// Every WVM precompile.
+///
+/// Addresses are stable: a precompile keeps its address for good, and the address of a
+/// deprecated precompile is never reused, the precompile is deactivated in the chain spec
+/// instead. `0x1a` to `0x1f` were skipped by the first precompiles and are free.
+pub const WVM_PRECOMPILES: [PrecompileSpec; 6] = [
+    ARWEAVE_UPLOAD_SPEC,
+    ARWEAVE_READ_SPEC,
+    HELLO_WORLD_SPEC,
+    WEVM_BLOCK_READ_SPEC,
+    KYVE_READ_SPEC,
+    ARWEAVE_QUERY_SPEC,
+];
+
+/// Block, timestamp or Ethereum hardfork a precompile is activated or deactivated at, as given in
+/// the chain spec.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ForkConfig {
+    Block { block: u64 },
+    Time { time: u64 },
+    Hardfork { hardfork: String },
+}
+
+impl ForkConfig {
+    pub(crate) fn condition(&self, chain_spec: &ChainSpec) -> eyre::Result<ForkCondition> {
+        let condition = match self {
+            ForkConfig::Block { block } => ForkCondition::Block(*block),
+            ForkConfig::Time { time } => ForkCondition::Timestamp(*time),
+            ForkConfig::Hardfork { hardfork } => {
+                let hardfork = EthereumHardfork::from_str(hardfork)
+                    .map_err(|_| eyre::eyre!("unknown hardfork `{hardfork}`"))?;
+                chain_spec.fork(hardfork)
+            }
+        };
+
+        match condition {
+            ForkCondition::TTD { fork_block: Some(block), .. } => Ok(ForkCondition::Block(block)),
+            ForkCondition::TTD { .. } => {
+                eyre::bail!("precompiles can not be activated by total difficulty")
+            }
+            condition => Ok(condition),
+        }
+    }
+}
+
+/// Activation of a precompile, as given in the chain spec.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActivationConfig {
+    /// The default activation of the precompile if not set, see
+    /// [`PrecompileSpec::default_activation`].
+    pub activation: Option<ForkConfig>,
+    /// Never deactivated if not set.
+    pub deactivation: Option<ForkConfig>,
+}
+
+/// A gateway the read precompiles may be pointed at, as given in the chain spec.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadGatewayConfig {
+    pub url: String,
+    pub activation: ForkConfig,
+}
+
+/// Blocks a precompile is active at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PrecompileActivation {
+    pub activation: ForkCondition,
+    pub deactivation: ForkCondition,
+}
+
+impl PrecompileActivation {
+    /// Default activation of the precompiles of the first release.
+    pub const ALWAYS: PrecompileActivation = PrecompileActivation {
+        activation: ForkCondition::Block(0),
+        deactivation: ForkCondition::Never,
+    };
+
+    /// Default activation of the precompiles added later.
+    pub const NEVER: PrecompileActivation = PrecompileActivation {
+        activation: ForkCondition::Never,
+        deactivation: ForkCondition::Never,
+    };
+
+    pub fn is_active(&self, block_number: u64, timestamp: u64) -> bool {
+        let reached = |condition: &ForkCondition| {
+            condition.active_at_block(block_number) || condition.active_at_timestamp(timestamp)
+        };
+
+        reached(&self.activation) && !reached(&self.deactivation)
+    }
+}
+
+/// Activations, gas schedules and archivers of the WVM precompiles, configured by hardfork in the
+/// chain spec.
+///
+/// Activations are configured under [`PRECOMPILE_ACTIVATIONS_KEY`] in the `config` section of the
+/// genesis file, by the name of the precompile, e.g.
+///
+/// ```json
+/// "wvmPrecompiles": {
+///     "kyve-read": { "activation": { "hardfork": "cancun" } },
+///     "hello-world": { "deactivation": { "block": 1000000 } }
+/// }
+/// ```
+///
+/// Precompiles that are not configured keep their default activation: the precompiles of the
+/// first release, `0x17` to `0x21`, are active from genesis, later ones like `arweave-query` stay
+/// inactive until they are activated in the chain spec.
+///
+/// Blocks commit to a read sidecar from the fork configured under [`READ_SIDECARS_KEY`], e.g.
+/// `"wvmReadSidecars": { "block": 1000000 }`, and never if it is not configured. The precompiles
+/// that took string inputs before accept ABI-encoded calls from the fork configured under
+/// [`ABI_CALLS_KEY`] on, before it an input that starts with a selector is a string input. The
+/// string input of the block precompile serves `miner` and `number` from the fork configured under
+/// [`BLOCK_FIELDS_KEY`] on.
+///
+/// The read precompiles may be pointed at the gateways listed under [`READ_GATEWAYS_KEY`], each
+/// from its activation on, e.g.
+///
+/// ```json
+/// "wvmReadGateways": [
+///     { "url": "https://ar-io.dev", "activation": { "block": 1000000 } }
+/// ]
+/// ```
+///
+/// The read precompiles are restricted to these gateways from the fork configured under
+/// [`READ_GATEWAY_ALLOW_LIST_KEY`] on, before it they read from any gateway in their input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrecompileForks {
+    pub gas: ReadGasSchedules,
+    /// Archivers the archived blocks are read from.
+    pub archive: ArchiveFilter,
+    /// Blocks whose header commits to a read sidecar.
+    pub read_sidecars: ForkCondition,
+    /// Blocks the string input precompiles accept ABI-encoded calls at.
+    pub abi_calls: ForkCondition,
+    /// Blocks the string input of the block precompile serves every field at.
+    pub block_fields: ForkCondition,
+    /// Gateways the read precompiles may read from instead of the node's storage.
+    pub read_gateways: Vec<AllowedGateway>,
+    /// Blocks the read precompiles only read from the allowed gateways at.
+    pub read_gateway_allow_list: ForkCondition,
+    activations: HashMap<&'static str, PrecompileActivation>,
+}
+
+impl Default for PrecompileForks {
+    fn default() -> Self {
+        PrecompileForks {
+            gas: ReadGasSchedules::default(),
+            archive: ArchiveFilter::default(),
+            read_sidecars: ForkCondition::Never,
+            abi_calls: ForkCondition::Never,
+            block_fields: ForkCondition::Never,
+            read_gateways: vec![],
+            read_gateway_allow_list: ForkCondition::Never,
+            activations: HashMap::new(),
+        }
+    }
+}
+
+impl PrecompileForks {
+    pub fn from_chain_spec(chain_spec: &ChainSpec) -> eyre::Result<Self> {
+        let gas = ReadGasSchedules::from_chain_spec(chain_spec)?;
+        let archive = ArchiveFilter::from_chain_spec(chain_spec)?;
+        let mut activations = HashMap::new();
+        for (name, config) in activation_configs(chain_spec.genesis())? {
+            let Some(spec) = WVM_PRECOMPILES.iter().find(|spec| spec.name == name) else {
+                eyre::bail!("unknown precompile `{name}` in `{PRECOMPILE_ACTIVATIONS_KEY}`")
+            };
+
+            let condition = |fork: Option<&ForkConfig>, default| {
+                fork.map_or(Ok(default), |fork| fork.condition(chain_spec))
+                    .map_err(|err| eyre::eyre!("invalid activation of precompile `{name}`: {err}"))
+            };
+            let activation = PrecompileActivation {
+                activation: condition(
+                    config.activation.as_ref(),
+                    spec.default_activation.activation,
+                )?,
+                deactivation: condition(config.deactivation.as_ref(), ForkCondition::Never)?,
+            };
+            activations.insert(spec.name, activation);
+        }
+
+        let read_sidecars = fork_activation(chain_spec, READ_SIDECARS_KEY)?;
+        let abi_calls = fork_activation(chain_spec, ABI_CALLS_KEY)?;
+        let block_fields = fork_activation(chain_spec, BLOCK_FIELDS_KEY)?;
+        let read_gateways = read_gateways(chain_spec)?;
+        let read_gateway_allow_list = fork_activation(chain_spec, READ_GATEWAY_ALLOW_LIST_KEY)?;
+
+        Ok(PrecompileForks {
+            gas,
+            archive,
+            read_sidecars,
+            abi_calls,
+            block_fields,
+            read_gateways,
+            read_gateway_allow_list,
+            activations,
+        })
+    }
+
+    pub fn activation(&self, spec: &PrecompileSpec) -> PrecompileActivation {
+        self.activations.get(spec.name).copied().unwrap_or(spec.default_activation)
+    }
+
+    /// Installs `precompile` at the address of `spec`, active at the blocks configured for it.
+    pub fn install(&self, spec: PrecompileSpec, precompile: Precompile) -> PrecompileWithAddress {
+        let activation = self.activation(&spec);
+        if activation == PrecompileActivation::ALWAYS {
+            return PrecompileWithAddress(spec.address, precompile)
+        }
+
+        let activated = ActivatedPrecompile { precompile, activation };
+        PrecompileWithAddress(spec.address, Precompile::StatefulMut(Box::new(activated)))
+    }
+}
+
+fn activation_configs(genesis: &Genesis) -> eyre::Result<HashMap<String, ActivationConfig>> {
+    let Some(configs) = genesis.config.extra_fields.get(PRECOMPILE_ACTIVATIONS_KEY) else {
+        return Ok(HashMap::new())
+    };
+
+    serde_json::from_value(configs.clone()).map_err(|err| {
+        eyre::eyre!("invalid `{PRECOMPILE_ACTIVATIONS_KEY}` in the chain spec: {err}")
+    })
+}
+
+/// Returns the fork configured under `key`, [`ForkCondition::Never`] if it is not configured.
+fn fork_activation(chain_spec: &ChainSpec, key: &str) -> eyre::Result<ForkCondition> {
+    let Some(config) = chain_spec.genesis().config.extra_fields.get(key) else {
+        return Ok(ForkCondition::Never)
+    };
+
+    serde_json::from_value::<ForkConfig>(config.clone())
+        .map_err(eyre::Report::from)
+        .and_then(|fork| fork.condition(chain_spec))
+        .map_err(|err| eyre::eyre!("invalid `{key}` in the chain spec: {err}"))
+}
+
+/// Returns the gateways configured under [`READ_GATEWAYS_KEY`], none if it is not configured.
+fn read_gateways(chain_spec: &ChainSpec) -> eyre::Result<Vec<AllowedGateway>> {
+    let Some(configs) = chain_spec.genesis().config.extra_fields.get(READ_GATEWAYS_KEY) else {
+        return Ok(vec![])
+    };
+
+    let invalid =
+        |err: eyre::Report| eyre::eyre!("invalid `{READ_GATEWAYS_KEY}` in the chain spec: {err}");
+    let configs = serde_json::from_value::<Vec<ReadGatewayConfig>>(configs.clone())
+        .map_err(|err| invalid(eyre::Report::from(err)))?;
+    configs
+        .iter()
+        .map(|config| {
+            let activation = config.activation.condition(chain_spec).map_err(invalid)?;
+            Ok(AllowedGateway::new(config.url.as_str(), activation))
+        })
+        .collect()
+}
+
+/// Returns whether `condition` is reached at the block of `env`.
+pub fn fork_reached(condition: &ForkCondition, env: &Env) -> bool {
+    let block_number = env.block.number.saturating_to();
+    let timestamp = env.block.timestamp.saturating_to();
+    condition.active_at_block(block_number) || condition.active_at_timestamp(timestamp)
+}
+
+/// A precompile that is only active at some blocks.
+///
+/// Outside of them a call succeeds, uses no gas and returns nothing. Unlike an account without
+/// code, the address is still part of the precompile set at every block, so it is warm from the
+/// start of a transaction and accesses to it are charged the warm access cost.
+#[derive(Clone)]
+struct ActivatedPrecompile {
+    precompile: Precompile,
+    activation: PrecompileActivation,
+}
+
+impl StatefulPrecompileMut for ActivatedPrecompile {
+    fn call_mut(&mut self, bytes: &Bytes, gas_limit: u64, env: &Env) -> PrecompileResult {
+        let block_number = env.block.number.saturating_to();
+        let timestamp = env.block.timestamp.saturating_to();
+        if !self.activation.is_active(block_number, timestamp) {
+            return Ok(PrecompileOutput::new(0, Bytes::new()))
+        }
+
+        self.precompile.call(bytes, gas_limit, env)
+    }
+}
+
+#[cfg(test)]
+mod registry_tests {
+    use crate::inner::{
+        registry::{
+            PrecompileActivation, PrecompileForks, ARWEAVE_QUERY_SPEC, HELLO_WORLD_SPEC,
+            KYVE_READ_SPEC, PRECOMPILE_ACTIVATIONS_KEY, READ_GATEWAYS_KEY,
+            READ_GATEWAY_ALLOW_LIST_KEY, READ_SIDECARS_KEY, WVM_PRECOMPILES,
+        },
+        test_precompile::HELLO_WORLD_PC,
+        util::AllowedGateway,
+    };
+    use reth::{
+        primitives::{revm_primitives::Env, Genesis, U256},
+        revm::precompile::u64_to_address,
+    };
+    use reth_chainspec::{ChainSpec, ForkCondition};
+    use std::collections::HashSet;
+
+    fn chain_spec(activations: serde_json::Value) -> ChainSpec {
+        let mut genesis = Genesis::default();
+        genesis.config.cancun_time = Some(1_000);
+        genesis.config.extra_fields.insert(PRECOMPILE_ACTIVATIONS_KEY.to_string(), activations);
+        ChainSpec::from(genesis)
+    }
+
+    #[test]
+    pub fn test_precompile_addresses() {
+        let addresses: Vec<_> = WVM_PRECOMPILES.iter().map(|spec| spec.address).collect();
+        assert_eq!(
+            addresses,
+            [0x17, 0x18, 0x19, 0x20, 0x21, 0x22].map(u64_to_address).to_vec(),
+            "precompile addresses must never change"
+        );
+
+        let names: HashSet<_> = WVM_PRECOMPILES.iter().map(|spec| spec.name).collect();
+        assert_eq!(names.len(), WVM_PRECOMPILES.len());
+    }
+
+    #[test]
+    pub fn test_precompile_activations() {
+        let forks = PrecompileForks::from_chain_spec(&chain_spec(serde_json::json!({
+            "kyve-read": { "activation": { "hardfork": "cancun" } },
+            "hello-world": { "activation": { "block": 10 }, "deactivation": { "block": 20 } },
+        })))
+        .unwrap();
+
+        assert_eq!(forks.activation(&KYVE_READ_SPEC).activation, ForkCondition::Timestamp(1_000));
+        assert_eq!(forks.activation(&WVM_PRECOMPILES[0]), PrecompileActivation::ALWAYS);
+
+        let hello_world = forks.install(WVM_PRECOMPILES[2], HELLO_WORLD_PC);
+        let call = |block: u64| {
+            let mut env = Env::default();
+            env.block.number = U256::from(block);
+            hello_world.1.clone().call(&Default::default(), 100, &env).unwrap().bytes
+        };
+        assert!(call(9).is_empty());
+        assert_eq!(call(10).as_ref(), b"Hello World");
+        assert!(call(20).is_empty());
+
+        // precompiles added after genesis are inactive unless activated
+        assert_eq!(forks.activation(&ARWEAVE_QUERY_SPEC), PrecompileActivation::NEVER);
+        let query = forks.install(ARWEAVE_QUERY_SPEC, HELLO_WORLD_PC);
+        assert!(query
+            .1
+            .clone()
+            .call(&Default::default(), 100, &Env::default())
+            .unwrap()
+            .bytes
+            .is_empty());
+
+        let forks = PrecompileForks::from_chain_spec(&chain_spec(serde_json::json!({
+            "arweave-query": { "activation": { "block": 10 } },
+            "hello-world": { "deactivation": { "block": 20 } },
+        })))
+        .unwrap();
+        assert_eq!(forks.activation(&ARWEAVE_QUERY_SPEC).activation, ForkCondition::Block(10));
+        assert_eq!(forks.activation(&HELLO_WORLD_SPEC).activation, ForkCondition::Block(0));
+        let forks = PrecompileForks::from_chain_spec(&chain_spec(serde_json::json!({
+            "arweave-query": { "deactivation": { "block": 20 } },
+        })))
+        .unwrap();
+        assert_eq!(forks.activation(&ARWEAVE_QUERY_SPEC).activation, ForkCondition::Never);
+
+        assert!(PrecompileForks::from_chain_spec(&chain_spec(serde_json::json!({
+            "unknown": { "activation": { "block": 10 } },
+        })))
+        .is_err());
+        assert!(PrecompileForks::from_chain_spec(&chain_spec(serde_json::json!({
+            "kyve-read": { "activation": { "hardfork": "unknown" } },
+        })))
+        .is_err());
+    }
+
+    #[test]
+    pub fn test_read_sidecars_activation() {
+        let mut genesis = Genesis::default();
+        genesis.config.cancun_time = Some(1_000);
+        let forks = PrecompileForks::from_chain_spec(&ChainSpec::from(genesis.clone())).unwrap();
+        assert_eq!(forks.read_sidecars, ForkCondition::Never);
+
+        genesis
+            .config
+            .extra_fields
+            .insert(READ_SIDECARS_KEY.to_string(), serde_json::json!({ "block": 1000 }));
+        let forks = PrecompileForks::from_chain_spec(&ChainSpec::from(genesis.clone())).unwrap();
+        assert_eq!(forks.read_sidecars, ForkCondition::Block(1000));
+
+        genesis
+            .config
+            .extra_fields
+            .insert(READ_SIDECARS_KEY.to_string(), serde_json::json!({ "hardfork": "cancun" }));
+        let forks = PrecompileForks::from_chain_spec(&ChainSpec::from(genesis.clone())).unwrap();
+        assert_eq!(forks.read_sidecars, ForkCondition::Timestamp(1_000));
+
+        genesis.config.extra_fields.insert(READ_SIDECARS_KEY.to_string(), serde_json::json!(1000));
+        assert!(PrecompileForks::from_chain_spec(&ChainSpec::from(genesis)).is_err());
+    }
+
+    #[test]
+    pub fn test_read_gateways() {
+        let mut genesis = Genesis::default();
+        genesis.config.cancun_time = Some(1_000);
+        let forks = PrecompileForks::from_chain_spec(&ChainSpec::from(genesis.clone())).unwrap();
+        assert!(forks.read_gateways.is_empty());
+        assert_eq!(forks.read_gateway_allow_list, ForkCondition::Never);
+
+        genesis.config.extra_fields.insert(
+            READ_GATEWAYS_KEY.to_string(),
+            serde_json::json!([
+                { "url": "https://ar-io.dev/", "activation": { "block": 1000 } },
+                { "url": "https://g8way.io", "activation": { "hardfork": "cancun" } },
+            ]),
+        );
+        let forks = PrecompileForks::from_chain_spec(&ChainSpec::from(genesis.clone())).unwrap();
+        assert_eq!(
+            forks.read_gateways,
+            vec![
+                AllowedGateway::new("https://ar-io.dev", ForkCondition::Block(1000)),
+                AllowedGateway::new("https://g8way.io", ForkCondition::Timestamp(1_000)),
+            ]
+        );
+
+        genesis
+            .config
+            .extra_fields
+            .insert(READ_GATEWAY_ALLOW_LIST_KEY.to_string(), serde_json::json!({ "block": 500 }));
+        let forks = PrecompileForks::from_chain_spec(&ChainSpec::from(genesis.clone())).unwrap();
+        assert_eq!(forks.read_gateway_allow_list, ForkCondition::Block(500));
+
+        // gateways are only allowed from an explicit activation
+        genesis.config.extra_fields.insert(
+            READ_GATEWAYS_KEY.to_string(),
+            serde_json::json!([{ "url": "https://ar-io.dev" }]),
+        );
+        assert!(PrecompileForks::from_chain_spec(&ChainSpec::from(genesis)).is_err());
+    }
+}
@@ -0,0 +1,502 @@
+use crate::inner::{
+    io::PrecompileIo,
+    wevm_block_precompile::{load_zstd_dictionary, ARCHIVE_DECODERS},
+};
+use irys::backend::{PermanentStorageBackend, TagQuery};
+use outbox::sidecar::ReadSidecarOutbox;
+use reth::primitives::{Bytes, Header, B256};
+use reth_chainspec::ForkCondition;
+use reth_tracing::tracing::{debug, warn};
+use revm_primitives::{PrecompileError, PrecompileErrors, PrecompileOutput, PrecompileResult};
+use std::{
+    cell::RefCell,
+    io,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use wevm_borsh::{
+    format::PayloadTags,
+    sidecar::{sidecar_commitment, ReadSidecar, RecordedRead, READ_SIDECAR_TYPE, SIDECAR_HASH_TAG},
+};
+
+/// What happens to the reads of the precompiles while a block is executed on this thread.
+enum ReadSession {
+    /// A block is built, the result of every read is recorded.
+    Record(ReadSidecar),
+    /// A block is validated, every read is served from the sidecar the block commits to.
+    Replay { sidecar: ReadSidecar, next: usize, diverged: bool },
+}
+
+thread_local! {
+    static READ_SESSION: RefCell<Option<ReadSession>> = const { RefCell::new(None) };
+}
+
+/// Restores the session that was active before a nested one, also if the execution panics.
+struct SessionGuard(Option<ReadSession>);
+
+impl Drop for SessionGuard {
+    fn drop(&mut self) {
+        READ_SESSION.set(self.0.take());
+    }
+}
+
+fn with_session<R>(session: ReadSession, f: impl FnOnce() -> R) -> (R, Option<ReadSession>) {
+    let guard = SessionGuard(READ_SESSION.replace(Some(session)));
+    let result = f();
+    let session = READ_SESSION.take();
+    drop(guard);
+
+    (result, session)
+}
+
+/// Runs `f`, recording the result of every read precompile call on this thread.
+pub fn record_reads<R>(f: impl FnOnce() -> R) -> (R, ReadSidecar) {
+    match with_session(ReadSession::Record(ReadSidecar::default()), f) {
+        (result, Some(ReadSession::Record(sidecar))) => (result, sidecar),
+        (result, _) => (result, ReadSidecar::default()),
+    }
+}
+
+/// Runs `f`, serving every read precompile call on this thread from `sidecar`.
+///
+/// Returns whether the calls matched the sidecar exactly, i.e. every call was answered from the
+/// sidecar and every recorded read was used.
+pub fn replay_reads<R>(sidecar: ReadSidecar, f: impl FnOnce() -> R) -> (R, bool) {
+    let session = ReadSession::Replay { sidecar, next: 0, diverged: false };
+    let (result, session) = with_session(session, f);
+    let matched = matches!(
+        session,
+        Some(ReadSession::Replay { sidecar, next, diverged })
+            if !diverged && next == sidecar.reads.len()
+    );
+
+    (result, matched)
+}
+
+/// Returns whether the reads of the precompiles on this thread are recorded or replayed.
+pub fn in_read_session() -> bool {
+    READ_SESSION.with_borrow(|session| session.is_some())
+}
+
+/// Calls the network part of a read precompile, `fetch`, through the active read session.
+///
+/// Without a session, e.g. for `eth_call`, the data is fetched. While a block is built the result
+/// is recorded, and while a block is validated it is replayed from the sidecar of the block. A
+/// call that does not match the next recorded read fails, and so does the validation of the block.
+/// Replayed reads are charged the gas `gas_used` returns for the replayed data.
+pub fn oracle_read(
+    precompile: &str,
+    input: &Bytes,
+    gas_used: impl FnOnce(&[u8]) -> u64,
+    fetch: impl FnOnce() -> PrecompileResult,
+) -> PrecompileResult {
+    let replayed = READ_SESSION.with_borrow_mut(|session| {
+        let Some(ReadSession::Replay { sidecar, next, diverged }) = session else { return None };
+
+        let read = sidecar
+            .reads
+            .get(*next)
+            .filter(|read| read.precompile == precompile && read.input == input.as_ref());
+        let Some(read) = read else {
+            *diverged = true;
+            return Some(Err(PrecompileErrors::Error(PrecompileError::Other(
+                "Read is not part of the block's sidecar".to_string(),
+            ))))
+        };
+
+        *next += 1;
+        Some(match &read.output {
+            Ok(data) => Ok(PrecompileOutput::new(gas_used(data), data.clone().into())),
+            Err(message) if *message == PrecompileError::OutOfGas.to_string() => {
+                Err(PrecompileErrors::Error(PrecompileError::OutOfGas))
+            }
+            Err(message) => Err(PrecompileErrors::Error(PrecompileError::Other(message.clone()))),
+        })
+    });
+    if let Some(result) = replayed {
+        return result
+    }
+
+    let result = fetch();
+    READ_SESSION.with_borrow_mut(|session| {
+        let Some(ReadSession::Record(sidecar)) = session else { return };
+
+        let output = match &result {
+            Ok(output) => Ok(output.bytes.to_vec()),
+            Err(PrecompileErrors::Error(err)) => Err(err.to_string()),
+            // fatal errors abort the block, it is never built
+            Err(PrecompileErrors::Fatal { .. }) => return,
+        };
+        sidecar.reads.push(RecordedRead {
+            precompile: precompile.to_string(),
+            input: input.to_vec(),
+            output,
+        });
+    });
+
+    result
+}
+
+/// Name of the RPC method nodes serve the sidecars of their outbox with, see
+/// [`ReadSidecarApiServer`](crate::rpc::ReadSidecarApiServer).
+pub const READ_SIDECAR_METHOD: &str = "wvmReadSidecar_get";
+/// Time [`ReadSidecars::wait_for_sidecar`] first waits for a missing sidecar.
+const SIDECAR_RETRY_INTERVAL: Duration = Duration::from_secs(1);
+/// Longest time [`ReadSidecars::wait_for_sidecar`] waits between two lookups.
+const MAX_SIDECAR_RETRY_INTERVAL: Duration = Duration::from_secs(60);
+/// Longest time [`ReadSidecars::wait_for_sidecar`] waits for a missing sidecar in total.
+const MAX_SIDECAR_WAIT: Duration = Duration::from_secs(30 * 60);
+
+/// Error of looking up the sidecar a block commits to.
+#[derive(Debug, thiserror::Error)]
+pub enum SidecarError {
+    /// The header does not commit to a sidecar although it has to, the block is invalid.
+    #[error("block {0} does not commit to a read sidecar")]
+    NotCommitted(u64),
+    /// Neither the outbox, the peers nor the archive have the sidecar yet. The block may be valid,
+    /// the sidecar is usually archived shortly after the block.
+    #[error("read sidecar {commitment} of block {block_number} is not available yet")]
+    Missing { block_number: u64, commitment: B256 },
+    #[error("failed to read the sidecar outbox: {0}")]
+    Outbox(#[from] io::Error),
+}
+
+impl SidecarError {
+    /// Returns whether the lookup may succeed later, i.e. the block is not known to be invalid.
+    pub fn is_retryable(&self) -> bool {
+        !matches!(self, SidecarError::NotCommitted(_))
+    }
+}
+
+/// Read sidecars of the blocks, from the fork the chain spec activates them at.
+///
+/// The payload builder stages the sidecar of every block it builds in the local outbox. Blocks
+/// built by other nodes are validated with the sidecar their builder serves over RPC until it is
+/// archived, and with the sidecar archived next to the block afterwards.
+///
+/// Replayed reads are only checked against the commitment in the header, the data they return is
+/// not verified against Arweave. Validating nodes trust the builder of a block for what its reads
+/// returned, the sidecar only makes sure that every node replays the block to the same state.
+#[derive(Debug, Clone)]
+pub struct ReadSidecars {
+    outbox: Arc<ReadSidecarOutbox>,
+    storage: Arc<dyn PermanentStorageBackend>,
+    io: PrecompileIo,
+    /// Blocks whose header commits to a sidecar, see
+    /// [`PrecompileForks::read_sidecars`](crate::inner::registry::PrecompileForks).
+    activation: ForkCondition,
+    /// RPC URLs of the nodes asked for sidecars that are not archived yet.
+    peers: Vec<String>,
+    client: reqwest::Client,
+    /// Longest time [`ReadSidecars::wait_for_sidecar`] waits for a missing sidecar.
+    max_wait: Duration,
+}
+
+impl ReadSidecars {
+    pub fn new(
+        outbox: Arc<ReadSidecarOutbox>,
+        storage: Arc<dyn PermanentStorageBackend>,
+        io: PrecompileIo,
+        activation: ForkCondition,
+    ) -> Self {
+        ReadSidecars {
+            outbox,
+            storage,
+            io,
+            activation,
+            peers: vec![],
+            client: reqwest::Client::new(),
+            max_wait: MAX_SIDECAR_WAIT,
+        }
+    }
+
+    /// Asks the nodes at the RPC URLs `peers` for the sidecars of their blocks before they are
+    /// archived.
+    pub fn with_peers(mut self, peers: Vec<String>) -> Self {
+        self.peers = peers;
+        self
+    }
+
+    /// Sets the longest time [`ReadSidecars::wait_for_sidecar`] waits for a missing sidecar.
+    pub fn with_max_wait(mut self, max_wait: Duration) -> Self {
+        self.max_wait = max_wait;
+        self
+    }
+
+    /// Returns whether the block at `block_number` and `timestamp` commits to a sidecar.
+    pub fn is_active(&self, block_number: u64, timestamp: u64) -> bool {
+        self.activation.active_at_block(block_number) ||
+            self.activation.active_at_timestamp(timestamp)
+    }
+
+    pub fn outbox(&self) -> &ReadSidecarOutbox {
+        self.outbox.as_ref()
+    }
+
+    /// Returns the sidecar the block of `header` commits to, `None` if the block does not commit
+    /// to one.
+    pub fn sidecar(&self, header: &Header) -> Result<Option<ReadSidecar>, SidecarError> {
+        if !self.is_active(header.number, header.timestamp) {
+            return Ok(None)
+        }
+
+        let Some(commitment) = sidecar_commitment(&header.extra_data) else {
+            return Err(SidecarError::NotCommitted(header.number))
+        };
+        if commitment == ReadSidecar::empty_commitment() {
+            return Ok(Some(ReadSidecar::default()))
+        }
+        if let Some(sidecar) = self.outbox.get(header.number, commitment)? {
+            return Ok(Some(sidecar))
+        }
+
+        let block_number = header.number;
+        let storage = self.storage.clone();
+        let client = self.client.clone();
+        let peers = self.peers.clone();
+        let sidecar = self.io.run(async move {
+            for peer in &peers {
+                let sidecar = fetch_peer_sidecar(&client, peer, block_number, commitment).await;
+                match sidecar {
+                    Ok(Some(sidecar)) => return Some(sidecar),
+                    Ok(None) => {}
+                    Err(err) => debug!(%err, peer, block_number, "Failed to fetch read sidecar"),
+                }
+            }
+
+            fetch_archived_sidecar(storage.as_ref(), commitment).await.ok().flatten()
+        });
+
+        match sidecar {
+            Ok(Some(sidecar)) => Ok(Some(sidecar)),
+            _ => Err(SidecarError::Missing { block_number, commitment }),
+        }
+    }
+
+    /// Returns the sidecar the block of `header` commits to like [`ReadSidecars::sidecar`], but
+    /// waits for a missing sidecar for up to [`MAX_SIDECAR_WAIT`] by default before failing, see
+    /// [`ReadSidecars::with_max_wait`].
+    ///
+    /// Used where a failed block is costly to retry, e.g. by the sync pipeline. A sidecar that is
+    /// still missing afterwards fails with the retryable [`SidecarError::Missing`], so a sidecar
+    /// that never shows up does not stall the node forever.
+    pub fn wait_for_sidecar(&self, header: &Header) -> Result<Option<ReadSidecar>, SidecarError> {
+        let started = Instant::now();
+        let mut backoff = SIDECAR_RETRY_INTERVAL;
+        loop {
+            match self.sidecar(header) {
+                Err(err @ SidecarError::Missing { .. }) => {
+                    let waited = started.elapsed();
+                    if waited >= self.max_wait {
+                        return Err(err)
+                    }
+
+                    let delay = backoff.min(self.max_wait - waited);
+                    warn!(%err, retry_in = ?delay, "Waiting for read sidecar");
+                    std::thread::sleep(delay);
+                    backoff = (backoff * 2).min(MAX_SIDECAR_RETRY_INTERVAL);
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Asks the node at `peer` for the sidecar of `block_number` with `commitment`.
+pub async fn fetch_peer_sidecar(
+    client: &reqwest::Client,
+    peer: &str,
+    block_number: u64,
+    commitment: B256,
+) -> eyre::Result<Option<ReadSidecar>> {
+    let request = serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": READ_SIDECAR_METHOD,
+        "params": [block_number, commitment],
+    });
+    let response: serde_json::Value =
+        client.post(peer).json(&request).send().await?.error_for_status()?.json().await?;
+    if let Some(error) = response.get("error") {
+        eyre::bail!("peer failed to serve read sidecar: {error}")
+    }
+    let Some(data) = response.get("result").filter(|result| !result.is_null()) else {
+        return Ok(None)
+    };
+
+    let data: Bytes = serde_json::from_value(data.clone())?;
+    let sidecar: ReadSidecar = borsh::from_slice(data.as_ref())?;
+    // peers are not trusted, only a sidecar with the expected content is accepted
+    Ok((sidecar.commitment() == commitment).then_some(sidecar))
+}
+
+/// Fetches the archived read sidecar with `commitment`, `None` if it is not archived.
+pub async fn fetch_archived_sidecar(
+    storage: &dyn PermanentStorageBackend,
+    commitment: B256,
+) -> eyre::Result<Option<ReadSidecar>> {
+    let commitment_tag = commitment.to_string();
+    let items = storage
+        .query(
+            &TagQuery::new()
+                .with_tag("WeaveVM:Type", READ_SIDECAR_TYPE)
+                .with_tag(SIDECAR_HASH_TAG, commitment_tag.as_str()),
+        )
+        .await?;
+
+    // anyone can tag an item, so only a sidecar with the expected content is accepted
+    for item in items {
+        let tags = PayloadTags::from_fn(|tag| item.tag(tag));
+        if load_zstd_dictionary(storage, tags).await.is_err() {
+            continue
+        }
+
+        let Ok(data) = storage.fetch(item.id.as_str()).await else { continue };
+        let sidecar =
+            ARCHIVE_DECODERS.open(tags, data.as_slice()).and_then(|payload| payload.read_sidecar());
+        let Ok(sidecar) = sidecar else { continue };
+        if sidecar.commitment() == commitment {
+            return Ok(Some(sidecar))
+        }
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod sidecar_tests {
+    use crate::inner::{
+        io::PrecompileIo,
+        sidecar::{
+            in_read_session, oracle_read, record_reads, replay_reads, ReadSidecars, SidecarError,
+        },
+    };
+    use irys::{
+        backend::{PermanentStorageBackend, Tag},
+        local::LocalStorage,
+    };
+    use outbox::sidecar::ReadSidecarOutbox;
+    use reth::primitives::{Bytes, Header};
+    use reth_chainspec::ForkCondition;
+    use revm_primitives::{PrecompileError, PrecompileErrors, PrecompileOutput, PrecompileResult};
+    use std::{sync::Arc, time::Duration};
+    use wevm_borsh::{
+        codec::Codec,
+        format::{encode_payload, ENCODING_TAG, FORMAT_VERSION_TAG},
+        sidecar::{ReadSidecar, RecordedRead, READ_SIDECAR_TYPE, SIDECAR_HASH_TAG},
+    };
+
+    fn read(input: &'static str, data: &'static str) -> PrecompileResult {
+        let read_gas = |_: &[u8]| 100;
+        oracle_read("test-read", &Bytes::from(input), read_gas, || {
+            if data.is_empty() {
+                Err(PrecompileErrors::Error(PrecompileError::Other("Not found".to_string())))
+            } else {
+                Ok(PrecompileOutput::new(100, Bytes::from(data)))
+            }
+        })
+    }
+
+    #[test]
+    pub fn test_record_and_replay_reads() {
+        // without a session the data is fetched
+        assert!(!in_read_session());
+        assert_eq!(read("a", "live").unwrap().bytes, Bytes::from("live"));
+
+        let (_, sidecar) = record_reads(|| {
+            assert!(in_read_session());
+            assert!(read("a", "first").is_ok());
+            assert!(read("b", "").is_err());
+        });
+        assert_eq!(sidecar.reads.len(), 2);
+        assert_eq!(sidecar.reads[1].output, Err("Not found".to_string()));
+
+        // the network returns something else by now, the recorded bytes are replayed
+        let (results, matched) =
+            replay_reads(sidecar.clone(), || (read("a", "changed"), read("b", "found")));
+        assert!(matched);
+        assert_eq!(results.0.unwrap().bytes, Bytes::from("first"));
+        assert_eq!(results.1.err().unwrap().to_string(), "Not found");
+
+        // reads that are not part of the sidecar fail
+        let (result, matched) = replay_reads(sidecar.clone(), || read("b", "found"));
+        assert!(!matched);
+        assert!(result.is_err());
+
+        // so do blocks that leave out a recorded read
+        let (_, matched) = replay_reads(sidecar, || read("a", "first"));
+        assert!(!matched);
+
+        // the session ends with the execution
+        assert!(!in_read_session());
+        assert_eq!(read("a", "live").unwrap().bytes, Bytes::from("live"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    pub async fn test_read_sidecars() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage: Arc<dyn PermanentStorageBackend> =
+            Arc::new(LocalStorage::new(dir.path().join("storage")).unwrap());
+        let outbox = Arc::new(ReadSidecarOutbox::new(&dir.path().join("outbox")).unwrap());
+        let io = PrecompileIo::new(Default::default()).unwrap();
+        let sidecars =
+            ReadSidecars::new(outbox.clone(), storage.clone(), io, ForkCondition::Block(10));
+
+        let sidecar = ReadSidecar {
+            reads: vec![RecordedRead {
+                precompile: "arweave-read".to_string(),
+                input: b"tx".to_vec(),
+                output: Ok(b"data".to_vec()),
+            }],
+        };
+        let commitment = sidecar.commitment();
+        let header = |number: u64, extra_data: &[u8]| Header {
+            number,
+            extra_data: Bytes::copy_from_slice(extra_data),
+            ..Default::default()
+        };
+
+        // blocks before the activation do not commit to a sidecar
+        assert_eq!(sidecars.sidecar(&header(9, b"reth")).unwrap(), None);
+        assert!(matches!(
+            sidecars.sidecar(&header(10, b"reth")),
+            Err(SidecarError::NotCommitted(10))
+        ));
+        assert_eq!(
+            sidecars.sidecar(&header(10, ReadSidecar::empty_commitment().as_slice())).unwrap(),
+            Some(ReadSidecar::default())
+        );
+
+        // the sidecar is neither staged nor archived yet, the block may be executed later
+        let err = sidecars.sidecar(&header(10, commitment.as_slice())).unwrap_err();
+        assert!(matches!(err, SidecarError::Missing { block_number: 10, .. }));
+        assert!(err.is_retryable());
+
+        // waiting for a sidecar that never shows up gives up eventually
+        let waiting = sidecars.clone().with_max_wait(Duration::from_millis(10));
+        let err = waiting.wait_for_sidecar(&header(10, commitment.as_slice())).unwrap_err();
+        assert!(matches!(err, SidecarError::Missing { block_number: 10, .. }));
+
+        outbox.stage(10, &sidecar).unwrap();
+        assert_eq!(
+            sidecars.sidecar(&header(10, commitment.as_slice())).unwrap(),
+            Some(sidecar.clone())
+        );
+
+        let codec = Codec::default();
+        let commitment_tag = commitment.to_string();
+        let tags = vec![
+            Tag::new(ENCODING_TAG, codec.encoding()),
+            Tag::new(FORMAT_VERSION_TAG, "1"),
+            Tag::new("WeaveVM:Type", READ_SIDECAR_TYPE),
+            Tag::new(SIDECAR_HASH_TAG, commitment_tag.as_str()),
+        ];
+        let data = codec.compress(&encode_payload(&sidecar).unwrap()).unwrap();
+        storage.upload(data, tags.clone()).await.unwrap();
+        // candidates that don't decode are skipped
+        storage.upload(b"not a sidecar".to_vec(), tags).await.unwrap();
+
+        // other nodes fetch the archived sidecar
+        assert_eq!(sidecars.sidecar(&header(11, commitment.as_slice())).unwrap(), Some(sidecar));
+    }
+}
@@ -42,7 +42,7 @@ impl From<BorshSealedBlockWithSenders> for Block {
             gas_used: Some(sealed_block.gas_used.to_string()),
             hash: Some(sealed_block.hash().to_string()),
             logs_bloom: Some(sealed_block.logs_bloom.to_string()),
-            miner: None,
+            miner: Some(sealed_block.beneficiary.to_string()),
             mix_hash: Some(sealed_block.mix_hash.to_string()),
             nonce: Some(sealed_block.nonce.to_string()),
             number: Some(sealed_block.number.to_string()),
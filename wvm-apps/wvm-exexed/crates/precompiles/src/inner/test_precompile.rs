@@ -1,10 +1,21 @@
+use crate::inner::abi::IHelloWorld;
+use alloy_sol_types::SolCall;
 use reth::primitives::{
     revm_primitives::{Precompile, PrecompileOutput, PrecompileResult},
     Bytes,
 };
 
+/// Name of the precompile in the chain spec.
+pub const HELLO_WORLD: &str = "hello-world";
+
 pub const HELLO_WORLD_PC: Precompile = Precompile::Standard(hello_world_pc);
 
-fn hello_world_pc(_input: &Bytes, _gas_limit: u64) -> PrecompileResult {
-    Ok(PrecompileOutput::new(0 as u64, "Hello World".into()))
+fn hello_world_pc(input: &Bytes, _gas_limit: u64) -> PrecompileResult {
+    let message = "Hello World";
+    if input.starts_with(&IHelloWorld::helloCall::SELECTOR) {
+        let output = IHelloWorld::helloCall::abi_encode_returns(&(message.to_string(),));
+        return Ok(PrecompileOutput::new(0, output.into()))
+    }
+
+    Ok(PrecompileOutput::new(0 as u64, message.into()))
 }
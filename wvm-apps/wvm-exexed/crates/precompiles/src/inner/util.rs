@@ -1,24 +1,188 @@
-use revm_primitives::{PrecompileError, PrecompileErrors, PrecompileOutput};
+use crate::inner::registry::fork_reached;
+use irys::{
+    backend::PermanentStorageBackend,
+    gateway::{clean_gateway_url, ArweaveGateway},
+};
+use reth::primitives::revm_primitives::{
+    Env, PrecompileError, PrecompileErrors, PrecompileOutput, PrecompileResult,
+};
+use reth_chainspec::ForkCondition;
+use std::{collections::HashMap, sync::Arc};
 
 pub const DEFAULT_ARWEAVE_TX_ENDPOINT: &str = "https://arweave.net/";
 
-pub fn clean_gateway_url(gateway: &str) -> String {
-    let clean_gateway =
-        if gateway.ends_with('/') { &gateway[..gateway.len() - 1] } else { gateway };
+/// A gateway the read precompiles may be pointed at from `activation` on, as configured in the
+/// chain spec.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AllowedGateway {
+    pub url: String,
+    pub activation: ForkCondition,
+}
 
-    clean_gateway.to_string()
+impl AllowedGateway {
+    pub fn new(url: &str, activation: ForkCondition) -> Self {
+        AllowedGateway { url: clean_gateway_url(url), activation }
+    }
+}
+
+/// Returns whether `gateway` is the default gateway, which reads from the node's storage backend.
+pub fn is_default_gateway(gateway: &str) -> bool {
+    clean_gateway_url(gateway) == clean_gateway_url(DEFAULT_ARWEAVE_TX_ENDPOINT)
+}
+
+/// Gateways the read precompiles may be pointed at in their input, allowed by the chain spec.
+///
+/// Only layer 1 transactions can be verified against their signed header, reading data items of
+/// bundles from these gateways fails. No gateway is allowed by default.
+///
+/// Before the activation of the allow-list, see [`ReadGateways::with_allow_list`], any gateway is
+/// read from as it is, like blocks before it were executed.
+#[derive(Debug, Clone)]
+pub struct ReadGateways {
+    gateways: Arc<HashMap<String, (ForkCondition, Arc<dyn PermanentStorageBackend>)>>,
+    allow_list: ForkCondition,
+}
+
+impl Default for ReadGateways {
+    fn default() -> Self {
+        ReadGateways { gateways: Arc::default(), allow_list: ForkCondition::Block(0) }
+    }
 }
 
-pub async fn download_tx(
-    gas_used: u64,
-    clean_gateway: String,
-    tx_id: String,
-) -> Result<PrecompileOutput, PrecompileErrors> {
-    let download_tx = reqwest::get(format!("{}/{}", clean_gateway, tx_id.as_str())).await;
-    match download_tx {
-        Ok(tx) => Ok(PrecompileOutput::new(gas_used, tx.bytes().await.unwrap().into())),
-        Err(_) => Err(PrecompileErrors::Error(PrecompileError::Other(
-            "Arweave Transaction was not found".to_string(),
-        ))),
+impl ReadGateways {
+    pub fn new<'a>(allowed: impl IntoIterator<Item = &'a AllowedGateway>) -> Self {
+        let gateways = allowed
+            .into_iter()
+            .map(|allowed| {
+                let gateway: Arc<dyn PermanentStorageBackend> =
+                    Arc::new(ArweaveGateway::new(allowed.url.as_str()));
+                (allowed.url.clone(), (allowed.activation, gateway))
+            })
+            .collect();
+
+        ReadGateways { gateways: Arc::new(gateways), ..Default::default() }
+    }
+
+    /// Restricts reads to the allowed gateways from `activation` on, from genesis by default.
+    pub fn with_allow_list(mut self, activation: ForkCondition) -> Self {
+        self.allow_list = activation;
+        self
+    }
+
+    /// Returns the gateways that are allowed at the block of `env`.
+    pub fn active_at(&self, env: &Env) -> Self {
+        if !fork_reached(&self.allow_list, env) {
+            return ReadGateways { gateways: Arc::default(), allow_list: ForkCondition::Never }
+        }
+
+        let gateways = self
+            .gateways
+            .iter()
+            .filter(|(_, (activation, _))| fork_reached(activation, env))
+            .map(|(url, gateway)| (url.clone(), gateway.clone()))
+            .collect();
+
+        ReadGateways { gateways: Arc::new(gateways), allow_list: self.allow_list }
+    }
+
+    /// Returns the storage to read from for a gateway given in the precompile input.
+    ///
+    /// Without a custom gateway the node's configured storage backend is used, a gateway that is
+    /// not allowed is an error once the allow-list is active.
+    pub fn storage(
+        &self,
+        storage: &Arc<dyn PermanentStorageBackend>,
+        gateway: &str,
+    ) -> Result<Arc<dyn PermanentStorageBackend>, PrecompileErrors> {
+        if is_default_gateway(gateway) {
+            return Ok(storage.clone())
+        }
+
+        // reads from gateways predating the allow-list trust the gateway, like they always did
+        if self.allow_list == ForkCondition::Never {
+            return Ok(Arc::new(ArweaveGateway::new(gateway).with_trusted_data_items(true)))
+        }
+
+        let allowed = self.gateways.get(&clean_gateway_url(gateway));
+        allowed.map(|(_, gateway)| gateway.clone()).ok_or_else(|| {
+            PrecompileErrors::Error(PrecompileError::Other(format!(
+                "Gateway {} is not allowed",
+                gateway
+            )))
+        })
+    }
+}
+
+/// Returns `data` as the output of a read that used `gas_used`, if it is within `gas_limit`.
+pub fn paid_output(gas_used: u64, data: Vec<u8>, gas_limit: u64) -> PrecompileResult {
+    if gas_used > gas_limit {
+        return Err(PrecompileErrors::Error(PrecompileError::OutOfGas))
+    }
+
+    Ok(PrecompileOutput::new(gas_used, data.into()))
+}
+
+#[cfg(test)]
+mod util_tests {
+    use crate::inner::util::{
+        is_default_gateway, AllowedGateway, ReadGateways, DEFAULT_ARWEAVE_TX_ENDPOINT,
+    };
+    use irys::{backend::PermanentStorageBackend, local::LocalStorage};
+    use reth::primitives::{revm_primitives::Env, U256};
+    use reth_chainspec::ForkCondition;
+    use std::sync::Arc;
+
+    #[test]
+    pub fn test_read_gateways() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage: Arc<dyn PermanentStorageBackend> =
+            Arc::new(LocalStorage::new(dir.path()).unwrap());
+        let gateways = ReadGateways::new(&[AllowedGateway::new(
+            "https://ar-io.dev/",
+            ForkCondition::Block(0),
+        )]);
+
+        let default = gateways.storage(&storage, DEFAULT_ARWEAVE_TX_ENDPOINT).unwrap();
+        assert!(Arc::ptr_eq(&default, &storage));
+        let default = gateways.storage(&storage, "https://arweave.net").unwrap();
+        assert!(Arc::ptr_eq(&default, &storage));
+        assert!(is_default_gateway("https://arweave.net"));
+        assert!(!is_default_gateway("https://arweave.net.evil"));
+        assert!(gateways.storage(&storage, "https://ar-io.dev").is_ok());
+        assert!(gateways.storage(&storage, "https://ar-io.dev/").is_ok());
+        assert!(gateways.storage(&storage, "http://localhost:1984").is_err());
+        assert!(ReadGateways::default().storage(&storage, "https://ar-io.dev").is_err());
+    }
+
+    #[test]
+    pub fn test_read_gateways_activation() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage: Arc<dyn PermanentStorageBackend> =
+            Arc::new(LocalStorage::new(dir.path()).unwrap());
+        let gateways = ReadGateways::new(&[
+            AllowedGateway::new("https://ar-io.dev", ForkCondition::Block(10)),
+            AllowedGateway::new("https://g8way.io", ForkCondition::Never),
+        ]);
+        let at_block = |block: u64| {
+            let mut env = Env::default();
+            env.block.number = U256::from(block);
+            gateways.active_at(&env)
+        };
+
+        assert!(at_block(9).storage(&storage, "https://ar-io.dev").is_err());
+        assert!(at_block(10).storage(&storage, "https://ar-io.dev").is_ok());
+        assert!(at_block(10).storage(&storage, "https://g8way.io").is_err());
+        assert!(at_block(10).storage(&storage, DEFAULT_ARWEAVE_TX_ENDPOINT).is_ok());
+
+        // before the allow-list is activated any gateway is read from
+        let gateways = gateways.with_allow_list(ForkCondition::Block(5));
+        let at_block = |block: u64| {
+            let mut env = Env::default();
+            env.block.number = U256::from(block);
+            gateways.active_at(&env)
+        };
+        assert!(at_block(4).storage(&storage, "http://localhost:1984").is_ok());
+        assert!(at_block(5).storage(&storage, "http://localhost:1984").is_err());
+        assert!(at_block(5).storage(&storage, "https://arweave.net").is_ok());
     }
 }
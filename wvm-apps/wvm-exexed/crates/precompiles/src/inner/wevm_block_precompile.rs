@@ -1,15 +1,167 @@
-use crate::inner::graphql_util::send_graphql;
-use crate::inner::string_block::Block;
-use crate::inner::util::{clean_gateway_url, download_tx, DEFAULT_ARWEAVE_TX_ENDPOINT};
-use rbrotli::from_brotli;
-use reth::primitives::revm_primitives::{Precompile, PrecompileOutput, PrecompileResult};
-use reth::primitives::Bytes;
+use crate::inner::{
+    abi::{
+        decode_call, BlockField, BlockWithdrawal,
+        IWvmBlockRead::{self, IWvmBlockReadCalls},
+    },
+    gas::{ReadGasSchedule, ReadGasSchedules},
+    io::PrecompileIo,
+    registry::fork_reached,
+    sidecar::oracle_read,
+    string_block::Block,
+    util::{paid_output, ReadGateways, DEFAULT_ARWEAVE_TX_ENDPOINT},
+};
+use alloy_sol_types::SolCall;
+use irys::backend::{PermanentStorageBackend, StoredItem, TagQuery};
+use reth::primitives::{
+    proofs::{
+        calculate_ommers_root, calculate_receipt_root_no_memo, calculate_transaction_root,
+        calculate_withdrawals_root,
+    },
+    revm_primitives::{Env, Precompile, PrecompileResult, StatefulPrecompile},
+    Bytes, Genesis, Receipt, SealedBlock, SealedBlockWithSenders, TransactionSigned, B256, U256,
+};
+use reth_chainspec::{ChainSpec, ForkCondition};
 use revm_primitives::{PrecompileError, PrecompileErrors};
-use wevm_borsh::block::BorshSealedBlockWithSenders;
+use std::{
+    collections::HashSet,
+    sync::{Arc, LazyLock},
+};
+use wevm_borsh::{
+    block::BorshSealedBlockWithSenders,
+    codec::{
+        ZstdDictionary, BORSH_BROTLI_ENCODING, BORSH_ENCODING, BORSH_ZSTD_ENCODING,
+        ZSTD_DICTIONARY_TAG, ZSTD_DICTIONARY_TYPE,
+    },
+    format::{DecoderRegistry, PayloadTags, ENCODING_TAG},
+    outcome::BlockOutcome,
+};
 
-pub const WEVM_BLOCK_PC: Precompile = Precompile::Standard(wevm_read_block_pc);
+/// Name of the precompile in read sidecars.
+pub const WEVM_BLOCK_READ: &str = "wvm-block-read";
 
-pub const WEVM_BLOCK_PC_READ_BASE: u64 = 10_000;
+/// Maximum number of archived blocks considered for a single height.
+const MAX_BLOCK_CANDIDATES: usize = 10;
+
+/// Key of the archiver addresses in the `config` section of the genesis file.
+pub const ARCHIVERS_KEY: &str = "wvmArchivers";
+
+/// Decoders of every archive format written so far.
+pub(crate) static ARCHIVE_DECODERS: LazyLock<DecoderRegistry> =
+    LazyLock::new(DecoderRegistry::default);
+
+/// Uploaders and network of the archived items blocks are read from.
+///
+/// Anyone can upload an item with the tags of a block or a revert marker, so blocks are only read
+/// from the archivers of the chain. They are configured as Arweave addresses under
+/// [`ARCHIVERS_KEY`] in the `config` section of the genesis file, e.g.
+///
+/// ```json
+/// "wvmArchivers": ["5JUE58yemNynRDeQDyVECKbGVCQbnX7unPrBRqCPVn5Z"]
+/// ```
+///
+/// Without archivers items of every uploader are read, e.g. from the local storage of a devnet,
+/// which does not know the owners of its items.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ArchiveFilter {
+    /// Arweave addresses of the archivers, any uploader if empty.
+    pub owners: Vec<String>,
+    /// `Network` tag of the items, any network if not set.
+    pub network: Option<String>,
+}
+
+impl ArchiveFilter {
+    pub fn new(owners: Vec<String>) -> Self {
+        ArchiveFilter { owners, network: None }
+    }
+
+    pub fn from_genesis(genesis: &Genesis) -> eyre::Result<Self> {
+        let Some(owners) = genesis.config.extra_fields.get(ARCHIVERS_KEY) else {
+            return Ok(Self::default())
+        };
+
+        let owners = serde_json::from_value(owners.clone())
+            .map_err(|err| eyre::eyre!("invalid `{ARCHIVERS_KEY}` in the chain spec: {err}"))?;
+        Ok(Self::new(owners))
+    }
+
+    pub fn from_chain_spec(chain_spec: &ChainSpec) -> eyre::Result<Self> {
+        Self::from_genesis(chain_spec.genesis())
+    }
+
+    /// Only reads items with the `Network` tag `network`.
+    pub fn with_network(mut self, network: &str) -> Self {
+        self.network = Some(network.to_string());
+        self
+    }
+
+    /// Restricts `query` to the items of the archivers.
+    pub fn apply(&self, mut query: TagQuery) -> TagQuery {
+        for owner in &self.owners {
+            query = query.with_owner(owner);
+        }
+
+        match &self.network {
+            Some(network) => query.with_tag("Network", network),
+            None => query,
+        }
+    }
+}
+
+/// Reads archived WVM blocks, looked up by their `Block-Number` tag.
+///
+/// The input is either a `gateway;block;field` string, returning the field as a string, or, from
+/// the activation of ABI calls on, an ABI-encoded call of [`IWvmBlockRead`], returning headers,
+/// transactions, senders, withdrawals and receipts ABI-encoded. The gateway of a string input has
+/// to be one of the [`ReadGateways`] allowed by the chain spec at the block once the allow-list is
+/// active. Reads are charged by the size of their output, see [`ReadGasSchedules`]. Only blocks
+/// uploaded by the archivers of the [`ArchiveFilter`] are read, ABI-encoded calls only read blocks
+/// that pass [`verify_archived_block`].
+///
+/// The string input serves the `miner` and `number` fields from the fork configured under
+/// [`BLOCK_FIELDS_KEY`](crate::inner::registry::BLOCK_FIELDS_KEY) on.
+#[derive(Debug, Clone)]
+pub struct WevmBlockPrecompile {
+    storage: Arc<dyn PermanentStorageBackend>,
+    gateways: ReadGateways,
+    gas: ReadGasSchedules,
+    archive: ArchiveFilter,
+    io: PrecompileIo,
+    /// Blocks ABI-encoded calls are accepted at.
+    abi_calls: ForkCondition,
+    /// Blocks the string input serves every field at.
+    block_fields: ForkCondition,
+}
+
+impl WevmBlockPrecompile {
+    pub fn new(
+        storage: Arc<dyn PermanentStorageBackend>,
+        gateways: ReadGateways,
+        gas: ReadGasSchedules,
+        archive: ArchiveFilter,
+        io: PrecompileIo,
+        abi_calls: ForkCondition,
+        block_fields: ForkCondition,
+    ) -> Self {
+        WevmBlockPrecompile { storage, gateways, gas, archive, io, abi_calls, block_fields }
+    }
+
+    pub fn into_precompile(self) -> Precompile {
+        Precompile::Stateful(Arc::new(self))
+    }
+}
+
+impl StatefulPrecompile for WevmBlockPrecompile {
+    fn call(&self, input: &Bytes, gas_limit: u64, env: &Env) -> PrecompileResult {
+        let gas = self.gas.at(env);
+        let abi_calls = fork_reached(&self.abi_calls, env);
+        let all_fields = fork_reached(&self.block_fields, env);
+        let gateways = self.gateways.active_at(env);
+        let Self { io, storage, archive, .. } = self;
+        wevm_read_block_pc(
+            io, storage, &gateways, archive, gas, input, gas_limit, abi_calls, all_fields,
+        )
+    }
+}
 
 pub fn parse_req_input(input: &str) -> (String, String, String) {
     let default_endpoint = DEFAULT_ARWEAVE_TX_ENDPOINT;
@@ -27,168 +179,1074 @@ pub fn parse_req_input(input: &str) -> (String, String, String) {
     (endpoint, second_part, third_part)
 }
 
-fn wevm_read_block_pc(input: &Bytes, gas_limit: u64) -> PrecompileResult {
-    let data_size = input.len();
-    let gas_used: u64 = (WEVM_BLOCK_PC_READ_BASE as usize + data_size * 3) as u64;
+/// Resolves the archived block at `block_number`, skipping blocks that were superseded by a reorg
+/// and have a revert marker.
+///
+/// Only blocks and markers that match `filter` are considered.
+pub async fn find_canonical_block(
+    storage: &dyn PermanentStorageBackend,
+    block_number: u64,
+    filter: &ArchiveFilter,
+) -> eyre::Result<Option<BorshSealedBlockWithSenders>> {
+    find_archived_block(storage, block_number, filter, |_| true).await
+}
+
+/// Like [`find_canonical_block`], but skips every candidate rejected by `accept`, e.g. because it
+/// does not link to its parent.
+pub async fn find_archived_block(
+    storage: &dyn PermanentStorageBackend,
+    block_number: u64,
+    filter: &ArchiveFilter,
+    accept: impl Fn(&SealedBlockWithSenders) -> bool,
+) -> eyre::Result<Option<BorshSealedBlockWithSenders>> {
+    find_block(storage, block_number, filter, |_, block| accept(block)).await
+}
+
+/// Like [`find_canonical_block`], but skips every candidate that does not pass
+/// [`verify_archived_block`] or is tagged with the hash of another block.
+pub async fn find_verified_block(
+    storage: &dyn PermanentStorageBackend,
+    block_number: u64,
+    filter: &ArchiveFilter,
+) -> eyre::Result<Option<BorshSealedBlockWithSenders>> {
+    find_block(storage, block_number, filter, |item, block| {
+        // batches are not tagged with the hashes of their blocks
+        let hash_tag = item.tag("Block-Hash");
+        hash_tag.map_or(true, |hash| hash == block.hash().to_string()) &&
+            verify_archived_block(block).is_ok()
+    })
+    .await
+}
+
+/// Checks that an archived block is sealed with the hash of its header, that the header commits
+/// to its transactions, ommers and withdrawals, and that its senders signed its transactions.
+///
+/// The decoders take the hash of a block as archived, so a block that does not pass this check
+/// may be anything its uploader made up.
+pub fn verify_archived_block(block: &SealedBlockWithSenders) -> eyre::Result<()> {
+    let number = block.number;
+    if block.header.header().hash_slow() != block.hash() {
+        eyre::bail!("archived block {number} is not sealed with the hash of its header")
+    }
+
+    if calculate_transaction_root(&block.body) != block.transactions_root {
+        eyre::bail!("transactions of archived block {number} do not match its transactions root")
+    }
+
+    if calculate_ommers_root(&block.ommers) != block.ommers_hash {
+        eyre::bail!("ommers of archived block {number} do not match its ommers hash")
+    }
+
+    let withdrawals_root =
+        block.withdrawals.as_ref().map(|withdrawals| calculate_withdrawals_root(withdrawals));
+    if withdrawals_root != block.withdrawals_root {
+        eyre::bail!("withdrawals of archived block {number} do not match its withdrawals root")
+    }
+
+    let senders =
+        block.body.iter().map(TransactionSigned::recover_signer).collect::<Option<Vec<_>>>();
+    if senders.as_ref() != Some(&block.senders) {
+        eyre::bail!("senders of archived block {number} did not sign its transactions")
+    }
+
+    Ok(())
+}
+
+async fn find_block(
+    storage: &dyn PermanentStorageBackend,
+    block_number: u64,
+    filter: &ArchiveFilter,
+    accept: impl Fn(&StoredItem, &SealedBlockWithSenders) -> bool,
+) -> eyre::Result<Option<BorshSealedBlockWithSenders>> {
+    let block_tag = block_number.to_string();
+
+    let markers = storage
+        .query(
+            &filter.apply(
+                TagQuery::new()
+                    .with_tag("WeaveVM:Type", "Revert-Marker")
+                    .with_tag("Block-Number", block_tag.as_str()),
+            ),
+        )
+        .await?;
+    let reverted: HashSet<&str> =
+        markers.iter().filter_map(|marker| marker.tag("Block-Hash")).collect();
+
+    // single blocks and batches are both tagged with the number of every block they contain
+    let candidates = storage
+        .query(
+            &filter.apply(
+                TagQuery::new()
+                    .with_tag("Block-Number", block_tag.as_str())
+                    .with_tag_values(
+                        ENCODING_TAG,
+                        &[BORSH_BROTLI_ENCODING, BORSH_ZSTD_ENCODING, BORSH_ENCODING],
+                    )
+                    .with_first(MAX_BLOCK_CANDIDATES),
+            ),
+        )
+        .await?;
+
+    // newest first, so a block that was archived again after a reorg wins
+    for candidate in candidates {
+        if candidate.tag("Block-Hash").is_some_and(|hash| reverted.contains(hash)) {
+            continue
+        }
+
+        // outcomes archived next to a block carry the same tags
+        if candidate.tag("WeaveVM:Type").is_some_and(|item_type| item_type != "Block-Batch") {
+            continue
+        }
+
+        // anyone can upload a candidate, one that can't be read doesn't hide older ones
+        let tags = PayloadTags::from_fn(|tag| candidate.tag(tag));
+        if load_zstd_dictionary(storage, tags).await.is_err() {
+            continue
+        }
+        let Ok(data) = storage.fetch(candidate.id.as_str()).await else { continue };
+        let Ok(Some(block)) = decode_block(&candidate, data, block_number) else { continue };
+
+        if !reverted.contains(block.0.hash().to_string().as_str()) && accept(&candidate, &block.0) {
+            return Ok(Some(block))
+        }
+    }
+
+    Ok(None)
+}
+
+/// Makes the Zstandard dictionary an item was compressed with known to the decoders, fetching it
+/// from `storage` the first time it is seen.
+pub(crate) async fn load_zstd_dictionary(
+    storage: &dyn PermanentStorageBackend,
+    tags: PayloadTags<'_>,
+) -> eyre::Result<()> {
+    let Some(hash) = tags.zstd_dictionary_hash()? else { return Ok(()) };
+    if ARCHIVE_DECODERS.dictionary(&hash).is_some() {
+        return Ok(())
+    }
+
+    let hash_tag = hash.to_string();
+    let items = storage
+        .query(
+            &TagQuery::new()
+                .with_tag("WeaveVM:Type", ZSTD_DICTIONARY_TYPE)
+                .with_tag(ZSTD_DICTIONARY_TAG, hash_tag.as_str()),
+        )
+        .await?;
+
+    // anyone can tag an item, so only a dictionary with the expected content is accepted
+    for item in items {
+        let dictionary = ZstdDictionary::new(storage.fetch(item.id.as_str()).await?);
+        if dictionary.hash() == hash {
+            ARCHIVE_DECODERS.add_dictionary(Arc::new(dictionary));
+            return Ok(())
+        }
+    }
+
+    eyre::bail!("zstd dictionary {hash} is not archived")
+}
+
+/// Decodes the block at `block_number` from an archived single block or block batch.
+///
+/// The decoder is picked by the encoding and format version of the item, so blocks archived in
+/// older formats are still readable.
+fn decode_block(
+    item: &StoredItem,
+    data: Vec<u8>,
+    block_number: u64,
+) -> eyre::Result<Option<BorshSealedBlockWithSenders>> {
+    let payload =
+        ARCHIVE_DECODERS.open(PayloadTags::from_fn(|tag| item.tag(tag)), data.as_slice())?;
+
+    let block = match item.tag("WeaveVM:Type") {
+        Some("Block-Batch") => payload.batch_block(block_number)?,
+        None => Some(payload.block()?),
+        Some(_) => None,
+    };
+
+    Ok(block.map(BorshSealedBlockWithSenders))
+}
+
+/// Finds the archived outcome of `block`, `None` if its receipts are not archived.
+///
+/// Anyone can tag an item, so an outcome is only accepted if its receipts are complete and match
+/// the `receipts_root` of the block.
+pub async fn find_block_outcome(
+    storage: &dyn PermanentStorageBackend,
+    block: &SealedBlock,
+) -> eyre::Result<Option<BlockOutcome>> {
+    let number_tag = block.number.to_string();
+    let hash_tag = block.hash().to_string();
+    let items = storage
+        .query(
+            &TagQuery::new()
+                .with_tag("WeaveVM:Type", "Block-Outcome")
+                .with_tag("Block-Number", number_tag.as_str())
+                .with_tag("Block-Hash", hash_tag.as_str())
+                .with_first(MAX_BLOCK_CANDIDATES),
+        )
+        .await?;
+
+    for item in items {
+        let tags = PayloadTags::from_fn(|tag| item.tag(tag));
+        if load_zstd_dictionary(storage, tags).await.is_err() {
+            continue
+        }
+
+        let Ok(data) = storage.fetch(item.id.as_str()).await else { continue };
+        let Ok(outcome) =
+            ARCHIVE_DECODERS.open(tags, data.as_slice()).and_then(|payload| payload.outcome())
+        else {
+            continue
+        };
+        if outcome.block_number != block.number || outcome.block_hash != block.hash() {
+            continue
+        }
+
+        let Some(receipts) =
+            outcome.receipts.iter().map(Option::as_ref).collect::<Option<Vec<&Receipt>>>()
+        else {
+            continue
+        };
+        if receipts.len() == block.body.len() &&
+            calculate_receipt_root_no_memo(&receipts) == block.receipts_root
+        {
+            return Ok(Some(outcome))
+        }
+    }
+
+    Ok(None)
+}
+
+fn block_error(message: &str) -> PrecompileErrors {
+    PrecompileErrors::Error(PrecompileError::Other(message.to_string()))
+}
+
+/// The node failed to query its storage, which says nothing about the call itself, so the block
+/// is not executed instead of failing the call, like on a timeout of the [`PrecompileIo`].
+fn storage_error(err: eyre::Report) -> PrecompileErrors {
+    PrecompileErrors::Fatal { msg: format!("Reading the archive failed: {err}") }
+}
+
+/// Reads the canonical block at `block_number`, only blocks that pass [`verify_archived_block`]
+/// if `verified`.
+fn read_block(
+    io: &PrecompileIo,
+    storage: &Arc<dyn PermanentStorageBackend>,
+    archive: &ArchiveFilter,
+    block_number: u64,
+    verified: bool,
+) -> Result<SealedBlockWithSenders, PrecompileErrors> {
+    let storage = storage.clone();
+    let archive = archive.clone();
+    let block = io.run(async move {
+        if verified {
+            find_verified_block(storage.as_ref(), block_number, &archive).await
+        } else {
+            find_canonical_block(storage.as_ref(), block_number, &archive).await
+        }
+    })?;
+
+    match block.map_err(storage_error)? {
+        Some(block) => Ok(block.0),
+        None => Err(block_error("Unknown Block")),
+    }
+}
 
-    if gas_used > gas_limit {
+#[allow(clippy::too_many_arguments)]
+fn wevm_read_block_pc(
+    io: &PrecompileIo,
+    storage: &Arc<dyn PermanentStorageBackend>,
+    gateways: &ReadGateways,
+    archive: &ArchiveFilter,
+    gas: ReadGasSchedule,
+    input: &Bytes,
+    gas_limit: u64,
+    abi_calls: bool,
+    all_fields: bool,
+) -> PrecompileResult {
+    if gas.input_gas(input.len()) > gas_limit {
         return Err(PrecompileErrors::Error(PrecompileError::OutOfGas));
     }
 
     if input.is_empty() {
-        return Err(PrecompileErrors::Error(PrecompileError::Other(
-            "A block id must be provided".to_string(),
-        )));
-    }
-
-    let block_id = unsafe { String::from_utf8(input.0.to_vec()) };
-
-    match block_id {
-        Ok(input_data) => {
-            let (gateway, block_id, field) = parse_req_input(input_data.as_str());
-            if field.len() == 0 {
-                Err(PrecompileErrors::Error(PrecompileError::Other(
-                    "A field must be specified".to_string(),
-                )))
-            } else {
-                tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap().block_on(
-                    async {
-                        let clean_gateway = clean_gateway_url(gateway.as_str());
-                        let query = {
-                            let query = "{\n  transactions(tags: [{name: \"Block-Number\", values: [\"$block_id\"]}]) {\n    edges {\n      node {\n        id\n        tags {\n          name\n          value\n        }\n        data {\n          size\n        }\n      }\n    }\n  }\n}\n";
-                            let query = query.replace("$block_id", block_id.as_str());
-                            query
-                        };
-
-                        let data = send_graphql(clean_gateway.as_str(), query.as_str()).await;
-
-                        let edge = match data {
-                            Ok(res) => {
-                                let resp = res.data.transactions.edges.get(0);
-                                if let Some(&ref tx) = resp {
-                                    Some(tx.clone())
-                                } else {
-                                    None
-                                }
-                            }
-                            Err(_) => {
-                                None
-                            }
-                        };
-
-                        if let Some(edge) = edge {
-                            let tags = edge.node.tags.unwrap();
-                            let encoding = tags.iter().find(|i| i.name == String::from("WeaveVM:Encoding")).unwrap();
-                            let get_data = download_tx(gas_used, clean_gateway, edge.node.id).await;
-
-                            let output = match get_data {
-                                Ok(resp) => {
-                                    let bytes = resp.bytes.to_vec();
-                                    match encoding.value.as_str() {
-                                        "Borsh-Brotli" => {
-                                            let unbrotli = from_brotli(bytes);
-                                            let unborsh = borsh::from_slice::<BorshSealedBlockWithSenders>(unbrotli.as_slice()).unwrap();
-                                            let str_block = Block::from(unborsh);
-
-                                            let data = match field.as_str() {
-                                                "base_fee_per_gas" => {
-                                                    Some(str_block.base_fee_per_gas.unwrap().into_bytes())
-                                                },
-                                                "blob_gas_used" => {
-                                                    Some(str_block.blob_gas_used.unwrap().into_bytes())
-                                                },
-                                                "difficulty" => {
-                                                    Some(str_block.difficulty.unwrap().into_bytes())
-                                                },
-                                                "excess_blob_gas" => {
-                                                    Some(str_block.excess_blob_gas.unwrap().into_bytes())
-                                                },
-                                                "extra_data" => {
-                                                    Some(str_block.extra_data.unwrap().into_bytes())
-                                                },
-                                                "gas_limit" => {
-                                                    Some(str_block.gas_limit.unwrap().into_bytes())
-                                                },
-                                                "gas_used" => {
-                                                    Some(str_block.gas_used.unwrap().into_bytes())
-                                                },
-                                                "hash" => {
-                                                    Some(str_block.hash.unwrap().into_bytes())
-                                                },
-                                                "logs_bloom" => {
-                                                    Some(str_block.logs_bloom.unwrap().into_bytes())
-                                                },
-                                                "mix_hash" => {
-                                                    Some(str_block.mix_hash.unwrap().into_bytes())
-                                                },
-                                                "nonce" => {
-                                                    Some(str_block.nonce.unwrap().into_bytes())
-                                                },
-                                                "parent_beacon_block_root" => {
-                                                    Some(str_block.parent_beacon_block_root.unwrap().into_bytes())
-                                                },
-                                                "parent_hash" => {
-                                                    Some(str_block.parent_hash.unwrap().into_bytes())
-                                                },
-                                                "receipts_root" => {
-                                                    Some(str_block.receipts_root.unwrap().into_bytes())
-                                                },
-                                                "size" => {
-                                                    Some(str_block.size.unwrap().into_bytes())
-                                                },
-                                                "state_root" => {
-                                                    Some(str_block.state_root.unwrap().into_bytes())
-                                                },
-                                                "timestamp" => {
-                                                    Some(str_block.timestamp.unwrap().into_bytes())
-                                                },
-                                                "transactions" => {
-                                                    Some(str_block.transactions.join(",").into_bytes())
-                                                },
-                                                _ => {
-                                                    None
-                                                }
-                                            };
-
-                                            if let Some(valid_data) = data {
-                                                Ok(PrecompileOutput::new(gas_used, valid_data.into()))
-                                            } else {
-                                                Err(PrecompileErrors::Error(PrecompileError::Other("Unknown field".to_string())))
-                                            }
-                                        },
-                                        _ => {
-                                            Err(PrecompileErrors::Error(PrecompileError::Other("Unknown encoding".to_string())))
-                                        }
-                                    }
-                                }
-                                Err(_) => {
-                                    Err(PrecompileErrors::Error(PrecompileError::Other("Invalid data".to_string())))
-                                }
-                            };
-
-                            output
-                        } else {
-                            Err(PrecompileErrors::Error(PrecompileError::Other("Unknown Block".to_string())))
-                        }
-                    }
-                )
+        return Err(block_error("A block id must be provided"));
+    }
+
+    let input_len = input.len();
+    let read_gas = move |output: &[u8]| gas.read_gas(input_len, output.len());
+
+    let call = if abi_calls { decode_call::<IWvmBlockReadCalls>(input)? } else { None };
+    if let Some(call) = call {
+        return oracle_read(WEVM_BLOCK_READ, input, read_gas, || {
+            let output = read_block_abi(io, storage, archive, call)?;
+            paid_output(read_gas(&output), output, gas_limit)
+        })
+    }
+
+    let Ok(input_data) = String::from_utf8(input.to_vec()) else {
+        return Err(block_error("Block id could not be parsed"))
+    };
+    let (gateway, block_id, field) = parse_req_input(input_data.as_str());
+    if field.is_empty() {
+        return Err(block_error("A field must be specified"))
+    }
+    let storage = gateways.storage(storage, gateway.as_str())?;
+
+    oracle_read(WEVM_BLOCK_READ, input, read_gas, || {
+        let Ok(block_number) = block_id.parse::<u64>() else {
+            return Err(block_error("Block id could not be parsed"))
+        };
+
+        let block = read_block(io, &storage, archive, block_number, false)?;
+        let block = Block::from(BorshSealedBlockWithSenders(block));
+        let value = legacy_field(block, field.as_str(), all_fields)?;
+        paid_output(read_gas(value.as_bytes()), value.into_bytes(), gas_limit)
+    })
+}
+
+/// Returns `field` of a block as a string, for the legacy `gateway;block;field` input.
+///
+/// `transactions` are the comma-separated hashes of the transactions of the block. `miner` and
+/// `number` were added later and are unknown fields unless `all_fields`.
+fn legacy_field(block: Block, field: &str, all_fields: bool) -> Result<String, PrecompileErrors> {
+    if !all_fields && matches!(field, "miner" | "number") {
+        return Err(block_error("Unknown field"))
+    }
+
+    let value = match field {
+        "base_fee_per_gas" => block.base_fee_per_gas,
+        "blob_gas_used" => block.blob_gas_used,
+        "difficulty" => block.difficulty,
+        "excess_blob_gas" => block.excess_blob_gas,
+        "extra_data" => block.extra_data,
+        "gas_limit" => block.gas_limit,
+        "gas_used" => block.gas_used,
+        "hash" => block.hash,
+        "logs_bloom" => block.logs_bloom,
+        "miner" => block.miner,
+        "mix_hash" => block.mix_hash,
+        "nonce" => block.nonce,
+        "number" => block.number,
+        "parent_beacon_block_root" => block.parent_beacon_block_root,
+        "parent_hash" => block.parent_hash,
+        "receipts_root" => block.receipts_root,
+        "size" => block.size,
+        "state_root" => block.state_root,
+        "timestamp" => block.timestamp,
+        "transactions" => Some(block.transactions.join(",")),
+        _ => return Err(block_error("Unknown field")),
+    };
+
+    value.ok_or_else(|| block_error("Field is not present in the block"))
+}
+
+/// Reads a block through the [`IWvmBlockRead`] interface and returns the ABI-encoded result.
+fn read_block_abi(
+    io: &PrecompileIo,
+    storage: &Arc<dyn PermanentStorageBackend>,
+    archive: &ArchiveFilter,
+    call: IWvmBlockReadCalls,
+) -> Result<Vec<u8>, PrecompileErrors> {
+    let block_number = match &call {
+        IWvmBlockReadCalls::blockField(call) => call.number,
+        IWvmBlockReadCalls::blockTransactions(call) => call.number,
+        IWvmBlockReadCalls::blockHeader(call) => call.number,
+        IWvmBlockReadCalls::blockTransaction(call) => call.number,
+        IWvmBlockReadCalls::blockSenders(call) => call.number,
+        IWvmBlockReadCalls::blockWithdrawals(call) => call.number,
+        IWvmBlockReadCalls::transactionReceipt(call) => call.number,
+    };
+    let block = read_block(io, storage, archive, block_number, true)?;
+
+    let output = match call {
+        IWvmBlockReadCalls::blockField(call) => {
+            let field =
+                BlockField::try_from(call.field).map_err(|_| block_error("Unknown field"))?;
+            IWvmBlockRead::blockFieldCall::abi_encode_returns(&(block_field(&block, field)?,))
+        }
+        IWvmBlockReadCalls::blockTransactions(_) => {
+            let hashes: Vec<B256> = block.transactions().map(|tx| tx.hash).collect();
+            IWvmBlockRead::blockTransactionsCall::abi_encode_returns(&(hashes,))
+        }
+        IWvmBlockReadCalls::blockHeader(_) => {
+            let header = alloy_rlp::encode(block.header.header());
+            IWvmBlockRead::blockHeaderCall::abi_encode_returns(&(header.into(),))
+        }
+        IWvmBlockReadCalls::blockTransaction(call) => {
+            let index = call.index as usize;
+            let (Some(tx), Some(sender)) = (block.body.get(index), block.senders.get(index)) else {
+                return Err(block_error("Unknown transaction"))
+            };
+            IWvmBlockRead::blockTransactionCall::abi_encode_returns(&(
+                tx.envelope_encoded(),
+                *sender,
+            ))
+        }
+        IWvmBlockReadCalls::blockSenders(_) => {
+            IWvmBlockRead::blockSendersCall::abi_encode_returns(&(block.senders.clone(),))
+        }
+        IWvmBlockReadCalls::blockWithdrawals(_) => {
+            let withdrawals: Vec<BlockWithdrawal> = block
+                .withdrawals
+                .iter()
+                .flat_map(|withdrawals| withdrawals.iter())
+                .map(|withdrawal| BlockWithdrawal {
+                    index: withdrawal.index,
+                    validatorIndex: withdrawal.validator_index,
+                    recipient: withdrawal.address,
+                    amountGwei: withdrawal.amount,
+                })
+                .collect();
+            IWvmBlockRead::blockWithdrawalsCall::abi_encode_returns(&(withdrawals,))
+        }
+        IWvmBlockReadCalls::transactionReceipt(call) => {
+            let index = call.index as usize;
+            if index >= block.body.len() {
+                return Err(block_error("Unknown transaction"))
             }
+
+            let storage = storage.clone();
+            let sealed_block = block.block.clone();
+            let outcome =
+                io.run(async move { find_block_outcome(storage.as_ref(), &sealed_block).await })?;
+            let Some(outcome) = outcome.map_err(storage_error)? else {
+                return Err(block_error("Receipts of the block are not archived"))
+            };
+            let Some(Some(receipt)) = outcome.receipts.into_iter().nth(index) else {
+                return Err(block_error("Receipts of the block are not archived"))
+            };
+            let receipt = receipt.with_bloom().envelope_encoded();
+            IWvmBlockRead::transactionReceiptCall::abi_encode_returns(&(receipt,))
         }
-        Err(_) => Err(PrecompileErrors::Error(PrecompileError::Other(
-            "Block id could not be parsed".to_string(),
-        ))),
-    }
+    };
+
+    Ok(output)
+}
+
+/// Returns `field` of `block` as a word, numbers are big-endian and addresses are left-padded.
+fn block_field(
+    block: &SealedBlockWithSenders,
+    field: BlockField,
+) -> Result<B256, PrecompileErrors> {
+    let number = |number: u64| B256::from(U256::from(number));
+    let optional =
+        |value: Option<B256>| value.ok_or_else(|| block_error("Field is not present in the block"));
+
+    let value = match field {
+        BlockField::Hash => block.hash(),
+        BlockField::ParentHash => block.parent_hash,
+        BlockField::Beneficiary => block.beneficiary.into_word(),
+        BlockField::StateRoot => block.state_root,
+        BlockField::TransactionsRoot => block.transactions_root,
+        BlockField::ReceiptsRoot => block.receipts_root,
+        BlockField::MixHash => block.mix_hash,
+        BlockField::ParentBeaconBlockRoot => optional(block.parent_beacon_block_root)?,
+        BlockField::Number => number(block.number),
+        BlockField::Timestamp => number(block.timestamp),
+        BlockField::GasLimit => number(block.gas_limit),
+        BlockField::GasUsed => number(block.gas_used),
+        BlockField::BaseFeePerGas => optional(block.base_fee_per_gas.map(number))?,
+        BlockField::BlobGasUsed => optional(block.blob_gas_used.map(number))?,
+        BlockField::ExcessBlobGas => optional(block.excess_blob_gas.map(number))?,
+        BlockField::Difficulty => B256::from(block.difficulty),
+        BlockField::Nonce => number(block.nonce),
+        BlockField::Size => B256::from(U256::from(block.size())),
+        BlockField::WithdrawalsRoot => optional(block.withdrawals_root)?,
+        // added by `sol!` for the values of a `uint8` that are not a field
+        BlockField::__Invalid => return Err(block_error("Unknown field")),
+    };
+
+    Ok(value)
 }
 
 #[cfg(test)]
 mod arweave_read_pc_tests {
-    use crate::inner::wevm_block_precompile::wevm_read_block_pc;
-    use reth::primitives::{revm_primitives::PrecompileOutput, Bytes};
+    use crate::{
+        inner::{
+            abi::{BlockField, IWvmBlockRead},
+            gas::ReadGasSchedule,
+            util::ReadGateways,
+            wevm_block_precompile::{
+                find_canonical_block, wevm_read_block_pc, ArchiveFilter, ARCHIVERS_KEY,
+            },
+        },
+        test_utils::io,
+    };
+    use alloy_sol_types::SolCall;
+    use irys::{
+        backend::{PermanentStorageBackend, Tag, TagQuery},
+        gateway::ArweaveGateway,
+        irys::IrysProvider,
+        local::LocalStorage,
+    };
+    use rbrotli::to_brotli;
+    use reth::primitives::{
+        keccak256,
+        proofs::{
+            calculate_receipt_root_no_memo, calculate_transaction_root, calculate_withdrawals_root,
+        },
+        revm_primitives::{PrecompileErrors, PrecompileOutput, PrecompileResult},
+        sign_message, Address, Bytes, Genesis, Header, Receipt, SealedBlock,
+        SealedBlockWithSenders, SealedHeader, Transaction, TransactionSigned, TxEip1559, TxKind,
+        TxType, Withdrawal, Withdrawals, B256, U256,
+    };
+    use std::sync::Arc;
+    use wevm_borsh::{
+        batch::BlockBatch,
+        block::BorshSealedBlockWithSenders,
+        codec::{Codec, ZstdDictionary, ZSTD_DICTIONARY_TAG},
+        format::{encode_payload, FORMAT_VERSION_TAG},
+        outcome::BlockOutcome,
+        test_utils::{block, block_with_extra_data},
+    };
+
+    const LEGACY: ReadGasSchedule = ReadGasSchedule::LEGACY;
+
+    fn read_block_pc(
+        storage: &Arc<dyn PermanentStorageBackend>,
+        input: &Bytes,
+    ) -> PrecompileResult {
+        let archive = ArchiveFilter::default();
+        wevm_read_block_pc(
+            &io(),
+            storage,
+            &ReadGateways::default(),
+            &archive,
+            LEGACY,
+            input,
+            100_000,
+            true,
+            true,
+        )
+    }
+
+    #[test]
+    pub fn test_read_wvm_block_local_storage() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage: Arc<dyn PermanentStorageBackend> =
+            Arc::new(LocalStorage::new(dir.path()).unwrap());
+
+        let block = SealedBlockWithSenders::default();
+        let hash = block.block.hash().to_string();
+        let data = to_brotli(encode_payload(&BorshSealedBlockWithSenders(block)).unwrap());
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(storage.upload(
+                data,
+                vec![
+                    Tag::new("WeaveVM:Encoding", "Borsh-Brotli"),
+                    Tag::new(FORMAT_VERSION_TAG, "1"),
+                    Tag::new("Block-Number", "0"),
+                ],
+            ))
+            .unwrap();
+
+        let input = Bytes::from("0;hash".as_bytes());
+        let PrecompileOutput { bytes, .. } = read_block_pc(&storage, &input).unwrap();
+        assert_eq!(bytes.to_vec(), hash.into_bytes());
+
+        let input = Bytes::from("1;hash".as_bytes());
+        assert!(read_block_pc(&storage, &input).is_err());
+
+        // only the gateways allowed by the chain spec can be read from
+        let input = Bytes::from("http://localhost:1;0;hash".as_bytes());
+        assert!(read_block_pc(&storage, &input).is_err());
+    }
+
+    async fn upload_block(storage: &LocalStorage, block: &SealedBlockWithSenders) -> String {
+        let data = to_brotli(encode_payload(&BorshSealedBlockWithSenders(block.clone())).unwrap());
+        let tags = vec![
+            Tag::new("WeaveVM:Encoding", "Borsh-Brotli"),
+            Tag::new(FORMAT_VERSION_TAG, "1"),
+            Tag::new("Block-Number", block.number.to_string().as_str()),
+            Tag::new("Block-Hash", block.hash().to_string().as_str()),
+        ];
+
+        storage.upload(data, tags).await.unwrap()
+    }
+
+    #[tokio::test]
+    pub async fn test_read_wvm_block_abi() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = Arc::new(LocalStorage::new(dir.path()).unwrap());
+        let block = block_with_extra_data(4, b"abi");
+        upload_block(&storage, &block).await;
+        let storage: Arc<dyn PermanentStorageBackend> = storage;
+
+        let field = |field: BlockField| {
+            let call = IWvmBlockRead::blockFieldCall { number: 4, field: field as u8 };
+            let PrecompileOutput { bytes, .. } =
+                read_block_pc(&storage, &call.abi_encode().into()).unwrap();
+            IWvmBlockRead::blockFieldCall::abi_decode_returns(&bytes, true).unwrap().value
+        };
+        assert_eq!(field(BlockField::Hash), block.hash());
+        assert_eq!(field(BlockField::Number), B256::from(U256::from(4)));
+
+        let call = IWvmBlockRead::blockTransactionsCall { number: 4 };
+        let PrecompileOutput { bytes, .. } =
+            read_block_pc(&storage, &call.abi_encode().into()).unwrap();
+        let hashes =
+            IWvmBlockRead::blockTransactionsCall::abi_decode_returns(&bytes, true).unwrap().hashes;
+        assert!(hashes.is_empty());
+
+        let call = IWvmBlockRead::blockFieldCall { number: 4, field: u8::MAX };
+        assert!(read_block_pc(&storage, &call.abi_encode().into()).is_err());
+
+        // before the activation of ABI calls the call is read as a string input
+        let call = IWvmBlockRead::blockFieldCall { number: 4, field: BlockField::Hash as u8 };
+        assert!(wevm_read_block_pc(
+            &io(),
+            &storage,
+            &ReadGateways::default(),
+            &ArchiveFilter::default(),
+            LEGACY,
+            &call.abi_encode().into(),
+            100_000,
+            false,
+            true,
+        )
+        .is_err());
+    }
+
+    #[tokio::test]
+    pub async fn test_read_wvm_block_legacy_fields() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = Arc::new(LocalStorage::new(dir.path()).unwrap());
+        let block = block_with_extra_data(4, b"legacy");
+        upload_block(&storage, &block).await;
+        let storage: Arc<dyn PermanentStorageBackend> = storage;
+
+        let field = |field: &str| {
+            let input = Bytes::from(format!("4;{field}").into_bytes());
+            read_block_pc(&storage, &input).map(|output| String::from_utf8(output.bytes.to_vec()))
+        };
+        assert_eq!(field("number").unwrap().unwrap(), "4");
+        assert_eq!(field("miner").unwrap().unwrap(), block.beneficiary.to_string());
+
+        // fields the block does not have are an error instead of a panic
+        assert!(field("base_fee_per_gas").is_err());
+        assert!(field("unknown").is_err());
+
+        // `miner` and `number` are unknown before their activation
+        let legacy_field = |field: &str| {
+            let input = Bytes::from(format!("4;{field}").into_bytes());
+            let archive = ArchiveFilter::default();
+            let gateways = ReadGateways::default();
+            wevm_read_block_pc(
+                &io(),
+                &storage,
+                &gateways,
+                &archive,
+                LEGACY,
+                &input,
+                100_000,
+                true,
+                false,
+            )
+        };
+        assert!(legacy_field("number").is_err());
+        assert!(legacy_field("miner").is_err());
+        assert_eq!(
+            legacy_field("hash").unwrap().bytes.to_vec(),
+            block.hash().to_string().into_bytes()
+        );
+    }
+
+    /// A block with a signed transaction and a withdrawal, and the receipt of the transaction.
+    fn block_with_body(number: u64) -> (SealedBlockWithSenders, Receipt) {
+        let transaction = Transaction::Eip1559(TxEip1559 {
+            chain_id: 9496,
+            nonce: 1,
+            gas_limit: 21_000,
+            to: TxKind::Call(Address::random()),
+            ..Default::default()
+        });
+        let signature = sign_message(B256::repeat_byte(1), transaction.signature_hash()).unwrap();
+        let tx = TransactionSigned::from_transaction_and_signature(transaction, signature);
+        let sender = tx.recover_signer().unwrap();
+        let receipt = Receipt {
+            tx_type: TxType::Eip1559,
+            success: true,
+            cumulative_gas_used: 21_000,
+            ..Default::default()
+        };
+        let withdrawal =
+            Withdrawal { index: 3, validator_index: 7, address: Address::random(), amount: 32 };
+        let withdrawals = Withdrawals::new(vec![withdrawal]);
+        let header = Header {
+            number,
+            base_fee_per_gas: Some(7),
+            transactions_root: calculate_transaction_root(&[tx.clone()]),
+            receipts_root: calculate_receipt_root_no_memo(&[&receipt]),
+            withdrawals_root: Some(calculate_withdrawals_root(&withdrawals)),
+            ..Default::default()
+        };
+        let block = SealedBlockWithSenders {
+            block: SealedBlock {
+                header: header.seal_slow(),
+                body: vec![tx],
+                withdrawals: Some(withdrawals),
+                ..Default::default()
+            },
+            senders: vec![sender],
+        };
+
+        (block, receipt)
+    }
+
+    #[tokio::test]
+    pub async fn test_read_wvm_block_abi_body() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = Arc::new(LocalStorage::new(dir.path()).unwrap());
+        let (block, _) = block_with_body(5);
+        upload_block(&storage, &block).await;
+        let storage: Arc<dyn PermanentStorageBackend> = storage;
+
+        let call = IWvmBlockRead::blockHeaderCall { number: 5 };
+        let output = read_block_pc(&storage, &call.abi_encode().into()).unwrap();
+        let header =
+            IWvmBlockRead::blockHeaderCall::abi_decode_returns(&output.bytes, true).unwrap().header;
+        assert_eq!(keccak256(&header), block.hash());
+        // charged by the size of the output
+        assert_eq!(output.gas_used, LEGACY.read_gas(call.abi_encode().len(), output.bytes.len()));
+
+        let call = IWvmBlockRead::blockTransactionCall { number: 5, index: 0 };
+        let output = read_block_pc(&storage, &call.abi_encode().into()).unwrap();
+        let tx =
+            IWvmBlockRead::blockTransactionCall::abi_decode_returns(&output.bytes, true).unwrap();
+        assert_eq!(tx.transaction, block.body[0].envelope_encoded());
+        assert_eq!(tx.sender, block.senders[0]);
+
+        let call = IWvmBlockRead::blockTransactionCall { number: 5, index: 1 };
+        assert!(read_block_pc(&storage, &call.abi_encode().into()).is_err());
+
+        let call = IWvmBlockRead::blockSendersCall { number: 5 };
+        let output = read_block_pc(&storage, &call.abi_encode().into()).unwrap();
+        let senders = IWvmBlockRead::blockSendersCall::abi_decode_returns(&output.bytes, true)
+            .unwrap()
+            .senders;
+        assert_eq!(senders, block.senders);
+
+        let call = IWvmBlockRead::blockWithdrawalsCall { number: 5 };
+        let output = read_block_pc(&storage, &call.abi_encode().into()).unwrap();
+        let withdrawals =
+            IWvmBlockRead::blockWithdrawalsCall::abi_decode_returns(&output.bytes, true)
+                .unwrap()
+                .withdrawals;
+        assert_eq!(withdrawals.len(), 1);
+        assert_eq!(
+            (withdrawals[0].index, withdrawals[0].validatorIndex, withdrawals[0].amountGwei),
+            (3, 7, 32)
+        );
+
+        let field = |field: BlockField| {
+            let call = IWvmBlockRead::blockFieldCall { number: 5, field: field as u8 };
+            read_block_pc(&storage, &call.abi_encode().into())
+        };
+        assert!(field(BlockField::BaseFeePerGas).is_ok());
+        // absent fields are an error instead of zero
+        assert!(field(BlockField::BlobGasUsed).is_err());
+        assert!(field(BlockField::ParentBeaconBlockRoot).is_err());
+    }
+
+    #[tokio::test]
+    pub async fn test_read_wvm_block_abi_verifies_blocks() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = Arc::new(LocalStorage::new(dir.path()).unwrap());
+        let storage_dyn: Arc<dyn PermanentStorageBackend> = storage.clone();
+        let (block, _) = block_with_body(7);
+        let senders = |number: u64| {
+            let call = IWvmBlockRead::blockSendersCall { number };
+            read_block_pc(&storage_dyn, &call.abi_encode().into())
+        };
+
+        // a block sealed with another hash than the hash of its header
+        let mut forged = block.clone();
+        forged.block.header = SealedHeader::new(block.header.header().clone(), B256::random());
+        upload_block(&storage, &forged).await;
+        assert!(senders(7).is_err());
+
+        // a block whose senders did not sign its transactions
+        let forged = SealedBlockWithSenders { senders: vec![Address::random()], ..block.clone() };
+        upload_block(&storage, &forged).await;
+        assert!(senders(7).is_err());
+
+        // a block whose transactions are not the ones of its header
+        let mut forged = block.clone();
+        forged.block.body.clear();
+        forged.senders.clear();
+        upload_block(&storage, &forged).await;
+        assert!(senders(7).is_err());
+
+        // a block whose withdrawals are not the ones of its header
+        let mut forged = block.clone();
+        forged.block.withdrawals = Some(Withdrawals::new(vec![]));
+        upload_block(&storage, &forged).await;
+        assert!(senders(7).is_err());
+
+        // a block tagged with the hash of another block
+        let tags = vec![
+            Tag::new("WeaveVM:Encoding", "Borsh-Brotli"),
+            Tag::new(FORMAT_VERSION_TAG, "1"),
+            Tag::new("Block-Number", "7"),
+            Tag::new("Block-Hash", B256::random().to_string().as_str()),
+        ];
+        let data = to_brotli(encode_payload(&BorshSealedBlockWithSenders(block.clone())).unwrap());
+        storage.upload(data, tags).await.unwrap();
+        assert!(senders(7).is_err());
+
+        // the string input still reads the newest block as archived
+        let input = Bytes::from("7;number".as_bytes());
+        assert_eq!(read_block_pc(&storage_dyn, &input).unwrap().bytes.as_ref(), b"7");
+
+        // forged candidates don't hide the block
+        upload_block(&storage, &block).await;
+        let output = senders(7).unwrap();
+        let found = IWvmBlockRead::blockSendersCall::abi_decode_returns(&output.bytes, true)
+            .unwrap()
+            .senders;
+        assert_eq!(found, block.senders);
+    }
+
+    #[test]
+    pub fn test_read_wvm_block_storage_errors() {
+        let storage: Arc<dyn PermanentStorageBackend> =
+            Arc::new(ArweaveGateway::new("http://127.0.0.1:1"));
+
+        // the node failing to reach its storage is not a failed call
+        let input = Bytes::from("1;hash".as_bytes());
+        assert!(matches!(read_block_pc(&storage, &input), Err(PrecompileErrors::Fatal { .. })));
+        let call = IWvmBlockRead::blockSendersCall { number: 1 };
+        assert!(matches!(
+            read_block_pc(&storage, &call.abi_encode().into()),
+            Err(PrecompileErrors::Fatal { .. })
+        ));
+    }
+
+    #[tokio::test]
+    pub async fn test_read_wvm_block_receipt() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = Arc::new(LocalStorage::new(dir.path()).unwrap());
+        let (block, receipt) = block_with_body(6);
+        upload_block(&storage, &block).await;
+
+        let upload_outcome = |receipts: Vec<Option<Receipt>>| {
+            let outcome = BlockOutcome {
+                block_number: 6,
+                block_hash: block.hash(),
+                receipts,
+                state_diff: None,
+            };
+            let tags = vec![
+                Tag::new("WeaveVM:Encoding", "Borsh-Brotli"),
+                Tag::new(FORMAT_VERSION_TAG, "1"),
+                Tag::new("WeaveVM:Type", "Block-Outcome"),
+                Tag::new("Block-Number", "6"),
+                Tag::new("Block-Hash", block.hash().to_string().as_str()),
+            ];
+            storage.upload(to_brotli(encode_payload(&outcome).unwrap()), tags)
+        };
+        let storage_dyn: Arc<dyn PermanentStorageBackend> = storage.clone();
+        let call = IWvmBlockRead::transactionReceiptCall { number: 6, index: 0 };
+
+        // the receipts of the block are not archived yet
+        assert!(read_block_pc(&storage_dyn, &call.abi_encode().into()).is_err());
+
+        // outcomes that don't match the receipts root of the block are ignored
+        let forged = Receipt { success: false, ..receipt.clone() };
+        upload_outcome(vec![Some(forged)]).await.unwrap();
+        assert!(read_block_pc(&storage_dyn, &call.abi_encode().into()).is_err());
+
+        upload_outcome(vec![Some(receipt.clone())]).await.unwrap();
+        let output = read_block_pc(&storage_dyn, &call.abi_encode().into()).unwrap();
+        let encoded =
+            IWvmBlockRead::transactionReceiptCall::abi_decode_returns(&output.bytes, true)
+                .unwrap()
+                .receipt;
+        assert_eq!(encoded, receipt.with_bloom().envelope_encoded());
+
+        let call = IWvmBlockRead::transactionReceiptCall { number: 6, index: 1 };
+        assert!(read_block_pc(&storage_dyn, &call.abi_encode().into()).is_err());
+    }
+
+    #[tokio::test]
+    pub async fn test_find_canonical_block_skips_reverted() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = LocalStorage::new(dir.path()).unwrap();
+
+        let old = block_with_extra_data(1, b"old");
+        let new = block_with_extra_data(1, b"new");
+        upload_block(&storage, &old).await;
+        upload_block(&storage, &new).await;
+
+        let found =
+            find_canonical_block(&storage, 1, &ArchiveFilter::default()).await.unwrap().unwrap();
+        assert_eq!(found.0.hash(), new.hash());
+
+        let marker_tags = vec![
+            Tag::new("WeaveVM:Type", "Revert-Marker"),
+            Tag::new("Block-Number", "1"),
+            Tag::new("Block-Hash", new.hash().to_string().as_str()),
+            Tag::new("Reverted-By", old.hash().to_string().as_str()),
+        ];
+        storage.upload(vec![], marker_tags).await.unwrap();
+
+        let found =
+            find_canonical_block(&storage, 1, &ArchiveFilter::default()).await.unwrap().unwrap();
+        assert_eq!(found.0.hash(), old.hash());
+        assert!(find_canonical_block(&storage, 2, &ArchiveFilter::default())
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    pub async fn test_find_canonical_block_in_batch() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = LocalStorage::new(dir.path()).unwrap();
+
+        let mut batch = BlockBatch::new();
+        let mut tags = vec![
+            Tag::new("WeaveVM:Encoding", "Borsh-Brotli"),
+            Tag::new(FORMAT_VERSION_TAG, "1"),
+            Tag::new("WeaveVM:Type", "Block-Batch"),
+        ];
+        for number in 5..=7 {
+            batch.push(&block(number)).unwrap();
+            tags.push(Tag::new("Block-Number", number.to_string().as_str()));
+        }
+        storage.upload(to_brotli(encode_payload(&batch).unwrap()), tags).await.unwrap();
+
+        let found =
+            find_canonical_block(&storage, 6, &ArchiveFilter::default()).await.unwrap().unwrap();
+        assert_eq!(found.0, block(6));
+        assert!(find_canonical_block(&storage, 8, &ArchiveFilter::default())
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    pub async fn test_find_canonical_block_legacy_format() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = LocalStorage::new(dir.path()).unwrap();
+
+        // blocks archived before the format header carry no version tag
+        let block = block_with_extra_data(3, b"legacy");
+        let data = to_brotli(borsh::to_vec(&BorshSealedBlockWithSenders(block.clone())).unwrap());
+        let tags =
+            vec![Tag::new("WeaveVM:Encoding", "Borsh-Brotli"), Tag::new("Block-Number", "3")];
+        storage.upload(data, tags).await.unwrap();
+
+        let found =
+            find_canonical_block(&storage, 3, &ArchiveFilter::default()).await.unwrap().unwrap();
+        assert_eq!(found.0, block);
+    }
+
+    #[tokio::test]
+    pub async fn test_find_canonical_block_skips_outcomes() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = LocalStorage::new(dir.path()).unwrap();
+
+        let block = block(1);
+        upload_block(&storage, &block).await;
+        let outcome_tags = vec![
+            Tag::new("WeaveVM:Encoding", "Borsh-Brotli"),
+            Tag::new("WeaveVM:Type", "Block-Outcome"),
+            Tag::new("Block-Number", "1"),
+            Tag::new("Block-Hash", block.hash().to_string().as_str()),
+        ];
+        storage.upload(to_brotli(vec![0]), outcome_tags).await.unwrap();
+
+        let found =
+            find_canonical_block(&storage, 1, &ArchiveFilter::default()).await.unwrap().unwrap();
+        assert_eq!(found.0, block);
+    }
+
+    #[tokio::test]
+    pub async fn test_find_canonical_block_zstd_dictionary() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = LocalStorage::new(dir.path()).unwrap();
+
+        let samples = (0..100).map(|number| {
+            let extra_data = if number % 2 == 0 { b"even".as_slice() } else { b"odd".as_slice() };
+            encode_payload(&BorshSealedBlockWithSenders(block_with_extra_data(number, extra_data)))
+                .unwrap()
+        });
+        let dictionary = Arc::new(ZstdDictionary::train(samples, 2048).unwrap());
+        let hash = dictionary.hash().to_string();
+        let codec = Codec::Zstd { level: 3, dictionary: Some(dictionary.clone()) };
+
+        let older = block_with_extra_data(9, b"older");
+        upload_block(&storage, &older).await;
+        let block = block_with_extra_data(9, b"zstd");
+        let data = codec
+            .compress(&encode_payload(&BorshSealedBlockWithSenders(block.clone())).unwrap())
+            .unwrap();
+        let tags = vec![
+            Tag::new("WeaveVM:Encoding", "Borsh-Zstd"),
+            Tag::new(FORMAT_VERSION_TAG, "1"),
+            Tag::new(ZSTD_DICTIONARY_TAG, hash.as_str()),
+            Tag::new("Block-Number", "9"),
+        ];
+        storage.upload(data, tags).await.unwrap();
+
+        // the dictionary has to be archived before blocks compressed with it can be read, until
+        // then the next candidate is read
+        let found =
+            find_canonical_block(&storage, 9, &ArchiveFilter::default()).await.unwrap().unwrap();
+        assert_eq!(found.0, older);
+
+        let dictionary_tags = vec![
+            Tag::new("WeaveVM:Type", "Zstd-Dictionary"),
+            Tag::new(ZSTD_DICTIONARY_TAG, hash.as_str()),
+        ];
+        storage.upload(dictionary.raw().to_vec(), dictionary_tags).await.unwrap();
+
+        let found =
+            find_canonical_block(&storage, 9, &ArchiveFilter::default()).await.unwrap().unwrap();
+        assert_eq!(found.0, block);
+    }
+
+    #[tokio::test]
+    pub async fn test_find_canonical_block_of_archivers() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = LocalStorage::new(dir.path()).unwrap();
+        upload_block(&storage, &block(1)).await;
+
+        let archive = ArchiveFilter::new(vec!["archiver".to_string()]).with_network("devnet");
+        let query = archive.apply(TagQuery::new());
+        assert_eq!(query.owners, vec!["archiver".to_string()]);
+        assert!(query.matches("id", &[Tag::new("Network", "devnet")]));
+
+        // local items have no owner, so none of them was uploaded by an archiver
+        assert!(find_canonical_block(&storage, 1, &archive).await.unwrap().is_none());
+    }
+
+    #[test]
+    pub fn test_archive_filter_from_genesis() {
+        let mut genesis = Genesis::default();
+        assert_eq!(ArchiveFilter::from_genesis(&genesis).unwrap(), ArchiveFilter::default());
+
+        genesis
+            .config
+            .extra_fields
+            .insert(ARCHIVERS_KEY.to_string(), serde_json::json!(["archiver"]));
+        let archive = ArchiveFilter::from_genesis(&genesis).unwrap();
+        assert_eq!(archive.owners, vec!["archiver".to_string()]);
+
+        genesis.config.extra_fields.insert(ARCHIVERS_KEY.to_string(), serde_json::json!("a"));
+        assert!(ArchiveFilter::from_genesis(&genesis).is_err());
+    }
 
     #[test]
+    #[ignore = "requires access to arweave.net"]
     pub fn test_read_wvm_block() {
+        let storage: Arc<dyn PermanentStorageBackend> = Arc::new(IrysProvider::new());
         let input = Bytes::from("141550;hash".as_bytes());
-        let PrecompileOutput { gas_used, bytes } = wevm_read_block_pc(&input, 100_000).unwrap();
+        let PrecompileOutput { gas_used, bytes } = read_block_pc(&storage, &input).unwrap();
         assert_eq!(bytes.len(), 66);
         assert_eq!(
             bytes.to_vec(),
@@ -1,5 +1,12 @@
 extern crate core;
 
+pub mod args;
+pub mod executor;
 pub mod inner;
 pub mod node;
+pub mod payload;
+pub mod rpc;
+#[cfg(any(test, feature = "test-utils"))]
+/// Common fixtures of tests.
+pub mod test_utils;
 pub mod wevm_node_config;
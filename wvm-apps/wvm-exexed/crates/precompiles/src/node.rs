@@ -1,4 +1,18 @@
-use crate::{inner::wvm_precompiles, wevm_node_config::WvmEthEvmConfig};
+use crate::{
+    executor::WvmExecutorProvider,
+    inner::{
+        blobs::{BlobSource, KyveBlobSource},
+        io::PrecompileIo,
+        read_cache::ReadCache,
+        registry::PrecompileForks,
+        sidecar::ReadSidecars,
+        wvm_precompiles,
+    },
+    payload::WvmPayloadServiceBuilder,
+    wevm_node_config::WvmEthEvmConfig,
+};
+use irys::backend::PermanentStorageBackend;
+use outbox::{sidecar::ReadSidecarOutbox, upload::UploadOutbox};
 use reth::{
     api::{FullNodeTypes, NodeTypes, PayloadTypes},
     builder::{
@@ -7,26 +21,34 @@ use reth::{
     },
     payload::{EthBuiltPayload, EthPayloadBuilderAttributes},
 };
+use reth_chainspec::ChainSpec;
 use reth_ethereum_engine_primitives::EthPayloadAttributes;
 use reth_node_ethereum::{
-    node::{
-        EthereumConsensusBuilder, EthereumNetworkBuilder, EthereumPayloadBuilder,
-        EthereumPoolBuilder,
-    },
+    node::{EthereumConsensusBuilder, EthereumNetworkBuilder, EthereumPoolBuilder},
     EthEngineTypes, EthEvmConfig, EthExecutorProvider,
 };
+use std::sync::Arc;
 
 /// Type configuration for a regular Ethereum node.
-#[derive(Debug, Default, Clone, Copy)]
+#[derive(Debug, Clone)]
 #[non_exhaustive]
-pub struct WvmEthereumNode;
+pub struct WvmEthereumNode {
+    /// Builder of the executor with the WVM precompiles.
+    pub executor: WvmEthExecutorBuilder,
+}
 
 impl WvmEthereumNode {
+    pub fn new(executor: WvmEthExecutorBuilder) -> Self {
+        WvmEthereumNode { executor }
+    }
+
     /// Returns a [`ComponentsBuilder`] configured for a regular Ethereum node.
-    pub fn components<Node>() -> ComponentsBuilder<
+    pub fn components<Node>(
+        executor: WvmEthExecutorBuilder,
+    ) -> ComponentsBuilder<
         Node,
         EthereumPoolBuilder,
-        EthereumPayloadBuilder,
+        WvmPayloadServiceBuilder,
         EthereumNetworkBuilder,
         WvmEthExecutorBuilder,
         EthereumConsensusBuilder,
@@ -42,9 +64,9 @@ impl WvmEthereumNode {
         ComponentsBuilder::default()
             .node_types::<Node>()
             .pool(EthereumPoolBuilder::default())
-            .payload(EthereumPayloadBuilder::default())
+            .payload(WvmPayloadServiceBuilder::from(executor.clone()))
             .network(EthereumNetworkBuilder::default())
-            .executor(WvmEthExecutorBuilder::default())
+            .executor(executor)
             .consensus(EthereumConsensusBuilder::default())
     }
 }
@@ -61,7 +83,7 @@ where
     type ComponentsBuilder = ComponentsBuilder<
         N,
         EthereumPoolBuilder,
-        EthereumPayloadBuilder,
+        WvmPayloadServiceBuilder,
         EthereumNetworkBuilder,
         WvmEthExecutorBuilder,
         EthereumConsensusBuilder,
@@ -69,31 +91,123 @@ where
     type AddOns = ();
 
     fn components_builder(&self) -> Self::ComponentsBuilder {
-        Self::components()
+        Self::components(self.executor.clone())
     }
 }
 
 /// A regular ethereum evm and executor builder.
-#[derive(Debug, Default, Clone, Copy)]
+#[derive(Debug, Clone)]
 #[non_exhaustive]
-pub struct WvmEthExecutorBuilder;
+pub struct WvmEthExecutorBuilder {
+    /// Permanent storage served by the read precompiles.
+    pub storage: Arc<dyn PermanentStorageBackend>,
+    /// Runtime shared by all precompiles that do network requests.
+    pub io: PrecompileIo,
+    /// RPC URLs of the nodes asked for read sidecars that are not archived yet, none by default.
+    pub sidecar_peers: Vec<String>,
+    /// Cache of the data read from permanent storage, disabled by default.
+    pub read_cache: ReadCache,
+    /// Source of the blob sidecars of Ethereum blocks, KYVE by default.
+    pub blob_source: Arc<dyn BlobSource>,
+}
+
+impl WvmEthExecutorBuilder {
+    pub fn new(storage: Arc<dyn PermanentStorageBackend>, io: PrecompileIo) -> Self {
+        WvmEthExecutorBuilder {
+            storage,
+            io,
+            sidecar_peers: vec![],
+            read_cache: ReadCache::disabled(),
+            blob_source: Arc::new(KyveBlobSource::default()),
+        }
+    }
+
+    /// Asks the nodes at the RPC URLs `peers` for read sidecars that are not archived yet.
+    pub fn with_sidecar_peers(mut self, peers: Vec<String>) -> Self {
+        self.sidecar_peers = peers;
+        self
+    }
+
+    /// Reads data from permanent storage through `cache`.
+    pub fn with_read_cache(mut self, cache: ReadCache) -> Self {
+        self.read_cache = cache;
+        self
+    }
+
+    /// Reads the blob sidecars of Ethereum blocks from `source`.
+    pub fn with_blob_source(mut self, source: Arc<dyn BlobSource>) -> Self {
+        self.blob_source = source;
+        self
+    }
+}
 
 impl<Node> ExecutorBuilder<Node> for WvmEthExecutorBuilder
 where
     Node: FullNodeTypes,
 {
     type EVM = WvmEthEvmConfig;
-    type Executor = EthExecutorProvider<Self::EVM>;
+    type Executor = WvmExecutorProvider<EthExecutorProvider<Self::EVM>>;
 
     async fn build_evm(
         self,
         ctx: &BuilderContext<Node>,
     ) -> eyre::Result<(Self::EVM, Self::Executor)> {
-        let chain_spec = ctx.chain_spec();
-        let evm_config =
-            WvmEthEvmConfig::new(EthEvmConfig::default(), Default::default(), wvm_precompiles());
-        let executor = EthExecutorProvider::new(chain_spec, evm_config.clone());
+        let datadir = ctx.config().datadir().data_dir();
+        let upload_outbox = UploadOutbox::at_datadir(datadir)?;
+        let sidecar_outbox = ReadSidecarOutbox::at_datadir(datadir)?;
+        let forks = PrecompileForks::from_chain_spec(&ctx.chain_spec())?;
+        let sidecars = ReadSidecars::new(
+            Arc::new(sidecar_outbox),
+            self.storage.clone(),
+            self.io.clone(),
+            forks.read_sidecars,
+        )
+        .with_peers(self.sidecar_peers);
 
-        Ok((evm_config, executor))
+        wvm_executor(
+            ctx.chain_spec(),
+            Arc::new(upload_outbox),
+            self.storage,
+            self.read_cache,
+            self.blob_source,
+            self.io,
+            sidecars,
+        )
     }
 }
+
+/// Creates the EVM config of a WVM node, with the WVM precompiles installed.
+pub fn wvm_evm_config(
+    storage: Arc<dyn PermanentStorageBackend>,
+    read_cache: ReadCache,
+    blob_source: Arc<dyn BlobSource>,
+    forks: PrecompileForks,
+    io: PrecompileIo,
+) -> WvmEthEvmConfig {
+    WvmEthEvmConfig::new(
+        EthEvmConfig::default(),
+        Default::default(),
+        wvm_precompiles(storage, read_cache, blob_source, &forks, io),
+    )
+}
+
+/// Creates the EVM config and block executor of a WVM node, with the WVM precompiles installed.
+///
+/// Blocks that commit to a read sidecar are executed with the reads of the sidecar, the payloads
+/// of the upload precompile are staged in `upload_outbox` for every executed block. The
+/// precompiles are activated and charged as configured in the chain spec.
+pub fn wvm_executor(
+    chain_spec: Arc<ChainSpec>,
+    upload_outbox: Arc<UploadOutbox>,
+    storage: Arc<dyn PermanentStorageBackend>,
+    read_cache: ReadCache,
+    blob_source: Arc<dyn BlobSource>,
+    io: PrecompileIo,
+    sidecars: ReadSidecars,
+) -> eyre::Result<(WvmEthEvmConfig, WvmExecutorProvider<EthExecutorProvider<WvmEthEvmConfig>>)> {
+    let forks = PrecompileForks::from_chain_spec(&chain_spec)?;
+    let evm_config = wvm_evm_config(storage, read_cache, blob_source, forks, io);
+    let executor = EthExecutorProvider::new(chain_spec, evm_config.clone());
+
+    Ok((evm_config, WvmExecutorProvider::new(executor, sidecars, upload_outbox)))
+}
@@ -0,0 +1,179 @@
+use crate::{
+    inner::{
+        registry::PrecompileForks,
+        sidecar::{record_reads, ReadSidecars},
+    },
+    node::{wvm_evm_config, WvmEthExecutorBuilder},
+    wevm_node_config::WvmEthEvmConfig,
+};
+use outbox::sidecar::ReadSidecarOutbox;
+use reth::{
+    api::{FullNodeTypes, NodeTypes, PayloadTypes},
+    builder::{components::PayloadServiceBuilder, BuilderContext, PayloadBuilderConfig},
+    payload::{
+        error::PayloadBuilderError, EthBuiltPayload, EthPayloadBuilderAttributes,
+        PayloadBuilderHandle, PayloadBuilderService,
+    },
+    primitives::Bytes,
+    providers::{CanonStateSubscriptions, StateProviderFactory},
+    transaction_pool::TransactionPool,
+};
+use reth_basic_payload_builder::{
+    BasicPayloadJobGenerator, BasicPayloadJobGeneratorConfig, BuildArguments, BuildOutcome,
+    PayloadBuilder, PayloadConfig,
+};
+use reth_ethereum_engine_primitives::EthPayloadAttributes;
+use std::sync::Arc;
+use wevm_borsh::sidecar::ReadSidecar;
+
+/// Builds WVM blocks with the WVM precompiles.
+///
+/// Blocks from the activation of read sidecars in the chain spec on commit to the data the read
+/// precompiles returned while the block was built: the extra data of the header is replaced by the
+/// commitment of the recorded [`ReadSidecar`], which is staged in the outbox for validation and
+/// archival.
+#[derive(Debug, Clone)]
+pub struct WvmPayloadBuilder {
+    inner: reth_ethereum_payload_builder::EthereumPayloadBuilder<WvmEthEvmConfig>,
+    sidecars: ReadSidecars,
+}
+
+impl WvmPayloadBuilder {
+    pub fn new(evm_config: WvmEthEvmConfig, sidecars: ReadSidecars) -> Self {
+        WvmPayloadBuilder {
+            inner: reth_ethereum_payload_builder::EthereumPayloadBuilder::new(evm_config),
+            sidecars,
+        }
+    }
+
+    /// Commits the block of `payload` to `sidecar`, staging non-empty sidecars.
+    fn commit(
+        &self,
+        payload: EthBuiltPayload,
+        sidecar: &ReadSidecar,
+    ) -> Result<EthBuiltPayload, PayloadBuilderError> {
+        let mut block = payload.block().clone().unseal();
+        let commitment = if sidecar.reads.is_empty() {
+            ReadSidecar::empty_commitment()
+        } else {
+            self.sidecars
+                .outbox()
+                .stage(block.number, sidecar)
+                .map_err(PayloadBuilderError::other)?
+        };
+        block.header.extra_data = Bytes::copy_from_slice(commitment.as_slice());
+
+        let mut committed = EthBuiltPayload::new(payload.id(), block.seal_slow(), payload.fees());
+        committed.extend_sidecars(payload.sidecars().to_vec());
+
+        Ok(committed)
+    }
+}
+
+impl<Pool, Client> PayloadBuilder<Pool, Client> for WvmPayloadBuilder
+where
+    Client: StateProviderFactory,
+    Pool: TransactionPool,
+{
+    type Attributes = EthPayloadBuilderAttributes;
+    type BuiltPayload = EthBuiltPayload;
+
+    fn try_build(
+        &self,
+        args: BuildArguments<Pool, Client, EthPayloadBuilderAttributes, EthBuiltPayload>,
+    ) -> Result<BuildOutcome<EthBuiltPayload>, PayloadBuilderError> {
+        let config = &args.config;
+        if !self.sidecars.is_active(config.parent_block.number + 1, config.attributes.timestamp) {
+            return self.inner.try_build(args)
+        }
+
+        let (outcome, sidecar) = record_reads(|| self.inner.try_build(args));
+        match outcome? {
+            BuildOutcome::Better { payload, cached_reads } => {
+                Ok(BuildOutcome::Better { payload: self.commit(payload, &sidecar)?, cached_reads })
+            }
+            outcome => Ok(outcome),
+        }
+    }
+
+    fn build_empty_payload(
+        &self,
+        client: &Client,
+        config: PayloadConfig<Self::Attributes>,
+    ) -> Result<EthBuiltPayload, PayloadBuilderError> {
+        let is_active =
+            self.sidecars.is_active(config.parent_block.number + 1, config.attributes.timestamp);
+        let payload = self.inner.build_empty_payload(client, config)?;
+        if !is_active {
+            return Ok(payload)
+        }
+
+        self.commit(payload, &ReadSidecar::default())
+    }
+}
+
+/// Payload service of a WVM node, builds blocks with the [`WvmPayloadBuilder`].
+#[derive(Debug, Clone)]
+pub struct WvmPayloadServiceBuilder {
+    executor: WvmEthExecutorBuilder,
+}
+
+impl From<WvmEthExecutorBuilder> for WvmPayloadServiceBuilder {
+    /// Uses the precompiles and read sidecars of the block executor.
+    fn from(executor: WvmEthExecutorBuilder) -> Self {
+        WvmPayloadServiceBuilder { executor }
+    }
+}
+
+impl<Node, Pool> PayloadServiceBuilder<Node, Pool> for WvmPayloadServiceBuilder
+where
+    Node: FullNodeTypes,
+    Pool: TransactionPool + Unpin + 'static,
+    <Node as NodeTypes>::Engine: PayloadTypes<
+        BuiltPayload = EthBuiltPayload,
+        PayloadAttributes = EthPayloadAttributes,
+        PayloadBuilderAttributes = EthPayloadBuilderAttributes,
+    >,
+{
+    async fn spawn_payload_service(
+        self,
+        ctx: &BuilderContext<Node>,
+        pool: Pool,
+    ) -> eyre::Result<PayloadBuilderHandle<Node::Engine>> {
+        let datadir = ctx.config().datadir().data_dir();
+        let sidecar_outbox = Arc::new(ReadSidecarOutbox::at_datadir(datadir)?);
+
+        let WvmEthExecutorBuilder { storage, io, sidecar_peers, read_cache, blob_source, .. } =
+            self.executor;
+        let forks = PrecompileForks::from_chain_spec(&ctx.chain_spec())?;
+        let sidecars =
+            ReadSidecars::new(sidecar_outbox, storage.clone(), io.clone(), forks.read_sidecars)
+                .with_peers(sidecar_peers);
+        let payload_builder = WvmPayloadBuilder::new(
+            wvm_evm_config(storage, read_cache, blob_source, forks, io),
+            sidecars,
+        );
+        let conf = ctx.payload_builder_config();
+
+        let payload_job_config = BasicPayloadJobGeneratorConfig::default()
+            .interval(conf.interval())
+            .deadline(conf.deadline())
+            .max_payload_tasks(conf.max_payload_tasks())
+            .extradata(conf.extradata_bytes());
+
+        let payload_generator = BasicPayloadJobGenerator::with_builder(
+            ctx.provider().clone(),
+            pool,
+            ctx.task_executor().clone(),
+            payload_job_config,
+            ctx.chain_spec(),
+            payload_builder,
+        );
+        let (payload_service, payload_builder) =
+            PayloadBuilderService::new(payload_generator, ctx.provider().canonical_state_stream());
+
+        ctx.task_executor().spawn_critical("payload builder service", Box::pin(payload_service));
+
+        Ok(payload_builder)
+    }
+}
@@ -0,0 +1,146 @@
+use crate::inner::read_cache::{ReadCache, ReadCacheEntry, ReadCacheStats};
+use jsonrpsee::{
+    core::RpcResult,
+    proc_macros::rpc,
+    types::{error::INTERNAL_ERROR_CODE, ErrorObject, ErrorObjectOwned},
+};
+use outbox::sidecar::ReadSidecarOutbox;
+use reth::primitives::{Bytes, B256};
+use std::{io, sync::Arc};
+
+/// Admin API to inspect and evict the cache of the read precompiles.
+///
+/// Only served on the transports the `admin` namespace is enabled on.
+#[rpc(server, namespace = "wvmReadCache")]
+pub trait ReadCacheApi {
+    /// Returns the number and total size of the cached items.
+    #[method(name = "stats")]
+    fn stats(&self) -> RpcResult<ReadCacheStats>;
+
+    /// Returns the cached items, most recently read first.
+    #[method(name = "entries")]
+    fn entries(&self) -> RpcResult<Vec<ReadCacheEntry>>;
+
+    /// Evicts the item with `id`, returns whether it was cached.
+    #[method(name = "evict")]
+    fn evict(&self, id: String) -> RpcResult<bool>;
+
+    /// Evicts every item, returns the number of evicted items.
+    #[method(name = "clear")]
+    fn clear(&self) -> RpcResult<usize>;
+}
+
+/// Implementation of the [`ReadCacheApiServer`].
+#[derive(Debug, Clone)]
+pub struct ReadCacheRpc {
+    cache: ReadCache,
+}
+
+impl ReadCacheRpc {
+    pub fn new(cache: ReadCache) -> Self {
+        ReadCacheRpc { cache }
+    }
+}
+
+fn internal_error(err: io::Error) -> ErrorObjectOwned {
+    ErrorObject::owned(INTERNAL_ERROR_CODE, err.to_string(), None::<()>)
+}
+
+impl ReadCacheApiServer for ReadCacheRpc {
+    fn stats(&self) -> RpcResult<ReadCacheStats> {
+        Ok(self.cache.stats())
+    }
+
+    fn entries(&self) -> RpcResult<Vec<ReadCacheEntry>> {
+        Ok(self.cache.entries())
+    }
+
+    fn evict(&self, id: String) -> RpcResult<bool> {
+        self.cache.evict(id.as_str()).map_err(internal_error)
+    }
+
+    fn clear(&self) -> RpcResult<usize> {
+        self.cache.clear().map_err(internal_error)
+    }
+}
+
+/// API serving the read sidecars of the blocks this node built, until they are archived.
+///
+/// Served as `wvmReadSidecar_get`, see
+/// [`READ_SIDECAR_METHOD`](crate::inner::sidecar::READ_SIDECAR_METHOD).
+#[rpc(server, namespace = "wvmReadSidecar")]
+pub trait ReadSidecarApi {
+    /// Returns the borsh encoded sidecar of `block_number` with `commitment`, `None` if it is not
+    /// in the outbox of this node.
+    #[method(name = "get")]
+    fn get(&self, block_number: u64, commitment: B256) -> RpcResult<Option<Bytes>>;
+}
+
+/// Implementation of the [`ReadSidecarApiServer`].
+#[derive(Debug, Clone)]
+pub struct ReadSidecarRpc {
+    outbox: Arc<ReadSidecarOutbox>,
+}
+
+impl ReadSidecarRpc {
+    pub fn new(outbox: Arc<ReadSidecarOutbox>) -> Self {
+        ReadSidecarRpc { outbox }
+    }
+}
+
+impl ReadSidecarApiServer for ReadSidecarRpc {
+    fn get(&self, block_number: u64, commitment: B256) -> RpcResult<Option<Bytes>> {
+        let sidecar = self.outbox.get(block_number, commitment).map_err(internal_error)?;
+        sidecar
+            .map(|sidecar| borsh::to_vec(&sidecar).map(Bytes::from))
+            .transpose()
+            .map_err(internal_error)
+    }
+}
+
+#[cfg(test)]
+mod rpc_tests {
+    use crate::{
+        inner::read_cache::{ReadCache, ReadCacheConfig},
+        rpc::{ReadCacheApiServer, ReadCacheRpc, ReadSidecarApiServer, ReadSidecarRpc},
+    };
+    use outbox::sidecar::ReadSidecarOutbox;
+    use reth::primitives::B256;
+    use std::sync::Arc;
+    use wevm_borsh::sidecar::{ReadSidecar, RecordedRead};
+
+    #[test]
+    pub fn test_read_cache_rpc() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = ReadCache::new(dir.path(), ReadCacheConfig::default()).unwrap();
+        cache.insert("tx-1", b"Hello world").unwrap();
+        cache.insert("tx-2", b"Hello again").unwrap();
+
+        let rpc = ReadCacheRpc::new(cache);
+        assert_eq!(rpc.stats().unwrap().entries, 2);
+        assert_eq!(rpc.entries().unwrap()[0].id, "tx-2");
+        assert!(rpc.evict("tx-1".to_string()).unwrap());
+        assert_eq!(rpc.clear().unwrap(), 1);
+        assert_eq!(rpc.stats().unwrap().entries, 0);
+    }
+
+    #[test]
+    pub fn test_read_sidecar_rpc() {
+        let dir = tempfile::tempdir().unwrap();
+        let outbox = Arc::new(ReadSidecarOutbox::new(dir.path()).unwrap());
+        let sidecar = ReadSidecar {
+            reads: vec![RecordedRead {
+                precompile: "arweave-read".to_string(),
+                input: b"tx".to_vec(),
+                output: Ok(b"data".to_vec()),
+            }],
+        };
+        let commitment = outbox.stage(10, &sidecar).unwrap();
+
+        let rpc = ReadSidecarRpc::new(outbox);
+        let data = rpc.get(10, commitment).unwrap().unwrap();
+        assert_eq!(borsh::from_slice::<ReadSidecar>(data.as_ref()).unwrap(), sidecar);
+        assert_eq!(rpc.get(11, commitment).unwrap(), None);
+        assert_eq!(rpc.get(10, B256::ZERO).unwrap(), None);
+    }
+}
@@ -0,0 +1,6 @@
+use crate::inner::io::PrecompileIo;
+
+/// Returns the I/O runtime of precompiles with its default configuration.
+pub fn io() -> PrecompileIo {
+    PrecompileIo::new(Default::default()).unwrap()
+}
@@ -111,6 +111,12 @@ impl WvmEthEvmConfig {
 
         loaded_precompiles.extend(extensions);
         for (address, precompile) in loaded_precompiles.to_mut().iter_mut() {
+            // only standard precompiles are pure functions of their input, everything else
+            // depends on the env (e.g. the block) and must not be served from the cache
+            if !matches!(precompile, ContextPrecompile::Ordinary(Precompile::Standard(_))) {
+                continue
+            }
+
             // get or insert the cache for this address / spec
             let mut cache = cache.write();
             let cache = cache
@@ -1,4 +1,5 @@
 use bigquery::client::BigQueryClient;
+use reth::primitives::BlockHash;
 
 use types;
 
@@ -15,4 +16,12 @@ impl StateRepository {
     pub async fn save(&self, state: types::types::ExecutionTipState) -> eyre::Result<()> {
         self.bq_client.bq_insert_state("state", state).await
     }
+
+    pub async fn mark_non_canonical(
+        &self,
+        block_number: u64,
+        block_hash: BlockHash,
+    ) -> eyre::Result<()> {
+        self.bq_client.bq_mark_non_canonical("state", block_number, block_hash).await
+    }
 }
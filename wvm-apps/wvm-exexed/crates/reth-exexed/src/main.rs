@@ -2,24 +2,45 @@
 
 #![doc(issue_tracker_base_url = "https://github.com/weaveVM/wvm-reth/issues/")]
 
+use arweave_import::command::{ImportArweaveCommand, IMPORT_ARWEAVE_COMMAND};
 use bigquery::client::BigQueryConfig;
-use irys::irys::IrysRequest;
+use clap::Parser;
+use exex_etl::{archiver::Archiver, args::ArchiveArgs, precompile_uploads};
+use irys::{args::StorageArgs, backend::PermanentStorageBackend};
 use lambda::lambda::exex_lambda_processor;
-use precompiles::node::WvmEthExecutorBuilder;
+use outbox::{archive::ArchiveOutbox, sidecar::ReadSidecarOutbox, upload::UploadOutbox};
+use precompiles::{
+    args::PrecompileArgs,
+    node::WvmEthExecutorBuilder,
+    payload::WvmPayloadServiceBuilder,
+    rpc::{ReadCacheApiServer, ReadCacheRpc, ReadSidecarApiServer, ReadSidecarRpc},
+};
 use repository::state_repository;
-use reth::{api::FullNodeComponents, builder::Node};
+use reth::{
+    api::FullNodeComponents, builder::Node, providers::Chain, rpc::builder::RethRpcModule,
+    CliRunner,
+};
 use reth_exex::{ExExContext, ExExEvent, ExExNotification};
-use std::env;
+use std::{env, sync::Arc, time::Instant};
 
 use reth_node_ethereum::{
     node::{EthereumAddOns, EthereumExecutorBuilder},
     EthereumNode,
 };
-use reth_tracing::tracing::info;
-use serde_json::to_string;
-use rbrotli::to_brotli;
-use types::types::ExecutionTipState;
-use wevm_borsh::block::BorshSealedBlockWithSenders;
+use reth_tracing::tracing::{info, warn};
+
+/// WVM specific command line arguments.
+#[derive(Debug, Clone, clap::Args)]
+struct WvmArgs {
+    #[command(flatten)]
+    storage: StorageArgs,
+
+    #[command(flatten)]
+    archive: ArchiveArgs,
+
+    #[command(flatten)]
+    precompiles: PrecompileArgs,
+}
 
 pub fn get_network_tag() -> &'static str {
     let devnet_flag = env::var("DEVNET").unwrap_or(String::from("false")).to_lowercase();
@@ -31,53 +52,84 @@ pub fn get_network_tag() -> &'static str {
 
 async fn exex_etl_processor<Node: FullNodeComponents>(
     mut ctx: ExExContext<Node>,
-    state_repository: state_repository::StateRepository,
-    irys_provider: irys::irys::IrysProvider,
+    mut archiver: Archiver,
+    storage: Arc<dyn PermanentStorageBackend>,
+    upload_outbox: UploadOutbox,
     _state_processor: exex_etl::state_processor::StateProcessor,
 ) -> eyre::Result<()> {
-    while let Some(notification) = ctx.notifications.recv().await {
-        match &notification {
-            ExExNotification::ChainCommitted { new } => {
-                info!(committed_chain = ?new.range(), "Received commit");
-            }
-            ExExNotification::ChainReorged { old, new } => {
-                info!(from_chain = ?old.range(), to_chain = ?new.range(), "Received reorg");
-            }
-            ExExNotification::ChainReverted { old } => {
-                info!(reverted_chain = ?old.range(), "Received revert");
-            }
-        };
+    // blocks committed while the node was down are archived first
+    if let Some(last_archived) = archiver.resume(ctx.provider(), ctx.head.number)? {
+        ctx.events.send(ExExEvent::FinishedHeight(last_archived))?;
+    }
+
+    // committed chains whose precompile uploads were not processed yet, oldest first
+    let mut upload_chains: Vec<Arc<Chain>> = vec![];
 
-        if let Some(committed_chain) = notification.committed_chain() {
-            ctx.events.send(ExExEvent::FinishedHeight(committed_chain.tip().number))?;
+    loop {
+        // uploads that never made it to permanent storage are archived again
+        if let Err(err) = archiver.stage_dropped(ctx.provider()).await {
+            warn!(%err, "Failed to stage dropped uploads");
         }
 
-        if let Some(committed_chain) = notification.committed_chain() {
-            let sealed_block_with_senders = committed_chain.tip();
-            let clone_block = BorshSealedBlockWithSenders(sealed_block_with_senders.clone());
-            let borsh_data = borsh::to_vec(&clone_block)?;
-            let brotli_borsh = to_brotli(borsh_data);
-            let json_str = to_string(&sealed_block_with_senders)?;
-
-            let arweave_id = IrysRequest::new()
-                .set_tag("Content-Type", "application/octet-stream")
-                .set_tag("WeaveVM:Encoding", "Borsh-Brotli")
-                .set_tag("Block-Number", sealed_block_with_senders.number.to_string().as_str())
-                .set_tag("Block-Hash", sealed_block_with_senders.block.hash().to_string().as_str())
-                .set_tag("Network", get_network_tag())
-                .set_data(brotli_borsh)
-                .send_with_provider(&irys_provider)
-                .await?;
-
-            println!("irys id: {}", arweave_id);
-
-            state_repository
-                .save(ExecutionTipState {
-                    block_number: committed_chain.tip().block.number,
-                    arweave_id: arweave_id.clone(),
-                    sealed_block_with_senders_serialized: json_str,
-                })
-                .await?;
+        // only blocks that are durably archived may be pruned
+        if let Some(archived) = archiver.archive_pending().await? {
+            ctx.events.send(ExExEvent::FinishedHeight(archived))?;
+        }
+
+        while let Some(chain) = upload_chains.first() {
+            let processed = precompile_uploads::process_committed_chain(
+                &upload_outbox,
+                chain,
+                storage.as_ref(),
+                get_network_tag(),
+            )
+            .await;
+            if let Err(err) = processed {
+                warn!(
+                    chain = ?chain.range(),
+                    %err,
+                    "Failed to process precompile uploads, will retry"
+                );
+                break
+            }
+            upload_chains.remove(0);
+        }
+
+        let wake_at = archiver.wake_at();
+
+        tokio::select! {
+            notification = ctx.notifications.recv() => {
+                let Some(notification) = notification else { break };
+
+                match &notification {
+                    ExExNotification::ChainCommitted { new } => {
+                        info!(committed_chain = ?new.range(), "Received commit");
+                    }
+                    ExExNotification::ChainReorged { old, new } => {
+                        info!(from_chain = ?old.range(), to_chain = ?new.range(), "Received reorg");
+                    }
+                    ExExNotification::ChainReverted { old } => {
+                        info!(reverted_chain = ?old.range(), "Received revert");
+                    }
+                };
+
+                // markers have to be staged first, a reorg replaces blocks at the same height
+                if let Some(reverted_chain) = notification.reverted_chain() {
+                    let committed_chain = notification.committed_chain();
+                    archiver.stage_reverted(&reverted_chain, committed_chain.as_deref())?;
+
+                    // uploads of reverted blocks must not be confirmed anymore
+                    let reverted_from = reverted_chain.first().number;
+                    upload_chains.retain(|chain| chain.tip().number < reverted_from);
+                }
+
+                if let Some(committed_chain) = notification.committed_chain() {
+                    archiver.stage_chain(&committed_chain)?;
+                    upload_chains.push(committed_chain);
+                }
+            }
+            _ = tokio::time::sleep_until(wake_at.unwrap_or_else(Instant::now).into()),
+                if wake_at.is_some() => {}
         }
     }
 
@@ -86,11 +138,48 @@ async fn exex_etl_processor<Node: FullNodeComponents>(
 
 /// Main loop of the exexed WVM node
 fn main() -> eyre::Result<()> {
-    reth::cli::Cli::parse_args().run(|builder, _| async move {
+    // reth's cli has no extension point for commands, so the import is dispatched before it
+    if env::args().nth(1).as_deref() == Some(IMPORT_ARWEAVE_COMMAND) {
+        let command = ImportArweaveCommand::parse_from(env::args().skip(1));
+        let _guard = command.init_tracing()?;
+        return CliRunner::default().run_blocking_until_ctrl_c(command.execute(get_network_tag()))
+    }
+
+    reth::cli::Cli::<WvmArgs>::parse().run(|builder, args| async move {
+        let storage = args.storage.backend(builder.config().datadir().data_dir())?;
+        let mirrors = args.archive.mirrors(&args.storage, builder.config().datadir().data_dir())?;
+        let quorum = args.archive.quorum()?;
+        let start_block = args.archive.start_block;
+        let batch = args.archive.batch_config();
+        let outcomes = args.archive.outcome_config();
+        let codec = args.archive.codec()?;
+        let precompile_io = args.precompiles.io()?;
+        let read_cache = args.precompiles.read_cache(builder.config().datadir().data_dir())?;
+        let blob_source = args.precompiles.blob_source(builder.config().datadir().data_dir())?;
+        let sidecar_outbox =
+            Arc::new(ReadSidecarOutbox::at_datadir(builder.config().datadir().data_dir())?);
+        let executor = WvmEthExecutorBuilder::new(storage.clone(), precompile_io)
+            .with_sidecar_peers(args.precompiles.sidecar_peers.clone())
+            .with_read_cache(read_cache.clone())
+            .with_blob_source(blob_source);
+
         let mut handle = builder
             .with_types::<EthereumNode>()
-            .with_components(EthereumNode::components().executor(WvmEthExecutorBuilder::default()))
-            .with_add_ons::<EthereumAddOns>();
+            .with_components(
+                EthereumNode::components()
+                    .payload(WvmPayloadServiceBuilder::from(executor.clone()))
+                    .executor(executor),
+            )
+            .with_add_ons::<EthereumAddOns>()
+            .extend_rpc_modules(move |ctx| {
+                // evicting the cache is an admin operation, only served with the admin namespace
+                ctx.modules.merge_if_module_configured(
+                    RethRpcModule::Admin,
+                    ReadCacheRpc::new(read_cache).into_rpc(),
+                )?;
+                ctx.modules.merge_configured(ReadSidecarRpc::new(sidecar_outbox).into_rpc())?;
+                Ok(())
+            });
 
         let run_exex = (std::env::var("RUN_EXEX").unwrap_or(String::from("false"))).to_lowercase();
         if run_exex == "true" {
@@ -116,13 +205,32 @@ fn main() -> eyre::Result<()> {
 
                     // init state repository
                     let state_repo = state_repository::StateRepository::new(bigquery_client);
+
+                    // blocks are staged in the archive outbox until they are archived
+                    let archive_outbox =
+                        ArchiveOutbox::at_datadir(ctx.config.datadir().data_dir())?;
+                    let archiver = Archiver::new(
+                        archive_outbox,
+                        storage.clone(),
+                        Arc::new(state_repo),
+                        get_network_tag(),
+                    )
+                    .with_mirrors(mirrors)
+                    .with_quorum(quorum)
+                    .with_start_block(start_block)
+                    .with_batch(batch)
+                    .with_outcomes(outcomes)
+                    .with_codec(codec)
+                    .with_read_sidecars(ReadSidecarOutbox::at_datadir(
+                        ctx.config.datadir().data_dir(),
+                    )?);
                     // init state processor
                     let state_processor = exex_etl::state_processor::StateProcessor::new();
 
-                    // init irys provider
-                    let irys_provider = irys::irys::IrysProvider::new(None);
+                    // payloads staged by the arweave upload precompile
+                    let upload_outbox = UploadOutbox::at_datadir(ctx.config.datadir().data_dir())?;
 
-                    Ok(exex_etl_processor(ctx, state_repo, irys_provider, state_processor))
+                    Ok(exex_etl_processor(ctx, archiver, storage, upload_outbox, state_processor))
                 })
                 .install_exex("exex-lambda", |ctx| async move { Ok(exex_lambda_processor(ctx)) })
         }
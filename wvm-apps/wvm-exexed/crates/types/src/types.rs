@@ -1,10 +1,15 @@
 use alloy_primitives;
 
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ExecutionTipState {
     pub block_number: alloy_primitives::BlockNumber,
+    pub block_hash: alloy_primitives::BlockHash,
     pub arweave_id: String,
+    /// Ids of the block at every archive destination it was uploaded to, by destination name.
+    #[serde(default)]
+    pub archive_ids: BTreeMap<String, String>,
     pub sealed_block_with_senders_serialized: String,
 }
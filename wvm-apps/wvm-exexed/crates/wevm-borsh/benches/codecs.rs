@@ -0,0 +1,131 @@
+#![allow(missing_docs)]
+
+//! Compares the codecs of archived blocks on size and speed.
+//!
+//! Blocks are read from the Brotli compressed items in `WVM_BENCH_BLOCKS_DIR`, e.g. raw archived
+//! blocks downloaded from a gateway. Without it synthetic blocks are used.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use rbrotli::try_from_brotli;
+use reth::primitives::{
+    Address, Header, SealedBlock, SealedBlockWithSenders, Transaction, TransactionSigned,
+    TxEip1559, TxKind, B256, U256,
+};
+use std::{fs, sync::Arc};
+use wevm_borsh::{
+    block::BorshSealedBlockWithSenders,
+    codec::{
+        decompress, Codec, ZstdDictionary, BORSH_BROTLI_ENCODING, DEFAULT_ZSTD_DICTIONARY_SIZE,
+    },
+    format::{encode_payload, split_payload, DecoderRegistry},
+};
+
+const BLOCKS_DIR_ENV: &str = "WVM_BENCH_BLOCKS_DIR";
+
+/// Number of blocks the dictionary is trained on, the remaining ones are benchmarked.
+const TRAINING_BLOCKS: usize = 200;
+
+fn archived_blocks(dir: &str) -> Vec<Vec<u8>> {
+    let registry = DecoderRegistry::default();
+    let mut paths: Vec<_> = fs::read_dir(dir)
+        .expect("blocks dir")
+        .map(|entry| entry.expect("blocks dir entry").path())
+        .collect();
+    paths.sort();
+
+    paths
+        .into_iter()
+        .filter_map(|path| {
+            let data = try_from_brotli(fs::read(&path).ok()?.as_slice()).ok()?;
+            let (version, body) = split_payload(data.as_slice()).ok()?;
+            let block = registry.decoder(BORSH_BROTLI_ENCODING, version)?.block(body).ok()?;
+            encode_payload(&BorshSealedBlockWithSenders(block)).ok()
+        })
+        .collect()
+}
+
+fn synthetic_blocks(count: u64) -> Vec<Vec<u8>> {
+    (0..count)
+        .map(|number| {
+            let body: Vec<_> = (0..number % 50)
+                .map(|nonce| {
+                    TransactionSigned::from_transaction_and_signature(
+                        Transaction::Eip1559(TxEip1559 {
+                            chain_id: 9496,
+                            nonce,
+                            gas_limit: 21_000,
+                            max_fee_per_gas: 1_000_000_000,
+                            to: TxKind::Call(Address::random()),
+                            value: U256::from(nonce * 1_000_000),
+                            ..Default::default()
+                        }),
+                        Default::default(),
+                    )
+                })
+                .collect();
+            let senders = vec![Default::default(); body.len()];
+            let header = Header { number, parent_hash: B256::random(), ..Default::default() };
+            let block = SealedBlockWithSenders {
+                block: SealedBlock { header: header.seal_slow(), body, ..Default::default() },
+                senders,
+            };
+            encode_payload(&BorshSealedBlockWithSenders(block)).unwrap()
+        })
+        .collect()
+}
+
+fn codecs(c: &mut Criterion) {
+    let mut blocks = match std::env::var(BLOCKS_DIR_ENV) {
+        Ok(dir) => archived_blocks(&dir),
+        Err(_) => synthetic_blocks(1_000),
+    };
+    assert!(blocks.len() > TRAINING_BLOCKS, "not enough blocks to train a dictionary");
+    let samples: Vec<_> = blocks.drain(..TRAINING_BLOCKS).collect();
+    let dictionary =
+        Arc::new(ZstdDictionary::train(samples, DEFAULT_ZSTD_DICTIONARY_SIZE).unwrap());
+
+    let codecs = [
+        ("none", Codec::None),
+        ("brotli-5", Codec::Brotli { quality: 5, window: rbrotli::DEFAULT_WINDOW }),
+        ("brotli-9", Codec::Brotli { quality: 9, window: rbrotli::DEFAULT_WINDOW }),
+        ("brotli-11", Codec::default()),
+        ("zstd-3", Codec::Zstd { level: 3, dictionary: None }),
+        ("zstd-19", Codec::Zstd { level: 19, dictionary: None }),
+        ("zstd-3-dict", Codec::Zstd { level: 3, dictionary: Some(dictionary.clone()) }),
+        ("zstd-19-dict", Codec::Zstd { level: 19, dictionary: Some(dictionary.clone()) }),
+    ];
+
+    let total: usize = blocks.iter().map(Vec::len).sum();
+    let mut group = c.benchmark_group("Archived block codecs");
+    group.throughput(Throughput::Bytes(total as u64));
+
+    for (name, codec) in codecs.iter() {
+        let compressed: Vec<_> =
+            blocks.iter().map(|block| codec.compress(block.as_slice()).unwrap()).collect();
+        let size: usize = compressed.iter().map(Vec::len).sum();
+        println!(
+            "{name}: {} blocks, {total} bytes compressed to {size} ({:.2}%)",
+            blocks.len(),
+            size as f64 * 100.0 / total as f64
+        );
+
+        group.bench_function(BenchmarkId::new("compress", name), |b| {
+            b.iter(|| {
+                for block in blocks.iter() {
+                    codec.compress(block.as_slice()).unwrap();
+                }
+            })
+        });
+        group.bench_function(BenchmarkId::new("decompress", name), |b| {
+            b.iter(|| {
+                for data in compressed.iter() {
+                    decompress(codec.encoding(), codec.dictionary().map(Arc::as_ref), data)
+                        .unwrap();
+                }
+            })
+        });
+    }
+}
+
+criterion_group!(benches, codecs);
+criterion_main!(benches);
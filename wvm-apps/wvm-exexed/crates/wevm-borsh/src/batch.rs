@@ -0,0 +1,209 @@
+use crate::{
+    b256::BorshB256,
+    block::{BorshSealedBlockWithSenders, LegacyBorshSealedBlockWithSenders},
+};
+use borsh::{BorshDeserialize, BorshSerialize};
+use reth::primitives::{BlockHash, SealedBlockWithSenders};
+use std::io::{self, ErrorKind, Read, Write};
+
+/// Location of a single block inside a [`BlockBatch`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockBatchEntry {
+    pub block_number: u64,
+    pub block_hash: BlockHash,
+    /// Offset of the Borsh encoded block, relative to the end of the index.
+    pub offset: u64,
+    pub length: u64,
+}
+
+impl BorshSerialize for BlockBatchEntry {
+    fn serialize<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.block_number.serialize(writer)?;
+        BorshB256(self.block_hash).serialize(writer)?;
+        self.offset.serialize(writer)?;
+        self.length.serialize(writer)?;
+
+        Ok(())
+    }
+}
+
+impl BorshDeserialize for BlockBatchEntry {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let block_number: u64 = BorshDeserialize::deserialize_reader(reader)?;
+        let block_hash = BorshB256::deserialize_reader(reader)?;
+        let offset: u64 = BorshDeserialize::deserialize_reader(reader)?;
+        let length: u64 = BorshDeserialize::deserialize_reader(reader)?;
+
+        Ok(BlockBatchEntry { block_number, block_hash: block_hash.0, offset, length })
+    }
+}
+
+/// Several blocks archived as a single item.
+///
+/// The container starts with an index of all blocks, followed by the Borsh encoded blocks. A
+/// single block can be decoded without decoding the others, see [`BlockBatchReader`].
+#[derive(Debug, Clone, Default)]
+pub struct BlockBatch {
+    entries: Vec<BlockBatchEntry>,
+    blocks: Vec<u8>,
+}
+
+impl BlockBatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, block: &SealedBlockWithSenders) -> io::Result<()> {
+        let data = borsh::to_vec(&BorshSealedBlockWithSenders(block.clone()))?;
+
+        self.entries.push(BlockBatchEntry {
+            block_number: block.number,
+            block_hash: block.hash(),
+            offset: self.blocks.len() as u64,
+            length: data.len() as u64,
+        });
+        self.blocks.extend_from_slice(data.as_slice());
+
+        Ok(())
+    }
+
+    pub fn entries(&self) -> &[BlockBatchEntry] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the size of the encoded blocks, without the index.
+    pub fn blocks_size(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// Returns the lowest and the highest block number in the batch.
+    pub fn block_range(&self) -> Option<(u64, u64)> {
+        let start = self.entries.iter().map(|entry| entry.block_number).min()?;
+        let end = self.entries.iter().map(|entry| entry.block_number).max()?;
+        Some((start, end))
+    }
+}
+
+impl BorshSerialize for BlockBatch {
+    fn serialize<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.entries.serialize(writer)?;
+        writer.write_all(self.blocks.as_slice())?;
+
+        Ok(())
+    }
+}
+
+/// Reads blocks out of an encoded [`BlockBatch`].
+#[derive(Debug)]
+pub struct BlockBatchReader<'a> {
+    entries: Vec<BlockBatchEntry>,
+    blocks: &'a [u8],
+}
+
+impl<'a> BlockBatchReader<'a> {
+    pub fn new(data: &'a [u8]) -> io::Result<Self> {
+        let mut blocks = data;
+        let entries = Vec::<BlockBatchEntry>::deserialize(&mut blocks)?;
+
+        for entry in entries.iter() {
+            if entry.offset.checked_add(entry.length).map_or(true, |end| end > blocks.len() as u64)
+            {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!("block {} is out of the batch bounds", entry.block_number),
+                ))
+            }
+        }
+
+        Ok(BlockBatchReader { entries, blocks })
+    }
+
+    pub fn entries(&self) -> &[BlockBatchEntry] {
+        &self.entries
+    }
+
+    pub fn entry(&self, block_number: u64) -> Option<&BlockBatchEntry> {
+        self.entries.iter().find(|entry| entry.block_number == block_number)
+    }
+
+    /// Decodes the block with `block_number`, `None` if it is not part of the batch.
+    pub fn block(&self, block_number: u64) -> io::Result<Option<BorshSealedBlockWithSenders>> {
+        let block = self.decode(block_number, |block: BorshSealedBlockWithSenders| block.0)?;
+        Ok(block.map(BorshSealedBlockWithSenders))
+    }
+
+    /// Decodes the block with `block_number` from a batch written before the native transaction
+    /// encoding.
+    pub fn legacy_block(
+        &self,
+        block_number: u64,
+    ) -> io::Result<Option<LegacyBorshSealedBlockWithSenders>> {
+        let block =
+            self.decode(block_number, |block: LegacyBorshSealedBlockWithSenders| block.0)?;
+        Ok(block.map(LegacyBorshSealedBlockWithSenders))
+    }
+
+    fn decode<T: BorshDeserialize>(
+        &self,
+        block_number: u64,
+        into_block: impl FnOnce(T) -> SealedBlockWithSenders,
+    ) -> io::Result<Option<SealedBlockWithSenders>> {
+        let Some(entry) = self.entry(block_number) else { return Ok(None) };
+
+        let start = entry.offset as usize;
+        let end = start + entry.length as usize;
+        let block = into_block(borsh::from_slice(&self.blocks[start..end])?);
+
+        if block.hash() != entry.block_hash {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!("hash of block {} does not match the batch index", block_number),
+            ))
+        }
+
+        Ok(Some(block))
+    }
+}
+
+#[cfg(test)]
+mod batch_tests {
+    use crate::{
+        batch::{BlockBatch, BlockBatchReader},
+        test_utils::block,
+    };
+
+    #[test]
+    pub fn test_batch_read_single_block() {
+        let mut batch = BlockBatch::new();
+        for number in 10..13 {
+            batch.push(&block(number)).unwrap();
+        }
+        assert_eq!(batch.len(), 3);
+        assert_eq!(batch.block_range(), Some((10, 12)));
+
+        let data = borsh::to_vec(&batch).unwrap();
+        let reader = BlockBatchReader::new(data.as_slice()).unwrap();
+        assert_eq!(reader.entries(), batch.entries());
+
+        let read = reader.block(11).unwrap().unwrap();
+        assert_eq!(read.0, block(11));
+        assert!(reader.block(13).unwrap().is_none());
+    }
+
+    #[test]
+    pub fn test_batch_truncated() {
+        let mut batch = BlockBatch::new();
+        batch.push(&block(1)).unwrap();
+
+        let data = borsh::to_vec(&batch).unwrap();
+        assert!(BlockBatchReader::new(&data[..data.len() - 1]).is_err());
+    }
+}
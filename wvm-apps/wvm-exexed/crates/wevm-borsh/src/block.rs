@@ -2,18 +2,23 @@ use crate::{
     address::BorshAddress,
     header::{BorshHeader, BorshSealedHeader},
     request::BorshRequest,
-    transaction::BorshTransactionSigned,
+    transaction::{BorshTransactionSigned, LegacyBorshTransactionSigned},
     withdrawal::BorshWithdrawal,
 };
 use borsh::{BorshDeserialize, BorshSerialize};
 use reth::primitives::{
-    Request, Requests, SealedBlock, SealedBlockWithSenders, Withdrawal, Withdrawals,
+    Request, Requests, SealedBlock, SealedBlockWithSenders, TransactionSigned, Withdrawal,
+    Withdrawals,
 };
 use std::io::{Read, Write};
 
 pub struct BorshSealedBlock(pub SealedBlock);
 pub struct BorshSealedBlockWithSenders(pub SealedBlockWithSenders);
 
+/// Block of the archives written before the native transaction encoding, see
+/// [`LegacyBorshTransactionSigned`].
+pub struct LegacyBorshSealedBlockWithSenders(pub SealedBlockWithSenders);
+
 impl BorshSerialize for BorshSealedBlock {
     fn serialize<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
         let borsh_sealed_header = BorshSealedHeader(self.0.header.clone());
@@ -44,36 +49,44 @@ impl BorshSerialize for BorshSealedBlock {
 
 impl BorshDeserialize for BorshSealedBlock {
     fn deserialize_reader<R: Read>(reader: &mut R) -> std::io::Result<Self> {
-        let sealed_header = BorshSealedHeader::deserialize_reader(reader)?;
-        let borsh_transactions = Vec::<BorshTransactionSigned>::deserialize_reader(reader)?;
-        let borsh_ommers = Vec::<BorshHeader>::deserialize_reader(reader)?;
-        let withdrawal = Option::<Vec<BorshWithdrawal>>::deserialize_reader(reader)?;
-
-        let requests = Option::<Vec<BorshRequest>>::deserialize_reader(reader)?;
-
-        let sealed_block = SealedBlock {
-            header: sealed_header.0,
-            body: borsh_transactions.into_iter().map(|i| i.0).collect(),
-            ommers: borsh_ommers.into_iter().map(|i| i.0).collect(),
-            withdrawals: withdrawal
-                .map(|i| {
-                    let original_withdrawals: Vec<Withdrawal> =
-                        i.into_iter().map(|e| e.0).collect();
-                    original_withdrawals
-                })
-                .map(|i| Withdrawals::new(i)),
-            requests: requests
-                .map(|i| {
-                    let original_reqs: Vec<Request> = i.into_iter().map(|e| e.0).collect();
-                    original_reqs
-                })
-                .map(|i| Requests(i)),
-        };
-
+        let sealed_block = deserialize_sealed_block(reader, |tx: BorshTransactionSigned| tx.0)?;
         Ok(BorshSealedBlock(sealed_block))
     }
 }
 
+/// Decodes a [`SealedBlock`] whose transactions are encoded as `T`.
+fn deserialize_sealed_block<R: Read, T: BorshDeserialize>(
+    reader: &mut R,
+    into_tx: impl Fn(T) -> TransactionSigned,
+) -> std::io::Result<SealedBlock> {
+    let sealed_header = BorshSealedHeader::deserialize_reader(reader)?;
+    let borsh_transactions = Vec::<T>::deserialize_reader(reader)?;
+    let borsh_ommers = Vec::<BorshHeader>::deserialize_reader(reader)?;
+    let withdrawal = Option::<Vec<BorshWithdrawal>>::deserialize_reader(reader)?;
+
+    let requests = Option::<Vec<BorshRequest>>::deserialize_reader(reader)?;
+
+    let sealed_block = SealedBlock {
+        header: sealed_header.0,
+        body: borsh_transactions.into_iter().map(into_tx).collect(),
+        ommers: borsh_ommers.into_iter().map(|i| i.0).collect(),
+        withdrawals: withdrawal
+            .map(|i| {
+                let original_withdrawals: Vec<Withdrawal> = i.into_iter().map(|e| e.0).collect();
+                original_withdrawals
+            })
+            .map(|i| Withdrawals::new(i)),
+        requests: requests
+            .map(|i| {
+                let original_reqs: Vec<Request> = i.into_iter().map(|e| e.0).collect();
+                original_reqs
+            })
+            .map(|i| Requests(i)),
+    };
+
+    Ok(sealed_block)
+}
+
 impl BorshSerialize for BorshSealedBlockWithSenders {
     fn serialize<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
         let borsh_sealed_block = BorshSealedBlock(self.0.block.clone());
@@ -101,6 +114,18 @@ impl BorshDeserialize for BorshSealedBlockWithSenders {
     }
 }
 
+impl BorshDeserialize for LegacyBorshSealedBlockWithSenders {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> std::io::Result<Self> {
+        let block = deserialize_sealed_block(reader, |tx: LegacyBorshTransactionSigned| tx.0)?;
+        let senders: Vec<BorshAddress> = BorshDeserialize::deserialize_reader(reader)?;
+
+        Ok(LegacyBorshSealedBlockWithSenders(SealedBlockWithSenders {
+            block,
+            senders: senders.into_iter().map(|i| i.0).collect(),
+        }))
+    }
+}
+
 #[cfg(test)]
 mod block_tests {
     use crate::block::{BorshSealedBlock, BorshSealedBlockWithSenders};
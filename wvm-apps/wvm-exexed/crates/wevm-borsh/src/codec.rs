@@ -0,0 +1,240 @@
+use rbrotli::{try_from_brotli_with_limit, MAX_DECOMPRESSED_SIZE};
+use reth::primitives::{keccak256, B256};
+use reth_nippy_jar::compression::{DecoderDictionary, Zstd};
+use std::{
+    fmt,
+    io::{self, ErrorKind, Read, Write},
+    sync::Arc,
+};
+
+/// Borsh encoded payload without compression.
+pub const BORSH_ENCODING: &str = "Borsh";
+/// Borsh encoded payload compressed with Brotli.
+pub const BORSH_BROTLI_ENCODING: &str = "Borsh-Brotli";
+/// Borsh encoded payload compressed with Zstandard.
+pub const BORSH_ZSTD_ENCODING: &str = "Borsh-Zstd";
+
+/// Tag holding the hash of the Zstandard dictionary an item was compressed with.
+///
+/// The dictionary itself is archived as an item of type [`ZSTD_DICTIONARY_TYPE`] with the same
+/// tag, so readers can fetch it by hash.
+pub const ZSTD_DICTIONARY_TAG: &str = "WeaveVM:Zstd-Dictionary";
+/// `WeaveVM:Type` of archived Zstandard dictionaries.
+pub const ZSTD_DICTIONARY_TYPE: &str = "Zstd-Dictionary";
+
+/// Default maximum size of a trained Zstandard dictionary.
+pub const DEFAULT_ZSTD_DICTIONARY_SIZE: usize = 112 * 1024;
+
+/// A Zstandard dictionary, identified by the hash of its raw bytes.
+pub struct ZstdDictionary {
+    hash: B256,
+    raw: Vec<u8>,
+    decoder: DecoderDictionary<'static>,
+}
+
+impl ZstdDictionary {
+    pub fn new(raw: Vec<u8>) -> Self {
+        ZstdDictionary { hash: keccak256(&raw), decoder: DecoderDictionary::copy(&raw), raw }
+    }
+
+    /// Trains a dictionary of at most `max_size` bytes on `samples`, e.g. encoded blocks.
+    pub fn train(samples: impl IntoIterator<Item = Vec<u8>>, max_size: usize) -> io::Result<Self> {
+        let raw = Zstd::train_dictionary(samples, max_size)
+            .map_err(|err| io::Error::new(ErrorKind::InvalidInput, err.to_string()))?;
+        Ok(Self::new(raw))
+    }
+
+    pub fn hash(&self) -> B256 {
+        self.hash
+    }
+
+    pub fn raw(&self) -> &[u8] {
+        self.raw.as_slice()
+    }
+}
+
+impl fmt::Debug for ZstdDictionary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ZstdDictionary")
+            .field("hash", &self.hash)
+            .field("size", &self.raw.len())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Compression of archived payloads.
+#[derive(Debug, Clone)]
+pub enum Codec {
+    None,
+    /// Brotli with a quality from 0 to 11 and the base two logarithm of the window, 10 to 24.
+    Brotli {
+        quality: u8,
+        window: u8,
+    },
+    /// Zstandard with a compression level, `0` uses the default of zstd.
+    Zstd {
+        level: i32,
+        dictionary: Option<Arc<ZstdDictionary>>,
+    },
+}
+
+impl Default for Codec {
+    fn default() -> Self {
+        Codec::Brotli { quality: rbrotli::DEFAULT_QUALITY, window: rbrotli::DEFAULT_WINDOW }
+    }
+}
+
+impl Codec {
+    /// Returns the value of the encoding tag of items compressed with this codec.
+    pub fn encoding(&self) -> &'static str {
+        match self {
+            Codec::None => BORSH_ENCODING,
+            Codec::Brotli { .. } => BORSH_BROTLI_ENCODING,
+            Codec::Zstd { .. } => BORSH_ZSTD_ENCODING,
+        }
+    }
+
+    /// Returns the dictionary items are compressed with, it has to be published for readers.
+    pub fn dictionary(&self) -> Option<&Arc<ZstdDictionary>> {
+        match self {
+            Codec::Zstd { dictionary, .. } => dictionary.as_ref(),
+            _ => None,
+        }
+    }
+
+    pub fn compress(&self, data: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            Codec::None => Ok(data.to_vec()),
+            Codec::Brotli { quality, window } => rbrotli::compress(data, *quality, *window),
+            Codec::Zstd { level, dictionary } => {
+                let raw = dictionary.as_ref().map(|dictionary| dictionary.raw()).unwrap_or(&[]);
+                let mut encoder = zstd::Encoder::with_dictionary(Vec::new(), *level, raw)?;
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+        }
+    }
+}
+
+/// Decompresses a payload of the given `encoding`.
+///
+/// Zstandard payloads compressed with a dictionary need that `dictionary`. Fails if the payload
+/// decompresses to more than [`MAX_DECOMPRESSED_SIZE`] bytes.
+pub fn decompress(
+    encoding: &str,
+    dictionary: Option<&ZstdDictionary>,
+    data: &[u8],
+) -> io::Result<Vec<u8>> {
+    decompress_with_limit(encoding, dictionary, data, MAX_DECOMPRESSED_SIZE)
+}
+
+/// Like [`decompress`], but fails if the payload decompresses to more than `limit` bytes.
+pub fn decompress_with_limit(
+    encoding: &str,
+    dictionary: Option<&ZstdDictionary>,
+    data: &[u8],
+    limit: usize,
+) -> io::Result<Vec<u8>> {
+    match encoding {
+        BORSH_ENCODING => Ok(data.to_vec()),
+        BORSH_BROTLI_ENCODING => try_from_brotli_with_limit(data, limit),
+        BORSH_ZSTD_ENCODING => {
+            let decoder: Box<dyn Read + '_> = match dictionary {
+                Some(dictionary) => {
+                    Box::new(zstd::Decoder::with_prepared_dictionary(data, &dictionary.decoder)?)
+                }
+                None => Box::new(zstd::Decoder::new(data)?),
+            };
+
+            let mut decompressed = Vec::with_capacity((data.len() * 4).min(limit));
+            decoder.take(limit as u64 + 1).read_to_end(&mut decompressed)?;
+            if decompressed.len() > limit {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!("zstd payload decompresses to more than {limit} bytes"),
+                ))
+            }
+
+            Ok(decompressed)
+        }
+        encoding => {
+            Err(io::Error::new(ErrorKind::Unsupported, format!("unknown encoding {encoding}")))
+        }
+    }
+}
+
+#[cfg(test)]
+mod codec_tests {
+    use crate::{
+        block::BorshSealedBlockWithSenders,
+        codec::{decompress, decompress_with_limit, Codec, ZstdDictionary},
+    };
+    use reth::primitives::{
+        Header, SealedBlock, SealedBlockWithSenders, Transaction, TransactionSigned, TxLegacy,
+        B256, U256,
+    };
+    use std::sync::Arc;
+
+    fn sample(number: u64) -> Vec<u8> {
+        let header = Header { number, parent_hash: B256::random(), ..Default::default() };
+        let tx = TransactionSigned::from_transaction_and_signature(
+            Transaction::Legacy(TxLegacy {
+                nonce: number,
+                value: U256::from(number * 1_000),
+                ..Default::default()
+            }),
+            Default::default(),
+        );
+        let block = SealedBlockWithSenders {
+            block: SealedBlock { header: header.seal_slow(), body: vec![tx], ..Default::default() },
+            senders: vec![Default::default()],
+        };
+        borsh::to_vec(&BorshSealedBlockWithSenders(block)).unwrap()
+    }
+
+    #[test]
+    pub fn test_codecs_roundtrip() {
+        let data = sample(1);
+        let codecs = [
+            Codec::None,
+            Codec::default(),
+            Codec::Brotli { quality: 5, window: 18 },
+            Codec::Zstd { level: 3, dictionary: None },
+        ];
+
+        for codec in codecs {
+            let compressed = codec.compress(data.as_slice()).unwrap();
+            let decompressed = decompress(codec.encoding(), None, compressed.as_slice()).unwrap();
+            assert_eq!(decompressed, data, "{}", codec.encoding());
+        }
+
+        assert!(decompress("Borsh-Lzma", None, data.as_slice()).is_err());
+    }
+
+    #[test]
+    pub fn test_decompression_limit() {
+        let data = vec![0u8; 64 * 1024];
+        for codec in [Codec::default(), Codec::Zstd { level: 3, dictionary: None }] {
+            let compressed = codec.compress(data.as_slice()).unwrap();
+            let limit = data.len();
+            assert!(decompress_with_limit(codec.encoding(), None, &compressed, limit).is_ok());
+            assert!(decompress_with_limit(codec.encoding(), None, &compressed, limit - 1).is_err());
+        }
+    }
+
+    #[test]
+    pub fn test_zstd_dictionary() {
+        let dictionary = Arc::new(ZstdDictionary::train((0..200).map(sample), 4096).unwrap());
+        let codec = Codec::Zstd { level: 3, dictionary: Some(dictionary.clone()) };
+
+        let data = sample(1_000);
+        let compressed = codec.compress(data.as_slice()).unwrap();
+        let decompressed =
+            decompress(codec.encoding(), Some(dictionary.as_ref()), compressed.as_slice()).unwrap();
+        assert_eq!(decompressed, data);
+
+        // a dictionary is identified by its content
+        assert_eq!(ZstdDictionary::new(dictionary.raw().to_vec()).hash(), dictionary.hash());
+        assert!(decompress(codec.encoding(), None, compressed.as_slice()).is_err());
+    }
+}
@@ -0,0 +1,493 @@
+use crate::{
+    batch::BlockBatchReader,
+    block::{BorshSealedBlockWithSenders, LegacyBorshSealedBlockWithSenders},
+    codec::{
+        decompress, ZstdDictionary, BORSH_BROTLI_ENCODING, BORSH_ENCODING, BORSH_ZSTD_ENCODING,
+        ZSTD_DICTIONARY_TAG,
+    },
+    outcome::BlockOutcome,
+    sidecar::ReadSidecar,
+};
+use borsh::{BorshDeserialize, BorshSerialize};
+use reth::primitives::{SealedBlockWithSenders, B256};
+use std::{
+    collections::{HashMap, VecDeque},
+    io::{self, ErrorKind},
+    sync::{Arc, RwLock},
+};
+
+/// Tag naming the encoding of an archived item.
+pub const ENCODING_TAG: &str = "WeaveVM:Encoding";
+/// Tag naming the format version of an archived item, absent on items of the legacy format.
+pub const FORMAT_VERSION_TAG: &str = "WeaveVM:Format-Version";
+
+/// Marks the start of a format header, followed by the version as a little endian `u16`.
+///
+/// Payloads of the legacy format have no header. Blocks start with the length of their hash and
+/// batches with the length of their index, neither of which matches the magic.
+pub const FORMAT_MAGIC: [u8; 4] = *b"WVMA";
+
+/// Payloads without a header, transactions are embedded as JSON.
+pub const LEGACY_FORMAT_VERSION: u16 = 0;
+/// Payloads with a header, transactions are encoded natively.
+pub const CURRENT_FORMAT_VERSION: u16 = 1;
+
+/// Encodes `value` as a payload of the current format, before compression.
+pub fn encode_payload<T: BorshSerialize>(value: &T) -> io::Result<Vec<u8>> {
+    let mut data = FORMAT_MAGIC.to_vec();
+    CURRENT_FORMAT_VERSION.serialize(&mut data)?;
+    value.serialize(&mut data)?;
+
+    Ok(data)
+}
+
+/// Splits a decompressed payload into its format version and its body.
+pub fn split_payload(data: &[u8]) -> io::Result<(u16, &[u8])> {
+    match data.strip_prefix(FORMAT_MAGIC.as_slice()) {
+        Some(mut body) => {
+            let version = u16::deserialize(&mut body)?;
+            Ok((version, body))
+        }
+        None => Ok((LEGACY_FORMAT_VERSION, data)),
+    }
+}
+
+/// Tags of an archived item that describe how its payload is encoded.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PayloadTags<'a> {
+    pub encoding: Option<&'a str>,
+    pub format_version: Option<&'a str>,
+    pub zstd_dictionary: Option<&'a str>,
+}
+
+impl<'a> PayloadTags<'a> {
+    /// Collects the tags with a lookup of a tag value by name.
+    pub fn from_fn(tag: impl Fn(&str) -> Option<&'a str>) -> Self {
+        PayloadTags {
+            encoding: tag(ENCODING_TAG),
+            format_version: tag(FORMAT_VERSION_TAG),
+            zstd_dictionary: tag(ZSTD_DICTIONARY_TAG),
+        }
+    }
+
+    /// Returns the hash of the Zstandard dictionary the payload was compressed with.
+    pub fn zstd_dictionary_hash(&self) -> io::Result<Option<B256>> {
+        self.zstd_dictionary
+            .map(|hash| {
+                hash.parse::<B256>().map_err(|err| {
+                    io::Error::new(
+                        ErrorKind::InvalidData,
+                        format!("invalid dictionary {hash}: {err}"),
+                    )
+                })
+            })
+            .transpose()
+    }
+}
+
+/// Decodes the body of the payloads of a single format version.
+pub trait PayloadDecoder: Send + Sync {
+    fn block(&self, body: &[u8]) -> io::Result<SealedBlockWithSenders>;
+
+    /// Decodes the block with `block_number` out of a block batch, `None` if it is not part of
+    /// the batch.
+    fn batch_block(
+        &self,
+        body: &[u8],
+        block_number: u64,
+    ) -> io::Result<Option<SealedBlockWithSenders>>;
+
+    fn outcome(&self, body: &[u8]) -> io::Result<BlockOutcome>;
+
+    fn read_sidecar(&self, body: &[u8]) -> io::Result<ReadSidecar>;
+}
+
+/// Decoder of [`LEGACY_FORMAT_VERSION`] payloads.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LegacyBorshDecoder;
+
+impl PayloadDecoder for LegacyBorshDecoder {
+    fn block(&self, body: &[u8]) -> io::Result<SealedBlockWithSenders> {
+        let block: LegacyBorshSealedBlockWithSenders = borsh::from_slice(body)?;
+        Ok(block.0)
+    }
+
+    fn batch_block(
+        &self,
+        body: &[u8],
+        block_number: u64,
+    ) -> io::Result<Option<SealedBlockWithSenders>> {
+        let block = BlockBatchReader::new(body)?.legacy_block(block_number)?;
+        Ok(block.map(|block| block.0))
+    }
+
+    fn outcome(&self, body: &[u8]) -> io::Result<BlockOutcome> {
+        borsh::from_slice(body)
+    }
+
+    fn read_sidecar(&self, _body: &[u8]) -> io::Result<ReadSidecar> {
+        Err(io::Error::new(ErrorKind::Unsupported, "read sidecars were introduced with version 1"))
+    }
+}
+
+/// Decoder of version `1` payloads.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BorshDecoderV1;
+
+impl PayloadDecoder for BorshDecoderV1 {
+    fn block(&self, body: &[u8]) -> io::Result<SealedBlockWithSenders> {
+        let block: BorshSealedBlockWithSenders = borsh::from_slice(body)?;
+        Ok(block.0)
+    }
+
+    fn batch_block(
+        &self,
+        body: &[u8],
+        block_number: u64,
+    ) -> io::Result<Option<SealedBlockWithSenders>> {
+        let block = BlockBatchReader::new(body)?.block(block_number)?;
+        Ok(block.map(|block| block.0))
+    }
+
+    fn outcome(&self, body: &[u8]) -> io::Result<BlockOutcome> {
+        borsh::from_slice(body)
+    }
+
+    fn read_sidecar(&self, body: &[u8]) -> io::Result<ReadSidecar> {
+        borsh::from_slice(body)
+    }
+}
+
+/// Maximum number of Zstandard dictionaries a [`DecoderRegistry`] keeps.
+pub const MAX_ZSTD_DICTIONARIES: usize = 32;
+
+/// Zstandard dictionaries, in the order they were added.
+#[derive(Default)]
+struct Dictionaries {
+    by_hash: HashMap<B256, Arc<ZstdDictionary>>,
+    order: VecDeque<B256>,
+}
+
+/// Decoders of archived payloads, by encoding and format version.
+///
+/// The default registry decodes every format this node has ever written. Zstandard dictionaries
+/// are added as they are discovered, see [`DecoderRegistry::add_dictionary`]. Anyone can archive
+/// a dictionary, so only the [`MAX_ZSTD_DICTIONARIES`] most recently added ones are kept.
+pub struct DecoderRegistry {
+    decoders: HashMap<(String, u16), Arc<dyn PayloadDecoder>>,
+    dictionaries: RwLock<Dictionaries>,
+}
+
+impl DecoderRegistry {
+    /// Creates a registry without any decoder.
+    pub fn empty() -> Self {
+        DecoderRegistry { decoders: HashMap::new(), dictionaries: Default::default() }
+    }
+
+    pub fn register(&mut self, encoding: &str, version: u16, decoder: Arc<dyn PayloadDecoder>) {
+        self.decoders.insert((encoding.to_string(), version), decoder);
+    }
+
+    pub fn decoder(&self, encoding: &str, version: u16) -> Option<&dyn PayloadDecoder> {
+        self.decoders.get(&(encoding.to_string(), version)).map(|decoder| decoder.as_ref())
+    }
+
+    /// Adds a dictionary, evicting the oldest one if [`MAX_ZSTD_DICTIONARIES`] are known.
+    pub fn add_dictionary(&self, dictionary: Arc<ZstdDictionary>) {
+        let mut dictionaries = self.dictionaries.write().unwrap();
+        let hash = dictionary.hash();
+        if dictionaries.by_hash.insert(hash, dictionary).is_some() {
+            return
+        }
+
+        dictionaries.order.push_back(hash);
+        if dictionaries.order.len() > MAX_ZSTD_DICTIONARIES {
+            let oldest = dictionaries.order.pop_front().expect("not empty");
+            dictionaries.by_hash.remove(&oldest);
+        }
+    }
+
+    pub fn dictionary(&self, hash: &B256) -> Option<Arc<ZstdDictionary>> {
+        self.dictionaries.read().unwrap().by_hash.get(hash).cloned()
+    }
+
+    /// Returns the format versions that can be decoded for `encoding`, in ascending order.
+    pub fn versions(&self, encoding: &str) -> Vec<u16> {
+        let mut versions: Vec<u16> = self
+            .decoders
+            .keys()
+            .filter(|(known, _)| known == encoding)
+            .map(|(_, version)| *version)
+            .collect();
+        versions.sort_unstable();
+        versions
+    }
+
+    /// Decompresses an archived item and picks the decoder for its format version.
+    ///
+    /// The version in the payload header has to match the [`FORMAT_VERSION_TAG`], items without
+    /// the tag are expected to be of the legacy format. Items compressed with a Zstandard
+    /// dictionary need the dictionary to be added first.
+    pub fn open(&self, tags: PayloadTags<'_>, data: &[u8]) -> io::Result<ArchivedPayload<'_>> {
+        let Some(encoding) = tags.encoding else {
+            return Err(io::Error::new(ErrorKind::InvalidData, "archived item has no encoding"))
+        };
+        let tagged_version = match tags.format_version {
+            Some(version) => version.parse::<u16>().map_err(|err| {
+                io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!("invalid format version {version}: {err}"),
+                )
+            })?,
+            None => LEGACY_FORMAT_VERSION,
+        };
+
+        let dictionary = match tags.zstd_dictionary_hash()? {
+            Some(hash) => Some(self.dictionary(&hash).ok_or_else(|| {
+                io::Error::new(ErrorKind::NotFound, format!("unknown zstd dictionary {hash}"))
+            })?),
+            None => None,
+        };
+
+        let data = decompress(encoding, dictionary.as_deref(), data)?;
+        let (version, body) = split_payload(data.as_slice())?;
+        if version != tagged_version {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!("payload has format version {version} but is tagged {tagged_version}"),
+            ))
+        }
+
+        let Some(decoder) = self.decoder(encoding, version) else {
+            return Err(io::Error::new(
+                ErrorKind::Unsupported,
+                format!(
+                    "unsupported format version {version} of encoding {encoding}, supported: {:?}",
+                    self.versions(encoding)
+                ),
+            ))
+        };
+        let header_len = data.len() - body.len();
+
+        Ok(ArchivedPayload { version, decoder, data, header_len })
+    }
+}
+
+impl Default for DecoderRegistry {
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        registry.register(
+            BORSH_BROTLI_ENCODING,
+            LEGACY_FORMAT_VERSION,
+            Arc::new(LegacyBorshDecoder),
+        );
+        // other codecs were introduced with version 1
+        for encoding in [BORSH_ENCODING, BORSH_BROTLI_ENCODING, BORSH_ZSTD_ENCODING] {
+            registry.register(encoding, 1, Arc::new(BorshDecoderV1));
+        }
+        registry
+    }
+}
+
+/// Decompressed archived item together with the decoder of its format version.
+pub struct ArchivedPayload<'a> {
+    pub version: u16,
+    decoder: &'a dyn PayloadDecoder,
+    data: Vec<u8>,
+    header_len: usize,
+}
+
+impl ArchivedPayload<'_> {
+    fn body(&self) -> &[u8] {
+        &self.data[self.header_len..]
+    }
+
+    pub fn block(&self) -> io::Result<SealedBlockWithSenders> {
+        self.decoder.block(self.body())
+    }
+
+    pub fn batch_block(&self, block_number: u64) -> io::Result<Option<SealedBlockWithSenders>> {
+        self.decoder.batch_block(self.body(), block_number)
+    }
+
+    pub fn outcome(&self) -> io::Result<BlockOutcome> {
+        self.decoder.outcome(self.body())
+    }
+
+    pub fn read_sidecar(&self) -> io::Result<ReadSidecar> {
+        self.decoder.read_sidecar(self.body())
+    }
+}
+
+#[cfg(test)]
+mod format_tests {
+    use crate::{
+        address::BorshAddress,
+        b256::BorshB256,
+        batch::BlockBatch,
+        block::BorshSealedBlockWithSenders,
+        codec::{Codec, ZstdDictionary, BORSH_BROTLI_ENCODING},
+        format::{
+            encode_payload, DecoderRegistry, PayloadTags, CURRENT_FORMAT_VERSION,
+            MAX_ZSTD_DICTIONARIES,
+        },
+        header::{BorshHeader, BorshSealedHeader},
+        outcome::BlockOutcome,
+        sidecar::{ReadSidecar, RecordedRead},
+        signature::BorshSignature,
+    };
+    use borsh::BorshSerialize;
+    use rbrotli::to_brotli;
+    use reth::primitives::{
+        Header, SealedBlock, SealedBlockWithSenders, Transaction, TransactionSigned, TxLegacy, B256,
+    };
+    use std::sync::Arc;
+
+    fn tags<'a>(encoding: Option<&'a str>, format_version: Option<&'a str>) -> PayloadTags<'a> {
+        PayloadTags { encoding, format_version, zstd_dictionary: None }
+    }
+
+    fn block(number: u64) -> SealedBlockWithSenders {
+        let header = Header { number, ..Default::default() };
+        let tx = TransactionSigned::from_transaction_and_signature(
+            Transaction::Legacy(TxLegacy { nonce: number, ..Default::default() }),
+            Default::default(),
+        );
+        SealedBlockWithSenders {
+            block: SealedBlock { header: header.seal_slow(), body: vec![tx], ..Default::default() },
+            senders: vec![Default::default()],
+        }
+    }
+
+    /// Encodes a block like the archiver did before the format header was introduced.
+    fn legacy_block(block: &SealedBlockWithSenders) -> Vec<u8> {
+        let mut data = vec![];
+        BorshSealedHeader(block.header.clone()).serialize(&mut data).unwrap();
+        (block.body.len() as u32).serialize(&mut data).unwrap();
+        for tx in block.body.iter() {
+            BorshB256(tx.hash).serialize(&mut data).unwrap();
+            BorshSignature(tx.signature).serialize(&mut data).unwrap();
+            serde_json::to_vec(&tx.transaction).unwrap().serialize(&mut data).unwrap();
+        }
+        Vec::<BorshHeader>::new().serialize(&mut data).unwrap();
+        0u8.serialize(&mut data).unwrap();
+        0u8.serialize(&mut data).unwrap();
+        let senders: Vec<BorshAddress> = block.senders.iter().copied().map(BorshAddress).collect();
+        senders.serialize(&mut data).unwrap();
+        data
+    }
+
+    #[test]
+    pub fn test_current_format_block() {
+        let block = block(1);
+        let data = to_brotli(encode_payload(&BorshSealedBlockWithSenders(block.clone())).unwrap());
+
+        let version = CURRENT_FORMAT_VERSION.to_string();
+        let payload = DecoderRegistry::default()
+            .open(tags(Some(BORSH_BROTLI_ENCODING), Some(version.as_str())), data.as_slice())
+            .unwrap();
+        assert_eq!(payload.version, CURRENT_FORMAT_VERSION);
+        assert_eq!(payload.block().unwrap(), block);
+    }
+
+    #[test]
+    pub fn test_legacy_format_block() {
+        let block = block(1);
+        let data = to_brotli(legacy_block(&block));
+
+        let payload = DecoderRegistry::default()
+            .open(tags(Some(BORSH_BROTLI_ENCODING), None), &data)
+            .unwrap();
+        assert_eq!(payload.version, 0);
+        assert_eq!(payload.block().unwrap(), block);
+    }
+
+    #[test]
+    pub fn test_current_format_batch_and_outcome() {
+        let mut batch = BlockBatch::new();
+        batch.push(&block(1)).unwrap();
+        batch.push(&block(2)).unwrap();
+        let data = to_brotli(encode_payload(&batch).unwrap());
+
+        let registry = DecoderRegistry::default();
+        let payload = registry.open(tags(Some(BORSH_BROTLI_ENCODING), Some("1")), &data).unwrap();
+        assert_eq!(payload.batch_block(2).unwrap(), Some(block(2)));
+        assert_eq!(payload.batch_block(3).unwrap(), None);
+
+        let outcome =
+            BlockOutcome { block_number: 2, block_hash: B256::random(), ..Default::default() };
+        let data = to_brotli(encode_payload(&outcome).unwrap());
+        let payload = registry.open(tags(Some(BORSH_BROTLI_ENCODING), Some("1")), &data).unwrap();
+        assert_eq!(payload.outcome().unwrap(), outcome);
+
+        let sidecar = ReadSidecar {
+            reads: vec![RecordedRead {
+                precompile: "arweave-read".to_string(),
+                input: b"tx".to_vec(),
+                output: Ok(b"data".to_vec()),
+            }],
+        };
+        let data = to_brotli(encode_payload(&sidecar).unwrap());
+        let payload = registry.open(tags(Some(BORSH_BROTLI_ENCODING), Some("1")), &data).unwrap();
+        assert_eq!(payload.read_sidecar().unwrap(), sidecar);
+    }
+
+    #[test]
+    pub fn test_format_negotiation_errors() {
+        let registry = DecoderRegistry::default();
+        let data = to_brotli(encode_payload(&BorshSealedBlockWithSenders(block(1))).unwrap());
+
+        // the tag has to match the header
+        assert!(registry.open(tags(Some(BORSH_BROTLI_ENCODING), None), &data).is_err());
+        assert!(registry.open(tags(Some(BORSH_BROTLI_ENCODING), Some("0")), &data).is_err());
+        assert!(registry.open(tags(Some("Json"), Some("1")), &data).is_err());
+        assert!(registry.open(tags(None, Some("1")), &data).is_err());
+
+        let mut future = encode_payload(&BorshSealedBlockWithSenders(block(1))).unwrap();
+        future[4] = 7;
+        let data = to_brotli(future);
+        let err = registry.open(tags(Some(BORSH_BROTLI_ENCODING), Some("7")), &data).err().unwrap();
+        assert_eq!(err.kind(), std::io::ErrorKind::Unsupported);
+        assert_eq!(registry.versions(BORSH_BROTLI_ENCODING), vec![0, 1]);
+    }
+
+    #[test]
+    pub fn test_zstd_dictionary_payload() {
+        let samples = (0..200)
+            .map(|number| borsh::to_vec(&BorshSealedBlockWithSenders(block(number))).unwrap());
+        let dictionary = Arc::new(ZstdDictionary::train(samples, 4096).unwrap());
+        let codec = Codec::Zstd { level: 3, dictionary: Some(dictionary.clone()) };
+        let data = codec
+            .compress(&encode_payload(&BorshSealedBlockWithSenders(block(1))).unwrap())
+            .unwrap();
+
+        let hash = dictionary.hash().to_string();
+        let payload_tags = PayloadTags {
+            encoding: Some(codec.encoding()),
+            format_version: Some("1"),
+            zstd_dictionary: Some(hash.as_str()),
+        };
+        let registry = DecoderRegistry::default();
+        let err = registry.open(payload_tags, &data).err().unwrap();
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+
+        registry.add_dictionary(dictionary);
+        assert_eq!(registry.open(payload_tags, &data).unwrap().block().unwrap(), block(1));
+    }
+
+    #[test]
+    pub fn test_dictionaries_bounded() {
+        let registry = DecoderRegistry::default();
+        let dictionaries: Vec<Arc<ZstdDictionary>> = (0..=MAX_ZSTD_DICTIONARIES)
+            .map(|index| Arc::new(ZstdDictionary::new(index.to_le_bytes().to_vec())))
+            .collect();
+        for dictionary in &dictionaries {
+            registry.add_dictionary(dictionary.clone());
+        }
+
+        // the oldest dictionary was evicted
+        assert!(registry.dictionary(&dictionaries[0].hash()).is_none());
+        assert!(registry.dictionary(&dictionaries[1].hash()).is_some());
+        assert!(registry.dictionary(&dictionaries[MAX_ZSTD_DICTIONARIES].hash()).is_some());
+    }
+}
@@ -1,10 +1,20 @@
 pub mod address;
 pub mod b256;
+pub mod batch;
 pub mod block;
 pub mod bloom;
+pub mod codec;
+pub mod format;
 pub mod header;
+pub mod outcome;
+pub mod receipt;
 pub mod request;
+pub mod sidecar;
 pub mod signature;
+pub mod state_diff;
+#[cfg(any(test, feature = "test-utils"))]
+/// Common fixtures of tests.
+pub mod test_utils;
 pub mod transaction;
 pub mod withdrawal;
 
@@ -32,7 +42,7 @@ mod tests {
         let borsh_serialize = borsh::to_vec(&borsh_block).unwrap();
 
         assert_eq!(serde_json_serialize.len(), 1847);
-        assert_eq!(borsh_serialize.len(), 920);
+        assert_eq!(borsh_serialize.len(), 889);
     }
 
     #[test]
@@ -0,0 +1,67 @@
+use crate::{b256::BorshB256, receipt::BorshReceipt, state_diff::StateDiff};
+use borsh::{BorshDeserialize, BorshSerialize};
+use reth::primitives::{BlockHash, Receipt};
+use std::io::{Read, Write};
+
+/// Execution results of a block, archived next to the block itself.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BlockOutcome {
+    pub block_number: u64,
+    pub block_hash: BlockHash,
+    /// Receipts in transaction order, `None` for pruned receipts. Empty if receipts are not
+    /// archived.
+    pub receipts: Vec<Option<Receipt>>,
+    pub state_diff: Option<StateDiff>,
+}
+
+impl BorshSerialize for BlockOutcome {
+    fn serialize<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        let receipts: Vec<Option<BorshReceipt>> =
+            self.receipts.clone().into_iter().map(|receipt| receipt.map(BorshReceipt)).collect();
+
+        self.block_number.serialize(writer)?;
+        BorshB256(self.block_hash).serialize(writer)?;
+        receipts.serialize(writer)?;
+        self.state_diff.serialize(writer)?;
+
+        Ok(())
+    }
+}
+
+impl BorshDeserialize for BlockOutcome {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> std::io::Result<Self> {
+        let block_number: u64 = BorshDeserialize::deserialize_reader(reader)?;
+        let block_hash = BorshB256::deserialize_reader(reader)?;
+        let receipts = Vec::<Option<BorshReceipt>>::deserialize_reader(reader)?;
+        let state_diff = Option::<StateDiff>::deserialize_reader(reader)?;
+
+        Ok(BlockOutcome {
+            block_number,
+            block_hash: block_hash.0,
+            receipts: receipts
+                .into_iter()
+                .map(|receipt| receipt.map(|receipt| receipt.0))
+                .collect(),
+            state_diff,
+        })
+    }
+}
+
+#[cfg(test)]
+mod outcome_tests {
+    use crate::{outcome::BlockOutcome, state_diff::StateDiff};
+    use reth::primitives::{Receipt, B256};
+
+    #[test]
+    pub fn test_block_outcome() {
+        let data = BlockOutcome {
+            block_number: 7,
+            block_hash: B256::random(),
+            receipts: vec![Some(Receipt::default()), None],
+            state_diff: Some(StateDiff::default()),
+        };
+        let to_borsh = borsh::to_vec(&data).unwrap();
+        let from_borsh: BlockOutcome = borsh::from_slice(to_borsh.as_slice()).unwrap();
+        assert_eq!(data, from_borsh);
+    }
+}
@@ -0,0 +1,104 @@
+use crate::{address::BorshAddress, b256::BorshB256};
+use borsh::{BorshDeserialize, BorshSerialize};
+use reth::primitives::{Bytes, Log, Receipt, TxType};
+use std::io::{Error, ErrorKind, Read, Write};
+
+pub struct BorshLog(pub Log);
+pub struct BorshReceipt(pub Receipt);
+
+impl BorshSerialize for BorshLog {
+    fn serialize<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        let topics: Vec<BorshB256> = self.0.topics().iter().copied().map(BorshB256).collect();
+
+        BorshAddress(self.0.address).serialize(writer)?;
+        topics.serialize(writer)?;
+        self.0.data.data.to_vec().serialize(writer)?;
+
+        Ok(())
+    }
+}
+
+impl BorshDeserialize for BorshLog {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> std::io::Result<Self> {
+        let address = BorshAddress::deserialize_reader(reader)?;
+        let topics = Vec::<BorshB256>::deserialize_reader(reader)?;
+        let data = Vec::<u8>::deserialize_reader(reader)?;
+
+        Ok(BorshLog(Log::new_unchecked(
+            address.0,
+            topics.into_iter().map(|topic| topic.0).collect(),
+            Bytes::from(data),
+        )))
+    }
+}
+
+impl BorshSerialize for BorshReceipt {
+    fn serialize<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        let logs: Vec<BorshLog> = self.0.logs.clone().into_iter().map(BorshLog).collect();
+
+        u8::from(self.0.tx_type).serialize(writer)?;
+        self.0.success.serialize(writer)?;
+        self.0.cumulative_gas_used.serialize(writer)?;
+        logs.serialize(writer)?;
+
+        Ok(())
+    }
+}
+
+impl BorshDeserialize for BorshReceipt {
+    // the update fills the fields only present with the optimism feature
+    #[allow(clippy::needless_update)]
+    fn deserialize_reader<R: Read>(reader: &mut R) -> std::io::Result<Self> {
+        let tx_type: u8 = BorshDeserialize::deserialize_reader(reader)?;
+        let tx_type =
+            TxType::try_from(tx_type).map_err(|err| Error::new(ErrorKind::InvalidData, err))?;
+        let success: bool = BorshDeserialize::deserialize_reader(reader)?;
+        let cumulative_gas_used: u64 = BorshDeserialize::deserialize_reader(reader)?;
+        let logs = Vec::<BorshLog>::deserialize_reader(reader)?;
+
+        Ok(BorshReceipt(Receipt {
+            tx_type,
+            success,
+            cumulative_gas_used,
+            logs: logs.into_iter().map(|log| log.0).collect(),
+            ..Default::default()
+        }))
+    }
+}
+
+#[cfg(test)]
+mod receipt_tests {
+    use crate::receipt::{BorshLog, BorshReceipt};
+    use reth::primitives::{Address, Bytes, Log, Receipt, TxType, B256};
+
+    fn log() -> Log {
+        Log::new_unchecked(
+            Address::random(),
+            vec![B256::random(), B256::random()],
+            Bytes::from(vec![1, 2, 3]),
+        )
+    }
+
+    #[test]
+    pub fn test_log() {
+        let data = log();
+        let to_borsh = borsh::to_vec(&BorshLog(data.clone())).unwrap();
+        let from_borsh: BorshLog = borsh::from_slice(to_borsh.as_slice()).unwrap();
+        assert_eq!(data, from_borsh.0);
+    }
+
+    #[test]
+    #[allow(clippy::needless_update)]
+    pub fn test_receipt() {
+        let data = Receipt {
+            tx_type: TxType::Eip1559,
+            success: true,
+            cumulative_gas_used: 21_000,
+            logs: vec![log(), log()],
+            ..Default::default()
+        };
+        let to_borsh = borsh::to_vec(&BorshReceipt(data.clone())).unwrap();
+        let from_borsh: BorshReceipt = borsh::from_slice(to_borsh.as_slice()).unwrap();
+        assert_eq!(data, from_borsh.0);
+    }
+}
@@ -0,0 +1,127 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+use reth::primitives::{keccak256, Bytes, B256};
+use std::io::{self, ErrorKind, Read, Write};
+
+/// `WeaveVM:Type` of archived read sidecars.
+pub const READ_SIDECAR_TYPE: &str = "Read-Sidecar";
+/// Tag holding the commitment of an archived read sidecar.
+pub const SIDECAR_HASH_TAG: &str = "WeaveVM:Sidecar-Hash";
+
+/// A call of a read precompile, as recorded by the block builder.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordedRead {
+    /// Name of the precompile, e.g. `arweave-read`.
+    pub precompile: String,
+    pub input: Vec<u8>,
+    /// Bytes the call returned, or the message of the error it failed with.
+    pub output: Result<Vec<u8>, String>,
+}
+
+/// External data returned by the read precompiles while a block was built, in call order.
+///
+/// The header of the block commits to the sidecar, so every node executing the block replays the
+/// same bytes instead of fetching them again.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReadSidecar {
+    pub reads: Vec<RecordedRead>,
+}
+
+impl ReadSidecar {
+    /// Returns the hash of the borsh encoding of the sidecar.
+    pub fn commitment(&self) -> B256 {
+        keccak256(borsh::to_vec(self).expect("encoding into a vec does not fail"))
+    }
+
+    /// Returns the commitment of a block without any read.
+    pub fn empty_commitment() -> B256 {
+        Self::default().commitment()
+    }
+}
+
+/// Returns the sidecar commitment held by the extra data of a header.
+pub fn sidecar_commitment(extra_data: &Bytes) -> Option<B256> {
+    (extra_data.len() == B256::len_bytes()).then(|| B256::from_slice(extra_data))
+}
+
+impl BorshSerialize for RecordedRead {
+    fn serialize<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.precompile.serialize(writer)?;
+        self.input.serialize(writer)?;
+        match &self.output {
+            Ok(data) => {
+                0u8.serialize(writer)?;
+                data.serialize(writer)?;
+            }
+            Err(message) => {
+                1u8.serialize(writer)?;
+                message.serialize(writer)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl BorshDeserialize for RecordedRead {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let precompile = String::deserialize_reader(reader)?;
+        let input = Vec::<u8>::deserialize_reader(reader)?;
+        let output = match u8::deserialize_reader(reader)? {
+            0 => Ok(Vec::<u8>::deserialize_reader(reader)?),
+            1 => Err(String::deserialize_reader(reader)?),
+            tag => {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!("invalid read output {tag}"),
+                ))
+            }
+        };
+
+        Ok(RecordedRead { precompile, input, output })
+    }
+}
+
+impl BorshSerialize for ReadSidecar {
+    fn serialize<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.reads.serialize(writer)
+    }
+}
+
+impl BorshDeserialize for ReadSidecar {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let reads = Vec::<RecordedRead>::deserialize_reader(reader)?;
+        Ok(ReadSidecar { reads })
+    }
+}
+
+#[cfg(test)]
+mod sidecar_tests {
+    use crate::sidecar::{ReadSidecar, RecordedRead};
+
+    #[test]
+    pub fn test_read_sidecar() {
+        let sidecar = ReadSidecar {
+            reads: vec![
+                RecordedRead {
+                    precompile: "arweave-read".to_string(),
+                    input: b"tx".to_vec(),
+                    output: Ok(b"data".to_vec()),
+                },
+                RecordedRead {
+                    precompile: "kyve-read".to_string(),
+                    input: b"blob".to_vec(),
+                    output: Err("not found".to_string()),
+                },
+            ],
+        };
+        let to_borsh = borsh::to_vec(&sidecar).unwrap();
+        let from_borsh: ReadSidecar = borsh::from_slice(to_borsh.as_slice()).unwrap();
+        assert_eq!(sidecar, from_borsh);
+
+        // the commitment covers every read, including failed ones
+        let mut changed = sidecar.clone();
+        changed.reads[1].output = Err("timed out".to_string());
+        assert_ne!(sidecar.commitment(), changed.commitment());
+        assert_ne!(sidecar.commitment(), ReadSidecar::empty_commitment());
+    }
+}
@@ -17,9 +17,13 @@ pub fn to_signature(bytes: &[u8]) -> std::io::Result<Signature> {
 
     let r = U256::from_be_bytes(r_bytes);
     let s = U256::from_be_bytes(s_bytes);
-    let odd_y_parity = bytes[64] - 27;
+    let odd_y_parity = match bytes[64] {
+        27 => false,
+        28 => true,
+        v => return Err(Error::new(ErrorKind::InvalidData, format!("invalid signature v {v}"))),
+    };
 
-    let signature = Signature { r, s, odd_y_parity: odd_y_parity != 0 };
+    let signature = Signature { r, s, odd_y_parity };
 
     Ok(signature)
 }
@@ -33,7 +37,7 @@ impl BorshSerialize for BorshSignature {
 impl BorshDeserialize for BorshSignature {
     fn deserialize_reader<R: Read>(reader: &mut R) -> std::io::Result<Self> {
         let sig_vec = Vec::<u8>::deserialize_reader(reader)?;
-        let sig = to_signature(sig_vec.as_slice()).unwrap();
+        let sig = to_signature(sig_vec.as_slice())?;
         Ok(BorshSignature(sig))
     }
 }
@@ -0,0 +1,270 @@
+use crate::{
+    address::BorshAddress,
+    b256::{BorshB256, BorshU256},
+};
+use borsh::{BorshDeserialize, BorshSerialize};
+use reth::{
+    primitives::{Account, Address, Bytes, B256, KECCAK_EMPTY, U256},
+    providers::ExecutionOutcome,
+    revm::db::states::AccountInfoRevert,
+};
+use std::{
+    collections::BTreeMap,
+    io::{Read, Write},
+};
+
+/// Changes a single block made to an account.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AccountDiff {
+    pub address: Address,
+    /// The account after the block, `None` if it does not exist anymore.
+    pub info: Option<Account>,
+    /// Whether the storage of the account was cleared before `storage` was written.
+    pub wipe_storage: bool,
+    /// Changed storage slots and their values after the block, ordered by slot.
+    pub storage: Vec<(U256, U256)>,
+}
+
+/// Compact state changes of a single block.
+///
+/// Only the values after the block are kept, the values before it are found in the diffs of the
+/// preceding blocks.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StateDiff {
+    /// Changed accounts, ordered by address.
+    pub accounts: Vec<AccountDiff>,
+    /// Bytecode of the contracts deployed by the block, by code hash.
+    pub contracts: Vec<(B256, Bytes)>,
+}
+
+impl StateDiff {
+    /// Returns the changes made by the last block of `outcome`.
+    ///
+    /// The outcome needs the reverts of that block, which is the case for the execution outcome
+    /// of a [`Chain`](reth::providers::Chain).
+    pub fn from_execution_outcome(outcome: &ExecutionOutcome) -> Self {
+        let bundle = &outcome.bundle;
+        let Some(reverts) = bundle.reverts.last() else { return Self::default() };
+
+        let mut accounts = BTreeMap::new();
+        let mut contracts = BTreeMap::new();
+
+        for (address, revert) in reverts.iter() {
+            let account = bundle.state.get(address);
+            let info = account.and_then(|account| account.info.clone());
+
+            if let Some(info) = &info {
+                let previous_code_hash = match &revert.account {
+                    AccountInfoRevert::DoNothing => Some(info.code_hash),
+                    AccountInfoRevert::DeleteIt => None,
+                    AccountInfoRevert::RevertTo(previous) => Some(previous.code_hash),
+                };
+
+                if info.code_hash != KECCAK_EMPTY && previous_code_hash != Some(info.code_hash) {
+                    if let Some(code) = bundle.contracts.get(&info.code_hash) {
+                        contracts.insert(info.code_hash, code.original_bytes());
+                    }
+                }
+            }
+
+            let mut storage: Vec<(U256, U256)> = revert
+                .storage
+                .keys()
+                .map(|slot| {
+                    let value = account
+                        .and_then(|account| account.storage.get(slot))
+                        .map(|slot| slot.present_value)
+                        .unwrap_or_default();
+                    (*slot, value)
+                })
+                .collect();
+            storage.sort_unstable();
+
+            accounts.insert(
+                *address,
+                AccountDiff {
+                    address: *address,
+                    info: info.map(|info| Account {
+                        nonce: info.nonce,
+                        balance: info.balance,
+                        bytecode_hash: (info.code_hash != KECCAK_EMPTY).then_some(info.code_hash),
+                    }),
+                    wipe_storage: revert.wipe_storage,
+                    storage,
+                },
+            );
+        }
+
+        StateDiff {
+            accounts: accounts.into_values().collect(),
+            contracts: contracts.into_iter().collect(),
+        }
+    }
+}
+
+pub struct BorshAccount(pub Account);
+
+impl BorshSerialize for BorshAccount {
+    fn serialize<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        self.0.nonce.serialize(writer)?;
+        BorshU256(self.0.balance).serialize(writer)?;
+        self.0.bytecode_hash.map(BorshB256).serialize(writer)?;
+
+        Ok(())
+    }
+}
+
+impl BorshDeserialize for BorshAccount {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> std::io::Result<Self> {
+        let nonce: u64 = BorshDeserialize::deserialize_reader(reader)?;
+        let balance = BorshU256::deserialize_reader(reader)?;
+        let bytecode_hash = Option::<BorshB256>::deserialize_reader(reader)?;
+
+        Ok(BorshAccount(Account {
+            nonce,
+            balance: balance.0,
+            bytecode_hash: bytecode_hash.map(|hash| hash.0),
+        }))
+    }
+}
+
+impl BorshSerialize for AccountDiff {
+    fn serialize<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        let storage: Vec<(BorshU256, BorshU256)> = self
+            .storage
+            .iter()
+            .map(|(slot, value)| (BorshU256(*slot), BorshU256(*value)))
+            .collect();
+
+        BorshAddress(self.address).serialize(writer)?;
+        self.info.map(BorshAccount).serialize(writer)?;
+        self.wipe_storage.serialize(writer)?;
+        storage.serialize(writer)?;
+
+        Ok(())
+    }
+}
+
+impl BorshDeserialize for AccountDiff {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> std::io::Result<Self> {
+        let address = BorshAddress::deserialize_reader(reader)?;
+        let info = Option::<BorshAccount>::deserialize_reader(reader)?;
+        let wipe_storage: bool = BorshDeserialize::deserialize_reader(reader)?;
+        let storage = Vec::<(BorshU256, BorshU256)>::deserialize_reader(reader)?;
+
+        Ok(AccountDiff {
+            address: address.0,
+            info: info.map(|info| info.0),
+            wipe_storage,
+            storage: storage.into_iter().map(|(slot, value)| (slot.0, value.0)).collect(),
+        })
+    }
+}
+
+impl BorshSerialize for StateDiff {
+    fn serialize<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        let contracts: Vec<(BorshB256, Vec<u8>)> =
+            self.contracts.iter().map(|(hash, code)| (BorshB256(*hash), code.to_vec())).collect();
+
+        self.accounts.serialize(writer)?;
+        contracts.serialize(writer)?;
+
+        Ok(())
+    }
+}
+
+impl BorshDeserialize for StateDiff {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> std::io::Result<Self> {
+        let accounts = Vec::<AccountDiff>::deserialize_reader(reader)?;
+        let contracts = Vec::<(BorshB256, Vec<u8>)>::deserialize_reader(reader)?;
+
+        Ok(StateDiff {
+            accounts,
+            contracts: contracts
+                .into_iter()
+                .map(|(hash, code)| (hash.0, Bytes::from(code)))
+                .collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod state_diff_tests {
+    use crate::state_diff::{AccountDiff, StateDiff};
+    use reth::{
+        primitives::{Account, Address, Bytes, B256, U256},
+        providers::ExecutionOutcome,
+        revm::{
+            db::states::bundle_state::BundleRetention,
+            primitives::{
+                Account as RevmAccount, AccountInfo, AccountStatus, Bytecode, EvmStorageSlot,
+                HashMap,
+            },
+            DatabaseCommit, State,
+        },
+    };
+
+    #[test]
+    pub fn test_state_diff() {
+        let data = StateDiff {
+            accounts: vec![AccountDiff {
+                address: Address::random(),
+                info: Some(Account {
+                    nonce: 1,
+                    balance: U256::from(10),
+                    bytecode_hash: Some(B256::random()),
+                }),
+                wipe_storage: true,
+                storage: vec![(U256::from(1), U256::from(2))],
+            }],
+            contracts: vec![(B256::random(), Bytes::from(vec![0x60, 0x00]))],
+        };
+        let to_borsh = borsh::to_vec(&data).unwrap();
+        let from_borsh: StateDiff = borsh::from_slice(to_borsh.as_slice()).unwrap();
+        assert_eq!(data, from_borsh);
+    }
+
+    #[test]
+    pub fn test_state_diff_from_execution_outcome() {
+        let address = Address::random();
+        let code = Bytecode::new_raw(Bytes::from(vec![0x60, 0x00]));
+        let code_hash = code.hash_slow();
+
+        let mut state = State::builder().with_bundle_update().build();
+        state.commit(HashMap::from([(
+            address,
+            RevmAccount {
+                status: AccountStatus::Touched | AccountStatus::Created,
+                info: AccountInfo {
+                    balance: U256::from(10),
+                    nonce: 1,
+                    code_hash,
+                    code: Some(code.clone()),
+                },
+                storage: HashMap::from_iter([(
+                    U256::from(1),
+                    EvmStorageSlot::new_changed(U256::ZERO, U256::from(7)),
+                )]),
+            },
+        )]));
+        state.merge_transitions(BundleRetention::Reverts);
+
+        let outcome = ExecutionOutcome::new(state.take_bundle(), Default::default(), 1, vec![]);
+        let diff = StateDiff::from_execution_outcome(&outcome);
+
+        assert_eq!(
+            diff.accounts,
+            vec![AccountDiff {
+                address,
+                info: Some(Account {
+                    nonce: 1,
+                    balance: U256::from(10),
+                    bytecode_hash: Some(code_hash),
+                }),
+                wipe_storage: false,
+                storage: vec![(U256::from(1), U256::from(7))],
+            }]
+        );
+        assert_eq!(diff.contracts, vec![(code_hash, code.original_bytes())]);
+    }
+}
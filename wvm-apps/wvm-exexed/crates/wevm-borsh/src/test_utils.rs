@@ -0,0 +1,15 @@
+use reth::primitives::{Header, SealedBlock, SealedBlockWithSenders};
+
+/// Returns an empty sealed block.
+pub fn block(number: u64) -> SealedBlockWithSenders {
+    block_with_extra_data(number, b"")
+}
+
+/// Returns an empty sealed block, blocks of the same number differ by their extra data.
+pub fn block_with_extra_data(number: u64, extra_data: &'static [u8]) -> SealedBlockWithSenders {
+    let header = Header { number, extra_data: extra_data.into(), ..Default::default() };
+    SealedBlockWithSenders {
+        block: SealedBlock { header: header.seal_slow(), ..Default::default() },
+        senders: vec![],
+    }
+}
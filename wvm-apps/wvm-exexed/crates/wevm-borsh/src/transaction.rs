@@ -1,27 +1,379 @@
-use crate::{b256::BorshB256, signature::BorshSignature};
+use crate::{
+    address::BorshAddress,
+    b256::{BorshB256, BorshU256},
+    signature::BorshSignature,
+};
 use borsh::{BorshDeserialize, BorshSerialize};
-use reth::primitives::{Transaction, TransactionSigned};
-use std::io::{Read, Write};
+use reth::primitives::{
+    alloy_primitives::{Parity, Signature},
+    eip7702::{Authorization, SignedAuthorization},
+    AccessList, AccessListItem, Bytes, Transaction, TransactionSigned, TxEip1559, TxEip2930,
+    TxEip4844, TxEip7702, TxKind, TxLegacy, TxType,
+};
+use std::io::{Error, ErrorKind, Read, Write};
+
+/// Version of the transaction encoding, written in front of every transaction.
+///
+/// Version `1` encodes the fields of every transaction type natively. Archives written before
+/// embedded transactions as JSON without a version, see [`LegacyBorshTransaction`].
+pub const TRANSACTION_ENCODING_VERSION: u8 = 1;
 
 pub struct BorshTransactionSigned(pub TransactionSigned);
 pub struct BorshTransaction(pub Transaction);
 
-impl BorshSerialize for BorshTransaction {
+/// Transaction in the JSON encoding of the archives written before
+/// [`TRANSACTION_ENCODING_VERSION`] was introduced.
+pub struct LegacyBorshTransaction(pub Transaction);
+pub struct LegacyBorshTransactionSigned(pub TransactionSigned);
+
+pub struct BorshTxKind(pub TxKind);
+pub struct BorshAccessList(pub AccessList);
+pub struct BorshSignedAuthorization(pub SignedAuthorization);
+
+impl BorshSerialize for BorshTxKind {
+    fn serialize<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        match self.0 {
+            TxKind::Create => None,
+            TxKind::Call(address) => Some(BorshAddress(address)),
+        }
+        .serialize(writer)
+    }
+}
+
+impl BorshDeserialize for BorshTxKind {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> std::io::Result<Self> {
+        let to = Option::<BorshAddress>::deserialize_reader(reader)?;
+        Ok(BorshTxKind(to.map_or(TxKind::Create, |address| TxKind::Call(address.0))))
+    }
+}
+
+impl BorshSerialize for BorshAccessList {
     fn serialize<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
-        let buff: Vec<u8> = serde_json::to_vec(&self.0).unwrap();
-        buff.serialize(writer)?;
+        let AccessList(items) = &self.0;
+        let items: Vec<(BorshAddress, Vec<BorshB256>)> = items
+            .iter()
+            .map(|item| {
+                (
+                    BorshAddress(item.address),
+                    item.storage_keys.iter().copied().map(BorshB256).collect(),
+                )
+            })
+            .collect();
+
+        items.serialize(writer)
+    }
+}
+
+impl BorshDeserialize for BorshAccessList {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> std::io::Result<Self> {
+        let items = Vec::<(BorshAddress, Vec<BorshB256>)>::deserialize_reader(reader)?;
+
+        Ok(BorshAccessList(AccessList(
+            items
+                .into_iter()
+                .map(|(address, storage_keys)| AccessListItem {
+                    address: address.0,
+                    storage_keys: storage_keys.into_iter().map(|key| key.0).collect(),
+                })
+                .collect(),
+        )))
+    }
+}
+
+impl BorshSerialize for BorshSignedAuthorization {
+    fn serialize<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        let signature = self.0.signature();
+
+        self.0.chain_id.serialize(writer)?;
+        BorshAddress(self.0.address).serialize(writer)?;
+        self.0.nonce().serialize(writer)?;
+        signature.v().y_parity().serialize(writer)?;
+        BorshU256(signature.r()).serialize(writer)?;
+        BorshU256(signature.s()).serialize(writer)?;
+
         Ok(())
     }
 }
 
+impl BorshDeserialize for BorshSignedAuthorization {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> std::io::Result<Self> {
+        let chain_id: u64 = BorshDeserialize::deserialize_reader(reader)?;
+        let address = BorshAddress::deserialize_reader(reader)?;
+        let nonce = Option::<u64>::deserialize_reader(reader)?;
+        let y_parity: bool = BorshDeserialize::deserialize_reader(reader)?;
+        let r = BorshU256::deserialize_reader(reader)?;
+        let s = BorshU256::deserialize_reader(reader)?;
+
+        let signature = Signature::from_rs_and_parity(r.0, s.0, Parity::Parity(y_parity))
+            .map_err(|err| Error::new(ErrorKind::InvalidData, err))?;
+        let authorization = Authorization { chain_id, address: address.0, nonce: nonce.into() };
+
+        Ok(BorshSignedAuthorization(authorization.into_signed(signature)))
+    }
+}
+
+fn serialize_legacy<W: Write>(tx: &TxLegacy, writer: &mut W) -> std::io::Result<()> {
+    tx.chain_id.serialize(writer)?;
+    tx.nonce.serialize(writer)?;
+    tx.gas_price.serialize(writer)?;
+    tx.gas_limit.serialize(writer)?;
+    BorshTxKind(tx.to).serialize(writer)?;
+    BorshU256(tx.value).serialize(writer)?;
+    tx.input.to_vec().serialize(writer)?;
+
+    Ok(())
+}
+
+fn deserialize_legacy<R: Read>(reader: &mut R) -> std::io::Result<TxLegacy> {
+    let chain_id = Option::<u64>::deserialize_reader(reader)?;
+    let nonce: u64 = BorshDeserialize::deserialize_reader(reader)?;
+    let gas_price: u128 = BorshDeserialize::deserialize_reader(reader)?;
+    let gas_limit: u64 = BorshDeserialize::deserialize_reader(reader)?;
+    let to = BorshTxKind::deserialize_reader(reader)?;
+    let value = BorshU256::deserialize_reader(reader)?;
+    let input = Vec::<u8>::deserialize_reader(reader)?;
+
+    Ok(TxLegacy {
+        chain_id,
+        nonce,
+        gas_price,
+        gas_limit,
+        to: to.0,
+        value: value.0,
+        input: Bytes::from(input),
+    })
+}
+
+fn serialize_eip2930<W: Write>(tx: &TxEip2930, writer: &mut W) -> std::io::Result<()> {
+    tx.chain_id.serialize(writer)?;
+    tx.nonce.serialize(writer)?;
+    tx.gas_price.serialize(writer)?;
+    tx.gas_limit.serialize(writer)?;
+    BorshTxKind(tx.to).serialize(writer)?;
+    BorshU256(tx.value).serialize(writer)?;
+    BorshAccessList(tx.access_list.clone()).serialize(writer)?;
+    tx.input.to_vec().serialize(writer)?;
+
+    Ok(())
+}
+
+fn deserialize_eip2930<R: Read>(reader: &mut R) -> std::io::Result<TxEip2930> {
+    let chain_id: u64 = BorshDeserialize::deserialize_reader(reader)?;
+    let nonce: u64 = BorshDeserialize::deserialize_reader(reader)?;
+    let gas_price: u128 = BorshDeserialize::deserialize_reader(reader)?;
+    let gas_limit: u64 = BorshDeserialize::deserialize_reader(reader)?;
+    let to = BorshTxKind::deserialize_reader(reader)?;
+    let value = BorshU256::deserialize_reader(reader)?;
+    let access_list = BorshAccessList::deserialize_reader(reader)?;
+    let input = Vec::<u8>::deserialize_reader(reader)?;
+
+    Ok(TxEip2930 {
+        chain_id,
+        nonce,
+        gas_price,
+        gas_limit,
+        to: to.0,
+        value: value.0,
+        access_list: access_list.0,
+        input: Bytes::from(input),
+    })
+}
+
+fn serialize_eip1559<W: Write>(tx: &TxEip1559, writer: &mut W) -> std::io::Result<()> {
+    tx.chain_id.serialize(writer)?;
+    tx.nonce.serialize(writer)?;
+    tx.gas_limit.serialize(writer)?;
+    tx.max_fee_per_gas.serialize(writer)?;
+    tx.max_priority_fee_per_gas.serialize(writer)?;
+    BorshTxKind(tx.to).serialize(writer)?;
+    BorshU256(tx.value).serialize(writer)?;
+    BorshAccessList(tx.access_list.clone()).serialize(writer)?;
+    tx.input.to_vec().serialize(writer)?;
+
+    Ok(())
+}
+
+fn deserialize_eip1559<R: Read>(reader: &mut R) -> std::io::Result<TxEip1559> {
+    let chain_id: u64 = BorshDeserialize::deserialize_reader(reader)?;
+    let nonce: u64 = BorshDeserialize::deserialize_reader(reader)?;
+    let gas_limit: u64 = BorshDeserialize::deserialize_reader(reader)?;
+    let max_fee_per_gas: u128 = BorshDeserialize::deserialize_reader(reader)?;
+    let max_priority_fee_per_gas: u128 = BorshDeserialize::deserialize_reader(reader)?;
+    let to = BorshTxKind::deserialize_reader(reader)?;
+    let value = BorshU256::deserialize_reader(reader)?;
+    let access_list = BorshAccessList::deserialize_reader(reader)?;
+    let input = Vec::<u8>::deserialize_reader(reader)?;
+
+    Ok(TxEip1559 {
+        chain_id,
+        nonce,
+        gas_limit,
+        max_fee_per_gas,
+        max_priority_fee_per_gas,
+        to: to.0,
+        value: value.0,
+        access_list: access_list.0,
+        input: Bytes::from(input),
+    })
+}
+
+fn serialize_eip4844<W: Write>(tx: &TxEip4844, writer: &mut W) -> std::io::Result<()> {
+    let blob_versioned_hashes: Vec<BorshB256> =
+        tx.blob_versioned_hashes.iter().copied().map(BorshB256).collect();
+
+    tx.chain_id.serialize(writer)?;
+    tx.nonce.serialize(writer)?;
+    tx.gas_limit.serialize(writer)?;
+    tx.max_fee_per_gas.serialize(writer)?;
+    tx.max_priority_fee_per_gas.serialize(writer)?;
+    tx.placeholder.is_some().serialize(writer)?;
+    BorshAddress(tx.to).serialize(writer)?;
+    BorshU256(tx.value).serialize(writer)?;
+    BorshAccessList(tx.access_list.clone()).serialize(writer)?;
+    blob_versioned_hashes.serialize(writer)?;
+    tx.max_fee_per_blob_gas.serialize(writer)?;
+    tx.input.to_vec().serialize(writer)?;
+
+    Ok(())
+}
+
+fn deserialize_eip4844<R: Read>(reader: &mut R) -> std::io::Result<TxEip4844> {
+    let chain_id: u64 = BorshDeserialize::deserialize_reader(reader)?;
+    let nonce: u64 = BorshDeserialize::deserialize_reader(reader)?;
+    let gas_limit: u64 = BorshDeserialize::deserialize_reader(reader)?;
+    let max_fee_per_gas: u128 = BorshDeserialize::deserialize_reader(reader)?;
+    let max_priority_fee_per_gas: u128 = BorshDeserialize::deserialize_reader(reader)?;
+    let placeholder: bool = BorshDeserialize::deserialize_reader(reader)?;
+    let to = BorshAddress::deserialize_reader(reader)?;
+    let value = BorshU256::deserialize_reader(reader)?;
+    let access_list = BorshAccessList::deserialize_reader(reader)?;
+    let blob_versioned_hashes = Vec::<BorshB256>::deserialize_reader(reader)?;
+    let max_fee_per_blob_gas: u128 = BorshDeserialize::deserialize_reader(reader)?;
+    let input = Vec::<u8>::deserialize_reader(reader)?;
+
+    Ok(TxEip4844 {
+        chain_id,
+        nonce,
+        gas_limit,
+        max_fee_per_gas,
+        max_priority_fee_per_gas,
+        placeholder: placeholder.then_some(()),
+        to: to.0,
+        value: value.0,
+        access_list: access_list.0,
+        blob_versioned_hashes: blob_versioned_hashes.into_iter().map(|hash| hash.0).collect(),
+        max_fee_per_blob_gas,
+        input: Bytes::from(input),
+    })
+}
+
+fn serialize_eip7702<W: Write>(tx: &TxEip7702, writer: &mut W) -> std::io::Result<()> {
+    let authorization_list: Vec<BorshSignedAuthorization> =
+        tx.authorization_list.iter().cloned().map(BorshSignedAuthorization).collect();
+
+    tx.chain_id.serialize(writer)?;
+    tx.nonce.serialize(writer)?;
+    tx.gas_limit.serialize(writer)?;
+    tx.max_fee_per_gas.serialize(writer)?;
+    tx.max_priority_fee_per_gas.serialize(writer)?;
+    BorshTxKind(tx.to).serialize(writer)?;
+    BorshU256(tx.value).serialize(writer)?;
+    BorshAccessList(tx.access_list.clone()).serialize(writer)?;
+    authorization_list.serialize(writer)?;
+    tx.input.to_vec().serialize(writer)?;
+
+    Ok(())
+}
+
+fn deserialize_eip7702<R: Read>(reader: &mut R) -> std::io::Result<TxEip7702> {
+    let chain_id: u64 = BorshDeserialize::deserialize_reader(reader)?;
+    let nonce: u64 = BorshDeserialize::deserialize_reader(reader)?;
+    let gas_limit: u64 = BorshDeserialize::deserialize_reader(reader)?;
+    let max_fee_per_gas: u128 = BorshDeserialize::deserialize_reader(reader)?;
+    let max_priority_fee_per_gas: u128 = BorshDeserialize::deserialize_reader(reader)?;
+    let to = BorshTxKind::deserialize_reader(reader)?;
+    let value = BorshU256::deserialize_reader(reader)?;
+    let access_list = BorshAccessList::deserialize_reader(reader)?;
+    let authorization_list = Vec::<BorshSignedAuthorization>::deserialize_reader(reader)?;
+    let input = Vec::<u8>::deserialize_reader(reader)?;
+
+    Ok(TxEip7702 {
+        chain_id,
+        nonce,
+        gas_limit,
+        max_fee_per_gas,
+        max_priority_fee_per_gas,
+        to: to.0,
+        value: value.0,
+        access_list: access_list.0,
+        authorization_list: authorization_list.into_iter().map(|auth| auth.0).collect(),
+        input: Bytes::from(input),
+    })
+}
+
+impl BorshSerialize for BorshTransaction {
+    fn serialize<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        TRANSACTION_ENCODING_VERSION.serialize(writer)?;
+        u8::from(self.0.tx_type()).serialize(writer)?;
+
+        match &self.0 {
+            Transaction::Legacy(tx) => serialize_legacy(tx, writer),
+            Transaction::Eip2930(tx) => serialize_eip2930(tx, writer),
+            Transaction::Eip1559(tx) => serialize_eip1559(tx, writer),
+            Transaction::Eip4844(tx) => serialize_eip4844(tx, writer),
+            Transaction::Eip7702(tx) => serialize_eip7702(tx, writer),
+            // deposit transactions only exist with the optimism feature
+            #[allow(unreachable_patterns)]
+            tx => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("unsupported transaction type {:?}", tx.tx_type()),
+            )),
+        }
+    }
+}
+
 impl BorshDeserialize for BorshTransaction {
     fn deserialize_reader<R: Read>(reader: &mut R) -> std::io::Result<Self> {
-        let bytes = Vec::<u8>::deserialize_reader(reader)?;
-        let tx: Transaction = serde_json::from_slice(bytes.as_slice()).unwrap();
+        let version: u8 = BorshDeserialize::deserialize_reader(reader)?;
+        if version != TRANSACTION_ENCODING_VERSION {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("unsupported transaction encoding version {version}"),
+            ))
+        }
+
+        let tx_type: u8 = BorshDeserialize::deserialize_reader(reader)?;
+        let tx_type =
+            TxType::try_from(tx_type).map_err(|err| Error::new(ErrorKind::InvalidData, err))?;
+
+        let tx = match tx_type {
+            TxType::Legacy => Transaction::Legacy(deserialize_legacy(reader)?),
+            TxType::Eip2930 => Transaction::Eip2930(deserialize_eip2930(reader)?),
+            TxType::Eip1559 => Transaction::Eip1559(deserialize_eip1559(reader)?),
+            TxType::Eip4844 => Transaction::Eip4844(deserialize_eip4844(reader)?),
+            TxType::Eip7702 => Transaction::Eip7702(deserialize_eip7702(reader)?),
+            #[allow(unreachable_patterns)]
+            tx_type => {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("unsupported transaction type {tx_type:?}"),
+                ))
+            }
+        };
+
         Ok(BorshTransaction(tx))
     }
 }
 
+impl BorshDeserialize for LegacyBorshTransaction {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> std::io::Result<Self> {
+        let bytes = Vec::<u8>::deserialize_reader(reader)?;
+        let tx: Transaction = serde_json::from_slice(bytes.as_slice())
+            .map_err(|err| Error::new(ErrorKind::InvalidData, err))?;
+        Ok(LegacyBorshTransaction(tx))
+    }
+}
+
 impl BorshSerialize for BorshTransactionSigned {
     fn serialize<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
         BorshB256(self.0.hash).serialize(writer)?;
@@ -46,10 +398,29 @@ impl BorshDeserialize for BorshTransactionSigned {
     }
 }
 
+impl BorshDeserialize for LegacyBorshTransactionSigned {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> std::io::Result<Self> {
+        let hash = BorshB256::deserialize_reader(reader)?;
+        let bytes_signature = BorshSignature::deserialize_reader(reader)?;
+        let tx = LegacyBorshTransaction::deserialize_reader(reader)?;
+
+        Ok(LegacyBorshTransactionSigned(TransactionSigned {
+            hash: hash.0,
+            signature: bytes_signature.0,
+            transaction: tx.0,
+        }))
+    }
+}
+
 #[cfg(test)]
 mod txs_tests {
-    use crate::transaction::BorshTransactionSigned;
-    use reth::primitives::TransactionSigned;
+    use crate::transaction::{
+        BorshTransaction, BorshTransactionSigned, LegacyBorshTransaction,
+        TRANSACTION_ENCODING_VERSION,
+    };
+    use proptest::prelude::*;
+    use proptest_arbitrary_interop::arb;
+    use reth::primitives::{Transaction, TransactionSigned};
 
     #[test]
     pub fn test_sealed_header() {
@@ -59,4 +430,55 @@ mod txs_tests {
         let from_borsh: BorshTransactionSigned = borsh::from_slice(to_borsh.as_slice()).unwrap();
         assert_eq!(data, from_borsh.0);
     }
+
+    #[test]
+    pub fn test_unknown_encoding_version() {
+        let mut to_borsh = borsh::to_vec(&BorshTransaction(Transaction::default())).unwrap();
+        assert_eq!(to_borsh[0], TRANSACTION_ENCODING_VERSION);
+
+        to_borsh[0] = TRANSACTION_ENCODING_VERSION + 1;
+        assert!(borsh::from_slice::<BorshTransaction>(to_borsh.as_slice()).is_err());
+    }
+
+    #[test]
+    pub fn test_unknown_tx_type() {
+        let mut to_borsh = borsh::to_vec(&BorshTransaction(Transaction::default())).unwrap();
+        to_borsh[1] = 0x7f;
+        assert!(borsh::from_slice::<BorshTransaction>(to_borsh.as_slice()).is_err());
+    }
+
+    #[test]
+    pub fn test_legacy_json_transaction() {
+        let data = Transaction::default();
+        let to_borsh = borsh::to_vec(&serde_json::to_vec(&data).unwrap()).unwrap();
+        let from_borsh: LegacyBorshTransaction = borsh::from_slice(to_borsh.as_slice()).unwrap();
+        assert_eq!(data, from_borsh.0);
+
+        let invalid = borsh::to_vec(&b"{".to_vec()).unwrap();
+        assert!(borsh::from_slice::<LegacyBorshTransaction>(invalid.as_slice()).is_err());
+
+        // legacy archives are decoded by their format version, never by guessing
+        assert!(borsh::from_slice::<BorshTransaction>(to_borsh.as_slice()).is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn test_transaction_roundtrip(tx in arb::<Transaction>()) {
+            prop_assume!(!tx.is_deposit());
+
+            let to_borsh = borsh::to_vec(&BorshTransaction(tx.clone())).unwrap();
+            let from_borsh: BorshTransaction = borsh::from_slice(to_borsh.as_slice()).unwrap();
+            prop_assert_eq!(tx, from_borsh.0);
+        }
+
+        #[test]
+        fn test_transaction_signed_roundtrip(tx in arb::<TransactionSigned>()) {
+            prop_assume!(!tx.is_deposit());
+
+            let to_borsh = borsh::to_vec(&BorshTransactionSigned(tx.clone())).unwrap();
+            let from_borsh: BorshTransactionSigned =
+                borsh::from_slice(to_borsh.as_slice()).unwrap();
+            prop_assert_eq!(tx, from_borsh.0);
+        }
+    }
 }